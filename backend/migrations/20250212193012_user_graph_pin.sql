@@ -0,0 +1,8 @@
+-- Table to store the graphs a user has pinned for quick access
+CREATE TABLE app_data.user_graph_pin (
+    user_id UUID NOT NULL REFERENCES app_data.user(id) ON DELETE CASCADE,
+    graph_id text NOT NULL REFERENCES app_data.graph_info(graph_id) ON DELETE CASCADE,
+    pinned_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_id, graph_id)
+);
+CREATE INDEX idx_user_graph_pin_user_id ON app_data.user_graph_pin (user_id);
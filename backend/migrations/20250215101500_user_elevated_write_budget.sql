@@ -0,0 +1,3 @@
+-- Service accounts with this flag are exempt from the per-user write budget
+ALTER TABLE app_data.user
+    ADD COLUMN elevated_write_budget BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,4 @@
+-- Scope within which node names must be unique: none, per_type or per_graph
+ALTER TABLE app_data.graph_info
+    ADD COLUMN node_name_uniqueness TEXT NOT NULL DEFAULT 'per_type'
+    CHECK (node_name_uniqueness IN ('none', 'per_type', 'per_graph'));
@@ -0,0 +1,4 @@
+-- Node types can extend another node type in the same graph and inherit its attributes
+ALTER TABLE app_data.node_types
+    ADD COLUMN parent_id TEXT REFERENCES app_data.node_types(id);
+CREATE INDEX idx_node_types_parent_id ON app_data.node_types (parent_id);
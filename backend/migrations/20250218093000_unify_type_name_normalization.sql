@@ -0,0 +1,25 @@
+-- Recompute normalized names with the single normalization used by the application:
+-- trimmed, whitespace runs collapsed to one underscore, uppercased.
+-- Edge types were previously normalized with a slightly different rule.
+UPDATE app_data.node_types
+SET normalized_name = upper(regexp_replace(btrim(name), '\s+', '_', 'g'))
+WHERE normalized_name <> upper(regexp_replace(btrim(name), '\s+', '_', 'g'));
+
+UPDATE app_data.edge_type
+SET normalized_name = upper(regexp_replace(btrim(name), '\s+', '_', 'g'))
+WHERE normalized_name <> upper(regexp_replace(btrim(name), '\s+', '_', 'g'));
+
+UPDATE app_data.node_type_attributes
+SET normalized_name = upper(regexp_replace(btrim(name), '\s+', '_', 'g'))
+WHERE normalized_name <> upper(regexp_replace(btrim(name), '\s+', '_', 'g'));
+
+UPDATE app_data.edge_type_attribute
+SET normalized_name = upper(regexp_replace(btrim(name), '\s+', '_', 'g'))
+WHERE normalized_name <> upper(regexp_replace(btrim(name), '\s+', '_', 'g'));
+
+-- The initial schema declares UNIQUE(graph_id, normalized_name) on both type tables.
+-- Make the index explicit by name so it can be relied on for conflict detection.
+CREATE UNIQUE INDEX IF NOT EXISTS uniq_node_types_graph_normalized_name
+    ON app_data.node_types (graph_id, normalized_name);
+CREATE UNIQUE INDEX IF NOT EXISTS uniq_edge_type_graph_normalized_name
+    ON app_data.edge_type (graph_id, normalized_name);
@@ -0,0 +1,20 @@
+-- Background jobs, e.g. async graph exports. Results are stored as large objects
+-- so they can be streamed back without loading them into memory
+CREATE TABLE app_data.job (
+    id UUID PRIMARY KEY,
+    org_id UUID NOT NULL REFERENCES app_data.org(id) ON DELETE CASCADE,
+    graph_id TEXT REFERENCES app_data.graph_info(graph_id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    status TEXT NOT NULL,
+    created_by UUID NOT NULL REFERENCES app_data.user(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    completed_at TIMESTAMPTZ,
+    error TEXT,
+    result_oid OID,
+    result_content_type TEXT,
+    result_filename TEXT,
+    result_size BIGINT,
+    expires_at TIMESTAMPTZ
+);
+CREATE INDEX idx_job_org_id ON app_data.job (org_id);
+CREATE INDEX idx_job_created_by ON app_data.job (created_by);
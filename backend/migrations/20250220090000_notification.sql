@@ -0,0 +1,11 @@
+-- In-app notifications, e.g. being added to an org. Rows are written in the same
+-- transaction as the change they describe
+CREATE TABLE app_data.notification (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES app_data.user(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    payload JSONB NOT NULL DEFAULT '{}',
+    read_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX idx_notification_user_id_created_at ON app_data.notification (user_id, created_at DESC);
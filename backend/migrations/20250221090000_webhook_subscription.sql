@@ -0,0 +1,11 @@
+-- Outbound webhooks for a graph. Subscribers pick the event categories they receive,
+-- e.g. only schema changes
+CREATE TABLE app_data.webhook_subscription (
+    id UUID PRIMARY KEY,
+    graph_id TEXT NOT NULL REFERENCES app_data.graph_info(graph_id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    categories TEXT[] NOT NULL,
+    created_by UUID NOT NULL REFERENCES app_data.user(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX idx_webhook_subscription_graph_id ON app_data.webhook_subscription (graph_id);
@@ -0,0 +1,22 @@
+-- Device authorization for headless clients: the client polls with device_code while
+-- the user approves user_code in the SPA. Rows are single use
+CREATE TABLE app_data.device_authorization (
+    device_code TEXT PRIMARY KEY,
+    user_code TEXT NOT NULL UNIQUE,
+    user_id UUID REFERENCES app_data.user(id) ON DELETE CASCADE,
+    federated_user_id UUID REFERENCES app_data.federated_user(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    approved_at TIMESTAMPTZ,
+    consumed_at TIMESTAMPTZ
+);
+
+-- Security relevant account events, e.g. a device being granted a session
+CREATE TABLE app_data.security_event (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES app_data.user(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    detail JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX idx_security_event_user_id ON app_data.security_event (user_id, created_at DESC);
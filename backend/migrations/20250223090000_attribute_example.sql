@@ -0,0 +1,3 @@
+-- Example values for attributes, used by form generators as placeholder text
+ALTER TABLE app_data.node_type_attributes ADD COLUMN example JSONB;
+ALTER TABLE app_data.edge_type_attribute ADD COLUMN example JSONB;
@@ -0,0 +1,20 @@
+-- Org-wide attribute dictionary. Node and edge type attributes created from an entry
+-- keep a reference to it so drift can be reported and updates propagated
+CREATE TABLE app_data.org_attribute (
+    id UUID PRIMARY KEY,
+    org_id UUID NOT NULL REFERENCES app_data.org(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    normalized_name TEXT NOT NULL,
+    data_type TEXT NOT NULL,
+    description TEXT NOT NULL,
+    constraints JSONB NOT NULL DEFAULT '{}',
+    created_by UUID NOT NULL REFERENCES app_data.user(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (org_id, normalized_name)
+);
+
+ALTER TABLE app_data.node_type_attributes
+    ADD COLUMN dictionary_id UUID REFERENCES app_data.org_attribute(id) ON DELETE SET NULL;
+ALTER TABLE app_data.edge_type_attribute
+    ADD COLUMN dictionary_id UUID REFERENCES app_data.org_attribute(id) ON DELETE SET NULL;
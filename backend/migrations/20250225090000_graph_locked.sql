@@ -0,0 +1,2 @@
+-- A locked graph rejects writes until an admin unlocks it, e.g. while it is under review
+ALTER TABLE app_data.graph_info ADD COLUMN locked BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,23 @@
+-- Append-only log of node property changes, for provenance of edits
+CREATE TABLE app_data.node_history (
+    id UUID PRIMARY KEY,
+    graph_id TEXT NOT NULL REFERENCES app_data.graph_info(graph_id) ON DELETE CASCADE,
+    node_id BIGINT NOT NULL,
+    change TEXT NOT NULL,
+    before JSONB,
+    after JSONB NOT NULL,
+    changed_by UUID NOT NULL REFERENCES app_data.user(id),
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX idx_node_history_node ON app_data.node_history (graph_id, node_id, changed_at);
+
+-- Entries are never edited. Deletes are left to the graph_id cascade
+CREATE FUNCTION app_data.node_history_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'node_history is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER node_history_no_update
+    BEFORE UPDATE ON app_data.node_history
+    FOR EACH ROW EXECUTE FUNCTION app_data.node_history_append_only();
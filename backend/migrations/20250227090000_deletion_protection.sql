@@ -0,0 +1,2 @@
+-- Graphs that must not be deleted until the protection is removed in a separate call
+ALTER TABLE app_data.graph_info ADD COLUMN deletion_protected BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,20 @@
+-- Attribute definitions are returned in the order they were declared in, and can be reordered
+ALTER TABLE app_data.node_type_attributes ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE app_data.edge_type_attribute ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+
+-- Existing attributes keep the alphabetical order they were effectively shown in
+UPDATE app_data.node_type_attributes a
+SET position = o.position
+FROM (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY type_id ORDER BY name, id) - 1 AS position
+    FROM app_data.node_type_attributes
+) o
+WHERE a.id = o.id;
+
+UPDATE app_data.edge_type_attribute a
+SET position = o.position
+FROM (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY type_id ORDER BY name, id) - 1 AS position
+    FROM app_data.edge_type_attribute
+) o
+WHERE a.id = o.id;
@@ -0,0 +1,13 @@
+-- Named sets of vertices a user collects while investigating a graph. Private to their
+-- creator unless shared with everyone who can read the graph
+CREATE TABLE app_data.selection (
+    id UUID PRIMARY KEY,
+    graph_id TEXT NOT NULL REFERENCES app_data.graph_info(graph_id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    node_ids BIGINT[] NOT NULL DEFAULT '{}',
+    shared BOOLEAN NOT NULL DEFAULT false,
+    created_by UUID NOT NULL REFERENCES app_data.user(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX idx_selection_graph_id ON app_data.selection (graph_id, created_by);
@@ -0,0 +1,2 @@
+-- Match node names ignoring case, for lookups and the uniqueness check
+ALTER TABLE app_data.graph_info ADD COLUMN case_insensitive_names BOOLEAN NOT NULL DEFAULT false;
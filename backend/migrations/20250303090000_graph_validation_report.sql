@@ -0,0 +1,7 @@
+-- Latest schema validation report of each graph, replaced by every validation job
+CREATE TABLE app_data.graph_validation_report (
+    graph_id TEXT PRIMARY KEY REFERENCES app_data.graph_info(graph_id) ON DELETE CASCADE,
+    job_id UUID NOT NULL,
+    report JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
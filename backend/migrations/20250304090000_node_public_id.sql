@@ -0,0 +1,9 @@
+-- Short stable node ids used in URLs, e.g. nK3F9QZ2A. The id is also stored on the vertex as
+-- the public_id property; this table keeps it unique per graph and maps it to the vertex
+CREATE TABLE app_data.node_public_id (
+    graph_id TEXT NOT NULL REFERENCES app_data.graph_info(graph_id) ON DELETE CASCADE,
+    public_id TEXT NOT NULL,
+    vertex_id BIGINT NOT NULL,
+    PRIMARY KEY (graph_id, public_id),
+    UNIQUE (graph_id, vertex_id)
+);
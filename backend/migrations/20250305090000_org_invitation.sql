@@ -0,0 +1,12 @@
+-- Pending org memberships for people without an account yet. Emails are stored lowercased
+-- and the invitation turns into a membership when a user signs up with that email
+CREATE TABLE app_data.org_invitation (
+    org_id UUID NOT NULL REFERENCES app_data.org(id) ON DELETE CASCADE,
+    email TEXT NOT NULL,
+    role TEXT NOT NULL,
+    invited_by UUID NOT NULL REFERENCES app_data.user(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (org_id, email),
+    CHECK (role <> '')
+);
+CREATE INDEX idx_org_invitation_email ON app_data.org_invitation (email);
@@ -0,0 +1,6 @@
+-- Attributes being phased out. Deprecated attributes are rejected on new writes but kept
+-- on the nodes that already have them, optionally naming the attribute to use instead
+ALTER TABLE app_data.node_type_attributes
+    ADD COLUMN deprecated BOOLEAN NOT NULL DEFAULT false,
+    ADD COLUMN deprecated_reason TEXT,
+    ADD COLUMN replaced_by TEXT;
@@ -0,0 +1,21 @@
+-- Read-only links to a frozen copy of some nodes of a graph, for people without an account.
+-- Only a hash of the token is kept, it is shown once when the share is created
+CREATE TABLE app_data.graph_share (
+    id UUID PRIMARY KEY,
+    graph_id TEXT NOT NULL REFERENCES app_data.graph_info(graph_id) ON DELETE CASCADE,
+    label TEXT,
+    token_hash TEXT NOT NULL UNIQUE,
+    passcode_hash TEXT,
+    -- Nodes and the edges among them, as they were when the share was created
+    snapshot JSONB NOT NULL,
+    node_count INTEGER NOT NULL,
+    edge_count INTEGER NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_by UUID NOT NULL REFERENCES app_data.user(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    revoked_at TIMESTAMPTZ,
+    access_count BIGINT NOT NULL DEFAULT 0,
+    last_accessed_at TIMESTAMPTZ,
+    failed_passcode_attempts INTEGER NOT NULL DEFAULT 0
+);
+CREATE INDEX idx_graph_share_graph_id ON app_data.graph_share (graph_id, created_at DESC);
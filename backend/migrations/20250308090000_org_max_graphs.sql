@@ -0,0 +1,2 @@
+-- Per-org override of the ORG_MAX_GRAPHS setting. NULL follows the setting, 0 lifts the limit
+ALTER TABLE app_data.org ADD COLUMN max_graphs INTEGER CHECK (max_graphs >= 0);
@@ -0,0 +1,4 @@
+-- Bumped after every data write to the graph, so a read can tell whether it reflects a write
+ALTER TABLE app_data.graph_info
+    ADD COLUMN revision BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN last_activity_at TIMESTAMPTZ;
@@ -0,0 +1,7 @@
+-- Longest string value, in characters, an attribute accepts on new writes. NULL falls
+-- back to the server-wide cap
+ALTER TABLE app_data.node_type_attributes
+    ADD COLUMN max_length INTEGER CHECK (max_length > 0);
+
+ALTER TABLE app_data.edge_type_attribute
+    ADD COLUMN max_length INTEGER CHECK (max_length > 0);
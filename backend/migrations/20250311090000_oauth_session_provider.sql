@@ -0,0 +1,4 @@
+-- Provider a login was started with, so the callback verifies the id token with the same
+-- one. Logins in flight before this were all with Google
+ALTER TABLE app_data.oauth_session
+    ADD COLUMN provider TEXT NOT NULL DEFAULT 'google';
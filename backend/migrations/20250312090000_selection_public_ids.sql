@@ -0,0 +1,13 @@
+-- Selections hold the public ids of their nodes instead of vertex ids, which change when a
+-- node is recreated. Members without a public id are dropped, as they would be on the
+-- next read of the selection
+ALTER TABLE app_data.selection ADD COLUMN node_public_ids TEXT[] NOT NULL DEFAULT '{}';
+UPDATE app_data.selection s
+SET node_public_ids = ARRAY(
+    SELECT p.public_id
+    FROM unnest(s.node_ids) WITH ORDINALITY AS m(vertex_id, ord)
+    JOIN app_data.node_public_id p ON p.graph_id = s.graph_id AND p.vertex_id = m.vertex_id
+    ORDER BY m.ord
+);
+ALTER TABLE app_data.selection DROP COLUMN node_ids;
+ALTER TABLE app_data.selection RENAME COLUMN node_public_ids TO node_ids;
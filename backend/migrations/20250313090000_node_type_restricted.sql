@@ -0,0 +1,2 @@
+-- Nodes of a restricted type are only listed, counted and exported for graph admins
+ALTER TABLE app_data.node_types ADD COLUMN restricted BOOLEAN NOT NULL DEFAULT FALSE;
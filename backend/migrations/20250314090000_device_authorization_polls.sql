@@ -0,0 +1,3 @@
+-- When the client last polled with the device code, so polls faster than the interval it
+-- was given are told to slow down
+ALTER TABLE app_data.device_authorization ADD COLUMN last_polled_at TIMESTAMPTZ;
@@ -0,0 +1,66 @@
+use crate::auth::Auth;
+use crate::catalog::{reconcile_catalog, CatalogReport};
+use crate::config::AppState;
+use crate::error::ApiError;
+use crate::user::User;
+use axum::{
+    extract::{Extension, State},
+    http::StatusCode,
+    Json,
+};
+use serde_json::Value as JsonValue;
+use tracing::{error, info};
+
+// The authenticated user, if they are a superadmin
+fn require_superadmin(auth: Auth) -> Result<User, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+    if !user.is_superadmin() {
+        return Err(ApiError::forbidden(
+            "Only superadmins can use admin endpoints",
+        ));
+    }
+    Ok(user)
+}
+
+// The configuration this process started with, secrets redacted
+pub async fn get_config(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+) -> Result<(StatusCode, Json<JsonValue>), ApiError> {
+    let user = require_superadmin(auth)?;
+
+    info!("User {} fetched the configuration", user.id);
+    Ok((StatusCode::OK, Json(state.redacted_config.as_ref().clone())))
+}
+
+// Graphs and types whose metadata and AGE catalog entries disagree
+pub async fn get_catalog_report(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+) -> Result<Json<CatalogReport>, ApiError> {
+    require_superadmin(auth)?;
+
+    let report = reconcile_catalog(&state.pool, false).await.map_err(|e| {
+        error!("Failed to check the AGE catalog: {}", e);
+        ApiError::InternalServerError
+    })?;
+    Ok(Json(report))
+}
+
+// Check like `get_catalog_report` and repair what can be repaired safely
+pub async fn repair_catalog(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+) -> Result<Json<CatalogReport>, ApiError> {
+    let user = require_superadmin(auth)?;
+
+    info!("User {} is repairing the AGE catalog", user.id);
+    let report = reconcile_catalog(&state.pool, true).await.map_err(|e| {
+        error!("Failed to repair the AGE catalog: {}", e);
+        ApiError::InternalServerError
+    })?;
+    Ok(Json(report))
+}
@@ -5,9 +5,121 @@ use sqlx::postgres::{PgRow, PgTypeInfo, PgValueRef, Postgres};
 use sqlx::{FromRow, Row};
 use tracing::{debug, error};
 
+// A decoded agtype value. AGE tags graph entities with a `::vertex`, `::edge` or
+// `::path` suffix; anything else (counts, strings, maps, lists...) is plain JSON
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum AgValue {
+    Vertex(Vertex),
+    Edge(Edge),
+    Path(Path),
+    Scalar(JsonValue),
+}
+
+impl AgValue {
+    fn kind(&self) -> &'static str {
+        match self {
+            AgValue::Vertex(_) => "vertex",
+            AgValue::Edge(_) => "edge",
+            AgValue::Path(_) => "path",
+            AgValue::Scalar(_) => "scalar",
+        }
+    }
+
+    // The JSON value of a scalar such as a count, label or property. Bare scalars carry
+    // no `::type` suffix, e.g. `42`, `"vABC"`, `true`
+    pub fn into_scalar(self) -> Result<JsonValue, serde_json::Error> {
+        match self {
+            AgValue::Scalar(value) => Ok(value),
+            other => Err(decode_error(&format!(
+                "expected scalar, found {}",
+                other.kind()
+            ))),
+        }
+    }
+
+    // Parse the text form of an agtype value. The JSON value is read first and only what
+    // follows it is taken as the suffix, so "::" inside string properties is never mistaken
+    // for one
+    pub fn parse(text: &str) -> Result<Self, serde_json::Error> {
+        let text = text.trim_start_matches(char::is_control).trim();
+        // Path elements carry their own suffixes, so the list as a whole isn't JSON
+        if let Some(content) = text.strip_suffix("::path") {
+            return parse_path(content)
+                .and_then(Path::from_elements)
+                .map(AgValue::Path);
+        }
+
+        let mut stream = serde_json::Deserializer::from_str(text).into_iter::<JsonValue>();
+        let value = stream
+            .next()
+            .ok_or_else(|| decode_error("empty agtype value"))??;
+        let rest = text[stream.byte_offset()..].trim_start();
+        let suffix = match rest.strip_prefix("::") {
+            Some(suffix) => suffix.trim_end(),
+            None if rest.is_empty() => return Ok(AgValue::Scalar(value)),
+            None => return Err(decode_error(&format!("trailing characters: {}", rest))),
+        };
+        debug!("Raw Content: {:?}", value);
+        debug!("Type: {}", suffix);
+        match suffix {
+            "vertex" => Ok(AgValue::Vertex(serde_json::from_value(value)?)),
+            "edge" => Ok(AgValue::Edge(serde_json::from_value(value)?)),
+            // Numerics are written as e.g. 3.14::numeric
+            "numeric" => Ok(AgValue::Scalar(value)),
+            other => Err(decode_error(&format!("unsupported agtype: {}", other))),
+        }
+    }
+}
+
+// A path is a list of alternating vertices and edges, each carrying its own suffix:
+// [{...}::vertex, {...}::edge, {...}::vertex]
+fn parse_path(content: &str) -> Result<Vec<AgValue>, serde_json::Error> {
+    let mut rest = content
+        .trim()
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .ok_or_else(|| decode_error("path must be a list"))?
+        .trim_start();
+
+    let mut elements = Vec::new();
+    while !rest.is_empty() {
+        // Read one JSON object, then the suffix that follows it
+        let mut stream = serde_json::Deserializer::from_str(rest).into_iter::<JsonValue>();
+        let value = stream
+            .next()
+            .ok_or_else(|| decode_error("unexpected end of path"))??;
+        rest = rest[stream.byte_offset()..]
+            .strip_prefix("::")
+            .ok_or_else(|| decode_error("path element is missing its type"))?;
+        let suffix_len = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let element = match &rest[..suffix_len] {
+            "vertex" => AgValue::Vertex(serde_json::from_value(value)?),
+            "edge" => AgValue::Edge(serde_json::from_value(value)?),
+            other => {
+                return Err(decode_error(&format!(
+                    "unexpected path element type: {}",
+                    other
+                )))
+            }
+        };
+        elements.push(element);
+
+        rest = rest[suffix_len..].trim_start();
+        rest = rest.strip_prefix(',').unwrap_or(rest).trim_start();
+    }
+    Ok(elements)
+}
+
+fn decode_error(message: &str) -> serde_json::Error {
+    <serde_json::Error as serde::de::Error>::custom(message)
+}
+
 // Custom type to represent agtype
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct AgType(pub JsonValue);
+#[derive(Debug, Clone)]
+pub struct AgType(pub AgValue);
 
 // Implement Type for AgType to tell SQLx about the custom type
 impl sqlx::Type<Postgres> for AgType {
@@ -24,17 +136,135 @@ impl<'r> FromRow<'r, PgRow> for AgType {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Vertex {
+    pub id: i64,
     pub label: String,
     pub properties: JsonValue,
 }
 
+impl TryFrom<AgValue> for Vertex {
+    type Error = serde_json::Error;
+
+    fn try_from(value: AgValue) -> Result<Self, Self::Error> {
+        match value {
+            AgValue::Vertex(vertex) => Ok(vertex),
+            other => Err(decode_error(&format!(
+                "expected vertex, found {}",
+                other.kind()
+            ))),
+        }
+    }
+}
+
 impl TryFrom<AgType> for Vertex {
     type Error = serde_json::Error;
 
     fn try_from(ag_type: AgType) -> Result<Self, Self::Error> {
-        serde_json::from_value(ag_type.0)
+        Vertex::try_from(ag_type.0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Edge {
+    pub id: i64,
+    pub label: String,
+    pub start_id: i64,
+    pub end_id: i64,
+    pub properties: JsonValue,
+}
+
+impl TryFrom<AgValue> for Edge {
+    type Error = serde_json::Error;
+
+    fn try_from(value: AgValue) -> Result<Self, Self::Error> {
+        match value {
+            AgValue::Edge(edge) => Ok(edge),
+            other => Err(decode_error(&format!(
+                "expected edge, found {}",
+                other.kind()
+            ))),
+        }
+    }
+}
+
+impl TryFrom<AgType> for Edge {
+    type Error = serde_json::Error;
+
+    fn try_from(ag_type: AgType) -> Result<Self, Self::Error> {
+        Edge::try_from(ag_type.0)
+    }
+}
+
+// A path through the graph, as returned for `MATCH p = ... RETURN p`. It alternates
+// vertices and edges: `edges[i]` joins `vertices[i]` and `vertices[i + 1]`. A path with
+// no vertices is empty, otherwise it has one more vertex than it has edges
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Path {
+    pub vertices: Vec<Vertex>,
+    pub edges: Vec<Edge>,
+}
+
+impl Path {
+    // Split the decoded elements of a path, checking that they alternate starting and
+    // ending with a vertex
+    fn from_elements(elements: Vec<AgValue>) -> Result<Self, serde_json::Error> {
+        let mut path = Path::default();
+        for (i, element) in elements.into_iter().enumerate() {
+            match (i % 2, element) {
+                (0, AgValue::Vertex(vertex)) => path.vertices.push(vertex),
+                (1, AgValue::Edge(edge)) => path.edges.push(edge),
+                (_, other) => {
+                    return Err(decode_error(&format!(
+                        "path element {} is a {}, vertices and edges must alternate",
+                        i,
+                        other.kind()
+                    )))
+                }
+            }
+        }
+        if !path.vertices.is_empty() && path.vertices.len() == path.edges.len() {
+            return Err(decode_error("path must end with a vertex"));
+        }
+        Ok(path)
+    }
+}
+
+impl TryFrom<AgValue> for Path {
+    type Error = serde_json::Error;
+
+    fn try_from(value: AgValue) -> Result<Self, Self::Error> {
+        match value {
+            AgValue::Path(path) => Ok(path),
+            other => Err(decode_error(&format!(
+                "expected path, found {}",
+                other.kind()
+            ))),
+        }
+    }
+}
+
+impl TryFrom<AgType> for Path {
+    type Error = serde_json::Error;
+
+    fn try_from(ag_type: AgType) -> Result<Self, Self::Error> {
+        Path::try_from(ag_type.0)
+    }
+}
+
+impl sqlx::Type<Postgres> for Path {
+    fn type_info() -> PgTypeInfo {
+        AgType::type_info()
+    }
+}
+
+// Lets a path column be read directly, e.g. `row.try_get::<Path, _>("p")`
+impl<'r> Decode<'r, Postgres> for Path {
+    fn decode(
+        value: PgValueRef<'r>,
+    ) -> Result<Self, Box<dyn std::error::Error + 'static + Send + Sync>> {
+        let ag_type = AgType::decode(value)?;
+        Ok(Path::try_from(ag_type)?)
     }
 }
 
@@ -42,35 +272,217 @@ impl<'r> Decode<'r, Postgres> for AgType {
     fn decode(
         value: PgValueRef<'r>,
     ) -> Result<Self, Box<dyn std::error::Error + 'static + Send + Sync>> {
-        // Convert the value to a string
-        let value_str: String = value.as_str().unwrap().to_string();
-
-        // Split the string by "::"
-        let parts: Vec<&str> = value_str.split("::").collect();
-
-        // Ensure there are at least two parts (content and type)
-        if parts.len() >= 2 {
-            let content = parts[0].trim(); // First part is the content
-            let value_type = parts[parts.len() - 1].trim(); // Last part is the type
-
-            debug!("Raw Content: {:?}", content);
-            debug!("Type: {}", value_type);
-
-            // Check if the type is "vertex"
-            if value_type == "vertex" {
-                // Handle vertex type by parsing the content as a Node
-                let content = content.trim_start_matches(char::is_control);
-                let vertex: Vertex = serde_json::from_str(content)?;
-                Ok(AgType(serde_json::to_value(vertex)?))
-            } else {
-                // Reject other types
-                error!("Unsupported type: {}", value_type);
-                Err("Unsupported type: expected 'vertex'".into())
-            }
-        } else {
-            // Handle invalid format (missing type or content)
-            error!("Invalid format: expected content::type");
-            Err("Invalid format: expected content::type".into())
+        let value_str = value.as_str()?;
+        AgValue::parse(value_str).map(AgType).map_err(|e| {
+            error!("Failed to decode agtype: {}", e);
+            e.into()
+        })
+    }
+}
+
+// AGE lookup failures worth telling apart from other database errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AgLookupError {
+    GraphNotFound,
+    LabelNotFound,
+}
+
+impl AgLookupError {
+    // Recognise AGE's "graph ... does not exist" (invalid_schema_name) and missing label
+    // errors, which surface either as AGE's own message or as an undefined_table on the
+    // label's backing relation
+    pub fn classify(e: &sqlx::Error) -> Option<Self> {
+        let sqlx::Error::Database(db_err) = e else {
+            return None;
+        };
+        let message = db_err.message();
+        if !message.contains("does not exist") {
+            return None;
+        }
+        match db_err.code().as_deref() {
+            Some("3F000") => Some(AgLookupError::GraphNotFound),
+            _ if message.starts_with("graph ") => Some(AgLookupError::GraphNotFound),
+            Some("42P01") => Some(AgLookupError::LabelNotFound),
+            _ if message.starts_with("label ") => Some(AgLookupError::LabelNotFound),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const VERTEX: &str =
+        r#"{"id": 844424930131969, "label": "Person", "properties": {"name": "Ada"}}::vertex"#;
+    const EDGE: &str = r#"{"id": 1125899906842625, "label": "KNOWS", "end_id": 844424930131970, "start_id": 844424930131969, "properties": {}}::edge"#;
+
+    #[test]
+    fn parses_vertex_suffix() {
+        let vertex = Vertex::try_from(AgValue::parse(VERTEX).unwrap()).unwrap();
+        assert_eq!(vertex.id, 844424930131969);
+        assert_eq!(vertex.label, "Person");
+        assert_eq!(vertex.properties, json!({"name": "Ada"}));
+    }
+
+    #[test]
+    fn parses_edge_suffix() {
+        let edge = Edge::try_from(AgValue::parse(EDGE).unwrap()).unwrap();
+        assert_eq!(edge.label, "KNOWS");
+        assert_eq!(edge.start_id, 844424930131969);
+        assert_eq!(edge.end_id, 844424930131970);
+    }
+
+    #[test]
+    fn parses_path_suffix() {
+        let text = format!("[{}, {}, {}]::path", VERTEX, EDGE, VERTEX);
+        let path = Path::try_from(AgValue::parse(&text).unwrap()).unwrap();
+        assert_eq!(path.vertices.len(), 2);
+        assert_eq!(path.edges.len(), 1);
+    }
+
+    #[test]
+    fn parses_numeric_suffix() {
+        let value = AgValue::parse("2.5::numeric")
+            .unwrap()
+            .into_scalar()
+            .unwrap();
+        assert_eq!(value, json!(2.5));
+    }
+
+    #[test]
+    fn parses_bare_scalars() {
+        for (text, expected) in [
+            ("42", json!(42)),
+            (r#""vABC""#, json!("vABC")),
+            ("true", json!(true)),
+            ("null", json!(null)),
+            (r#"{"a": [1, 2]}"#, json!({"a": [1, 2]})),
+        ] {
+            assert_eq!(
+                AgValue::parse(text).unwrap().into_scalar().unwrap(),
+                expected
+            );
         }
     }
+
+    // Captured from AGE, with property strings that look like suffixes
+    const TRICKY_VERTEX: &str = r#"{"id": 844424930131971, "label": "Note", "properties": {"text": "a::vertex, b::edge", "tag": "x}::path"}}::vertex"#;
+    const TRICKY_EDGE: &str = r#"{"id": 1125899906842626, "label": "CITES", "end_id": 844424930131971, "start_id": 844424930131969, "properties": {"why": "]::edge, {"}}::edge"#;
+
+    #[test]
+    fn parse_ignores_suffixes_inside_strings() {
+        let vertex = Vertex::try_from(AgValue::parse(TRICKY_VERTEX).unwrap()).unwrap();
+        assert_eq!(vertex.properties["text"], "a::vertex, b::edge");
+        assert_eq!(vertex.properties["tag"], "x}::path");
+
+        let edge = Edge::try_from(AgValue::parse(TRICKY_EDGE).unwrap()).unwrap();
+        assert_eq!(edge.properties["why"], "]::edge, {");
+
+        let value = AgValue::parse(r#""ends with ::vertex""#).unwrap();
+        assert_eq!(value.into_scalar().unwrap(), json!("ends with ::vertex"));
+    }
+
+    #[test]
+    fn parse_tolerates_surrounding_whitespace_and_control_characters() {
+        let text = format!("\u{1}  {}  ", VERTEX);
+        assert!(Vertex::try_from(AgValue::parse(&text).unwrap()).is_ok());
+    }
+
+    #[test]
+    fn parse_rejects_trailing_characters() {
+        assert!(AgValue::parse(r#"{"a": 1} extra"#).is_err());
+    }
+
+    #[test]
+    fn parse_path_reads_elements_with_tricky_properties() {
+        let content = format!("[{}, {}, {}]", VERTEX, TRICKY_EDGE, TRICKY_VERTEX);
+        let elements = parse_path(&content).unwrap();
+        assert_eq!(elements.len(), 3);
+        assert!(matches!(elements[0], AgValue::Vertex(_)));
+        assert!(matches!(elements[1], AgValue::Edge(_)));
+        let AgValue::Vertex(vertex) = &elements[2] else {
+            panic!("expected a vertex, found {:?}", elements[2]);
+        };
+        assert_eq!(vertex.properties["text"], "a::vertex, b::edge");
+    }
+
+    #[test]
+    fn parse_path_reads_an_empty_path() {
+        assert!(parse_path("[]").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_path_rejects_malformed_lists() {
+        assert!(parse_path(VERTEX).is_err());
+        let untyped = r#"[{"id": 1, "label": "A", "properties": {}}]"#;
+        assert!(parse_path(untyped).is_err());
+        let scalar = "[1::numeric]";
+        assert!(parse_path(scalar).is_err());
+    }
+
+    fn vertex(id: i64) -> AgValue {
+        AgValue::Vertex(Vertex {
+            id,
+            label: "Person".to_string(),
+            properties: json!({}),
+        })
+    }
+
+    fn edge(id: i64, start_id: i64, end_id: i64) -> AgValue {
+        AgValue::Edge(Edge {
+            id,
+            label: "KNOWS".to_string(),
+            start_id,
+            end_id,
+            properties: json!({}),
+        })
+    }
+
+    #[test]
+    fn path_splits_alternating_vertices_and_edges() {
+        let path = Path::from_elements(vec![
+            vertex(1),
+            edge(10, 1, 2),
+            vertex(2),
+            edge(11, 3, 2),
+            vertex(3),
+        ])
+        .unwrap();
+        let vertex_ids: Vec<i64> = path.vertices.iter().map(|v| v.id).collect();
+        let edge_ids: Vec<i64> = path.edges.iter().map(|e| e.id).collect();
+        assert_eq!(vertex_ids, [1, 2, 3]);
+        assert_eq!(edge_ids, [10, 11]);
+    }
+
+    #[test]
+    fn path_of_one_vertex_or_none() {
+        let path = Path::from_elements(vec![vertex(1)]).unwrap();
+        assert_eq!((path.vertices.len(), path.edges.len()), (1, 0));
+        let path = Path::from_elements(Vec::new()).unwrap();
+        assert!(path.vertices.is_empty() && path.edges.is_empty());
+    }
+
+    #[test]
+    fn path_rejects_elements_out_of_order() {
+        for elements in [
+            vec![edge(10, 1, 2)],
+            vec![vertex(1), vertex(2)],
+            vec![vertex(1), edge(10, 1, 2), edge(11, 2, 3), vertex(3)],
+            vec![vertex(1), AgValue::Scalar(json!(1)), vertex(2)],
+        ] {
+            assert!(Path::from_elements(elements).is_err());
+        }
+    }
+
+    #[test]
+    fn path_rejects_ending_with_an_edge() {
+        assert!(Path::from_elements(vec![vertex(1), edge(10, 1, 2)]).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_suffix() {
+        assert!(AgValue::parse("1::bogus").is_err());
+    }
 }
@@ -0,0 +1,140 @@
+use serde::Deserialize;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+// Node and edge type attribute definitions both carry a `position`, set from the order they
+// were declared in. Reordering works the same way for either table
+#[derive(Debug, Clone, Copy)]
+pub enum AttributeTable {
+    Node,
+    Edge,
+}
+
+impl AttributeTable {
+    fn name(&self) -> &'static str {
+        match self {
+            AttributeTable::Node => "app_data.node_type_attributes",
+            AttributeTable::Edge => "app_data.edge_type_attribute",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReorderAttributesRequest {
+    // Every attribute id of the type, in the new order
+    pub attribute_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReorderError {
+    #[error("attribute_ids must list every attribute of the type exactly once")]
+    Mismatch,
+
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+// Set the positions of a type's own attributes to the order of `attribute_ids`, which must
+// be a permutation of them. Rows are locked first so concurrent reorders don't interleave
+pub async fn reorder(
+    pool: &sqlx::PgPool,
+    table: AttributeTable,
+    type_id: &str,
+    attribute_ids: &[Uuid],
+) -> Result<(), ReorderError> {
+    let mut transaction = pool.begin().await?;
+
+    let select_query = format!(
+        "SELECT id FROM {} WHERE type_id = $1 FOR UPDATE",
+        table.name()
+    );
+    let existing: Vec<Uuid> = sqlx::query_scalar(&select_query)
+        .bind(type_id)
+        .fetch_all(&mut *transaction)
+        .await?;
+
+    check_permutation(&existing, attribute_ids)?;
+
+    let update_query = format!(
+        r#"
+        UPDATE {} a SET position = (o.ord - 1)::int
+        FROM unnest($2::uuid[]) WITH ORDINALITY AS o(id, ord)
+        WHERE a.type_id = $1 AND a.id = o.id
+        "#,
+        table.name()
+    );
+    sqlx::query(&update_query)
+        .bind(type_id)
+        .bind(attribute_ids)
+        .execute(&mut *transaction)
+        .await?;
+
+    transaction.commit().await?;
+    Ok(())
+}
+
+// `attribute_ids` must list every id of `existing` exactly once, and nothing else
+fn check_permutation(existing: &[Uuid], attribute_ids: &[Uuid]) -> Result<(), ReorderError> {
+    let requested: HashSet<&Uuid> = attribute_ids.iter().collect();
+    if requested.len() != attribute_ids.len()
+        || existing.len() != attribute_ids.len()
+        || !existing.iter().all(|id| requested.contains(id))
+    {
+        return Err(ReorderError::Mismatch);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(n: usize) -> Vec<Uuid> {
+        (0..n).map(|_| Uuid::new_v4()).collect()
+    }
+
+    fn is_mismatch(result: Result<(), ReorderError>) -> bool {
+        matches!(result, Err(ReorderError::Mismatch))
+    }
+
+    #[test]
+    fn tables_are_the_attribute_tables() {
+        assert_eq!(AttributeTable::Node.name(), "app_data.node_type_attributes");
+        assert_eq!(AttributeTable::Edge.name(), "app_data.edge_type_attribute");
+    }
+
+    // Node and edge attributes share the check, each case applies to both
+    #[test]
+    fn any_permutation_is_accepted() {
+        let existing = ids(3);
+        let reversed: Vec<Uuid> = existing.iter().rev().copied().collect();
+        assert!(check_permutation(&existing, &reversed).is_ok());
+        assert!(check_permutation(&existing, &existing).is_ok());
+        assert!(check_permutation(&[], &[]).is_ok());
+    }
+
+    #[test]
+    fn missing_ids_are_rejected() {
+        let existing = ids(3);
+        assert!(is_mismatch(check_permutation(&existing, &existing[..2])));
+        assert!(is_mismatch(check_permutation(&existing, &[])));
+    }
+
+    #[test]
+    fn duplicated_ids_are_rejected() {
+        let existing = ids(3);
+        let duplicated = [existing[0], existing[1], existing[1]];
+        assert!(is_mismatch(check_permutation(&existing, &duplicated)));
+        let extra = [existing[0], existing[1], existing[2], existing[0]];
+        assert!(is_mismatch(check_permutation(&existing, &extra)));
+    }
+
+    #[test]
+    fn ids_of_another_type_are_rejected() {
+        let (existing, other_type) = (ids(2), ids(1));
+        let mixed = [existing[0], other_type[0]];
+        assert!(is_mismatch(check_permutation(&existing, &mixed)));
+        let appended = [existing[0], existing[1], other_type[0]];
+        assert!(is_mismatch(check_permutation(&existing, &appended)));
+    }
+}
@@ -0,0 +1,212 @@
+use crate::auth::AuthProvider;
+use serde_json::Value as JsonValue;
+use std::env;
+
+// Which ID token claims supply the profile fields of a user. Each field lists claim names
+// tried in order, the first one present wins. Defaults are per provider, and each field can
+// be overridden with a comma separated list in `<PROVIDER>_CLAIMS_<FIELD>`, e.g.
+// GOOGLE_CLAIMS_FIRST_NAME=given_name,name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClaimMapping {
+    pub first_name: Vec<String>,
+    pub last_name: Vec<String>,
+    pub email: Vec<String>,
+    pub picture: Vec<String>,
+}
+
+// Profile fields read from a set of claims. Absent claims give empty names and no picture
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MappedClaims {
+    pub first_name: String,
+    pub last_name: String,
+    pub email: Option<String>,
+    pub picture_url: Option<String>,
+}
+
+fn claim_names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+impl ClaimMapping {
+    // Built-in mapping of the provider
+    pub fn defaults(provider: AuthProvider) -> Self {
+        match provider {
+            AuthProvider::Google => Self {
+                first_name: claim_names(&["given_name"]),
+                last_name: claim_names(&["family_name"]),
+                email: claim_names(&["email"]),
+                picture: claim_names(&["picture"]),
+            },
+            // Microsoft has no locale or picture claims, and may only send the email
+            // as preferred_username
+            AuthProvider::Microsoft => Self {
+                first_name: claim_names(&["given_name", "name"]),
+                last_name: claim_names(&["family_name"]),
+                email: claim_names(&["email", "preferred_username"]),
+                picture: Vec::new(),
+            },
+        }
+    }
+
+    // The provider's defaults with any overrides from the environment applied
+    pub fn from_env(provider: AuthProvider) -> Self {
+        let mut mapping = Self::defaults(provider);
+        let prefix = match provider {
+            AuthProvider::Google => "GOOGLE",
+            AuthProvider::Microsoft => "MICROSOFT",
+        };
+        for (field, names) in [
+            ("FIRST_NAME", &mut mapping.first_name),
+            ("LAST_NAME", &mut mapping.last_name),
+            ("EMAIL", &mut mapping.email),
+            ("PICTURE", &mut mapping.picture),
+        ] {
+            if let Ok(value) = env::var(format!("{}_CLAIMS_{}", prefix, field)) {
+                *names = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_string)
+                    .collect();
+            }
+        }
+        mapping
+    }
+
+    // Read the profile fields from serialized ID token claims. Localized claims are
+    // serialized as "<claim>#<language tag>", the variant matching the `locale` claim is
+    // preferred over the plain one when both exist
+    pub fn apply(&self, claims: &JsonValue) -> MappedClaims {
+        let locale = claims.get("locale").and_then(JsonValue::as_str);
+        let find = |names: &[String]| -> Option<String> {
+            names.iter().find_map(|name| {
+                locale
+                    .and_then(|locale| string_claim(claims, &format!("{}#{}", name, locale)))
+                    .or_else(|| string_claim(claims, name))
+            })
+        };
+
+        MappedClaims {
+            first_name: find(&self.first_name).unwrap_or_default(),
+            last_name: find(&self.last_name).unwrap_or_default(),
+            email: find(&self.email),
+            picture_url: find(&self.picture),
+        }
+    }
+}
+
+// A non-empty string claim
+fn string_claim(claims: &JsonValue, name: &str) -> Option<String> {
+    claims
+        .get(name)
+        .and_then(JsonValue::as_str)
+        .filter(|value| !value.trim().is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn mapped(provider: AuthProvider, claims: JsonValue) -> MappedClaims {
+        ClaimMapping::defaults(provider).apply(&claims)
+    }
+
+    #[test]
+    fn google_claims() {
+        let claims = json!({
+            "sub": "1234",
+            "email": "ada@example.com",
+            "given_name": "Ada",
+            "family_name": "Lovelace",
+            "name": "Ada Lovelace",
+            "picture": "https://example.com/ada.png",
+        });
+        assert_eq!(
+            mapped(AuthProvider::Google, claims),
+            MappedClaims {
+                first_name: "Ada".to_string(),
+                last_name: "Lovelace".to_string(),
+                email: Some("ada@example.com".to_string()),
+                picture_url: Some("https://example.com/ada.png".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn google_prefers_the_localized_claim() {
+        let claims = json!({
+            "locale": "fr",
+            "given_name": "Ada",
+            "given_name#fr": "Adèle",
+            "family_name#de": "Lovelace",
+        });
+        let mapped = mapped(AuthProvider::Google, claims);
+        assert_eq!(mapped.first_name, "Adèle");
+        assert_eq!(mapped.last_name, "");
+    }
+
+    #[test]
+    fn google_without_optional_claims() {
+        let claims = json!({ "sub": "1234", "email": "ada@example.com", "given_name": " " });
+        assert_eq!(
+            mapped(AuthProvider::Google, claims),
+            MappedClaims {
+                email: Some("ada@example.com".to_string()),
+                ..MappedClaims::default()
+            }
+        );
+    }
+
+    #[test]
+    fn microsoft_claims() {
+        let claims = json!({
+            "sub": "abcd",
+            "email": "ada@example.com",
+            "preferred_username": "ada@contoso.com",
+            "given_name": "Ada",
+            "family_name": "Lovelace",
+            "picture": "https://example.com/ada.png",
+        });
+        assert_eq!(
+            mapped(AuthProvider::Microsoft, claims),
+            MappedClaims {
+                first_name: "Ada".to_string(),
+                last_name: "Lovelace".to_string(),
+                email: Some("ada@example.com".to_string()),
+                picture_url: None,
+            }
+        );
+    }
+
+    #[test]
+    fn microsoft_falls_back_to_name_and_preferred_username() {
+        let claims = json!({
+            "sub": "abcd",
+            "name": "Ada Lovelace",
+            "preferred_username": "ada@contoso.com",
+        });
+        assert_eq!(
+            mapped(AuthProvider::Microsoft, claims),
+            MappedClaims {
+                first_name: "Ada Lovelace".to_string(),
+                last_name: String::new(),
+                email: Some("ada@contoso.com".to_string()),
+                picture_url: None,
+            }
+        );
+    }
+
+    #[test]
+    fn overridden_mapping_tries_names_in_order() {
+        let mapping = ClaimMapping {
+            first_name: claim_names(&["nickname", "given_name"]),
+            ..ClaimMapping::defaults(AuthProvider::Google)
+        };
+        let claims = json!({ "given_name": "Ada" });
+        assert_eq!(mapping.apply(&claims).first_name, "Ada");
+        let claims = json!({ "nickname": "Countess", "given_name": "Ada" });
+        assert_eq!(mapping.apply(&claims).first_name, "Countess");
+    }
+}
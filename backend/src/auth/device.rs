@@ -0,0 +1,386 @@
+use super::{SecurityEvent, SecurityEventKind, Session};
+use crate::utils::create_id;
+use chrono::{DateTime, Utc};
+use rand::{rng, Rng};
+use sqlx::{postgres::PgRow, FromRow, Row};
+use tracing::warn;
+use uuid::Uuid;
+
+// How long a device has to be approved and redeemed
+pub const DEVICE_CODE_TTL_MINUTES: i64 = 10;
+// Minimum seconds between token polls the client is told to respect
+pub const DEVICE_POLL_INTERVAL_SECS: u64 = 5;
+// How long a session issued to a device lasts. It has no provider token to renew, so the
+// device logs in again once it ends
+pub const DEVICE_SESSION_TTL_DAYS: i64 = 30;
+
+const DEVICE_CODE_LENGTH: u64 = 48;
+// Consonants only, so user codes are easy to read out and never spell words
+const USER_CODE_ALPHABET: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+const USER_CODE_LENGTH: usize = 8;
+// Codes drawn before giving up when each collides with one already stored
+const CODE_ATTEMPTS: usize = 5;
+
+#[derive(Debug)]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    pub user_code: String,
+    pub user_id: Option<Uuid>,
+    pub federated_user_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub approved_at: Option<DateTime<Utc>>,
+    pub consumed_at: Option<DateTime<Utc>>,
+}
+
+impl<'r> FromRow<'r, PgRow> for DeviceAuthorization {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            device_code: row.try_get("device_code")?,
+            user_code: row.try_get("user_code")?,
+            user_id: row.try_get("user_id")?,
+            federated_user_id: row.try_get("federated_user_id")?,
+            created_at: row.try_get("created_at")?,
+            expires_at: row.try_get("expires_at")?,
+            approved_at: row.try_get("approved_at")?,
+            consumed_at: row.try_get("consumed_at")?,
+        })
+    }
+}
+
+// Outcome of a client polling for its session
+#[derive(Debug)]
+pub enum DeviceTokenPoll {
+    Pending,
+    // Polled again before the interval passed
+    SlowDown,
+    Expired,
+    AlreadyUsed,
+    Issued(Session),
+}
+
+impl DeviceAuthorization {
+    pub fn generate() -> Self {
+        let now = Utc::now();
+        let user_code = (0..USER_CODE_LENGTH)
+            .map(|_| USER_CODE_ALPHABET[rng().random_range(0..USER_CODE_ALPHABET.len())] as char)
+            .collect();
+        Self {
+            device_code: create_id(DEVICE_CODE_LENGTH),
+            user_code,
+            user_id: None,
+            federated_user_id: None,
+            created_at: now,
+            expires_at: now + chrono::Duration::minutes(DEVICE_CODE_TTL_MINUTES),
+            approved_at: None,
+            consumed_at: None,
+        }
+    }
+
+    // The user code as shown to people, e.g. BCDF-GHJK
+    pub fn display_user_code(&self) -> String {
+        let (first, second) = self.user_code.split_at(USER_CODE_LENGTH / 2);
+        format!("{}-{}", first, second)
+    }
+
+    // Accept user codes typed with any case, spacing or dashes
+    pub fn normalize_user_code(user_code: &str) -> String {
+        user_code
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_uppercase()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(Utc::now())
+    }
+
+    fn is_expired_at(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at <= now
+    }
+
+    // Store the authorization. User codes are short, so one can collide with a code still
+    // stored, in which case fresh codes are drawn and the insert is tried again
+    pub async fn persist(&mut self, pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+        self.persist_to(pool).await
+    }
+
+    async fn persist_to(&mut self, store: &impl DeviceCodeStore) -> Result<(), sqlx::Error> {
+        let mut attempt = 1;
+        loop {
+            match store.insert(self).await {
+                Err(sqlx::Error::Database(e))
+                    if e.is_unique_violation() && attempt < CODE_ATTEMPTS =>
+                {
+                    warn!("Device codes collided with stored ones, drawing new ones");
+                    *self = Self::generate();
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    // What a poll at `now` gets without redeeming the code, given when the previous poll
+    // was. None when the code is approved and can be redeemed
+    fn poll_outcome(
+        &self,
+        previous_poll_at: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Option<DeviceTokenPoll> {
+        if self.consumed_at.is_some() {
+            return Some(DeviceTokenPoll::AlreadyUsed);
+        }
+        if self.is_expired_at(now) {
+            return Some(DeviceTokenPoll::Expired);
+        }
+        let interval = chrono::Duration::seconds(DEVICE_POLL_INTERVAL_SECS as i64);
+        if previous_poll_at.is_some_and(|at| now - at < interval) {
+            return Some(DeviceTokenPoll::SlowDown);
+        }
+        if self.user_id.is_none() || self.federated_user_id.is_none() {
+            return Some(DeviceTokenPoll::Pending);
+        }
+        None
+    }
+
+    // Bind a pending, unexpired user code to the approving user and record it in their
+    // security event log. Returns false when there is no such pending code
+    pub async fn approve(
+        pool: &sqlx::PgPool,
+        user_code: &str,
+        session: &Session,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let query = "
+        UPDATE app_data.device_authorization
+        SET user_id = $2, federated_user_id = $3, approved_at = now()
+        WHERE user_code = $1 AND approved_at IS NULL AND expires_at > now()
+        ";
+        let result = sqlx::query(query)
+            .bind(Self::normalize_user_code(user_code))
+            .bind(session.user_id)
+            .bind(session.federated_user_id)
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        SecurityEvent::record(
+            &mut tx,
+            session.user_id,
+            SecurityEventKind::DeviceAuthorized,
+            serde_json::json!({
+                "user_code": Self::normalize_user_code(user_code),
+                "approved_from_session": session.id,
+            }),
+        )
+        .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    // Redeem an approved device code for a new session. Each code yields at most one
+    // session; `None` means the device code is unknown. Every poll is recorded, one sooner
+    // than the interval after the previous is told to slow down
+    pub async fn poll(
+        pool: &sqlx::PgPool,
+        device_code: &str,
+    ) -> Result<Option<DeviceTokenPoll>, sqlx::Error> {
+        let query = "
+        WITH previous AS (
+            SELECT device_code, last_polled_at FROM app_data.device_authorization
+            WHERE device_code = $1
+            FOR UPDATE
+        )
+        UPDATE app_data.device_authorization d SET last_polled_at = now()
+        FROM previous WHERE d.device_code = previous.device_code
+        RETURNING d.*, previous.last_polled_at AS previous_poll_at
+        ";
+        let Some(row) = sqlx::query(query)
+            .bind(device_code)
+            .fetch_optional(pool)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let authorization = DeviceAuthorization::from_row(&row)?;
+        let previous_poll_at: Option<DateTime<Utc>> = row.try_get("previous_poll_at")?;
+
+        if let Some(outcome) = authorization.poll_outcome(previous_poll_at, Utc::now()) {
+            return Ok(Some(outcome));
+        }
+        let (Some(user_id), Some(federated_user_id)) =
+            (authorization.user_id, authorization.federated_user_id)
+        else {
+            return Ok(Some(DeviceTokenPoll::Pending));
+        };
+
+        // Claim the code before creating the session so concurrent polls can't both win
+        let claim = "
+        UPDATE app_data.device_authorization SET consumed_at = now()
+        WHERE device_code = $1 AND consumed_at IS NULL
+        ";
+        let claimed = sqlx::query(claim).bind(device_code).execute(pool).await?;
+        if claimed.rows_affected() == 0 {
+            return Ok(Some(DeviceTokenPoll::AlreadyUsed));
+        }
+
+        // Device sessions carry no provider refresh token
+        let expiry = Utc::now() + chrono::Duration::days(DEVICE_SESSION_TTL_DAYS);
+        let session = Session::create_unrenewable(pool, user_id, federated_user_id, expiry).await?;
+        Ok(Some(DeviceTokenPoll::Issued(session)))
+    }
+}
+
+// Where new authorizations are stored, so the retry on colliding codes can be tested
+// without a database
+trait DeviceCodeStore {
+    async fn insert(&self, authorization: &DeviceAuthorization) -> Result<(), sqlx::Error>;
+}
+
+impl DeviceCodeStore for sqlx::PgPool {
+    async fn insert(&self, authorization: &DeviceAuthorization) -> Result<(), sqlx::Error> {
+        let query = "INSERT INTO app_data.device_authorization (device_code, user_code, created_at, expires_at) VALUES ($1, $2, $3, $4)";
+        sqlx::query(query)
+            .bind(&authorization.device_code)
+            .bind(&authorization.user_code)
+            .bind(authorization.created_at)
+            .bind(authorization.expires_at)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use std::sync::Mutex;
+
+    // Fails the first inserts with the given errors and records the codes tried
+    struct FakeStore {
+        failures: Mutex<Vec<sqlx::Error>>,
+        user_codes: Mutex<Vec<String>>,
+    }
+
+    impl FakeStore {
+        fn failing_with(failures: Vec<sqlx::Error>) -> Self {
+            Self {
+                failures: Mutex::new(failures),
+                user_codes: Mutex::default(),
+            }
+        }
+
+        fn user_codes(&self) -> Vec<String> {
+            self.user_codes.lock().unwrap().clone()
+        }
+    }
+
+    impl DeviceCodeStore for FakeStore {
+        async fn insert(&self, authorization: &DeviceAuthorization) -> Result<(), sqlx::Error> {
+            self.user_codes
+                .lock()
+                .unwrap()
+                .push(authorization.user_code.clone());
+            let mut failures = self.failures.lock().unwrap();
+            if failures.is_empty() {
+                return Ok(());
+            }
+            Err(failures.remove(0))
+        }
+    }
+
+    fn approved() -> DeviceAuthorization {
+        let mut authorization = DeviceAuthorization::generate();
+        authorization.user_id = Some(Uuid::new_v4());
+        authorization.federated_user_id = Some(Uuid::new_v4());
+        authorization.approved_at = Some(authorization.created_at);
+        authorization
+    }
+
+    #[tokio::test]
+    async fn colliding_codes_are_drawn_again() {
+        let store = FakeStore::failing_with(vec![
+            crate::db::database_error("23505"),
+            crate::db::database_error("23505"),
+        ]);
+        let mut authorization = DeviceAuthorization::generate();
+        authorization.persist_to(&store).await.unwrap();
+
+        let tried = store.user_codes();
+        assert_eq!(tried.len(), 3);
+        // The stored code is the one returned to the client
+        assert_eq!(tried[2], authorization.user_code);
+    }
+
+    #[tokio::test]
+    async fn code_collisions_give_up_after_a_few_attempts() {
+        let failures = (0..CODE_ATTEMPTS)
+            .map(|_| crate::db::database_error("23505"))
+            .collect();
+        let store = FakeStore::failing_with(failures);
+        let result = DeviceAuthorization::generate().persist_to(&store).await;
+
+        assert!(matches!(result, Err(sqlx::Error::Database(e)) if e.is_unique_violation()));
+        assert_eq!(store.user_codes().len(), CODE_ATTEMPTS);
+    }
+
+    #[tokio::test]
+    async fn other_insert_errors_are_not_retried() {
+        let store = FakeStore::failing_with(vec![crate::db::database_error("42P01")]);
+        let result = DeviceAuthorization::generate().persist_to(&store).await;
+
+        assert!(result.is_err());
+        assert_eq!(store.user_codes().len(), 1);
+    }
+
+    #[test]
+    fn polls_sooner_than_the_interval_slow_down() {
+        let authorization = DeviceAuthorization::generate();
+        let now = authorization.created_at + Duration::seconds(30);
+        let interval = Duration::seconds(DEVICE_POLL_INTERVAL_SECS as i64);
+
+        assert!(matches!(
+            authorization.poll_outcome(None, now),
+            Some(DeviceTokenPoll::Pending)
+        ));
+        assert!(matches!(
+            authorization.poll_outcome(Some(now - interval + Duration::seconds(1)), now),
+            Some(DeviceTokenPoll::SlowDown)
+        ));
+        assert!(matches!(
+            authorization.poll_outcome(Some(now - interval), now),
+            Some(DeviceTokenPoll::Pending)
+        ));
+        // An approved code isn't redeemed by a poll that came too soon either
+        assert!(matches!(
+            approved().poll_outcome(Some(now - Duration::seconds(1)), now),
+            Some(DeviceTokenPoll::SlowDown)
+        ));
+    }
+
+    #[test]
+    fn approved_codes_are_redeemed_until_they_expire() {
+        let authorization = approved();
+        let now = authorization.created_at + Duration::seconds(30);
+        assert!(authorization.poll_outcome(None, now).is_none());
+
+        let expired = authorization.expires_at;
+        assert!(matches!(
+            authorization.poll_outcome(None, expired),
+            Some(DeviceTokenPoll::Expired)
+        ));
+
+        let mut consumed = approved();
+        consumed.consumed_at = Some(now);
+        assert!(matches!(
+            consumed.poll_outcome(None, now),
+            Some(DeviceTokenPoll::AlreadyUsed)
+        ));
+    }
+}
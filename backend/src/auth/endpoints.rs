@@ -1,12 +1,15 @@
-use crate::auth::{AuthProvider, OauthSession, Session};
+use crate::auth::{
+    Auth, DeviceAuthorization, DeviceTokenPoll, OauthSession, OauthSessionError, Session,
+    DEVICE_CODE_TTL_MINUTES, DEVICE_POLL_INTERVAL_SECS,
+};
 use crate::config::AppState;
 use crate::error::ApiError;
+use crate::org::OrgInvitation;
 use crate::user::{FederatedUser, User};
-use axum::extract::State;
+use axum::extract::{Extension, State};
 use axum::response::IntoResponse;
 use axum::Json;
-use oauth2::{AuthorizationCode, CsrfToken, TokenResponse};
-use openidconnect::{LanguageTag, TokenResponse as OidcTokenResponse};
+use oauth2::{AuthorizationCode, CsrfToken};
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use tracing::{error, info};
@@ -16,11 +19,22 @@ struct AuthResponse {
     url: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct AuthorizeRequest {
+    // Key of a configured OIDC provider, e.g. "google"
+    provider: String,
+}
+
 // Endpoint to start the oidc authorization flow
-pub async fn authorize(State(state): State<AppState>) -> Result<impl IntoResponse, ApiError> {
-    info!("Creating new session");
+pub async fn authorize(
+    State(state): State<AppState>,
+    Json(body): Json<AuthorizeRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    info!("Creating new session with provider {}", body.provider);
     // Get the OIDC provider and generate the authorization URL
-    let oidc_provider = state.oidc_providers.get("google").unwrap();
+    let oidc_provider = state.oidc_providers.get(&body.provider).ok_or_else(|| {
+        ApiError::BadRequest(format!("Unknown identity provider '{}'", body.provider))
+    })?;
     // Creates oauth session and returns the authorization URL
     let authorize_url = oidc_provider
         .generate_oidc_auth_url(&state)
@@ -71,37 +85,37 @@ pub async fn callback(
         return Err(ApiError::Unauthorized);
     }
 
-    // Get the OIDC provider
-    let oidc_provider = state.oidc_providers.get("google").ok_or_else(|| {
-        error!("OIDC provider not found");
-        ApiError::InternalServerError
-    })?;
-
-    let code = AuthorizationCode::new(params.code.clone());
-    let token_res = oauth_session
-        .convert_auth_code(&state, &oidc_provider, code.clone())
-        .await
-        .map_err(|e| {
-            error!("Failed to convert auth code: {}", e);
+    // The provider the login was started with verifies the id token
+    let oidc_provider = state
+        .oidc_providers
+        .get(oauth_session.provider.key())
+        .ok_or_else(|| {
+            error!(
+                "OIDC provider {} of the login is not configured",
+                oauth_session.provider.key()
+            );
             ApiError::InternalServerError
         })?;
 
-    let id_token = token_res.id_token().unwrap().clone();
-    // get the signing key
-    let id_token_verifier = oidc_provider.client.id_token_verifier();
-    let nonce_verifier = oauth_session.nonce;
-    // Verify the ID token and retrieve the claims
-    let claims = id_token
-        .claims(&id_token_verifier, &nonce_verifier)
-        .map_err(|e| {
-            error!("Failed to verify ID token: {:?}", e);
-            ApiError::Unauthorized
+    let code = AuthorizationCode::new(params.code.clone());
+    let identity = oidc_provider
+        .exchange_code(&state, &oauth_session, code)
+        .await
+        .map_err(|e| match e {
+            OauthSessionError::ValidationError(_) => {
+                error!("Failed to verify ID token: {}", e);
+                ApiError::Unauthorized
+            }
+            OauthSessionError::NetworkError(_) => {
+                error!("Failed to convert auth code: {}", e);
+                ApiError::InternalServerError
+            }
         })?;
 
-    let sub = claims.subject().clone();
+    let provider = oidc_provider.provider();
 
     // Check if FederatedUser already exists in DB
-    let federated_user = FederatedUser::from_sub(&*state.pool, AuthProvider::Google, sub.clone())
+    let federated_user = FederatedUser::from_sub(&state.pool, provider, identity.sub.clone())
         .await
         .map_err(|e| {
             error!("Failed to fetch federated user: {:?}", e);
@@ -114,13 +128,12 @@ pub async fn callback(
             "User exists, creating new session: sub: {:?}, provider: {:?}",
             federated_user.sub, federated_user.provider
         );
-        //let expires_at = claims.issue_time() + token_res.expires_in().unwrap();
         let session = Session::create(
-            &*state.pool,
+            &state.pool,
             federated_user.user_id,
             federated_user.id,
-            token_res.refresh_token(),
-            claims.issue_time() + token_res.expires_in().unwrap(), // Expires at
+            identity.refresh_token.as_ref(),
+            identity.token_expiry,
         )
         .await
         .map_err(|e| {
@@ -132,48 +145,33 @@ pub async fn callback(
         return Ok((StatusCode::OK, Json(session.id.to_string())).into_response());
     }
 
-    let language_tag = LanguageTag::new("en".to_string());
-    let locale = claims.locale().unwrap_or(&language_tag);
-    let first_name = claims
-        .given_name()
-        .map(|n| n.get(Some(locale))) // this returns Option<Option<&EndUserGivenName>>
-        .flatten() // this returns Option<&EndUserGivenName>
-        .map(|n| n.to_string())
-        .unwrap_or("".to_string());
-
-    let last_name = claims
-        .family_name()
-        .map(|n| n.get(Some(locale))) // this returns Option<Option<&EndUserFamilyName>>
-        .flatten() // this returns Option<&EndUserFamilyName>
-        .map(|n| n.to_string())
-        .unwrap_or("".to_string());
-
-    // return an error if the email is not present
-    let email = claims
-        .email()
-        .clone()
-        .ok_or_else(|| {
-            error!("Email not present in claims");
-            ApiError::Unauthorized
-        })?
-        .to_string();
-
     // If the user does not exist, create a new user and federated user
     let mut transaction = state.pool.begin().await?;
-    let user = User::new(email.clone(), first_name, last_name);
+    let user = User::new(
+        identity.email.clone(),
+        identity.first_name,
+        identity.last_name,
+    );
     user.persist(&mut transaction).await.map_err(|e| {
         error!("Failed to create user: {:?}", e);
         ApiError::InternalServerError
     })?;
 
-    let picture_url = claims
-        .picture()
-        .map(|p| p.get(Some(locale)))
-        .flatten()
-        .map(|p| p.to_string());
+    // Join the orgs the email was invited to before the account existed
+    OrgInvitation::accept_all(&mut transaction, &user)
+        .await
+        .map_err(|e| {
+            error!("Failed to accept org invitations: {:?}", e);
+            ApiError::InternalServerError
+        })?;
 
-    let federated_user =
-        FederatedUser::new(user.id, AuthProvider::Google, sub, Some(email), picture_url);
+    let federated_user = FederatedUser::new(
+        user.id,
+        provider,
+        identity.sub,
+        Some(identity.email),
+        identity.picture_url,
+    );
 
     federated_user
         .persist(&mut transaction)
@@ -189,15 +187,12 @@ pub async fn callback(
     })?;
 
     // Create a new session
-    // TODO: Verify nonce
-    // use openidconnect::Nonce;
-    let expires_at = claims.issue_time() + token_res.expires_in().unwrap();
     let session = Session::create(
-        &*state.pool,
+        &state.pool,
         federated_user.user_id,
         federated_user.id,
-        token_res.refresh_token(),
-        expires_at,
+        identity.refresh_token.as_ref(),
+        identity.token_expiry,
     )
     .await
     .map_err(|e| {
@@ -208,3 +203,240 @@ pub async fn callback(
     // return the session id as json
     Ok((StatusCode::OK, Json(session.id.to_string())).into_response())
 }
+
+#[derive(Serialize)]
+pub struct DeviceAuthorizationResponse {
+    device_code: String,
+    user_code: String,
+    verification_url: String,
+    expires_in: i64,
+    interval: u64,
+}
+
+// Start a device login for a headless client. The client shows the user code and
+// verification url, then polls /auth/device/token with the device code
+pub async fn device_authorize(
+    State(state): State<AppState>,
+) -> Result<Json<DeviceAuthorizationResponse>, ApiError> {
+    let mut authorization = DeviceAuthorization::generate();
+    authorization.persist(&state.pool).await.map_err(|e| {
+        error!("Failed to create device authorization: {}", e);
+        ApiError::InternalServerError
+    })?;
+
+    Ok(Json(DeviceAuthorizationResponse {
+        user_code: authorization.display_user_code(),
+        device_code: authorization.device_code,
+        verification_url: state.device_verification_url.clone(),
+        expires_in: DEVICE_CODE_TTL_MINUTES * 60,
+        interval: DEVICE_POLL_INTERVAL_SECS,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApproveDeviceRequest {
+    user_code: String,
+}
+
+// Called by the SPA for the logged in user to approve the device showing `user_code`
+pub async fn approve_device(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Json(body): Json<ApproveDeviceRequest>,
+) -> Result<StatusCode, ApiError> {
+    let (Some(user), Some(session_id)) = (auth.user, auth.session_id) else {
+        error!("Unauthorized access: no valid user found in middleware");
+        return Err(ApiError::Unauthorized);
+    };
+
+    let session = Session::from_id(&state.pool, session_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch session: {}", e);
+            ApiError::InternalServerError
+        })?;
+
+    let approved = DeviceAuthorization::approve(&state.pool, &body.user_code, &session)
+        .await
+        .map_err(|e| {
+            error!("Failed to approve device authorization: {}", e);
+            ApiError::InternalServerError
+        })?;
+    if !approved {
+        return Err(ApiError::NotFound {
+            code: "DEVICE_CODE_NOT_FOUND".into(),
+            message: "No pending device login with this code".into(),
+        });
+    }
+
+    info!("User {} approved a device login", user.id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// End the session the request was authenticated with. Its token stops working at once
+pub async fn logout(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+) -> Result<StatusCode, ApiError> {
+    let (Some(user), Some(session_id)) = (auth.user, auth.session_id) else {
+        error!("Unauthorized access: no valid user found in middleware");
+        return Err(ApiError::Unauthorized);
+    };
+
+    let deleted = Session::delete(&state.pool, session_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to delete session: {}", e);
+            ApiError::InternalServerError
+        })?;
+    if deleted {
+        info!("User {} logged out", user.id);
+    } else {
+        // Another request ended the session after this one was authenticated
+        info!("Session of user {} was already ended", user.id);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Renew the provider tokens of the session the request was authenticated with, using its
+// refresh token. Sessions whose token hasn't expired yet are returned as they are. A refresh
+// token the provider rejects ends the session
+pub async fn refresh(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (Some(user), Some(session_id)) = (auth.user, auth.session_id) else {
+        error!("Unauthorized access: no valid user found in middleware");
+        return Err(ApiError::Unauthorized);
+    };
+
+    let mut session = Session::from_id(&state.pool, session_id)
+        .await
+        .map_err(|e| match e {
+            // Ended by another request after this one was authenticated
+            sqlx::Error::RowNotFound => ApiError::Unauthorized,
+            e => {
+                error!("Failed to fetch session: {}", e);
+                ApiError::InternalServerError
+            }
+        })?;
+
+    if !session.is_token_expired() {
+        return Ok((StatusCode::OK, Json(session.id.to_string())));
+    }
+
+    let Some(refresh_token) = session.refresh_token.clone() else {
+        info!(
+            "Session of user {} has no refresh token and can't be renewed",
+            user.id
+        );
+        return Err(ApiError::Unauthorized);
+    };
+
+    // The refresh token was issued by the provider the user signed in with
+    let federated_user = FederatedUser::from_id(&state.pool, session.federated_user_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch federated user: {:?}", e);
+            ApiError::InternalServerError
+        })?;
+    let oidc_provider = state
+        .oidc_providers
+        .get(federated_user.provider.key())
+        .ok_or_else(|| {
+            error!(
+                "OIDC provider {} of the session is not configured",
+                federated_user.provider.key()
+            );
+            ApiError::InternalServerError
+        })?;
+
+    let tokens = match oidc_provider.exchange_refresh_token(&refresh_token).await {
+        Ok(tokens) => tokens,
+        Err(OauthSessionError::ValidationError(e)) => {
+            info!("Refresh token of user {} was rejected: {}", user.id, e);
+            Session::delete(&state.pool, session.id)
+                .await
+                .map_err(|e| {
+                    error!("Failed to delete session: {}", e);
+                    ApiError::InternalServerError
+                })?;
+            return Err(ApiError::Unauthorized);
+        }
+        Err(e) => {
+            error!("Failed to refresh session: {}", e);
+            return Err(ApiError::InternalServerError);
+        }
+    };
+
+    session
+        .update_tokens(
+            &state.pool,
+            tokens.refresh_token.as_ref(),
+            tokens.token_expiry,
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to update session: {}", e);
+            ApiError::InternalServerError
+        })?;
+
+    info!("Refreshed session of user {}", user.id);
+    // Same shape as the OIDC callback: the session id as json
+    Ok((StatusCode::OK, Json(session.id.to_string())))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeviceTokenRequest {
+    device_code: String,
+}
+
+// Polled by the headless client. Returns 202 until the user approves, then the session
+// token exactly once. Polls faster than the interval get 429 slow_down
+pub async fn device_token(
+    State(state): State<AppState>,
+    Json(body): Json<DeviceTokenRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let poll = DeviceAuthorization::poll(&state.pool, &body.device_code)
+        .await
+        .map_err(|e| {
+            error!("Failed to poll device authorization: {}", e);
+            ApiError::InternalServerError
+        })?
+        .ok_or_else(|| ApiError::NotFound {
+            code: "DEVICE_CODE_NOT_FOUND".into(),
+            message: "Unknown device code".into(),
+        })?;
+
+    match poll {
+        DeviceTokenPoll::Pending => Ok((
+            StatusCode::ACCEPTED,
+            Json(serde_json::json!({
+                "status": "authorization_pending",
+                "interval": DEVICE_POLL_INTERVAL_SECS,
+            })),
+        )
+            .into_response()),
+        // Polled before the interval passed, as in RFC 8628
+        DeviceTokenPoll::SlowDown => Ok((
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(serde_json::json!({
+                "status": "slow_down",
+                "interval": DEVICE_POLL_INTERVAL_SECS,
+            })),
+        )
+            .into_response()),
+        DeviceTokenPoll::Expired => Err(ApiError::Gone {
+            code: "DEVICE_CODE_EXPIRED".into(),
+            message: "The device code has expired, start a new device login".into(),
+        }),
+        DeviceTokenPoll::AlreadyUsed => Err(ApiError::Gone {
+            code: "DEVICE_CODE_USED".into(),
+            message: "The device code has already been redeemed".into(),
+        }),
+        DeviceTokenPoll::Issued(session) => {
+            // Same shape as the OIDC callback: the session id as json
+            Ok((StatusCode::OK, Json(session.id.to_string())).into_response())
+        }
+    }
+}
@@ -13,6 +13,8 @@ use axum_extra::TypedHeader;
 #[derive(Debug, Clone, FromRef)]
 pub struct Auth {
     pub user: Option<User>,
+    // The session the user authenticated with
+    pub session_id: Option<uuid::Uuid>,
 }
 
 pub async fn auth_middleware(
@@ -26,7 +28,10 @@ pub async fn auth_middleware(
 
     if token.is_empty() {
         // Bearer token is not set. Handle accordingly.
-        request.extensions_mut().insert(Auth { user: None });
+        request.extensions_mut().insert(Auth {
+            user: None,
+            session_id: None,
+        });
         return next.run(request).await;
     }
 
@@ -35,7 +40,10 @@ pub async fn auth_middleware(
         Ok(token) => token,
         Err(_) => {
             // If token is invalid, pass through with no user.
-            request.extensions_mut().insert(Auth { user: None });
+            request.extensions_mut().insert(Auth {
+                user: None,
+                session_id: None,
+            });
             return next.run(request).await;
         }
     };
@@ -49,7 +57,24 @@ pub async fn auth_middleware(
         }
     };
 
+    // An expired session authenticates no one. Its row is removed so it isn't checked again
+    let session = match session {
+        Some(session) if session.is_expired() => {
+            tracing::info!(
+                "Session {} expired at {}",
+                session.id,
+                session.session_expiry
+            );
+            if let Err(e) = Session::delete(&state.pool, session.id).await {
+                tracing::warn!("Failed to delete expired session {}: {}", session.id, e);
+            }
+            None
+        }
+        session => session,
+    };
+
     // Attempt to get user if session exists.
+    let session_id = session.as_ref().map(|s| s.id);
     let user = if let Some(session) = session {
         match User::from_id(&state.pool, session.user_id).await {
             Ok(user) => Some(user),
@@ -62,6 +87,12 @@ pub async fn auth_middleware(
         None
     };
 
-    request.extensions_mut().insert(Auth { user });
+    // Attribute every log line of this request to the acting user. The field is
+    // declared empty on the request span built by the trace layer
+    if let Some(user) = &user {
+        tracing::Span::current().record("user_id", tracing::field::display(user.id));
+    }
+
+    request.extensions_mut().insert(Auth { user, session_id });
     next.run(request).await
 }
@@ -1,11 +1,17 @@
+mod claims;
+mod device;
 mod endpoints;
 mod middleware;
 mod oauth_session;
 mod oidc;
+mod security_event;
 mod session;
 
+pub use claims::*;
+pub use device::*;
 pub use endpoints::*;
 pub use middleware::*;
 pub use oauth_session::*;
 pub use oidc::*;
+pub use security_event::*;
 pub use session::*;
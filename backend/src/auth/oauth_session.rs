@@ -1,4 +1,4 @@
-use crate::auth::OidcProvider;
+use crate::auth::{AuthProvider, OidcProvider};
 use crate::config::AppState;
 use oauth2::basic::BasicTokenType;
 use openidconnect::{
@@ -15,6 +15,8 @@ pub struct OauthSession {
     pub state: CsrfToken,
     pub nonce: Nonce, // Nonce for OIDC verification
     pub pkce_verifier: PkceCodeVerifier,
+    // Provider the login was started with, which has to verify the callback
+    pub provider: AuthProvider,
 }
 
 // Implement FromRow for OauthSession to convert from PgRow to OauthSession
@@ -29,10 +31,16 @@ impl<'r> FromRow<'r, PgRow> for OauthSession {
         let pkce_verifier: String = row.try_get("pkce_verifier")?;
         let pkce_verifier = PkceCodeVerifier::new(pkce_verifier);
 
+        let provider = row
+            .try_get::<String, _>("provider")?
+            .parse::<AuthProvider>()
+            .map_err(|_| sqlx::Error::Decode("Invalid provider".into()))?;
+
         Ok(Self {
             state,
             nonce,
             pkce_verifier,
+            provider,
         })
     }
 }
@@ -42,27 +50,34 @@ impl<'r> FromRow<'r, PgRow> for OauthSession {
 pub enum OauthSessionError {
     #[error("Failed to create HTTP client: {0}")]
     NetworkError(String),
-    //#[error("Failed to validate token: {0}")]
-    //ValidationError(String),
+    #[error("Failed to validate token: {0}")]
+    ValidationError(String),
 }
 
 impl OauthSession {
-    pub fn new(state: CsrfToken, nonce: Nonce, pkce_verifier: PkceCodeVerifier) -> Self {
+    pub fn new(
+        state: CsrfToken,
+        nonce: Nonce,
+        pkce_verifier: PkceCodeVerifier,
+        provider: AuthProvider,
+    ) -> Self {
         Self {
             state,
             nonce,
             pkce_verifier,
+            provider,
         }
     }
 
     pub async fn persist(&self, state: &AppState) -> Result<(), sqlx::Error> {
         let query =
-            "INSERT INTO app_data.oauth_session (state, nonce, pkce_verifier, expires_at) VALUES ($1, $2, $3, $4)";
+            "INSERT INTO app_data.oauth_session (state, nonce, pkce_verifier, expires_at, provider) VALUES ($1, $2, $3, $4, $5)";
         sqlx::query(query)
             .bind(self.state.secret())
             .bind(self.nonce.secret())
             .bind(self.pkce_verifier.secret())
             .bind(chrono::Utc::now() + chrono::Duration::days(730))
+            .bind(self.provider.key())
             .execute(&*state.pool)
             .await?;
 
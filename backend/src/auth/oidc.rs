@@ -1,10 +1,15 @@
-use crate::auth::OauthSession;
-use crate::AppState;
-use openidconnect::core::{CoreClient, CoreProviderMetadata, CoreResponseType};
+use crate::auth::{ClaimMapping, OauthSession, OauthSessionError};
+use crate::config::AppState;
+use async_trait::async_trait;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use oauth2::{AuthorizationCode, RefreshToken, RequestTokenError, TokenResponse};
+use openidconnect::core::{CoreClient, CoreJsonWebKeySet, CoreProviderMetadata, CoreResponseType};
 use openidconnect::PkceCodeChallenge;
 use openidconnect::{
     AuthenticationFlow, ClientId, ClientSecret, CsrfToken, EndpointMaybeSet, EndpointNotSet,
-    EndpointSet, IssuerUrl, Nonce, RedirectUrl, Scope,
+    EndpointSet, IssuerUrl, Nonce, RedirectUrl, Scope, SubjectIdentifier,
+    TokenResponse as OidcTokenResponse,
 };
 use reqwest::ClientBuilder;
 use std::env;
@@ -42,15 +47,30 @@ use strum_macros::EnumString;
 pub enum AuthProvider {
     #[strum(serialize = "google", serialize = "https://accounts.google.com")]
     Google,
-    // Microsoft, // Add more providers here in the future
+    // Microsoft Entra ID through the multi-tenant common endpoint, so work, school and
+    // personal accounts of any tenant can sign in
+    #[strum(
+        serialize = "microsoft",
+        to_string = "https://login.microsoftonline.com/common/v2.0"
+    )]
+    Microsoft,
 }
 
 impl AuthProvider {
+    // Name of the provider in API requests and in AppState::oidc_providers. Display gives
+    // the issuer URL instead
+    pub fn key(&self) -> &'static str {
+        match self {
+            AuthProvider::Google => "google",
+            AuthProvider::Microsoft => "microsoft",
+        }
+    }
+
     // Returns the issuer URL for the provider
     fn issuer_url(&self) -> &'static str {
         match self {
             AuthProvider::Google => "https://accounts.google.com",
-            // AuthProvider::Microsoft => "https://login.microsoftonline.com/common/v2.0",
+            AuthProvider::Microsoft => "https://login.microsoftonline.com/common/v2.0",
         }
     }
 
@@ -58,9 +78,15 @@ impl AuthProvider {
     fn env_vars(&self) -> (&'static str, &'static str) {
         match self {
             AuthProvider::Google => ("GOOGLE_CLIENT_ID", "GOOGLE_CLIENT_SECRET"),
-            // AuthProvider::Microsoft => ("MICROSOFT_CLIENT_ID", "MICROSOFT_CLIENT_SECRET"),
+            AuthProvider::Microsoft => ("MICROSOFT_CLIENT_ID", "MICROSOFT_CLIENT_SECRET"),
         }
     }
+
+    // Whether the issuer URL is shared by many tenants, each issuing tokens under its own
+    // issuer https://login.microsoftonline.com/{tid}/v2.0
+    fn multi_tenant(&self) -> bool {
+        matches!(self, AuthProvider::Microsoft)
+    }
 }
 
 pub struct OidcConfig {
@@ -68,6 +94,7 @@ pub struct OidcConfig {
     client_secret: ClientSecret,
     redirect_url: RedirectUrl,
     provider: AuthProvider,
+    claim_mapping: ClaimMapping,
 }
 
 impl OidcConfig {
@@ -93,14 +120,119 @@ impl OidcConfig {
             client_secret,
             redirect_url,
             provider,
+            claim_mapping: ClaimMapping::from_env(provider),
         })
     }
 }
 
+// The metadata of a multi-tenant endpoint names the templated issuer
+// https://login.microsoftonline.com/{tenantid}/v2.0, which discover_async rejects for not
+// being the URL it was fetched from. It is read here without that check, and id tokens are
+// held to the issuer of their own tenant in exchange_code instead
+async fn discover_multi_tenant(
+    issuer_url: &IssuerUrl,
+    http_client: &reqwest::Client,
+) -> Result<CoreProviderMetadata, OidcError> {
+    let discovery_url = format!(
+        "{}/.well-known/openid-configuration",
+        issuer_url.as_str().trim_end_matches('/')
+    );
+    let provider_metadata = http_client
+        .get(discovery_url)
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(|err| OidcError::DiscoveryError(err.to_string()))?
+        .json::<CoreProviderMetadata>()
+        .await
+        .map_err(|err| OidcError::DiscoveryError(err.to_string()))?;
+
+    let jwks = CoreJsonWebKeySet::fetch_async(provider_metadata.jwks_uri(), http_client)
+        .await
+        .map_err(|err| OidcError::DiscoveryError(err.to_string()))?;
+    Ok(provider_metadata.set_jwks(jwks))
+}
+
+// Check the issuer of an id token from a multi-tenant endpoint against the tenant it names
+// in its `tid` claim. Tokens of any tenant are signed with the same keys, so this is what
+// stops one tenant from issuing tokens in the name of another. Personal accounts carry the
+// tid of the consumer tenant 9188040d-6c67-4c5b-b112-36a304b66dad
+fn check_tenant_issuer(id_token: &str, issuer: &str) -> Result<(), String> {
+    let payload = id_token
+        .split('.')
+        .nth(1)
+        .ok_or_else(|| "Malformed ID token".to_string())?;
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(payload)
+        .map_err(|e| format!("Malformed ID token payload: {}", e))?;
+    let claims: serde_json::Value = serde_json::from_slice(&payload)
+        .map_err(|e| format!("Malformed ID token payload: {}", e))?;
+    let tid = claims
+        .get("tid")
+        .and_then(serde_json::Value::as_str)
+        .ok_or_else(|| "Missing tid claim".to_string())?;
+
+    let expected = format!("https://login.microsoftonline.com/{}/v2.0", tid);
+    if issuer != expected {
+        return Err(format!(
+            "Issuer {} does not match tenant {} of the token",
+            issuer, tid
+        ));
+    }
+    Ok(())
+}
+
+// The identity of a user as verified by an OIDC provider after a successful code exchange
+#[derive(Debug, Clone)]
+pub struct OidcIdentity {
+    pub sub: SubjectIdentifier,
+    pub email: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub picture_url: Option<String>,
+    pub refresh_token: Option<RefreshToken>,
+    pub token_expiry: DateTime<Utc>,
+}
+
+// Tokens issued in exchange for a refresh token. Providers may rotate the refresh token,
+// in which case the new one replaces the stored one
+#[derive(Debug, Clone)]
+pub struct RefreshedTokens {
+    pub refresh_token: Option<RefreshToken>,
+    pub token_expiry: DateTime<Utc>,
+}
+
+// Operations the auth endpoints need from an OIDC provider.
+// Kept behind a trait so that AppState can be built without network access to the provider.
+#[async_trait]
+pub trait OidcProviderApi: std::fmt::Debug + Send + Sync {
+    fn provider(&self) -> AuthProvider;
+
+    // Generate the authorization URL to which we'll redirect the user
+    async fn generate_oidc_auth_url(&self, state: &AppState) -> Result<String, OidcError>;
+
+    // Exchange the authorization code for tokens and verify the ID token
+    async fn exchange_code(
+        &self,
+        state: &AppState,
+        oauth_session: &OauthSession,
+        code: AuthorizationCode,
+    ) -> Result<OidcIdentity, OauthSessionError>;
+
+    // Exchange a refresh token for new tokens. A refresh token the provider rejects is a
+    // ValidationError, any other failure a NetworkError
+    async fn exchange_refresh_token(
+        &self,
+        refresh_token: &RefreshToken,
+    ) -> Result<RefreshedTokens, OauthSessionError>;
+}
+
 #[derive(Debug, Clone)]
 pub struct OidcProvider {
     pub client: Client,
     pub http_client: reqwest::Client,
+    provider: AuthProvider,
+    claim_mapping: ClaimMapping,
 }
 
 impl OidcProvider {
@@ -118,9 +250,13 @@ impl OidcProvider {
             .map_err(|err| OidcError::HttpClientError(err.to_string()))?;
 
         info!("Discovering provider metadata for {:?}...", config.provider);
-        let provider_metadata = CoreProviderMetadata::discover_async(issuer_url, &http_client)
-            .await
-            .map_err(|err| OidcError::DiscoveryError(err.to_string()))?;
+        let provider_metadata = if config.provider.multi_tenant() {
+            discover_multi_tenant(&issuer_url, &http_client).await?
+        } else {
+            CoreProviderMetadata::discover_async(issuer_url, &http_client)
+                .await
+                .map_err(|err| OidcError::DiscoveryError(err.to_string()))?
+        };
 
         let client = CoreClient::from_provider_metadata(
             provider_metadata,
@@ -136,11 +272,19 @@ impl OidcProvider {
         Ok(Self {
             client,
             http_client,
+            provider: config.provider,
+            claim_mapping: config.claim_mapping,
         })
     }
+}
+
+#[async_trait]
+impl OidcProviderApi for OidcProvider {
+    fn provider(&self) -> AuthProvider {
+        self.provider
+    }
 
-    // Generate the authorization URL to which we'll redirect the user
-    pub async fn generate_oidc_auth_url(&self, state: &AppState) -> Result<String, OidcError> {
+    async fn generate_oidc_auth_url(&self, state: &AppState) -> Result<String, OidcError> {
         let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
         let (authorize_url, csrf_state, nonce) = self
             .client
@@ -155,14 +299,185 @@ impl OidcProvider {
             .add_scope(Scope::new("profile".to_string()))
             .url();
 
-        OauthSession::new(csrf_state.clone(), nonce.clone(), pkce_verifier)
-            .persist(state)
-            .await
+        OauthSession::new(
+            csrf_state.clone(),
+            nonce.clone(),
+            pkce_verifier,
+            self.provider,
+        )
+        .persist(state)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to persist OAuth state: {}", e);
+            OidcError::DatabaseError(e.to_string())
+        })?;
+
+        Ok(authorize_url.to_string())
+    }
+
+    async fn exchange_code(
+        &self,
+        state: &AppState,
+        oauth_session: &OauthSession,
+        code: AuthorizationCode,
+    ) -> Result<OidcIdentity, OauthSessionError> {
+        let token_res = oauth_session.convert_auth_code(state, self, code).await?;
+
+        let id_token = token_res.id_token().ok_or_else(|| {
+            error!("ID token not present in token response");
+            OauthSessionError::ValidationError("Missing ID token".to_string())
+        })?;
+        // Verify the ID token and retrieve the claims. The issuer of a multi-tenant
+        // provider differs per tenant, so it is checked against the tid claim instead
+        let mut id_token_verifier = self.client.id_token_verifier();
+        if self.provider.multi_tenant() {
+            id_token_verifier = id_token_verifier.require_issuer_match(false);
+        }
+        let claims = id_token
+            .claims(&id_token_verifier, &oauth_session.nonce)
             .map_err(|e| {
-                eprintln!("Failed to persist OAuth state: {}", e);
-                OidcError::DatabaseError(e.to_string())
+                error!("Failed to verify ID token: {:?}", e);
+                OauthSessionError::ValidationError(e.to_string())
+            })?;
+        if self.provider.multi_tenant() {
+            check_tenant_issuer(&id_token.to_string(), claims.issuer().as_str()).map_err(|e| {
+                error!("Failed to verify ID token issuer: {}", e);
+                OauthSessionError::ValidationError(e)
             })?;
+        }
 
-        Ok(authorize_url.to_string())
+        // Profile fields come from whichever claims the provider's mapping names
+        let claims_json = serde_json::to_value(claims).map_err(|e| {
+            error!("Failed to read ID token claims: {:?}", e);
+            OauthSessionError::ValidationError(e.to_string())
+        })?;
+        let mapped = self.claim_mapping.apply(&claims_json);
+
+        // return an error if the email is not present
+        let email = mapped.email.ok_or_else(|| {
+            error!("Email not present in claims");
+            OauthSessionError::ValidationError("Missing email claim".to_string())
+        })?;
+
+        let expires_in = token_res
+            .expires_in()
+            .and_then(|d| chrono::Duration::from_std(d).ok())
+            .unwrap_or_else(|| chrono::Duration::hours(1));
+
+        Ok(OidcIdentity {
+            sub: claims.subject().clone(),
+            email,
+            first_name: mapped.first_name,
+            last_name: mapped.last_name,
+            picture_url: mapped.picture_url,
+            refresh_token: token_res.refresh_token().cloned(),
+            token_expiry: claims.issue_time() + expires_in,
+        })
+    }
+
+    async fn exchange_refresh_token(
+        &self,
+        refresh_token: &RefreshToken,
+    ) -> Result<RefreshedTokens, OauthSessionError> {
+        let token_res = self
+            .client
+            .exchange_refresh_token(refresh_token)
+            .map_err(|e| {
+                error!("Failed to build refresh token request: {:?}", e);
+                OauthSessionError::NetworkError(e.to_string())
+            })?
+            .request_async(&self.http_client)
+            .await
+            .map_err(|e| match e {
+                // The provider answered with an OAuth error, e.g. invalid_grant for a
+                // revoked or expired refresh token
+                RequestTokenError::ServerResponse(response) => {
+                    info!("Provider rejected refresh token: {:?}", response);
+                    OauthSessionError::ValidationError(response.to_string())
+                }
+                e => {
+                    error!("Failed to refresh token: {:?}", e);
+                    OauthSessionError::NetworkError(e.to_string())
+                }
+            })?;
+
+        let expires_in = token_res
+            .expires_in()
+            .and_then(|d| chrono::Duration::from_std(d).ok())
+            .unwrap_or_else(|| chrono::Duration::hours(1));
+
+        Ok(RefreshedTokens {
+            refresh_token: token_res.refresh_token().cloned(),
+            token_expiry: Utc::now() + expires_in,
+        })
+    }
+}
+
+// Provider used when no identity provider is reachable, e.g. when building the router in tests.
+// The authorization code is used as the subject, so each distinct code maps to a distinct user.
+#[cfg(test)]
+#[derive(Debug, Clone)]
+pub struct MockOidcProvider {
+    pub provider: AuthProvider,
+}
+
+#[cfg(test)]
+#[async_trait]
+impl OidcProviderApi for MockOidcProvider {
+    fn provider(&self) -> AuthProvider {
+        self.provider
+    }
+
+    async fn generate_oidc_auth_url(&self, state: &AppState) -> Result<String, OidcError> {
+        let csrf_state = CsrfToken::new_random();
+        let (_, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+        OauthSession::new(
+            csrf_state.clone(),
+            Nonce::new_random(),
+            pkce_verifier,
+            self.provider,
+        )
+        .persist(state)
+        .await
+        .map_err(|e| OidcError::DatabaseError(e.to_string()))?;
+
+        Ok(format!(
+            "http://localhost/mock-oidc/authorize?state={}",
+            csrf_state.secret()
+        ))
+    }
+
+    async fn exchange_code(
+        &self,
+        state: &AppState,
+        oauth_session: &OauthSession,
+        code: AuthorizationCode,
+    ) -> Result<OidcIdentity, OauthSessionError> {
+        oauth_session
+            .delete(state)
+            .await
+            .map_err(|e| OauthSessionError::NetworkError(e.to_string()))?;
+
+        let sub = code.secret().to_string();
+        Ok(OidcIdentity {
+            sub: SubjectIdentifier::new(sub.clone()),
+            email: format!("{}@example.com", sub.to_lowercase()),
+            first_name: "Test".to_string(),
+            last_name: sub,
+            picture_url: None,
+            refresh_token: None,
+            token_expiry: Utc::now() + chrono::Duration::hours(1),
+        })
+    }
+
+    // Every refresh token is accepted and kept
+    async fn exchange_refresh_token(
+        &self,
+        _refresh_token: &RefreshToken,
+    ) -> Result<RefreshedTokens, OauthSessionError> {
+        Ok(RefreshedTokens {
+            refresh_token: None,
+            token_expiry: Utc::now() + chrono::Duration::hours(1),
+        })
     }
 }
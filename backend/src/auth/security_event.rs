@@ -0,0 +1,77 @@
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use sqlx::{postgres::PgRow, FromRow, PgConnection, Row};
+use strum_macros::Display;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum SecurityEventKind {
+    DeviceAuthorized,
+    // Deletion protection of a graph or node was turned on or off
+    GraphProtectionChanged,
+    NodeProtectionChanged,
+    // Members were added or invited to an org in bulk
+    OrgMembersImported,
+    // A share link of a graph was created, opened or revoked
+    ShareLinkCreated,
+    ShareLinkAccessed,
+    ShareLinkRevoked,
+}
+
+pub struct SecurityEvent;
+
+// A stored security event, as included in audit log exports
+#[derive(Debug, Serialize)]
+pub struct SecurityEventEntry {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub kind: String,
+    pub detail: JsonValue,
+    #[serde(with = "crate::utils::rfc3339")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl<'r> FromRow<'r, PgRow> for SecurityEventEntry {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            user_id: row.try_get("user_id")?,
+            kind: row.try_get("kind")?,
+            detail: row.try_get("detail")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+impl SecurityEvent {
+    // Takes a connection so the event is recorded in the same transaction as the change
+    pub async fn record(
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        kind: SecurityEventKind,
+        detail: JsonValue,
+    ) -> Result<(), sqlx::Error> {
+        let query = "INSERT INTO app_data.security_event (id, user_id, kind, detail) VALUES ($1, $2, $3, $4)";
+        sqlx::query(query)
+            .bind(Uuid::new_v4())
+            .bind(user_id)
+            .bind(kind.to_string())
+            .bind(detail)
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+
+    // Events about any of the given graphs or their nodes, oldest first
+    pub async fn for_graphs(
+        pool: &sqlx::PgPool,
+        graph_ids: &[String],
+    ) -> Result<Vec<SecurityEventEntry>, sqlx::Error> {
+        let query = "SELECT * FROM app_data.security_event WHERE detail->>'graph_id' = ANY($1) ORDER BY created_at, id";
+        sqlx::query_as::<_, SecurityEventEntry>(query)
+            .bind(graph_ids)
+            .fetch_all(pool)
+            .await
+    }
+}
@@ -48,11 +48,41 @@ impl Session {
         federated_user_id: Uuid,
         refresh_token: Option<&RefreshToken>,
         token_expiry: DateTime<Utc>,
+    ) -> Result<Self, sqlx::Error> {
+        let session_expiry = chrono::Utc::now() + chrono::Duration::days(365);
+        Self::insert(
+            pool,
+            user_id,
+            federated_user_id,
+            refresh_token,
+            token_expiry,
+            session_expiry,
+        )
+        .await
+    }
+
+    // A session with no provider token to renew, e.g. one issued to a device. Its token
+    // lasts as long as the session, so /auth/refresh returns it as it is until it expires
+    pub async fn create_unrenewable(
+        pool: &sqlx::PgPool,
+        user_id: Uuid,
+        federated_user_id: Uuid,
+        expiry: DateTime<Utc>,
+    ) -> Result<Self, sqlx::Error> {
+        Self::insert(pool, user_id, federated_user_id, None, expiry, expiry).await
+    }
+
+    async fn insert(
+        pool: &sqlx::PgPool,
+        user_id: Uuid,
+        federated_user_id: Uuid,
+        refresh_token: Option<&RefreshToken>,
+        token_expiry: DateTime<Utc>,
+        session_expiry: DateTime<Utc>,
     ) -> Result<Self, sqlx::Error> {
         let id = Uuid::new_v4();
         // Convert the refresh token to a string if it exists, otherwise None
         let sql_refresh_token = refresh_token.clone().map(|t| t.secret().to_string());
-        let session_expiry = chrono::Utc::now() + chrono::Duration::days(365);
         let query =
             "INSERT INTO app_data.session (id, user_id, federated_user_id, refresh_token, token_expiry, session_expiry) VALUES ($1, $2, $3, $4, $5, $6)";
         sqlx::query(query)
@@ -84,4 +114,83 @@ impl Session {
             .await?;
         Ok(row)
     }
+
+    // Whether the session has outlived its session_expiry and must no longer authenticate
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(Utc::now())
+    }
+
+    fn is_expired_at(&self, now: DateTime<Utc>) -> bool {
+        self.session_expiry < now
+    }
+
+    // Whether the provider token the session was issued with has expired and should be
+    // renewed with the refresh token
+    pub fn is_token_expired(&self) -> bool {
+        self.token_expiry < Utc::now()
+    }
+
+    // Store the outcome of a token refresh. A refresh token is only replaced when the
+    // provider issued a new one
+    pub async fn update_tokens(
+        &mut self,
+        pool: &sqlx::PgPool,
+        refresh_token: Option<&RefreshToken>,
+        token_expiry: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        let query = "UPDATE app_data.session SET refresh_token = COALESCE($2, refresh_token), token_expiry = $3 WHERE id = $1";
+        sqlx::query(query)
+            .bind(self.id)
+            .bind(refresh_token.map(|t| t.secret().to_string()))
+            .bind(token_expiry)
+            .execute(pool)
+            .await?;
+
+        if let Some(refresh_token) = refresh_token {
+            self.refresh_token = Some(refresh_token.clone());
+        }
+        self.token_expiry = token_expiry;
+        Ok(())
+    }
+
+    // Delete the session, returning whether it still existed. Deleting a session that is
+    // already gone is not an error, e.g. when logout is sent twice
+    pub async fn delete(pool: &sqlx::PgPool, id: Uuid) -> Result<bool, sqlx::Error> {
+        let query = "DELETE FROM app_data.session WHERE id = $1";
+        let result = sqlx::query(query).bind(id).execute(pool).await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn session_expiring_at(session_expiry: DateTime<Utc>) -> Session {
+        Session {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            federated_user_id: Uuid::new_v4(),
+            refresh_token: None,
+            token_expiry: session_expiry,
+            session_expiry,
+            created_at: session_expiry - Duration::days(365),
+        }
+    }
+
+    #[test]
+    fn session_is_valid_until_its_expiry() {
+        let expiry = Utc::now();
+        let session = session_expiring_at(expiry);
+        assert!(!session.is_expired_at(expiry - Duration::milliseconds(1)));
+        assert!(!session.is_expired_at(expiry));
+    }
+
+    #[test]
+    fn session_is_expired_just_after_its_expiry() {
+        let expiry = Utc::now();
+        let session = session_expiring_at(expiry);
+        assert!(session.is_expired_at(expiry + Duration::milliseconds(1)));
+    }
 }
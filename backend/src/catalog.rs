@@ -0,0 +1,1396 @@
+use crate::ids::GraphId;
+use crate::utils::is_type_id;
+use serde::Serialize;
+use sqlx::Row;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::future::Future;
+use tracing::{info, warn};
+
+// Labels AGE creates for every graph as parents of all vertex and edge labels
+const DEFAULT_LABELS: [&str; 2] = ["_ag_label_vertex", "_ag_label_edge"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LabelKind {
+    Vertex,
+    Edge,
+}
+
+impl LabelKind {
+    fn from_catalog(kind: &str) -> Option<Self> {
+        match kind {
+            "v" => Some(LabelKind::Vertex),
+            "e" => Some(LabelKind::Edge),
+            _ => None,
+        }
+    }
+
+    fn create_function(&self) -> &'static str {
+        match self {
+            LabelKind::Vertex => "create_vlabel",
+            LabelKind::Edge => "create_elabel",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CatalogIssueKind {
+    // Graph metadata whose AGE graph doesn't exist
+    MissingAgeGraph,
+    // AGE graph, named like the application names graphs, without graph metadata
+    OrphanAgeGraph,
+    // Node or edge type whose AGE label doesn't exist
+    MissingLabel,
+    // AGE label of a known graph that no node or edge type accounts for
+    OrphanLabel,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CatalogIssue {
+    pub kind: CatalogIssueKind,
+    pub graph_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub type_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label_kind: Option<LabelKind>,
+    pub repaired: bool,
+    // Why a repair wasn't made or failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+impl CatalogIssue {
+    fn graph(kind: CatalogIssueKind, graph_id: &str) -> Self {
+        Self {
+            kind,
+            graph_id: graph_id.to_string(),
+            type_id: None,
+            label: None,
+            label_kind: None,
+            repaired: false,
+            note: None,
+        }
+    }
+
+    fn label(
+        kind: CatalogIssueKind,
+        graph_id: &str,
+        type_id: Option<&str>,
+        label: &str,
+        label_kind: LabelKind,
+    ) -> Self {
+        Self {
+            type_id: type_id.map(str::to_string),
+            label: Some(label.to_string()),
+            label_kind: Some(label_kind),
+            ..Self::graph(kind, graph_id)
+        }
+    }
+
+    // Record the outcome of a repair
+    fn repair_result(&mut self, result: Result<(), sqlx::Error>) {
+        match result {
+            Ok(()) => self.repaired = true,
+            Err(e) => {
+                warn!(
+                    "Failed to repair {:?} in {}: {}",
+                    self.kind, self.graph_id, e
+                );
+                self.note = Some(format!("Repair failed: {}", e));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CatalogReport {
+    // Whether repairs were attempted
+    pub repair: bool,
+    pub graphs_checked: usize,
+    pub types_checked: usize,
+    pub issues: Vec<CatalogIssue>,
+}
+
+// A node or edge type as recorded in the metadata tables
+struct TypeRecord {
+    id: String,
+    name: String,
+    normalized_name: String,
+    kind: LabelKind,
+}
+
+// Graph and type metadata next to the AGE catalog, as read by the catalog check
+struct CatalogSnapshot {
+    graph_ids: BTreeSet<String>,
+    age_graphs: HashSet<String>,
+    // Types of each graph
+    types: BTreeMap<String, Vec<TypeRecord>>,
+    // Labels of each AGE graph, without the default ones
+    labels: BTreeMap<String, BTreeSet<(LabelKind, String)>>,
+}
+
+impl CatalogSnapshot {
+    async fn load(pool: &sqlx::PgPool) -> Result<Self, sqlx::Error> {
+        let graph_ids = sqlx::query_scalar("SELECT graph_id FROM app_data.graph_info")
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .collect();
+        let age_graphs = sqlx::query_scalar("SELECT name::text FROM ag_catalog.ag_graph")
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .collect();
+        Ok(Self {
+            graph_ids,
+            age_graphs,
+            types: type_records(pool, None).await?,
+            labels: age_labels(pool, None).await?,
+        })
+    }
+
+    fn graph_types(&self, graph_id: &str) -> &[TypeRecord] {
+        self.types
+            .get(graph_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
+
+// Labels of a graph compared with its types: a missing label for each type whose
+// normalized name isn't a label, then an orphan label for each label that no type's id
+// or normalized name accounts for
+fn label_issues(
+    graph_id: &str,
+    types: &[TypeRecord],
+    labels: &BTreeSet<(LabelKind, String)>,
+) -> Vec<CatalogIssue> {
+    let mut issues: Vec<CatalogIssue> = types
+        .iter()
+        .filter(|record| !labels.contains(&(record.kind, record.normalized_name.clone())))
+        .map(|record| {
+            CatalogIssue::label(
+                CatalogIssueKind::MissingLabel,
+                graph_id,
+                Some(&record.id),
+                &record.normalized_name,
+                record.kind,
+            )
+        })
+        .collect();
+
+    let accounted: HashSet<(LabelKind, &str)> = types
+        .iter()
+        .flat_map(|record| {
+            [
+                (record.kind, record.normalized_name.as_str()),
+                (record.kind, record.id.as_str()),
+            ]
+        })
+        .collect();
+    issues.extend(
+        labels
+            .iter()
+            .filter(|(kind, label)| !accounted.contains(&(*kind, label.as_str())))
+            .map(|(kind, label)| {
+                CatalogIssue::label(CatalogIssueKind::OrphanLabel, graph_id, None, label, *kind)
+            }),
+    );
+    issues
+}
+
+// AGE graphs without graph metadata, by name. Ones not named like the application names
+// graphs are noted as never dropped
+fn orphan_graph_issues(
+    graph_ids: &BTreeSet<String>,
+    age_graphs: &HashSet<String>,
+) -> Vec<CatalogIssue> {
+    let mut orphan_graphs: Vec<&String> = age_graphs
+        .iter()
+        .filter(|name| !graph_ids.contains(*name))
+        .collect();
+    orphan_graphs.sort();
+    orphan_graphs
+        .into_iter()
+        .map(|name| {
+            let mut issue = CatalogIssue::graph(CatalogIssueKind::OrphanAgeGraph, name);
+            if !is_type_id('g', name) {
+                // Not created by the application, so never dropped by it
+                issue.note =
+                    Some("Not named like an application graph, it was left in place".into());
+            }
+            issue
+        })
+        .collect()
+}
+
+// The changes the catalog check and the creation cleanups make to AGE, and what they
+// check first
+trait AgeCatalog {
+    async fn create_graph(&self, graph_id: &str) -> Result<(), sqlx::Error>;
+    async fn drop_graph(&self, graph_id: &str) -> Result<(), sqlx::Error>;
+    async fn create_label(
+        &self,
+        graph_id: &str,
+        label: &str,
+        kind: LabelKind,
+    ) -> Result<(), sqlx::Error>;
+    async fn drop_label(&self, graph_id: &str, label: &str) -> Result<(), sqlx::Error>;
+    async fn graph_has_data(&self, graph_id: &str) -> Result<bool, sqlx::Error>;
+    async fn label_has_data(&self, graph_id: &str, label: &str) -> Result<bool, sqlx::Error>;
+    async fn graph_exists(&self, graph_id: &str) -> Result<bool, sqlx::Error>;
+    // Whether graph metadata accounts for the AGE graph
+    async fn graph_has_metadata(&self, graph_id: &str) -> Result<bool, sqlx::Error>;
+    async fn label_exists(
+        &self,
+        graph_id: &str,
+        label: &str,
+        kind: LabelKind,
+    ) -> Result<bool, sqlx::Error>;
+    // Whether a type of the label's kind has the label as its id or normalized name
+    async fn label_has_type(
+        &self,
+        graph_id: &str,
+        label: &str,
+        kind: LabelKind,
+    ) -> Result<bool, sqlx::Error>;
+}
+
+impl AgeCatalog for sqlx::PgPool {
+    async fn create_graph(&self, graph_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT ag_catalog.create_graph($1)")
+            .bind(graph_id)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+
+    async fn drop_graph(&self, graph_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT ag_catalog.drop_graph($1, true)")
+            .bind(graph_id)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+
+    async fn create_label(
+        &self,
+        graph_id: &str,
+        label: &str,
+        kind: LabelKind,
+    ) -> Result<(), sqlx::Error> {
+        let query = format!("SELECT ag_catalog.{}($1, $2)", kind.create_function());
+        sqlx::query(&query)
+            .bind(graph_id)
+            .bind(label)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+
+    async fn drop_label(&self, graph_id: &str, label: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT ag_catalog.drop_label($1, $2, true)")
+            .bind(graph_id)
+            .bind(label)
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+
+    async fn graph_has_data(&self, graph_id: &str) -> Result<bool, sqlx::Error> {
+        graph_has_data(self, graph_id).await
+    }
+
+    async fn label_has_data(&self, graph_id: &str, label: &str) -> Result<bool, sqlx::Error> {
+        label_has_data(self, graph_id, label).await
+    }
+
+    async fn graph_exists(&self, graph_id: &str) -> Result<bool, sqlx::Error> {
+        let query = "SELECT EXISTS (SELECT 1 FROM ag_catalog.ag_graph WHERE name::text = $1)";
+        sqlx::query_scalar(query)
+            .bind(graph_id)
+            .fetch_one(self)
+            .await
+    }
+
+    async fn graph_has_metadata(&self, graph_id: &str) -> Result<bool, sqlx::Error> {
+        let query = "SELECT EXISTS (SELECT 1 FROM app_data.graph_info WHERE graph_id = $1)";
+        sqlx::query_scalar(query)
+            .bind(graph_id)
+            .fetch_one(self)
+            .await
+    }
+
+    async fn label_exists(
+        &self,
+        graph_id: &str,
+        label: &str,
+        kind: LabelKind,
+    ) -> Result<bool, sqlx::Error> {
+        Ok(age_labels(self, Some(graph_id))
+            .await?
+            .get(graph_id)
+            .is_some_and(|labels| labels.contains(&(kind, label.to_string()))))
+    }
+
+    async fn label_has_type(
+        &self,
+        graph_id: &str,
+        label: &str,
+        kind: LabelKind,
+    ) -> Result<bool, sqlx::Error> {
+        let types_table = match kind {
+            LabelKind::Vertex => "app_data.node_types",
+            LabelKind::Edge => "app_data.edge_type",
+        };
+        let query = format!(
+            "SELECT EXISTS (SELECT 1 FROM {} WHERE graph_id = $1 AND (normalized_name = $2 OR id = $2))",
+            types_table
+        );
+        sqlx::query_scalar(&query)
+            .bind(graph_id)
+            .bind(label)
+            .fetch_one(self)
+            .await
+    }
+}
+
+// Compare graph, node type and edge type metadata with the AGE catalog. Type saves create
+// the label from the normalized name, and data is written under labels named by the type
+// id, so either name accounts for a label.
+//
+// With `repair` set, missing AGE graphs and labels are created, and AGE graphs and labels
+// nothing accounts for are dropped if they hold no data. Ones holding data are only
+// reported, as are AGE graphs not named like the application names graphs
+pub async fn reconcile_catalog(
+    pool: &sqlx::PgPool,
+    repair: bool,
+) -> Result<CatalogReport, sqlx::Error> {
+    let snapshot = CatalogSnapshot::load(pool).await?;
+    let report = reconcile(&snapshot, pool, repair).await?;
+    info!(
+        "Checked {} graph(s) and {} type(s) against the AGE catalog: {} issue(s), {} repaired",
+        report.graphs_checked,
+        report.types_checked,
+        report.issues.len(),
+        report.issues.iter().filter(|issue| issue.repaired).count()
+    );
+    Ok(report)
+}
+
+async fn reconcile(
+    snapshot: &CatalogSnapshot,
+    age: &impl AgeCatalog,
+    repair: bool,
+) -> Result<CatalogReport, sqlx::Error> {
+    let mut report = CatalogReport {
+        repair,
+        graphs_checked: snapshot.graph_ids.len(),
+        types_checked: snapshot.types.values().map(Vec::len).sum(),
+        issues: Vec::new(),
+    };
+
+    let no_labels = BTreeSet::new();
+    for graph_id in &snapshot.graph_ids {
+        let mut labels = snapshot.labels.get(graph_id).unwrap_or(&no_labels);
+        if !snapshot.age_graphs.contains(graph_id) {
+            let mut issue = CatalogIssue::graph(CatalogIssueKind::MissingAgeGraph, graph_id);
+            if repair {
+                issue.repair_result(age.create_graph(graph_id).await);
+            }
+            let created = issue.repaired;
+            report.issues.push(issue);
+            if !created {
+                // Without the graph its labels can't be created either
+                continue;
+            }
+            labels = &no_labels;
+        }
+
+        for mut issue in label_issues(graph_id, snapshot.graph_types(graph_id), labels) {
+            if repair {
+                repair_label(age, &mut issue).await?;
+            }
+            report.issues.push(issue);
+        }
+    }
+
+    for mut issue in orphan_graph_issues(&snapshot.graph_ids, &snapshot.age_graphs) {
+        if repair && issue.note.is_none() {
+            if age.graph_has_data(&issue.graph_id).await? {
+                issue.note = Some("The graph holds data, it was left in place".into());
+            } else {
+                issue.repair_result(age.drop_graph(&issue.graph_id).await);
+            }
+        }
+        report.issues.push(issue);
+    }
+    Ok(report)
+}
+
+// Create a missing label, or drop an orphan one that holds no data
+async fn repair_label(age: &impl AgeCatalog, issue: &mut CatalogIssue) -> Result<(), sqlx::Error> {
+    let (Some(label), Some(kind)) = (issue.label.clone(), issue.label_kind) else {
+        return Ok(());
+    };
+    match issue.kind {
+        CatalogIssueKind::MissingLabel => {
+            issue.repair_result(age.create_label(&issue.graph_id, &label, kind).await);
+        }
+        CatalogIssueKind::OrphanLabel => {
+            if age.label_has_data(&issue.graph_id, &label).await? {
+                issue.note = Some("The label holds data, it was left in place".into());
+            } else {
+                issue.repair_result(age.drop_label(&issue.graph_id, &label).await);
+            }
+        }
+        CatalogIssueKind::MissingAgeGraph | CatalogIssueKind::OrphanAgeGraph => {}
+    }
+    Ok(())
+}
+
+// Run a graph creation, dropping the AGE graph it leaves behind if it fails. The graph is
+// created in the same transaction as the metadata and should roll back with it. Until that
+// is confirmed for every AGE version, one left behind is dropped
+pub async fn with_graph_cleanup<T, E>(
+    pool: &sqlx::PgPool,
+    graph_id: &GraphId,
+    create: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    cleanup_graph_after(pool, graph_id.as_str(), create).await
+}
+
+async fn cleanup_graph_after<T, E>(
+    age: &impl AgeCatalog,
+    graph_id: &str,
+    create: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let result = create.await;
+    if result.is_err() {
+        drop_orphan_graph(age, graph_id).await;
+    }
+    result
+}
+
+// Run a type creation, dropping the AGE label it leaves behind if it fails, as graphs are
+pub async fn with_label_cleanup<T, E>(
+    pool: &sqlx::PgPool,
+    graph_id: &GraphId,
+    label: &str,
+    kind: LabelKind,
+    create: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    cleanup_label_after(pool, graph_id.as_str(), label, kind, create).await
+}
+
+async fn cleanup_label_after<T, E>(
+    age: &impl AgeCatalog,
+    graph_id: &str,
+    label: &str,
+    kind: LabelKind,
+    create: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let result = create.await;
+    if result.is_err() {
+        drop_orphan_label(age, graph_id, label, kind).await;
+    }
+    result
+}
+
+// Drop the AGE graph of a graph creation that failed. A graph that has metadata or holds
+// data is left alone. Failures are only logged, the catalog check reports whatever is left
+async fn drop_orphan_graph(age: &impl AgeCatalog, graph_id: &str) {
+    let result = async {
+        if !age.graph_exists(graph_id).await?
+            || age.graph_has_metadata(graph_id).await?
+            || age.graph_has_data(graph_id).await?
+        {
+            return Ok(false);
+        }
+        age.drop_graph(graph_id).await?;
+        Ok::<_, sqlx::Error>(true)
+    }
+    .await;
+    match result {
+        Ok(true) => warn!("Dropped AGE graph {} left by a failed creation", graph_id),
+        Ok(false) => {}
+        Err(e) => warn!("Failed to clean up AGE graph {}: {}", graph_id, e),
+    }
+}
+
+// Drop the AGE label of a deleted type, unless another type accounts for it or it holds data
+pub async fn drop_label_of_deleted_type(
+    pool: &sqlx::PgPool,
+    graph_id: &GraphId,
+    label: &str,
+    kind: LabelKind,
+) {
+    drop_orphan_label(pool, graph_id.as_str(), label, kind).await
+}
+
+// Drop a label no type accounts for, if it holds no data. Failures are only logged
+async fn drop_orphan_label(age: &impl AgeCatalog, graph_id: &str, label: &str, kind: LabelKind) {
+    let result = async {
+        if !age.label_exists(graph_id, label, kind).await?
+            || age.label_has_type(graph_id, label, kind).await?
+            || age.label_has_data(graph_id, label).await?
+        {
+            return Ok(false);
+        }
+        age.drop_label(graph_id, label).await?;
+        Ok::<_, sqlx::Error>(true)
+    }
+    .await;
+    match result {
+        Ok(true) => warn!(
+            "Dropped AGE label {} of graph {} that no type accounts for",
+            label, graph_id
+        ),
+        Ok(false) => {}
+        Err(e) => warn!(
+            "Failed to clean up AGE label {} of graph {}: {}",
+            label, graph_id, e
+        ),
+    }
+}
+
+// Types of every graph, or only of `graph_id`
+async fn type_records(
+    pool: &sqlx::PgPool,
+    graph_id: Option<&str>,
+) -> Result<BTreeMap<String, Vec<TypeRecord>>, sqlx::Error> {
+    let query = "
+        SELECT graph_id, id, name, normalized_name, 'v' AS kind FROM app_data.node_types
+        WHERE $1::text IS NULL OR graph_id = $1
+        UNION ALL
+        SELECT graph_id, id, name, normalized_name, 'e' AS kind FROM app_data.edge_type
+        WHERE $1::text IS NULL OR graph_id = $1
+        ORDER BY graph_id, id
+    ";
+    let mut types: BTreeMap<String, Vec<TypeRecord>> = BTreeMap::new();
+    for row in sqlx::query(query).bind(graph_id).fetch_all(pool).await? {
+        let kind: String = row.try_get("kind")?;
+        types
+            .entry(row.try_get("graph_id")?)
+            .or_default()
+            .push(TypeRecord {
+                id: row.try_get("id")?,
+                name: row.try_get("name")?,
+                normalized_name: row.try_get("normalized_name")?,
+                kind: LabelKind::from_catalog(&kind).unwrap_or(LabelKind::Vertex),
+            });
+    }
+    Ok(types)
+}
+
+// Labels of every AGE graph, or only of `graph_id`, without the default ones
+async fn age_labels(
+    pool: &sqlx::PgPool,
+    graph_id: Option<&str>,
+) -> Result<BTreeMap<String, BTreeSet<(LabelKind, String)>>, sqlx::Error> {
+    let query = "
+        SELECT g.name::text AS graph_name, l.name::text AS label, l.kind::text AS kind
+        FROM ag_catalog.ag_label l
+        JOIN ag_catalog.ag_graph g ON g.graphid = l.graph
+        WHERE $1::text IS NULL OR g.name::text = $1
+    ";
+    let mut labels: BTreeMap<String, BTreeSet<(LabelKind, String)>> = BTreeMap::new();
+    for row in sqlx::query(query).bind(graph_id).fetch_all(pool).await? {
+        let label: String = row.try_get("label")?;
+        let kind: String = row.try_get("kind")?;
+        let Some(kind) = LabelKind::from_catalog(&kind) else {
+            continue;
+        };
+        if DEFAULT_LABELS.contains(&label.as_str()) {
+            continue;
+        }
+        labels
+            .entry(row.try_get("graph_name")?)
+            .or_default()
+            .insert((kind, label));
+    }
+    Ok(labels)
+}
+
+// A label of a graph that holds data, and the node or edge type it belongs to
+#[derive(Debug, Serialize)]
+pub struct GraphLabel {
+    pub label: String,
+    pub kind: LabelKind,
+    // Vertices or edges stored under the label
+    pub count: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub type_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub type_name: Option<String>,
+    // No node or edge type accounts for the label, e.g. data loaded outside the app
+    pub unknown: bool,
+}
+
+// Labels of a graph that hold data, with the types they map to. Like the reconciliation,
+// a label belongs to a type of the same kind if it is the type's id or normalized name
+pub async fn graph_labels(
+    pool: &sqlx::PgPool,
+    graph_id: &GraphId,
+) -> Result<Vec<GraphLabel>, sqlx::Error> {
+    let graph_id = graph_id.as_str();
+    let labels = age_labels(pool, Some(graph_id))
+        .await?
+        .remove(graph_id)
+        .unwrap_or_default();
+
+    let types = type_records(pool, Some(graph_id))
+        .await?
+        .remove(graph_id)
+        .unwrap_or_default();
+
+    let mut counted = Vec::new();
+    for (kind, label) in labels {
+        let count = label_count(pool, graph_id, &label).await?;
+        counted.push((kind, label, count));
+    }
+    Ok(describe_labels(counted, &types))
+}
+
+// The labels holding data, each with the type it belongs to if any
+fn describe_labels(
+    counted: Vec<(LabelKind, String, i64)>,
+    types: &[TypeRecord],
+) -> Vec<GraphLabel> {
+    let mut owners: HashMap<(LabelKind, &str), &TypeRecord> = HashMap::new();
+    for record in types {
+        // An id wins over another type's normalized name, since data is written under ids
+        owners
+            .entry((record.kind, &record.normalized_name))
+            .or_insert(record);
+        owners.insert((record.kind, &record.id), record);
+    }
+
+    counted
+        .into_iter()
+        .filter(|(_, _, count)| *count > 0)
+        .map(|(kind, label, count)| {
+            let owner = owners.get(&(kind, label.as_str()));
+            GraphLabel {
+                unknown: owner.is_none(),
+                type_id: owner.map(|record| record.id.clone()),
+                type_name: owner.map(|record| record.name.clone()),
+                label,
+                kind,
+                count,
+            }
+        })
+        .collect()
+}
+
+// Identifiers come from the AGE catalog rather than from users, quoting keeps them intact
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+async fn label_has_data(
+    pool: &sqlx::PgPool,
+    graph_id: &str,
+    label: &str,
+) -> Result<bool, sqlx::Error> {
+    let query = format!(
+        "SELECT EXISTS (SELECT 1 FROM {}.{})",
+        quote_identifier(graph_id),
+        quote_identifier(label)
+    );
+    sqlx::query_scalar(&query).fetch_one(pool).await
+}
+
+// Rows stored under the label itself, not under labels inheriting from it
+async fn label_count(pool: &sqlx::PgPool, graph_id: &str, label: &str) -> Result<i64, sqlx::Error> {
+    let query = format!(
+        "SELECT count(*) FROM ONLY {}.{}",
+        quote_identifier(graph_id),
+        quote_identifier(label)
+    );
+    sqlx::query_scalar(&query).fetch_one(pool).await
+}
+
+async fn graph_has_data(pool: &sqlx::PgPool, graph_id: &str) -> Result<bool, sqlx::Error> {
+    let schema = quote_identifier(graph_id);
+    let query = format!(
+        "SELECT EXISTS (SELECT 1 FROM {0}._ag_label_vertex) OR EXISTS (SELECT 1 FROM {0}._ag_label_edge)",
+        schema
+    );
+    sqlx::query_scalar(&query).fetch_one(pool).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Records the changes made, failing them for `failing` graphs. Graphs and labels are
+    // keyed "graph" and "graph/label", as are `with_data`, the ones holding data, and
+    // `accounted`, the ones metadata accounts for
+    #[derive(Default)]
+    struct FakeAge {
+        with_data: HashSet<String>,
+        accounted: HashSet<String>,
+        failing: HashSet<String>,
+        existing: Mutex<HashSet<String>>,
+        changes: Mutex<Vec<String>>,
+    }
+
+    impl FakeAge {
+        fn change(&self, graph_id: &str, change: String) -> Result<(), sqlx::Error> {
+            self.changes.lock().unwrap().push(change);
+            if self.failing.contains(graph_id) {
+                return Err(sqlx::Error::Protocol("AGE is unavailable".into()));
+            }
+            Ok(())
+        }
+
+        fn changes(&self) -> Vec<String> {
+            self.changes.lock().unwrap().clone()
+        }
+
+        fn exists(&self, key: &str) -> bool {
+            self.existing.lock().unwrap().contains(key)
+        }
+
+        fn set_exists(&self, key: String, exists: bool) {
+            let mut existing = self.existing.lock().unwrap();
+            if exists {
+                existing.insert(key);
+            } else {
+                existing.remove(&key);
+            }
+        }
+    }
+
+    impl AgeCatalog for FakeAge {
+        async fn create_graph(&self, graph_id: &str) -> Result<(), sqlx::Error> {
+            self.change(graph_id, format!("create graph {}", graph_id))?;
+            self.set_exists(graph_id.to_string(), true);
+            Ok(())
+        }
+
+        async fn drop_graph(&self, graph_id: &str) -> Result<(), sqlx::Error> {
+            self.change(graph_id, format!("drop graph {}", graph_id))?;
+            self.set_exists(graph_id.to_string(), false);
+            Ok(())
+        }
+
+        async fn create_label(
+            &self,
+            graph_id: &str,
+            label: &str,
+            kind: LabelKind,
+        ) -> Result<(), sqlx::Error> {
+            self.change(
+                graph_id,
+                format!("create {:?} {}/{}", kind, graph_id, label),
+            )?;
+            self.set_exists(format!("{}/{}", graph_id, label), true);
+            Ok(())
+        }
+
+        async fn drop_label(&self, graph_id: &str, label: &str) -> Result<(), sqlx::Error> {
+            self.change(graph_id, format!("drop label {}/{}", graph_id, label))?;
+            self.set_exists(format!("{}/{}", graph_id, label), false);
+            Ok(())
+        }
+
+        async fn graph_has_data(&self, graph_id: &str) -> Result<bool, sqlx::Error> {
+            Ok(self.with_data.contains(graph_id))
+        }
+
+        async fn label_has_data(&self, graph_id: &str, label: &str) -> Result<bool, sqlx::Error> {
+            Ok(self.with_data.contains(&format!("{}/{}", graph_id, label)))
+        }
+
+        async fn graph_exists(&self, graph_id: &str) -> Result<bool, sqlx::Error> {
+            Ok(self.exists(graph_id))
+        }
+
+        async fn graph_has_metadata(&self, graph_id: &str) -> Result<bool, sqlx::Error> {
+            Ok(self.accounted.contains(graph_id))
+        }
+
+        async fn label_exists(
+            &self,
+            graph_id: &str,
+            label: &str,
+            _kind: LabelKind,
+        ) -> Result<bool, sqlx::Error> {
+            Ok(self.exists(&format!("{}/{}", graph_id, label)))
+        }
+
+        async fn label_has_type(
+            &self,
+            graph_id: &str,
+            label: &str,
+            _kind: LabelKind,
+        ) -> Result<bool, sqlx::Error> {
+            Ok(self.accounted.contains(&format!("{}/{}", graph_id, label)))
+        }
+    }
+
+    const PEOPLE: &str = "gPEOPLE01";
+    const PLACES: &str = "gPLACES01";
+
+    fn record(id: &str, normalized_name: &str, kind: LabelKind) -> TypeRecord {
+        TypeRecord {
+            id: id.into(),
+            name: normalized_name.to_lowercase(),
+            normalized_name: normalized_name.into(),
+            kind,
+        }
+    }
+
+    fn labels(labels: &[(LabelKind, &str)]) -> BTreeSet<(LabelKind, String)> {
+        labels
+            .iter()
+            .map(|(kind, label)| (*kind, label.to_string()))
+            .collect()
+    }
+
+    fn strings(values: &[&str]) -> HashSet<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    // PEOPLE has its AGE graph, with the PERSON label and data under the person type's
+    // id, no KNOWS label and two labels no type has. PLACES has no AGE graph. gORPHAN01
+    // and "scratch" are AGE graphs without metadata
+    fn snapshot() -> CatalogSnapshot {
+        CatalogSnapshot {
+            graph_ids: [PEOPLE, PLACES].map(String::from).into(),
+            age_graphs: strings(&[PEOPLE, "gORPHAN01", "scratch"]),
+            types: [
+                (
+                    PEOPLE.to_string(),
+                    vec![
+                        record("vPERSON01", "PERSON", LabelKind::Vertex),
+                        record("eKNOWS001", "KNOWS", LabelKind::Edge),
+                    ],
+                ),
+                (
+                    PLACES.to_string(),
+                    vec![record("vCITY0001", "CITY", LabelKind::Vertex)],
+                ),
+            ]
+            .into(),
+            labels: [(
+                PEOPLE.to_string(),
+                labels(&[
+                    (LabelKind::Vertex, "PERSON"),
+                    (LabelKind::Vertex, "vPERSON01"),
+                    (LabelKind::Vertex, "ROBOT"),
+                    (LabelKind::Edge, "LIKES"),
+                ]),
+            )]
+            .into(),
+        }
+    }
+
+    // (kind, graph, label, repaired, note)
+    fn summary(report: &CatalogReport) -> Vec<(CatalogIssueKind, &str, Option<&str>, bool, bool)> {
+        report
+            .issues
+            .iter()
+            .map(|issue| {
+                (
+                    issue.kind,
+                    issue.graph_id.as_str(),
+                    issue.label.as_deref(),
+                    issue.repaired,
+                    issue.note.is_some(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lists_missing_and_orphan_labels() {
+        let types = [
+            record("vPERSON01", "PERSON", LabelKind::Vertex),
+            record("eKNOWS001", "KNOWS", LabelKind::Edge),
+        ];
+        let issues = label_issues(
+            PEOPLE,
+            &types,
+            &labels(&[
+                (LabelKind::Vertex, "PERSON"),
+                // Data is written under the type id
+                (LabelKind::Vertex, "vPERSON01"),
+                (LabelKind::Vertex, "ROBOT"),
+                // A vertex type doesn't account for an edge label of the same name
+                (LabelKind::Edge, "PERSON"),
+            ]),
+        );
+        let found: Vec<_> = issues
+            .iter()
+            .map(|issue| {
+                (
+                    issue.kind,
+                    issue.type_id.as_deref(),
+                    issue.label.as_deref().unwrap(),
+                    issue.label_kind.unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    CatalogIssueKind::MissingLabel,
+                    Some("eKNOWS001"),
+                    "KNOWS",
+                    LabelKind::Edge
+                ),
+                (
+                    CatalogIssueKind::OrphanLabel,
+                    None,
+                    "ROBOT",
+                    LabelKind::Vertex
+                ),
+                (
+                    CatalogIssueKind::OrphanLabel,
+                    None,
+                    "PERSON",
+                    LabelKind::Edge
+                ),
+            ]
+        );
+        assert!(issues.iter().all(|issue| issue.graph_id == PEOPLE));
+    }
+
+    #[test]
+    fn labels_of_a_consistent_graph_have_no_issues() {
+        let types = [record("vPERSON01", "PERSON", LabelKind::Vertex)];
+        let labels = labels(&[(LabelKind::Vertex, "PERSON")]);
+        assert!(label_issues(PEOPLE, &types, &labels).is_empty());
+        assert!(label_issues(PEOPLE, &[], &BTreeSet::new()).is_empty());
+    }
+
+    #[test]
+    fn lists_orphan_graphs_in_name_order() {
+        let issues = orphan_graph_issues(
+            &[PEOPLE.to_string()].into(),
+            &strings(&["scratch", "gORPHAN02", PEOPLE, "gORPHAN01"]),
+        );
+        let found: Vec<_> = issues
+            .iter()
+            .map(|issue| (issue.graph_id.as_str(), issue.note.is_some()))
+            .collect();
+        // Only graphs named like application graphs are candidates for dropping
+        assert_eq!(
+            found,
+            [
+                ("gORPHAN01", false),
+                ("gORPHAN02", false),
+                ("scratch", true)
+            ]
+        );
+        assert!(issues
+            .iter()
+            .all(|issue| issue.kind == CatalogIssueKind::OrphanAgeGraph));
+    }
+
+    #[tokio::test]
+    async fn check_without_repair_changes_nothing() {
+        let age = FakeAge::default();
+        let report = reconcile(&snapshot(), &age, false).await.unwrap();
+
+        assert!(!report.repair);
+        assert_eq!(report.graphs_checked, 2);
+        assert_eq!(report.types_checked, 3);
+        assert_eq!(
+            summary(&report),
+            [
+                (
+                    CatalogIssueKind::MissingLabel,
+                    PEOPLE,
+                    Some("KNOWS"),
+                    false,
+                    false
+                ),
+                (
+                    CatalogIssueKind::OrphanLabel,
+                    PEOPLE,
+                    Some("ROBOT"),
+                    false,
+                    false
+                ),
+                (
+                    CatalogIssueKind::OrphanLabel,
+                    PEOPLE,
+                    Some("LIKES"),
+                    false,
+                    false
+                ),
+                // The labels of a graph without its AGE graph aren't listed
+                (
+                    CatalogIssueKind::MissingAgeGraph,
+                    PLACES,
+                    None,
+                    false,
+                    false
+                ),
+                (
+                    CatalogIssueKind::OrphanAgeGraph,
+                    "gORPHAN01",
+                    None,
+                    false,
+                    false
+                ),
+                (
+                    CatalogIssueKind::OrphanAgeGraph,
+                    "scratch",
+                    None,
+                    false,
+                    true
+                ),
+            ]
+        );
+        assert!(age.changes().is_empty());
+    }
+
+    #[tokio::test]
+    async fn repair_creates_missing_and_drops_empty_orphans() {
+        let age = FakeAge {
+            with_data: strings(&["gPEOPLE01/LIKES"]),
+            ..Default::default()
+        };
+        let report = reconcile(&snapshot(), &age, true).await.unwrap();
+
+        assert_eq!(
+            summary(&report),
+            [
+                (
+                    CatalogIssueKind::MissingLabel,
+                    PEOPLE,
+                    Some("KNOWS"),
+                    true,
+                    false
+                ),
+                (
+                    CatalogIssueKind::OrphanLabel,
+                    PEOPLE,
+                    Some("ROBOT"),
+                    true,
+                    false
+                ),
+                // Labels holding data are left in place
+                (
+                    CatalogIssueKind::OrphanLabel,
+                    PEOPLE,
+                    Some("LIKES"),
+                    false,
+                    true
+                ),
+                (CatalogIssueKind::MissingAgeGraph, PLACES, None, true, false),
+                // A created graph has none of its labels yet
+                (
+                    CatalogIssueKind::MissingLabel,
+                    PLACES,
+                    Some("CITY"),
+                    true,
+                    false
+                ),
+                (
+                    CatalogIssueKind::OrphanAgeGraph,
+                    "gORPHAN01",
+                    None,
+                    true,
+                    false
+                ),
+                (
+                    CatalogIssueKind::OrphanAgeGraph,
+                    "scratch",
+                    None,
+                    false,
+                    true
+                ),
+            ]
+        );
+        assert_eq!(
+            age.changes(),
+            [
+                "create Edge gPEOPLE01/KNOWS",
+                "drop label gPEOPLE01/ROBOT",
+                "create graph gPLACES01",
+                "create Vertex gPLACES01/CITY",
+                "drop graph gORPHAN01",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn repair_keeps_orphan_graphs_holding_data() {
+        let age = FakeAge {
+            with_data: strings(&["gORPHAN01"]),
+            ..Default::default()
+        };
+        let report = reconcile(&snapshot(), &age, true).await.unwrap();
+
+        let orphan = report
+            .issues
+            .iter()
+            .find(|issue| issue.graph_id == "gORPHAN01")
+            .unwrap();
+        assert!(!orphan.repaired);
+        assert_eq!(
+            orphan.note.as_deref(),
+            Some("The graph holds data, it was left in place")
+        );
+        assert!(!age
+            .changes()
+            .iter()
+            .any(|change| change.contains("gORPHAN01")));
+    }
+
+    #[tokio::test]
+    async fn failed_repairs_are_reported() {
+        let age = FakeAge {
+            failing: strings(&[PLACES, PEOPLE]),
+            ..Default::default()
+        };
+        let report = reconcile(&snapshot(), &age, true).await.unwrap();
+
+        assert_eq!(
+            summary(&report),
+            [
+                (
+                    CatalogIssueKind::MissingLabel,
+                    PEOPLE,
+                    Some("KNOWS"),
+                    false,
+                    true
+                ),
+                (
+                    CatalogIssueKind::OrphanLabel,
+                    PEOPLE,
+                    Some("ROBOT"),
+                    false,
+                    true
+                ),
+                (
+                    CatalogIssueKind::OrphanLabel,
+                    PEOPLE,
+                    Some("LIKES"),
+                    false,
+                    true
+                ),
+                // Without the graph, its labels aren't attempted
+                (CatalogIssueKind::MissingAgeGraph, PLACES, None, false, true),
+                (
+                    CatalogIssueKind::OrphanAgeGraph,
+                    "gORPHAN01",
+                    None,
+                    true,
+                    false
+                ),
+                (
+                    CatalogIssueKind::OrphanAgeGraph,
+                    "scratch",
+                    None,
+                    false,
+                    true
+                ),
+            ]
+        );
+        assert!(!age
+            .changes()
+            .contains(&"create Vertex gPLACES01/CITY".to_string()));
+    }
+
+    #[test]
+    fn describes_labels_holding_data() {
+        let types = [
+            record("vPERSON01", "PERSON", LabelKind::Vertex),
+            record("vROBOT001", "ROBOT", LabelKind::Vertex),
+        ];
+        let counted = vec![
+            (LabelKind::Vertex, "vPERSON01".to_string(), 3),
+            (LabelKind::Vertex, "ROBOT".to_string(), 1),
+            // Left by a deleted type
+            (LabelKind::Vertex, "vGONE0001".to_string(), 2),
+            (LabelKind::Edge, "vPERSON01".to_string(), 4),
+            (LabelKind::Vertex, "PERSON".to_string(), 0),
+        ];
+        let described: Vec<_> = describe_labels(counted, &types)
+            .into_iter()
+            .map(|label| {
+                (
+                    label.label,
+                    label.kind,
+                    label.count,
+                    label.type_id,
+                    label.type_name,
+                    label.unknown,
+                )
+            })
+            .collect();
+        assert_eq!(
+            described,
+            [
+                (
+                    "vPERSON01".to_string(),
+                    LabelKind::Vertex,
+                    3,
+                    Some("vPERSON01".to_string()),
+                    Some("person".to_string()),
+                    false
+                ),
+                (
+                    "ROBOT".to_string(),
+                    LabelKind::Vertex,
+                    1,
+                    Some("vROBOT001".to_string()),
+                    Some("robot".to_string()),
+                    false
+                ),
+                (
+                    "vGONE0001".to_string(),
+                    LabelKind::Vertex,
+                    2,
+                    None,
+                    None,
+                    true
+                ),
+                (
+                    "vPERSON01".to_string(),
+                    LabelKind::Edge,
+                    4,
+                    None,
+                    None,
+                    true
+                ),
+            ]
+        );
+    }
+
+    // A type creation whose AGE label outlives the transaction, as NodeType::save creates
+    // it: the metadata insert and the label succeed, then `fail_with`, if any, fails an
+    // insert after them and the metadata rolls back without the label
+    async fn create_type(age: &FakeAge, fail_with: Option<&str>) -> Result<(), sqlx::Error> {
+        age.create_label(PEOPLE, "PERSON", LabelKind::Vertex)
+            .await?;
+        match fail_with {
+            Some(code) => Err(crate::db::database_error(code)),
+            None => Ok(()),
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_type_creation_drops_its_label() {
+        let age = FakeAge::default();
+        // An attribute insert after the label violates a unique constraint
+        let created = cleanup_label_after(
+            &age,
+            PEOPLE,
+            "PERSON",
+            LabelKind::Vertex,
+            create_type(&age, Some("23505")),
+        )
+        .await;
+
+        assert!(matches!(created, Err(sqlx::Error::Database(_))));
+        assert!(!age.exists("gPEOPLE01/PERSON"));
+        assert_eq!(
+            age.changes(),
+            [
+                "create Vertex gPEOPLE01/PERSON",
+                "drop label gPEOPLE01/PERSON"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn successful_type_creation_keeps_its_label() {
+        let age = FakeAge {
+            accounted: strings(&["gPEOPLE01/PERSON"]),
+            ..Default::default()
+        };
+        cleanup_label_after(
+            &age,
+            PEOPLE,
+            "PERSON",
+            LabelKind::Vertex,
+            create_type(&age, None),
+        )
+        .await
+        .unwrap();
+
+        assert!(age.exists("gPEOPLE01/PERSON"));
+        assert_eq!(age.changes(), ["create Vertex gPEOPLE01/PERSON"]);
+    }
+
+    #[tokio::test]
+    async fn failed_type_creation_keeps_labels_in_use() {
+        // Another type with the name was created meanwhile, or the label holds data
+        for age in [
+            FakeAge {
+                accounted: strings(&["gPEOPLE01/PERSON"]),
+                ..Default::default()
+            },
+            FakeAge {
+                with_data: strings(&["gPEOPLE01/PERSON"]),
+                ..Default::default()
+            },
+        ] {
+            let created = cleanup_label_after(
+                &age,
+                PEOPLE,
+                "PERSON",
+                LabelKind::Vertex,
+                create_type(&age, Some("23505")),
+            )
+            .await;
+
+            assert!(created.is_err());
+            assert!(age.exists("gPEOPLE01/PERSON"));
+            assert_eq!(age.changes(), ["create Vertex gPEOPLE01/PERSON"]);
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_graph_creation_drops_its_graph() {
+        let age = FakeAge::default();
+        // The graph is created, then the metadata insert fails and rolls back
+        let create = async {
+            age.create_graph(PEOPLE).await?;
+            Err::<(), _>(crate::db::database_error("23505"))
+        };
+        let created = cleanup_graph_after(&age, PEOPLE, create).await;
+
+        assert!(created.is_err());
+        assert!(!age.exists(PEOPLE));
+        assert_eq!(
+            age.changes(),
+            ["create graph gPEOPLE01", "drop graph gPEOPLE01"]
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_graph_creation_keeps_graphs_in_use() {
+        for age in [
+            FakeAge {
+                accounted: strings(&[PEOPLE]),
+                ..Default::default()
+            },
+            FakeAge {
+                with_data: strings(&[PEOPLE]),
+                ..Default::default()
+            },
+        ] {
+            let create = async {
+                age.create_graph(PEOPLE).await?;
+                Err::<(), _>(crate::db::database_error("40001"))
+            };
+            assert!(cleanup_graph_after(&age, PEOPLE, create).await.is_err());
+            assert!(age.exists(PEOPLE));
+            assert_eq!(age.changes(), ["create graph gPEOPLE01"]);
+        }
+    }
+}
@@ -1,15 +1,66 @@
+use crate::auth::OidcProviderApi;
+use crate::db::RetryPolicy;
+use crate::features::Features;
+use crate::graph::EdgeStatsCache;
+use crate::membership::MembershipCache;
+use crate::notification::Notifier;
+use crate::rate_limit::{WriteBudget, WriteClass, WriteThrottle};
+use crate::utils::MAX_STRING_VALUE_LENGTH;
+use crate::webhook::WebhookDispatcher;
+use axum::http::HeaderValue;
 use dotenvy::dotenv;
+use serde_json::{json, Value as JsonValue};
 use sqlx::PgPool;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
-use std::num::ParseIntError;
+use std::fmt;
+use std::net::SocketAddr;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
+use url::Url;
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub database_url: String,
     pub max_connections: u32,
+    // Number of items a user may create within the write budget window
+    pub write_budget_limit: u32,
+    pub write_budget_window: Duration,
+    // How long a user's org and graph memberships are cached. Role changes made through
+    // another instance are only seen after this long, 0 disables the cache
+    pub membership_cache_ttl: Duration,
+    // How long graph stats are cached. Reads with a min_revision the cached stats don't
+    // reflect bypass the cache, 0 disables it
+    pub stats_cache_ttl: Duration,
+    // Attempts at a transaction that hit a deadlock or serialization failure
+    pub db_retry_attempts: u32,
+    // SPA page where users approve device logins
+    pub device_verification_url: String,
+    pub features: Features,
+    // Process-wide writes per second for each class of request, 0 for unlimited
+    pub write_throttle_rates: HashMap<WriteClass, u32>,
+    // How long a request may wait for the throttle before it is rejected
+    pub write_throttle_max_wait: Duration,
+    // Graphs an org may have unless it overrides it, 0 for unlimited
+    pub org_max_graphs: u32,
+    // Longest string property value accepted on new writes, in characters
+    pub max_property_value_length: usize,
+    // Log cypher statements with their values instead of type placeholders. For
+    // development only, values may hold personal data
+    pub log_cypher_values: bool,
+    pub bind_address: SocketAddr,
+    // Origins the SPA is served from, allowed by CORS
+    pub cors_origins: Vec<String>,
+    // Google OIDC client. The secret is read by the provider itself and only checked here
+    pub google_client_id: String,
+    // Microsoft Entra ID client, only offered at login when set
+    pub microsoft_client_id: Option<String>,
+    pub redirect_url: String,
+    // Emails that get the superadmin role when they sign up
+    pub superadmins: Vec<String>,
 }
 
 #[derive(Debug, Error)]
@@ -20,30 +71,365 @@ pub enum ConfigError {
     InvalidValue(String, String),
 }
 
+// Every problem found with the environment, so a deployment can be fixed in one go
+#[derive(Debug)]
+pub struct ConfigErrors(pub Vec<ConfigError>);
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid configuration, {} problem(s):", self.0.len())?;
+        for error in &self.0 {
+            write!(f, "\n  - {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigErrors {}
+
+// Reads variables, collecting problems instead of stopping at the first. Invalid values
+// fall back to their default so the remaining variables are still checked
+#[derive(Default)]
+struct EnvReader {
+    errors: Vec<ConfigError>,
+}
+
+impl EnvReader {
+    fn optional(&self, var: &str) -> Option<String> {
+        env::var(var).ok().filter(|value| !value.trim().is_empty())
+    }
+
+    fn required(&mut self, var: &str) -> String {
+        self.optional(var).unwrap_or_else(|| {
+            self.errors.push(ConfigError::MissingVar(var.to_string()));
+            String::new()
+        })
+    }
+
+    fn invalid(&mut self, var: &str, message: impl Into<String>) {
+        self.errors
+            .push(ConfigError::InvalidValue(var.to_string(), message.into()));
+    }
+
+    fn number<T>(&mut self, var: &str, default: T, range: RangeInclusive<T>) -> T
+    where
+        T: FromStr + PartialOrd + Copy + fmt::Display,
+        T::Err: fmt::Display,
+    {
+        let Some(value) = self.optional(var) else {
+            return default;
+        };
+        match value.trim().parse::<T>() {
+            Ok(number) if range.contains(&number) => number,
+            Ok(number) => {
+                self.invalid(
+                    var,
+                    format!(
+                        "{} is not between {} and {}",
+                        number,
+                        range.start(),
+                        range.end()
+                    ),
+                );
+                default
+            }
+            Err(e) => {
+                self.invalid(var, e.to_string());
+                default
+            }
+        }
+    }
+
+    // Check that `value` is an absolute URL with one of the given schemes
+    fn url(&mut self, var: &str, value: &str, schemes: &[&str]) {
+        if value.is_empty() {
+            return;
+        }
+        match Url::parse(value) {
+            Ok(url) if !schemes.contains(&url.scheme()) => self.invalid(
+                var,
+                format!(
+                    "scheme must be one of {}, found '{}'",
+                    schemes.join(", "),
+                    url.scheme()
+                ),
+            ),
+            Ok(url) if !url.has_host() => self.invalid(var, "URL has no host"),
+            Ok(_) => {}
+            Err(e) => self.invalid(var, e.to_string()),
+        }
+    }
+
+    fn flag(&mut self, var: &str, default: bool) -> bool {
+        let Some(value) = self.optional(var) else {
+            return default;
+        };
+        match value.trim().to_lowercase().as_str() {
+            "true" | "1" => true,
+            "false" | "0" => false,
+            _ => {
+                self.invalid(var, format!("'{}' is not true or false", value));
+                default
+            }
+        }
+    }
+
+    fn list(&self, var: &str, default: &str) -> Vec<String> {
+        self.optional(var)
+            .unwrap_or_else(|| default.to_string())
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
 impl Config {
-    pub fn from_env() -> Result<Self, ConfigError> {
+    pub fn from_env() -> Result<Self, ConfigErrors> {
         // Load .env file if it exists
         dotenv().ok();
+        let mut env = EnvReader::default();
+
+        let database_url = env.required("DATABASE_URL");
+        env.url("DATABASE_URL", &database_url, &["postgres", "postgresql"]);
+
+        let max_connections = env.number("PG_MAX_CONNECTIONS", 20, 1..=1000);
+        let write_budget_limit = env.number("WRITE_BUDGET_LIMIT", 1000, 1..=u32::MAX);
+        let write_budget_window =
+            Duration::from_secs(env.number("WRITE_BUDGET_WINDOW_SECS", 3600, 1..=86400 * 7));
+        let membership_cache_ttl = Duration::from_secs(env.number(
+            "MEMBERSHIP_CACHE_TTL_SECS",
+            DEFAULT_MEMBERSHIP_CACHE_TTL_SECS,
+            0..=300,
+        ));
+        let stats_cache_ttl = Duration::from_secs(env.number(
+            "STATS_CACHE_TTL_SECS",
+            DEFAULT_STATS_CACHE_TTL_SECS,
+            0..=300,
+        ));
+        let db_retry_attempts = env.number("DB_RETRY_ATTEMPTS", 3, 1..=10);
+
+        let device_verification_url = env
+            .optional("DEVICE_VERIFICATION_URL")
+            .unwrap_or_else(|| "http://localhost:3000/device".to_string());
+        env.url(
+            "DEVICE_VERIFICATION_URL",
+            &device_verification_url,
+            &["http", "https"],
+        );
+
+        let mut write_throttle_rates = HashMap::new();
+        for (class, default) in [
+            (WriteClass::Metadata, 20),
+            (WriteClass::Data, 200),
+            (WriteClass::Exports, 2),
+        ] {
+            let var = format!(
+                "WRITE_THROTTLE_{}_PER_SEC",
+                class.to_string().to_uppercase()
+            );
+            write_throttle_rates.insert(class, env.number(&var, default, 0..=u32::MAX));
+        }
+
+        // Waiting has to end well within the 10 second request timeout
+        let write_throttle_max_wait =
+            Duration::from_millis(env.number("WRITE_THROTTLE_MAX_WAIT_MS", 250, 0..=5000));
+
+        let org_max_graphs = env.number("ORG_MAX_GRAPHS", 100, 0..=100_000);
+        // Request payloads are already held to MAX_STRING_VALUE_LENGTH, so this can only
+        // lower the cap. Attributes with their own max_length are held to the smaller one
+        let max_property_value_length = env.number(
+            "MAX_PROPERTY_VALUE_LENGTH",
+            MAX_STRING_VALUE_LENGTH,
+            1..=MAX_STRING_VALUE_LENGTH,
+        );
+        let log_cypher_values = env.flag("LOG_CYPHER_VALUES", false);
+
+        let features = match env.optional("FEATURES") {
+            Some(features) => features.parse::<Features>().unwrap_or_else(|e| {
+                env.invalid("FEATURES", e);
+                Features::default()
+            }),
+            None => Features::default(),
+        };
+
+        let bind_address = env
+            .optional("BIND_ADDRESS")
+            .unwrap_or_else(|| "127.0.0.1:3210".to_string());
+        let bind_address = bind_address.parse::<SocketAddr>().unwrap_or_else(|e| {
+            env.invalid("BIND_ADDRESS", format!("'{}': {}", bind_address, e));
+            SocketAddr::from(([127, 0, 0, 1], 3210))
+        });
+
+        // Browsers send origins as scheme://host[:port], anything more never matches
+        let cors_origins = env.list("CORS_ORIGINS", "http://localhost:3000");
+        for origin in &cors_origins {
+            let valid = HeaderValue::from_str(origin).is_ok()
+                && Url::parse(origin).is_ok_and(|url| {
+                    matches!(url.scheme(), "http" | "https")
+                        && url.has_host()
+                        && url.origin().ascii_serialization() == *origin
+                });
+            if !valid {
+                env.invalid(
+                    "CORS_ORIGINS",
+                    format!("'{}' is not an origin like https://example.com", origin),
+                );
+            }
+        }
 
-        let database_url = env::var("DATABASE_URL")
-            .map_err(|_| ConfigError::MissingVar("DATABASE_URL".to_string()))?;
+        // The OIDC client needs its id, secret and redirect URL together
+        let google_client_id = env.required("GOOGLE_CLIENT_ID");
+        env.required("GOOGLE_CLIENT_SECRET");
+        let microsoft_client_id = env.optional("MICROSOFT_CLIENT_ID");
+        if microsoft_client_id.is_some() {
+            env.required("MICROSOFT_CLIENT_SECRET");
+        }
+        let redirect_url = env.required("REDIRECT_URL");
+        env.url("REDIRECT_URL", &redirect_url, &["http", "https"]);
 
-        let max_connections = env::var("PG_MAX_CONNECTIONS")
-            .unwrap_or_else(|_| "20".to_string())
-            .parse::<u32>()
-            .map_err(|e: ParseIntError| {
-                ConfigError::InvalidValue("PG_MAX_CONNECTIONS".to_string(), e.to_string())
-            })?;
+        let superadmins = env.list("SL_SUPERADMINS", "");
+        for email in &superadmins {
+            if !validator::validate_email(email.as_str()) {
+                env.invalid("SL_SUPERADMINS", format!("'{}' is not an email", email));
+            }
+        }
 
+        if !env.errors.is_empty() {
+            return Err(ConfigErrors(env.errors));
+        }
         Ok(Config {
             database_url,
             max_connections,
+            write_budget_limit,
+            write_budget_window,
+            membership_cache_ttl,
+            stats_cache_ttl,
+            db_retry_attempts,
+            device_verification_url,
+            features,
+            write_throttle_rates,
+            write_throttle_max_wait,
+            org_max_graphs,
+            max_property_value_length,
+            log_cypher_values,
+            bind_address,
+            cors_origins,
+            google_client_id,
+            microsoft_client_id,
+            redirect_url,
+            superadmins,
+        })
+    }
+
+    // The configuration as JSON, for support to check what a deployment runs with.
+    // Passwords and secrets are replaced
+    pub fn redacted(&self) -> JsonValue {
+        let database_url = match Url::parse(&self.database_url) {
+            Ok(mut url) => {
+                if url.password().is_some() {
+                    let _ = url.set_password(Some(REDACTED));
+                }
+                url.to_string()
+            }
+            Err(_) => REDACTED.to_string(),
+        };
+        let write_throttle_rates: BTreeMap<String, u32> = self
+            .write_throttle_rates
+            .iter()
+            .map(|(class, rate)| (class.to_string(), *rate))
+            .collect();
+        json!({
+            "database_url": database_url,
+            "max_connections": self.max_connections,
+            "write_budget_limit": self.write_budget_limit,
+            "write_budget_window_secs": self.write_budget_window.as_secs(),
+            "membership_cache_ttl_secs": self.membership_cache_ttl.as_secs(),
+            "stats_cache_ttl_secs": self.stats_cache_ttl.as_secs(),
+            "db_retry_attempts": self.db_retry_attempts,
+            "device_verification_url": self.device_verification_url,
+            "features": self.features,
+            "write_throttle_rates": write_throttle_rates,
+            "write_throttle_max_wait_ms": self.write_throttle_max_wait.as_millis() as u64,
+            "org_max_graphs": self.org_max_graphs,
+            "max_property_value_length": self.max_property_value_length,
+            "log_cypher_values": self.log_cypher_values,
+            "bind_address": self.bind_address.to_string(),
+            "cors_origins": self.cors_origins,
+            "google_client_id": self.google_client_id,
+            "google_client_secret": REDACTED,
+            "microsoft_client_id": self.microsoft_client_id,
+            "microsoft_client_secret": self.microsoft_client_id.as_ref().map(|_| REDACTED),
+            "redirect_url": self.redirect_url,
+            "superadmins": self.superadmins,
         })
     }
 }
 
+const REDACTED: &str = "[redacted]";
+
+const DEFAULT_MEMBERSHIP_CACHE_TTL_SECS: u64 = 30;
+
+const DEFAULT_STATS_CACHE_TTL_SECS: u64 = 30;
+
 #[derive(Debug, Clone)]
 pub struct AppState {
     pub pool: Arc<PgPool>,
-    pub oidc_providers: HashMap<String, crate::auth::OidcProvider>,
+    pub oidc_providers: HashMap<String, Arc<dyn OidcProviderApi>>,
+    pub write_budget: Arc<WriteBudget>,
+    // Consulted by GraphAccess and OrgAccess, invalidated by member and role changes
+    pub memberships: Arc<MembershipCache>,
+    pub edge_stats: Arc<EdgeStatsCache>,
+    pub write_throttle: Arc<WriteThrottle>,
+    pub notifier: Arc<Notifier>,
+    pub db_retry: RetryPolicy,
+    pub webhooks: Arc<WebhookDispatcher>,
+    pub device_verification_url: String,
+    pub features: Features,
+    // Default limit on graphs per org, 0 for unlimited
+    pub org_max_graphs: u32,
+    // Longest string property value accepted on new writes
+    pub max_property_value_length: usize,
+    // Origins allowed by CORS
+    pub cors_origins: Vec<HeaderValue>,
+    // Served to superadmins by GET /admin/config
+    pub redacted_config: Arc<JsonValue>,
+}
+
+impl AppState {
+    // Build an AppState that does not need network access to any identity provider.
+    // The "google" provider is backed by MockOidcProvider. Writes are not throttled.
+    #[cfg(test)]
+    pub fn for_tests(pool: PgPool) -> Self {
+        use crate::auth::{AuthProvider, MockOidcProvider};
+
+        let mock_provider = MockOidcProvider {
+            provider: AuthProvider::Google,
+        };
+        Self {
+            pool: Arc::new(pool),
+            oidc_providers: HashMap::from([(
+                "google".to_string(),
+                Arc::new(mock_provider) as Arc<dyn OidcProviderApi>,
+            )]),
+            write_budget: Arc::new(WriteBudget::new(1000, Duration::from_secs(3600))),
+            memberships: Arc::new(MembershipCache::new(Duration::from_secs(
+                DEFAULT_MEMBERSHIP_CACHE_TTL_SECS,
+            ))),
+            edge_stats: Arc::new(EdgeStatsCache::new(Duration::from_secs(
+                DEFAULT_STATS_CACHE_TTL_SECS,
+            ))),
+            write_throttle: Arc::new(WriteThrottle::new(&HashMap::new(), Duration::ZERO)),
+            notifier: Arc::new(Notifier::default()),
+            db_retry: RetryPolicy::default(),
+            webhooks: Arc::new(WebhookDispatcher::default()),
+            device_verification_url: "http://localhost:3000/device".to_string(),
+            features: Features::all(),
+            org_max_graphs: 0,
+            max_property_value_length: MAX_STRING_VALUE_LENGTH,
+            cors_origins: vec![HeaderValue::from_static("http://localhost:3000")],
+            redacted_config: Arc::new(json!({})),
+        }
+    }
 }
@@ -0,0 +1,101 @@
+// Reader for CSV uploads, following RFC 4180: comma separated fields, optionally wrapped in
+// double quotes with "" for a literal quote. Quoted fields may contain commas and line
+// breaks. A leading UTF-8 BOM is skipped, lines may end in CRLF or LF and blank lines are
+// ignored
+
+#[derive(Debug, thiserror::Error)]
+pub enum CsvError {
+    #[error("Line {0}: quoted field is never closed")]
+    UnterminatedQuote(usize),
+
+    #[error("Line {0}: unexpected character after a closing quote")]
+    TextAfterQuote(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvRecord {
+    // Line the record starts on, counting from 1
+    pub line: usize,
+    pub fields: Vec<String>,
+}
+
+pub fn parse(text: &str) -> Result<Vec<CsvRecord>, CsvError> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut in_quotes = false;
+    // The current field was quoted and its closing quote has been read
+    let mut after_quote = false;
+    // Tells a record holding one quoted empty field apart from a blank line
+    let mut record_quoted = false;
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => {
+                    in_quotes = false;
+                    after_quote = true;
+                }
+                '\n' => {
+                    line += 1;
+                    field.push(c);
+                }
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            ',' => {
+                fields.push(std::mem::take(&mut field));
+                after_quote = false;
+            }
+            '\r' | '\n' => {
+                if c == '\r' && chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+                fields.push(std::mem::take(&mut field));
+                let blank = !record_quoted && fields.len() == 1 && fields[0].is_empty();
+                if !blank {
+                    records.push(CsvRecord {
+                        line: record_line,
+                        fields: std::mem::take(&mut fields),
+                    });
+                }
+                fields.clear();
+                line += 1;
+                record_line = line;
+                after_quote = false;
+                record_quoted = false;
+            }
+            _ if after_quote => return Err(CsvError::TextAfterQuote(line)),
+            '"' if field.is_empty() => {
+                in_quotes = true;
+                record_quoted = true;
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(CsvError::UnterminatedQuote(record_line));
+    }
+    // Last record without a trailing line break
+    if record_quoted || !fields.is_empty() || !field.is_empty() {
+        fields.push(field);
+        records.push(CsvRecord {
+            line: record_line,
+            fields,
+        });
+    }
+    Ok(records)
+}
@@ -0,0 +1,249 @@
+use crate::ag::AgType;
+use crate::ids::GraphId;
+use sqlx::postgres::PgRow;
+use sqlx::PgExecutor;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{debug, enabled, Level};
+
+// Whether logged statements keep their literal values. Off unless LOG_CYPHER_VALUES is
+// set, since property values may hold personal data
+static LOG_VALUES: AtomicBool = AtomicBool::new(false);
+
+pub fn log_values(enabled: bool) {
+    LOG_VALUES.store(enabled, Ordering::Relaxed);
+}
+
+// A cypher statement against one graph. All cypher is run through here, so every
+// statement is logged at debug level with the graph id, inside the request span that
+// carries the request id
+#[derive(Debug, Clone)]
+pub struct Cypher {
+    graph_id: String,
+    statement: String,
+    // Column list of the cypher() call
+    columns: &'static str,
+}
+
+impl Cypher {
+    // A statement returning a single agtype column named `row`
+    pub fn new(graph_id: &GraphId, statement: impl Into<String>) -> Self {
+        Self {
+            graph_id: graph_id.to_string(),
+            statement: statement.into(),
+            columns: "row agtype",
+        }
+    }
+
+    // Name the returned columns, e.g. "total agtype" or "a agtype, r agtype, b agtype"
+    pub fn returning(mut self, columns: &'static str) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    fn sql(&self) -> String {
+        format!(
+            "SELECT * FROM cypher('{}', $$ {} $$) as ({})",
+            self.graph_id, self.statement, self.columns
+        )
+    }
+
+    fn log(&self) {
+        if !enabled!(Level::DEBUG) {
+            return;
+        }
+        if LOG_VALUES.load(Ordering::Relaxed) {
+            debug!(graph_id = %self.graph_id, statement = %self.statement, "Running cypher");
+        } else {
+            debug!(graph_id = %self.graph_id, statement = %redact(&self.statement), "Running cypher");
+        }
+    }
+
+    pub async fn fetch_all<'e>(
+        &self,
+        executor: impl PgExecutor<'e>,
+    ) -> Result<Vec<AgType>, sqlx::Error> {
+        self.log();
+        sqlx::query_as::<_, AgType>(&self.sql())
+            .fetch_all(executor)
+            .await
+    }
+
+    pub async fn fetch_one<'e>(
+        &self,
+        executor: impl PgExecutor<'e>,
+    ) -> Result<AgType, sqlx::Error> {
+        self.log();
+        sqlx::query_as::<_, AgType>(&self.sql())
+            .fetch_one(executor)
+            .await
+    }
+
+    pub async fn fetch_optional<'e>(
+        &self,
+        executor: impl PgExecutor<'e>,
+    ) -> Result<Option<AgType>, sqlx::Error> {
+        self.log();
+        sqlx::query_as::<_, AgType>(&self.sql())
+            .fetch_optional(executor)
+            .await
+    }
+
+    // Rows of a statement returning several columns, read with `row.try_get::<AgType, _>`
+    pub async fn fetch_rows<'e>(
+        &self,
+        executor: impl PgExecutor<'e>,
+    ) -> Result<Vec<PgRow>, sqlx::Error> {
+        self.log();
+        sqlx::query(&self.sql()).fetch_all(executor).await
+    }
+
+    // Run a statement for its effect, e.g. a SET without RETURN
+    pub async fn execute<'e>(&self, executor: impl PgExecutor<'e>) -> Result<(), sqlx::Error> {
+        self.log();
+        sqlx::query(&self.sql()).execute(executor).await?;
+        Ok(())
+    }
+}
+
+// Replace the literal values of a statement with their type, so {name: 'Ada', age: 36}
+// is logged as {name: <string>, age: <number>}. Labels, keys and variables are kept, and so
+// are the numbers of SKIP and LIMIT
+fn redact(statement: &str) -> String {
+    let chars: Vec<char> = statement.chars().collect();
+    let mut out = String::with_capacity(statement.len());
+    let mut last_word = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            // Strings are single or double quoted, and escape their quote as \' or ''
+            quote @ ('\'' | '"') => {
+                i += 1;
+                while i < chars.len() {
+                    match chars[i] {
+                        '\\' => i += 2,
+                        c if c == quote && chars.get(i + 1) == Some(&quote) => i += 2,
+                        c if c == quote => break,
+                        _ => i += 1,
+                    }
+                }
+                out.push_str("<string>");
+                last_word.clear();
+            }
+            // Quoted keys, with backticks doubled
+            '`' => {
+                out.push(c);
+                i += 1;
+                while i < chars.len() {
+                    out.push(chars[i]);
+                    if chars[i] == '`' {
+                        if chars.get(i + 1) == Some(&'`') {
+                            i += 1;
+                            out.push('`');
+                        } else {
+                            break;
+                        }
+                    }
+                    i += 1;
+                }
+                last_word.clear();
+            }
+            c if c.is_ascii_digit() => {
+                let start = i;
+                while i + 1 < chars.len()
+                    && (chars[i + 1].is_ascii_alphanumeric() || chars[i + 1] == '.')
+                {
+                    i += 1;
+                }
+                if last_word.eq_ignore_ascii_case("skip") || last_word.eq_ignore_ascii_case("limit")
+                {
+                    out.extend(&chars[start..=i]);
+                } else {
+                    out.push_str("<number>");
+                }
+                last_word.clear();
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i + 1 < chars.len() && (chars[i + 1].is_alphanumeric() || chars[i + 1] == '_')
+                {
+                    i += 1;
+                }
+                let word: String = chars[start..=i].iter().collect();
+                if word.eq_ignore_ascii_case("true") || word.eq_ignore_ascii_case("false") {
+                    out.push_str("<boolean>");
+                } else {
+                    out.push_str(&word);
+                }
+                last_word = word;
+            }
+            c => {
+                out.push(c);
+                if !c.is_whitespace() {
+                    last_word.clear();
+                }
+            }
+        }
+        i += 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_and_double_quoted_strings() {
+        assert_eq!(
+            redact("CREATE (n:Person {name: 'Ada', email: \"ada@example.com\"})"),
+            "CREATE (n:Person {name: <string>, email: <string>})"
+        );
+    }
+
+    #[test]
+    fn escaped_quotes_stay_inside_the_string() {
+        assert_eq!(
+            redact(r#"SET n.a = 'it\'s', n.b = 'it''s', n.c = "say \"hi\"", n.d = "a""b""#),
+            "SET n.a = <string>, n.b = <string>, n.c = <string>, n.d = <string>"
+        );
+        // A quote of the other kind doesn't end the string
+        assert_eq!(
+            redact(r#"SET n.a = "it's 1", n.b = 'say "2"'"#),
+            "SET n.a = <string>, n.b = <string>"
+        );
+        assert_eq!(redact(r"SET n.a = 'C:\\'"), "SET n.a = <string>");
+    }
+
+    #[test]
+    fn backtick_keys_are_kept() {
+        assert_eq!(
+            redact("MATCH (n) WHERE n.`first name` = 'Ada' AND n.`a``b 1` = 2 RETURN n"),
+            "MATCH (n) WHERE n.`first name` = <string> AND n.`a``b 1` = <number> RETURN n"
+        );
+    }
+
+    #[test]
+    fn skip_and_limit_numbers_are_kept() {
+        assert_eq!(
+            redact("MATCH (n) WHERE n.age > 36 RETURN n SKIP 20 LIMIT 10"),
+            "MATCH (n) WHERE n.age > <number> RETURN n SKIP 20 LIMIT 10"
+        );
+        assert_eq!(
+            redact("RETURN n skip 5 limit 2.5"),
+            "RETURN n skip 5 limit 2.5"
+        );
+        assert_eq!(
+            redact("WHERE id(n) = 844424930131969"),
+            "WHERE id(n) = <number>"
+        );
+    }
+
+    #[test]
+    fn booleans_and_identifiers() {
+        assert_eq!(
+            redact("SET n.active = true, n.archived = FALSE, n2.truth = n.true_value"),
+            "SET n.active = <boolean>, n.archived = <boolean>, n2.truth = n.true_value"
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use crate::error::ApiError;
+use rand::{rng, Rng};
+use std::future::Future;
+use std::time::Duration;
+use tracing::warn;
+
+// SQLSTATEs after which the whole transaction can safely be run again
+const RETRYABLE_SQLSTATES: [&str; 2] = [
+    "40001", // serialization_failure
+    "40P01", // deadlock_detected
+];
+
+// How often a transactional operation is attempted before its error is returned
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(50),
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Exponential backoff with full jitter, so competing requests don't collide again
+    fn delay(&self, attempt: u32) -> Duration {
+        let max = self.base_delay.as_millis() as u64 * 2u64.pow(attempt - 1);
+        Duration::from_millis(rng().random_range(0..=max))
+    }
+}
+
+pub trait Retryable {
+    fn is_retryable(&self) -> bool;
+}
+
+impl Retryable for sqlx::Error {
+    fn is_retryable(&self) -> bool {
+        match self {
+            sqlx::Error::Database(db_err) => db_err
+                .code()
+                .is_some_and(|code| RETRYABLE_SQLSTATES.contains(&code.as_ref())),
+            _ => false,
+        }
+    }
+}
+
+impl Retryable for ApiError {
+    fn is_retryable(&self) -> bool {
+        matches!(self, ApiError::Database(e) if e.is_retryable())
+    }
+}
+
+// Run `operation`, which must begin and commit its own transaction, again whenever it
+// fails with a deadlock or serialization failure. Attempts are logged inside the
+// current request span
+pub async fn with_retry<T, E, F, Fut>(
+    policy: &RetryPolicy,
+    name: &str,
+    mut operation: F,
+) -> Result<T, E>
+where
+    E: Retryable + std::fmt::Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Err(e) if e.is_retryable() && attempt < policy.max_attempts => {
+                let delay = policy.delay(attempt);
+                warn!(
+                    "{} failed on attempt {}/{}, retrying in {:?}: {}",
+                    name, attempt, policy.max_attempts, delay, e
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+// A database error with the given SQLSTATE, as returned by a failed statement
+#[cfg(test)]
+pub(crate) fn database_error(code: &str) -> sqlx::Error {
+    use sqlx::error::{DatabaseError, ErrorKind};
+    use std::borrow::Cow;
+    use std::error::Error as StdError;
+
+    #[derive(Debug)]
+    struct TestDatabaseError(String);
+
+    impl std::fmt::Display for TestDatabaseError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "error with SQLSTATE {}", self.0)
+        }
+    }
+
+    impl StdError for TestDatabaseError {}
+
+    impl DatabaseError for TestDatabaseError {
+        fn message(&self) -> &str {
+            "test error"
+        }
+
+        fn code(&self) -> Option<Cow<'_, str>> {
+            Some(Cow::Borrowed(&self.0))
+        }
+
+        fn as_error(&self) -> &(dyn StdError + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn StdError + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn StdError + Send + Sync + 'static> {
+            self
+        }
+
+        fn kind(&self) -> ErrorKind {
+            match self.0.as_str() {
+                "23505" => ErrorKind::UniqueViolation,
+                _ => ErrorKind::Other,
+            }
+        }
+    }
+
+    sqlx::Error::Database(Box::new(TestDatabaseError(code.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: RetryPolicy = RetryPolicy {
+        max_attempts: 3,
+        base_delay: Duration::from_millis(1),
+    };
+
+    // Run with_retry on an operation that fails with `failures` in turn, then succeeds.
+    // Returns the result and the number of attempts
+    async fn run(failures: Vec<sqlx::Error>) -> (Result<u32, sqlx::Error>, u32) {
+        let mut attempts = 0;
+        let mut failures = failures.into_iter();
+        let result = with_retry(&POLICY, "test", || {
+            attempts += 1;
+            let (attempt, failure) = (attempts, failures.next());
+            async move {
+                match failure {
+                    Some(e) => Err(e),
+                    None => Ok(attempt),
+                }
+            }
+        })
+        .await;
+        (result, attempts)
+    }
+
+    #[tokio::test]
+    async fn deadlocks_and_serialization_failures_are_retried() {
+        for code in ["40P01", "40001"] {
+            let (result, attempts) = run(vec![database_error(code), database_error(code)]).await;
+            assert_eq!(result.unwrap(), 3, "{}", code);
+            assert_eq!(attempts, 3, "{}", code);
+        }
+        let (result, attempts) = run(vec![database_error("40001"), database_error("40P01")]).await;
+        assert!(result.is_ok());
+        assert_eq!(attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn retries_stop_after_max_attempts() {
+        let failures = (0..POLICY.max_attempts).map(|_| database_error("40P01"));
+        let (result, attempts) = run(failures.collect()).await;
+        assert!(result.unwrap_err().is_retryable());
+        assert_eq!(attempts, POLICY.max_attempts);
+    }
+
+    #[tokio::test]
+    async fn other_errors_are_not_retried() {
+        for error in [
+            database_error("23505"),
+            database_error("42P01"),
+            sqlx::Error::RowNotFound,
+            sqlx::Error::PoolTimedOut,
+        ] {
+            let (result, attempts) = run(vec![error]).await;
+            assert!(result.is_err());
+            assert_eq!(attempts, 1);
+        }
+    }
+
+    #[test]
+    fn api_errors_are_retried_for_retryable_database_errors() {
+        assert!(ApiError::Database(database_error("40P01")).is_retryable());
+        assert!(!ApiError::Database(database_error("23505")).is_retryable());
+        assert!(!ApiError::BadRequest("no".into()).is_retryable());
+    }
+}
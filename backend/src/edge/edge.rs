@@ -1,49 +1,285 @@
-use crate::utils::{validate_label, validate_properties};
+use super::{EdgeType, EdgeTypeAttributeDefinition};
+use crate::ag::{self, AgLookupError, AgType, Vertex};
+use crate::cypher::Cypher;
+use crate::ids::{EdgeTypeId, GraphId};
+use crate::node::{Node, LIST_PAGE_SIZE};
+use crate::utils::{generate_props_clause, rfc3339};
+use crate::validation::{
+    RequiredAttributeHint, ValidationErrorList, ValidationOutcome, ValidationWarning, WriteRules,
+};
+use futures::future::try_join_all;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
+use sqlx::{PgConnection, Row};
 use std::collections::HashMap;
-use validator::{Validate, ValidationError};
-
-#[derive(Debug, Serialize, Deserialize)]
-struct Edge {
-    id: Option<i64>,
-    label: String,
-    from_id: i64,
-    to_id: i64,
-    properties: HashMap<String, JsonValue>,
+use tracing::info;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Edge {
+    pub id: i64,
+    pub label: String,
+    pub from_id: i64,
+    pub to_id: i64,
+    pub properties: HashMap<String, JsonValue>,
 }
 
-#[derive(Debug, Validate, Deserialize)]
-pub struct CreateEdgeRequest {
-    #[validate(length(min = 1, max = 50))]
-    #[validate(custom = "validate_edge_type")]
-    label: String,
+#[derive(Debug, thiserror::Error)]
+pub enum CreateEdgeError {
+    #[error("Validation error: {0}")]
+    ValidationError(ValidationErrorList, Vec<RequiredAttributeHint>),
 
-    #[validate(range(min = 0))]
-    from_id: i64,
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
 
-    #[validate(range(min = 0))]
-    to_id: i64,
+impl TryFrom<ag::Edge> for Edge {
+    type Error = serde_json::Error;
 
-    #[validate(custom = "validate_properties")]
-    properties: HashMap<String, JsonValue>,
+    fn try_from(edge: ag::Edge) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: edge.id,
+            label: edge.label,
+            from_id: edge.start_id,
+            to_id: edge.end_id,
+            properties: serde_json::from_value(edge.properties)?,
+        })
+    }
 }
 
-// TODO: Add additional validation functions specific to edges
-// TODO: Check if edge label has been created, and if not, return an error
-fn validate_edge_type(label: &str) -> Result<(), ValidationError> {
-    // Add any edge-specific label validation rules
-    if label.len() > 50 {
-        return Err(ValidationError::new("edge_label_too_long"));
+impl Edge {
+    // Edges into or out of a vertex
+    pub async fn incident(
+        conn: &mut PgConnection,
+        graph_id: &GraphId,
+        node_id: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let query = Cypher::new(
+            graph_id,
+            format!(
+                "MATCH (a)-[r]->(b) WHERE id(a) = {} OR id(b) = {} RETURN r ORDER BY id(r)",
+                node_id, node_id
+            ),
+        );
+        let ag_rows = query.fetch_all(&mut *conn).await?;
+        ag_rows
+            .into_iter()
+            .map(|ag_row| {
+                ag::Edge::try_from(ag_row)
+                    .and_then(Edge::try_from)
+                    .map_err(|e| sqlx::Error::Decode(Box::new(e)))
+            })
+            .collect()
+    }
+
+    // Edges whose both endpoints are among the given vertices
+    pub async fn among(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        node_ids: &[i64],
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        if node_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let id_list = node_ids
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let query = Cypher::new(
+            graph_id,
+            format!(
+                "MATCH (a)-[r]->(b) WHERE id(a) IN [{}] AND id(b) IN [{}] RETURN r ORDER BY id(r)",
+                id_list, id_list
+            ),
+        );
+        let ag_rows = query.fetch_all(pool).await?;
+        ag_rows
+            .into_iter()
+            .map(|ag_row| {
+                ag::Edge::try_from(ag_row)
+                    .and_then(Edge::try_from)
+                    .map_err(|e| sqlx::Error::Decode(Box::new(e)))
+            })
+            .collect()
     }
 
-    // Call the existing label validation
-    validate_label(label)?;
+    // A page of the graph's edges ordered by id, optionally only those of one type. Pages
+    // start at 1 and are as large as node listing pages
+    pub async fn list(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        edge_type: Option<&EdgeType>,
+        page: u32,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        // Only the stored type id is interpolated as the label, never client input
+        let pattern = match edge_type {
+            Some(edge_type) => format!("()-[r:{}]->()", edge_type.id),
+            None => "()-[r]->()".to_string(),
+        };
+        let query = Cypher::new(
+            graph_id,
+            format!(
+                "MATCH {} RETURN r ORDER BY id(r) SKIP {} LIMIT {}",
+                pattern,
+                (page - 1) * LIST_PAGE_SIZE,
+                LIST_PAGE_SIZE
+            ),
+        );
+
+        // The type's label only exists once an edge of the type has been created
+        let ag_rows = match query.fetch_all(pool).await {
+            Ok(rows) => rows,
+            Err(e) if AgLookupError::classify(&e) == Some(AgLookupError::LabelNotFound) => {
+                return Ok(Vec::new())
+            }
+            Err(e) => return Err(e),
+        };
+        ag_rows
+            .into_iter()
+            .map(|ag_row| {
+                ag::Edge::try_from(ag_row)
+                    .and_then(Edge::try_from)
+                    .map_err(|e| sqlx::Error::Decode(Box::new(e)))
+            })
+            .collect()
+    }
+
+    // Validate the properties against the edge type's attributes and create the edge,
+    // stamped like nodes are. Both vertices must already exist in the graph
+    pub async fn create(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        edge_type: &EdgeType,
+        (from_id, to_id): (i64, i64),
+        properties: HashMap<String, JsonValue>,
+        created_by: Uuid,
+        rules: WriteRules,
+    ) -> Result<(Self, Vec<ValidationWarning>), CreateEdgeError> {
+        let attributes = EdgeTypeAttributeDefinition::from_edge_type(pool, &edge_type.id).await?;
+        let outcome = ValidationOutcome::validate_write(&attributes, properties, rules);
+        if !outcome.is_valid() {
+            return Err(CreateEdgeError::ValidationError(
+                ValidationErrorList(outcome.errors),
+                RequiredAttributeHint::for_attributes(&attributes),
+            ));
+        }
+
+        let mut properties = outcome.coerced_properties;
+        properties.insert(
+            "created_by".to_string(),
+            JsonValue::String(created_by.to_string()),
+        );
+        properties.insert(
+            "created_at".to_string(),
+            JsonValue::String(rfc3339::format(&chrono::Utc::now())),
+        );
+
+        info!(
+            "Creating {} edge from {} to {} in graph: {}, by: {}",
+            edge_type.id, from_id, to_id, graph_id, created_by
+        );
+        let mut transaction = pool.begin().await?;
+        let edge = Edge::insert(
+            &mut transaction,
+            graph_id,
+            &edge_type.id,
+            from_id,
+            to_id,
+            &properties,
+        )
+        .await?;
+        transaction.commit().await?;
+        Ok((edge, outcome.warnings))
+    }
 
-    // Add edge-specific checks
-    if label.contains("->") || label.contains("<-") {
-        return Err(ValidationError::new("edge_label_contains_arrow"));
+    // Create an edge between two existing vertices. The label is the edge type id
+    pub async fn insert(
+        conn: &mut PgConnection,
+        graph_id: &GraphId,
+        label: &EdgeTypeId,
+        from_id: i64,
+        to_id: i64,
+        properties: &HashMap<String, JsonValue>,
+    ) -> Result<Self, sqlx::Error> {
+        let query = Cypher::new(
+            graph_id,
+            format!(
+                "MATCH (a), (b) WHERE id(a) = {} AND id(b) = {} CREATE (a)-[r:{} {}]->(b) RETURN r",
+                from_id,
+                to_id,
+                label,
+                generate_props_clause(properties)
+            ),
+        );
+        let ag_row = query.fetch_one(&mut *conn).await?;
+        ag::Edge::try_from(ag_row)
+            .and_then(Edge::try_from)
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))
     }
+}
+
+// Normalized document of edges together with their (deduplicated) endpoint nodes.
+// Nodes are only included when the caller asked for the endpoints to be expanded.
+#[derive(Debug, Serialize)]
+pub struct Subgraph {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nodes: Option<Vec<Node>>,
+    pub edges: Vec<Edge>,
+}
+
+impl Subgraph {
+    // Get the edges incident to a vertex in either direction
+    pub async fn neighbors(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        node_id: i64,
+        expand_endpoints: bool,
+    ) -> Result<Self, sqlx::Error> {
+        let query = Cypher::new(
+            graph_id,
+            format!(
+                "MATCH (a)-[r]->(b) WHERE id(a) = {} OR id(b) = {} RETURN a, r, b ORDER BY id(r)",
+                node_id, node_id
+            ),
+        )
+        .returning("a agtype, r agtype, b agtype");
+        let rows = query.fetch_rows(pool).await?;
 
-    Ok(())
+        let mut edges = Vec::new();
+        let mut vertices: HashMap<i64, Vertex> = HashMap::new();
+        for row in rows {
+            let r: AgType = row.try_get("r")?;
+            let edge = ag::Edge::try_from(r)
+                .and_then(Edge::try_from)
+                .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+            edges.push(edge);
+
+            if expand_endpoints {
+                for column in ["a", "b"] {
+                    let ag_type: AgType = row.try_get(column)?;
+                    let vertex =
+                        Vertex::try_from(ag_type).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+                    vertices.entry(vertex.id).or_insert(vertex);
+                }
+            }
+        }
+
+        if !expand_endpoints {
+            return Ok(Self { nodes: None, edges });
+        }
+
+        let node_futures = vertices.into_values().map(|vertex| async move {
+            Node::try_from(pool, vertex, graph_id)
+                .await
+                .map_err(|e| sqlx::Error::Decode(Box::new(e)))
+        });
+        let mut nodes = try_join_all(node_futures).await?;
+        nodes.sort_by_key(|node| node.id());
+
+        Ok(Self {
+            nodes: Some(nodes),
+            edges,
+        })
+    }
 }
@@ -1,5 +1,8 @@
-use crate::utils::create_id;
+use crate::ids::{EdgeTypeId, GraphId};
+use crate::node::NodeTypeAttributeDataType;
+use crate::utils::validate_label;
 use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
 use sqlx::postgres::PgRow;
 use sqlx::{FromRow, Postgres, Row, Transaction};
 use strum_macros::{AsRefStr, Display, EnumString};
@@ -9,18 +12,19 @@ use super::CreateEdgeTypeRequest;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EdgeType {
-    pub id: String,
-    pub graph_id: String,
+    pub id: EdgeTypeId,
+    pub graph_id: GraphId,
     pub name: String,
     pub normalized_name: String,
     pub description: String,
     pub created_by: Uuid,
+    #[serde(with = "crate::utils::rfc3339")]
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
 impl EdgeType {
     pub fn new(
-        graph_id: &str,
+        graph_id: &GraphId,
         name: &str,
         description: String,
         created_by: Uuid,
@@ -36,12 +40,19 @@ impl EdgeType {
         }
 
         let now = chrono::Utc::now();
-        // Convert name to uppercase and replace spaces with underscores
-        let normalized_name = name.to_uppercase().replace(' ', "_");
+        let normalized_name = crate::utils::normalize(name);
+
+        // The normalized name is used as the AGE label, so it must be a valid AGE identifier
+        validate_label(&normalized_name).map_err(|e| {
+            format!(
+                "Edge type name '{}' cannot be used as a label '{}': {}",
+                name, normalized_name, e.code
+            )
+        })?;
 
         Ok(Self {
-            id: format!("e{}", create_id(8)),
-            graph_id: graph_id.to_string(),
+            id: EdgeTypeId::generate(),
+            graph_id: graph_id.clone(),
             name: name.to_string(),
             normalized_name,
             created_by,
@@ -52,7 +63,7 @@ impl EdgeType {
 
     pub fn from_request(
         req: &CreateEdgeTypeRequest,
-        graph_id: &str,
+        graph_id: &GraphId,
         created_by: Uuid,
     ) -> Result<Self, String> {
         let edge_type = Self::new(graph_id, &req.name, req.description.clone(), created_by)?;
@@ -63,14 +74,6 @@ impl EdgeType {
         &self,
         transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
     ) -> Result<(), sqlx::Error> {
-        // In AGE, edge types are implemented as edge labels
-        let age_query = "SELECT ag_catalog.create_elabel($1, $2)";
-        sqlx::query(age_query)
-            .bind(&self.graph_id)
-            .bind(&self.normalized_name)
-            .execute(&mut **transaction)
-            .await?;
-
         let insert_edge_type_query = "INSERT INTO app_data.edge_type (id, graph_id, name, normalized_name, description, created_by, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7)";
         sqlx::query(insert_edge_type_query)
             .bind(&self.id)
@@ -82,13 +85,22 @@ impl EdgeType {
             .bind(&self.created_at)
             .execute(&mut **transaction)
             .await?;
+
+        // In AGE, edge types are implemented as edge labels. Created after the metadata in
+        // the same transaction, as node type labels are
+        let age_query = "SELECT ag_catalog.create_elabel($1, $2)";
+        sqlx::query(age_query)
+            .bind(&self.graph_id)
+            .bind(&self.normalized_name)
+            .execute(&mut **transaction)
+            .await?;
         Ok(())
     }
 
     pub async fn from_id(
         pool: &sqlx::PgPool,
-        graph_id: &str,
-        edge_type_id: &str,
+        graph_id: &GraphId,
+        edge_type_id: &EdgeTypeId,
     ) -> Result<Self, sqlx::Error> {
         let query = "SELECT * FROM app_data.edge_type WHERE graph_id = $1 AND id = $2";
         let edge_type = sqlx::query_as::<_, EdgeType>(query)
@@ -102,7 +114,7 @@ impl EdgeType {
 
     pub async fn from_name(
         pool: &sqlx::PgPool,
-        graph_id: &str,
+        graph_id: &GraphId,
         edge_type_name: &str,
     ) -> Result<Self, sqlx::Error> {
         let query = "SELECT * FROM app_data.edge_type WHERE graph_id = $1 AND normalized_name = $2";
@@ -115,8 +127,22 @@ impl EdgeType {
         Ok(edge_type)
     }
 
-    pub async fn list(pool: &sqlx::PgPool, graph_id: &str) -> Result<Vec<EdgeType>, sqlx::Error> {
-        let query = "SELECT * FROM app_data.edge_type WHERE graph_id = $1";
+    // Remove the type's metadata, its attributes go with it. Edges keep their label
+    pub async fn delete(&self, pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+        let query = "DELETE FROM app_data.edge_type WHERE graph_id = $1 AND id = $2";
+        sqlx::query(query)
+            .bind(&self.graph_id)
+            .bind(&self.id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn list(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+    ) -> Result<Vec<EdgeType>, sqlx::Error> {
+        let query = "SELECT * FROM app_data.edge_type WHERE graph_id = $1 ORDER BY name, id";
         let rows = sqlx::query(query).bind(graph_id).fetch_all(pool).await?;
         let edge_types: Vec<EdgeType> = rows
             .iter()
@@ -149,6 +175,20 @@ pub enum EdgeTypeAttributeDataType {
     Number,
     Boolean,
     Date,
+    Url,
+}
+
+// Edge and node attributes share the same set of data types
+impl From<&NodeTypeAttributeDataType> for EdgeTypeAttributeDataType {
+    fn from(data_type: &NodeTypeAttributeDataType) -> Self {
+        match data_type {
+            NodeTypeAttributeDataType::String => EdgeTypeAttributeDataType::String,
+            NodeTypeAttributeDataType::Number => EdgeTypeAttributeDataType::Number,
+            NodeTypeAttributeDataType::Boolean => EdgeTypeAttributeDataType::Boolean,
+            NodeTypeAttributeDataType::Date => EdgeTypeAttributeDataType::Date,
+            NodeTypeAttributeDataType::Url => EdgeTypeAttributeDataType::Url,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -157,6 +197,10 @@ pub struct NewEdgeTypeAttributeDefinition {
     pub data_type: EdgeTypeAttributeDataType,
     pub required: bool,
     pub description: String,
+    pub example: Option<JsonValue>,
+    // Longest string value accepted, below the server-wide cap
+    #[serde(default)]
+    pub max_length: Option<i32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -168,10 +212,16 @@ pub struct EdgeTypeAttributeDefinition {
     pub data_type: EdgeTypeAttributeDataType,
     pub required: bool,
     pub description: String,
+    pub example: Option<JsonValue>,
+    // Org dictionary entry the attribute was created from
+    pub dictionary_id: Option<Uuid>,
+    // Place among the type's own attributes, starting at 0
+    pub position: i32,
+    pub max_length: Option<i32>,
 }
 
 impl EdgeTypeAttributeDefinition {
-    pub fn from_request(req: &NewEdgeTypeAttributeDefinition, type_id: &str) -> Self {
+    pub fn from_request(req: &NewEdgeTypeAttributeDefinition, type_id: &EdgeTypeId) -> Self {
         Self {
             id: Uuid::new_v4(),
             type_id: type_id.to_string(),
@@ -180,6 +230,10 @@ impl EdgeTypeAttributeDefinition {
             data_type: req.data_type.clone(),
             required: req.required,
             description: req.description.clone(),
+            example: req.example.clone(),
+            dictionary_id: None,
+            position: 0,
+            max_length: req.max_length,
         }
     }
 
@@ -195,8 +249,12 @@ impl EdgeTypeAttributeDefinition {
                 normalized_name,
                 data_type,
                 required,
-                description
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7)
+                description,
+                example,
+                dictionary_id,
+                position,
+                max_length
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
         "#;
 
         sqlx::query(insert_query)
@@ -207,6 +265,10 @@ impl EdgeTypeAttributeDefinition {
             .bind(&self.data_type.to_string())
             .bind(&self.required)
             .bind(&self.description)
+            .bind(&self.example)
+            .bind(self.dictionary_id)
+            .bind(self.position)
+            .bind(self.max_length)
             .execute(&mut **transaction)
             .await?;
 
@@ -215,9 +277,10 @@ impl EdgeTypeAttributeDefinition {
 
     pub async fn from_edge_type(
         pool: &sqlx::PgPool,
-        edge_type_id: &str,
+        edge_type_id: &EdgeTypeId,
     ) -> Result<Vec<EdgeTypeAttributeDefinition>, sqlx::Error> {
-        let query = "SELECT * FROM app_data.edge_type_attribute WHERE type_id = $1";
+        let query =
+            "SELECT * FROM app_data.edge_type_attribute WHERE type_id = $1 ORDER BY position, name";
         let rows = sqlx::query_as::<_, EdgeTypeAttributeDefinition>(query)
             .bind(edge_type_id)
             .fetch_all(pool)
@@ -242,6 +305,10 @@ impl<'r> FromRow<'r, PgRow> for EdgeTypeAttributeDefinition {
             data_type,
             required: row.try_get("required")?,
             description: row.try_get("description")?,
+            example: row.try_get("example")?,
+            dictionary_id: row.try_get("dictionary_id")?,
+            position: row.try_get("position")?,
+            max_length: row.try_get("max_length")?,
         })
     }
 }
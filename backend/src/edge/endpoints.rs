@@ -1,32 +1,45 @@
 use super::{
-    EdgeTypeAttributeDataType, EdgeTypeAttributeDefinition, NewEdgeTypeAttributeDefinition,
+    CreateEdgeError, Edge, EdgeTypeAttributeDataType, EdgeTypeAttributeDefinition,
+    NewEdgeTypeAttributeDefinition,
 };
+use crate::attribute_order::{self, AttributeTable, ReorderAttributesRequest};
 use crate::auth::Auth;
+use crate::catalog::{self, LabelKind};
 use crate::config::AppState;
+use crate::db::with_retry;
 use crate::edge::EdgeType;
 use crate::error::ApiError;
-use crate::graph::GraphInfo;
-use crate::org::{Org, Role};
+use crate::graph::{GraphAccess, GraphRevision, MinRevision};
+use crate::ids::{EdgeTypeId, GraphId};
+use crate::node::{resolve_node_type, Node, LIST_PAGE_SIZE};
+use crate::org::AttributeSpec;
+use crate::utils::validate_properties;
+use crate::validation::{validate_example, WriteRules};
+use crate::webhook::WebhookEvent;
 use axum::{
-    extract::{Extension, Path, State},
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
     Json,
 };
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
 use sqlx::{Postgres, Transaction};
+use std::collections::HashMap;
 use tracing::{error, info};
 use uuid::Uuid;
+use validator::Validate;
 
 #[derive(Debug, Deserialize)]
 pub struct CreateEdgeTypeRequest {
     pub name: String,
     pub description: String,
-    pub attributes: Vec<NewEdgeTypeAttributeDefinition>,
+    pub attributes: Vec<AttributeSpec<NewEdgeTypeAttributeDefinition>>,
 }
 
 pub async fn create_edge_type(
     State(state): State<AppState>,
     Extension(auth): Extension<Auth>,
-    Path(graph_id): Path<String>,
+    Path(graph_id): Path<GraphId>,
     Json(payload): Json<CreateEdgeTypeRequest>,
 ) -> Result<Json<()>, ApiError> {
     let user = auth.user.ok_or_else(|| {
@@ -37,37 +50,9 @@ pub async fn create_edge_type(
     // TODO: Add validation for the request payload
     //payload.validate()?;
 
-    let graph_info = GraphInfo::from_id(&state.pool, &graph_id)
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch graph info: {}", e);
-            ApiError::InternalServerError
-        })?;
-
-    let org = Org::from_id(&state.pool, &graph_info.org_id)
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch organization: {}", e);
-            ApiError::InternalServerError
-        })?;
-
-    // Check if the user is a member of the org
-    let org_member = org
-        .get_member(&state.pool, user.id)
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch org member: {}", e);
-            ApiError::InternalServerError
-        })?
-        .ok_or_else(|| {
-            error!("User is not a member of the organization");
-            ApiError::Unauthorized
-        })?;
-
-    // Check if the user is an admin of the org
-    if org_member.role != Role::Admin {
-        return Err(ApiError::Unauthorized);
-    }
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_schema_write()?;
+    let graph_info = access.graph;
 
     //
     // User is an admin of the org, proceed with creating the edge type
@@ -77,37 +62,100 @@ pub async fn create_edge_type(
     let edge_type =
         EdgeType::from_request(&payload, &graph_info.graph_id, user.id).map_err(|e| {
             error!("Failed to create edge type: {}", e);
-            ApiError::BadRequest("Invalid edge type configuration".to_string())
+            ApiError::BadRequest(e)
         })?;
 
-    let existing_edge_type =
-        EdgeType::from_name(&state.pool, &graph_info.graph_id, &edge_type.name).await;
+    let existing_edge_type = EdgeType::from_name(
+        &state.pool,
+        &graph_info.graph_id,
+        &edge_type.normalized_name,
+    )
+    .await;
     if existing_edge_type.is_ok() {
-        return Err(ApiError::BadRequest("Edge type already exists".to_string()));
+        return Err(ApiError::type_exists(
+            "EDGE_TYPE_EXISTS",
+            &edge_type.normalized_name,
+        ));
     };
 
-    // Start a transaction
-    let mut transaction: Transaction<Postgres> = state.pool.begin().await.map_err(|e| {
-        error!("Failed to start transaction for create_edge_label: {}", e);
-        ApiError::InternalServerError
-    })?;
+    let attributes = AttributeSpec::resolve_all(
+        &state.pool,
+        graph_info.org_id,
+        payload.attributes,
+        |entry, required| NewEdgeTypeAttributeDefinition {
+            name: entry.name.clone(),
+            data_type: EdgeTypeAttributeDataType::from(&entry.data_type),
+            required,
+            description: entry.description.clone(),
+            example: None,
+            max_length: entry.max_length(),
+        },
+    )
+    .await
+    .map_err(ApiError::from_dictionary_error)?;
+    let attrs: Vec<EdgeTypeAttributeDefinition> = attributes
+        .iter()
+        .enumerate()
+        .map(|(position, (new_attr, dictionary_id))| {
+            let mut attr = EdgeTypeAttributeDefinition::from_request(new_attr, &edge_type.id);
+            attr.dictionary_id = *dictionary_id;
+            attr.position = position as i32;
+            attr
+        })
+        .collect();
+    for attr in &attrs {
+        if attr.max_length.is_some_and(|n| n < 1) {
+            return Err(ApiError::BadRequest(format!(
+                "Attribute '{}' needs a max_length of at least 1",
+                attr.name
+            )));
+        }
+        if let Some(example) = &attr.example {
+            validate_example(attr, example)
+                .map_err(|e| ApiError::BadRequest(format!("Invalid example: {}", e)))?;
+        }
+    }
 
     info!("Creating edge type for graph: {}", graph_info.name);
-    edge_type.save(&mut transaction).await.map_err(|e| {
-        error!("Failed to save edge type: {}", e);
-        ApiError::InternalServerError
-    })?;
-
-    for new_attr in &payload.attributes {
-        let attr = EdgeTypeAttributeDefinition::from_request(&new_attr, &edge_type.id);
-        attr.save(&mut transaction).await.map_err(|e| {
-            error!("Failed to save edge attribute: {}", e);
-            ApiError::InternalServerError
+    // Retried as a whole if the label and metadata inserts deadlock with another writer
+    let (pool, edge_type, attrs) = (&state.pool, &edge_type, &attrs);
+    let create = with_retry(&state.db_retry, "create_edge_type", move || async move {
+        let mut transaction: Transaction<Postgres> = pool.begin().await?;
+
+        edge_type.save(&mut transaction).await.map_err(|e| {
+            error!("Failed to save edge type: {}", e);
+            ApiError::from_type_save_error(e, &edge_type.normalized_name, "EDGE_TYPE_EXISTS")
         })?;
-    }
 
-    // Commit the transaction
-    transaction.commit().await?;
+        for attr in attrs {
+            attr.save(&mut transaction).await.map_err(|e| {
+                error!("Failed to save edge attribute: {}", e);
+                ApiError::Database(e)
+            })?;
+        }
+
+        transaction.commit().await?;
+        Ok::<_, ApiError>(())
+    });
+    // Drops the label if it outlived the failed transaction, as create_node_type does
+    catalog::with_label_cleanup(
+        pool,
+        &graph_info.graph_id,
+        &edge_type.normalized_name,
+        LabelKind::Edge,
+        create,
+    )
+    .await?;
+
+    state
+        .webhooks
+        .dispatch(
+            pool,
+            &graph_info.graph_id,
+            WebhookEvent::EdgeTypeCreated,
+            serde_json::json!(EdgeTypeResponse::from(edge_type, attrs)),
+        )
+        .await;
 
     Ok(Json(()))
 }
@@ -115,51 +163,63 @@ pub async fn create_edge_type(
 pub async fn get_edge_types(
     State(state): State<AppState>,
     Extension(auth): Extension<Auth>,
-    Path(graph_id): Path<String>,
+    Path(graph_id): Path<GraphId>,
 ) -> Result<Json<Vec<EdgeType>>, ApiError> {
     let user = auth.user.ok_or_else(|| {
         error!("Unauthorized access: no valid user found in middleware");
         ApiError::Unauthorized
     })?;
 
-    let graph_info = GraphInfo::from_id(&state.pool, &graph_id)
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch graph info: {}", e);
-            ApiError::InternalServerError
-        })?;
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let graph_info = access.graph;
 
-    let org = Org::from_id(&state.pool, &graph_info.org_id)
+    // Fetch all edge types for the graph
+    let edge_types = EdgeType::list(&state.pool, &graph_info.graph_id)
         .await
         .map_err(|e| {
-            error!("Failed to fetch organization: {}", e);
+            error!("Failed to fetch edge types: {}", e);
             ApiError::InternalServerError
         })?;
 
-    // Check if the user is a member of the org
-    let org_member = org
-        .get_member(&state.pool, user.id)
+    Ok(Json(edge_types))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApplicableEdgeTypesQueryParams {
+    // Node type ids or names of the edge's start and end
+    pub from: String,
+    pub to: String,
+}
+
+// Edge types that can connect a node of type `from` to a node of type `to`, for offering
+// only valid relationship types when creating an edge
+pub async fn get_applicable_edge_types(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+    Query(params): Query<ApplicableEdgeTypesQueryParams>,
+) -> Result<Json<Vec<EdgeType>>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let graph_info = access.graph;
+
+    // Both types must exist, even though they don't narrow the result yet
+    resolve_node_type(&state.pool, &graph_info.graph_id, &params.from).await?;
+    resolve_node_type(&state.pool, &graph_info.graph_id, &params.to).await?;
+
+    // Edge types don't declare endpoint constraints yet, so every edge type is
+    // unconstrained and can connect any pair of node types
+    let edge_types = EdgeType::list(&state.pool, &graph_info.graph_id)
         .await
         .map_err(|e| {
-            error!("Failed to fetch org member: {}", e);
+            error!("Failed to fetch edge types: {}", e);
             ApiError::InternalServerError
-        })?
-        .ok_or_else(|| {
-            error!("User is not a member of the organization");
-            ApiError::Unauthorized
         })?;
 
-    // Check if the user is an admin or viewer of the org
-    if org_member.role != Role::Admin && org_member.role != Role::Viewer {
-        return Err(ApiError::Unauthorized);
-    }
-
-    // Fetch all edge types for the graph
-    let edge_types = EdgeType::list(&state.pool, &graph_id).await.map_err(|e| {
-        error!("Failed to fetch edge types: {}", e);
-        ApiError::InternalServerError
-    })?;
-
     Ok(Json(edge_types))
 }
 
@@ -170,6 +230,12 @@ pub struct EdgeTypeAttributeResponse {
     pub data_type: EdgeTypeAttributeDataType,
     pub required: bool,
     pub description: String,
+    pub example: Option<JsonValue>,
+    pub dictionary_id: Option<Uuid>,
+    pub position: i32,
+    pub max_length: Option<i32>,
+    // Values are validated https URLs the UI may render, e.g. as an image
+    pub renderable: bool,
 }
 
 impl EdgeTypeAttributeResponse {
@@ -180,6 +246,11 @@ impl EdgeTypeAttributeResponse {
             data_type: attr.data_type.clone(),
             required: attr.required,
             description: attr.description.clone(),
+            example: attr.example.clone(),
+            dictionary_id: attr.dictionary_id,
+            position: attr.position,
+            max_length: attr.max_length,
+            renderable: matches!(attr.data_type, EdgeTypeAttributeDataType::Url),
         }
     }
 }
@@ -190,21 +261,22 @@ pub struct EdgeTypeResponse {
     pub graph_id: String,
     pub name: String,
     pub description: String,
+    #[serde(with = "crate::utils::rfc3339")]
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub created_by: Uuid,
     pub attributes: Vec<EdgeTypeAttributeResponse>,
 }
 
 impl EdgeTypeResponse {
-    pub fn from(node_type: &EdgeType, attributes: Vec<EdgeTypeAttributeDefinition>) -> Self {
+    pub fn from(node_type: &EdgeType, attributes: &[EdgeTypeAttributeDefinition]) -> Self {
         // Convert the attributes to the response type
         let attributes: Vec<EdgeTypeAttributeResponse> = attributes
             .iter()
             .map(|attr| EdgeTypeAttributeResponse::from(attr))
             .collect();
         Self {
-            id: node_type.id.clone(),
-            graph_id: node_type.graph_id.clone(),
+            id: node_type.id.to_string(),
+            graph_id: node_type.graph_id.to_string(),
             name: node_type.name.clone(),
             description: node_type.description.clone(),
             created_at: node_type.created_at,
@@ -217,46 +289,169 @@ impl EdgeTypeResponse {
 pub async fn get_edge_type(
     State(state): State<AppState>,
     Extension(auth): Extension<Auth>,
-    Path((graph_id, edge_type_id)): Path<(String, String)>,
+    Path((graph_id, edge_type_id)): Path<(GraphId, EdgeTypeId)>,
 ) -> Result<Json<EdgeTypeResponse>, ApiError> {
     let user = auth.user.ok_or_else(|| {
         error!("Unauthorized access: no valid user found in middleware");
         ApiError::Unauthorized
     })?;
 
-    let graph_info = GraphInfo::from_id(&state.pool, &graph_id)
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let graph_info = access.graph;
+
+    // Fetch the edge type
+    let edge_type = EdgeType::from_id(&state.pool, &graph_info.graph_id, &edge_type_id)
         .await
         .map_err(|e| {
-            error!("Failed to fetch graph info: {}", e);
+            error!("Failed to fetch edge type: {}", e);
             ApiError::InternalServerError
         })?;
 
-    let org = Org::from_id(&state.pool, &graph_info.org_id)
+    let edge_type_attributes =
+        EdgeTypeAttributeDefinition::from_edge_type(&state.pool, &edge_type.id)
+            .await
+            .map_err(|e| {
+                error!("Failed to fetch edge type attributes: {}", e);
+                ApiError::InternalServerError
+            })?;
+
+    let response = EdgeTypeResponse::from(&edge_type, &edge_type_attributes);
+
+    Ok(Json(response))
+}
+
+pub async fn reorder_edge_type_attributes(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path((graph_id, edge_type_id)): Path<(GraphId, EdgeTypeId)>,
+    Json(payload): Json<ReorderAttributesRequest>,
+) -> Result<Json<Vec<EdgeTypeAttributeResponse>>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_schema_write()?;
+    let graph_info = access.graph;
+
+    let edge_type = EdgeType::from_id(&state.pool, &graph_info.graph_id, &edge_type_id)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => ApiError::NotFound {
+                code: "TYPE_NOT_FOUND".into(),
+                message: format!("Edge type '{}' does not exist", edge_type_id),
+            },
+            e => {
+                error!("Failed to fetch edge type: {}", e);
+                ApiError::Database(e)
+            }
+        })?;
+
+    attribute_order::reorder(
+        &state.pool,
+        AttributeTable::Edge,
+        edge_type.id.as_str(),
+        &payload.attribute_ids,
+    )
+    .await
+    .map_err(ApiError::from_reorder_error)?;
+
+    let attributes = EdgeTypeAttributeDefinition::from_edge_type(&state.pool, &edge_type.id)
         .await
         .map_err(|e| {
-            error!("Failed to fetch organization: {}", e);
+            error!("Failed to fetch edge type attributes: {}", e);
             ApiError::InternalServerError
         })?;
 
-    // Check if the user is a member of the org
-    let org_member = org
-        .get_member(&state.pool, user.id)
+    Ok(Json(
+        attributes
+            .iter()
+            .map(EdgeTypeAttributeResponse::from)
+            .collect(),
+    ))
+}
+
+// Delete an edge type. Its edges are kept under a label no type has, its AGE label is
+// dropped when it holds none
+pub async fn delete_edge_type(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path((graph_id, edge_type_id)): Path<(GraphId, EdgeTypeId)>,
+) -> Result<StatusCode, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_schema_write()?;
+    let graph_info = access.graph;
+
+    let edge_type = EdgeType::from_id(&state.pool, &graph_info.graph_id, &edge_type_id)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => ApiError::NotFound {
+                code: "TYPE_NOT_FOUND".into(),
+                message: format!("Edge type '{}' does not exist", edge_type_id),
+            },
+            e => {
+                error!("Failed to fetch edge type: {}", e);
+                ApiError::Database(e)
+            }
+        })?;
+
+    // The definition as it was, for subscribers
+    let attributes = EdgeTypeAttributeDefinition::from_edge_type(&state.pool, &edge_type.id)
         .await
         .map_err(|e| {
-            error!("Failed to fetch org member: {}", e);
+            error!("Failed to fetch edge type attributes: {}", e);
             ApiError::InternalServerError
-        })?
-        .ok_or_else(|| {
-            error!("User is not a member of the organization");
-            ApiError::Unauthorized
         })?;
 
-    // Check if the user is an admin or viewer of the org
-    if org_member.role != Role::Admin && org_member.role != Role::Viewer {
-        return Err(ApiError::Unauthorized);
-    }
+    info!(
+        "Deleting edge type {} of graph {}",
+        edge_type.id, graph_info.name
+    );
+    edge_type.delete(&state.pool).await.map_err(|e| {
+        error!("Failed to delete edge type: {}", e);
+        ApiError::Database(e)
+    })?;
+    catalog::drop_label_of_deleted_type(
+        &state.pool,
+        &graph_info.graph_id,
+        &edge_type.normalized_name,
+        LabelKind::Edge,
+    )
+    .await;
+
+    state
+        .webhooks
+        .dispatch(
+            &state.pool,
+            &graph_info.graph_id,
+            WebhookEvent::EdgeTypeDeleted,
+            json!(EdgeTypeResponse::from(&edge_type, &attributes)),
+        )
+        .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
 
-    // Fetch the edge type
+pub async fn get_edge_type_attributes(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path((graph_id, edge_type_id)): Path<(GraphId, EdgeTypeId)>,
+) -> Result<Json<Vec<EdgeTypeAttributeResponse>>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let graph_info = access.graph;
+
+    // Make sure the edge type belongs to this graph before returning its attributes
     let edge_type = EdgeType::from_id(&state.pool, &graph_info.graph_id, &edge_type_id)
         .await
         .map_err(|e| {
@@ -272,7 +467,204 @@ pub async fn get_edge_type(
                 ApiError::InternalServerError
             })?;
 
-    let response = EdgeTypeResponse::from(&edge_type, edge_type_attributes);
+    let response = edge_type_attributes
+        .iter()
+        .map(EdgeTypeAttributeResponse::from)
+        .collect();
 
     Ok(Json(response))
 }
+
+// A node an edge starts or ends at, either by the id returned when it was created or by
+// its public id
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum NodeRef {
+    Vertex(i64),
+    Public(String),
+}
+
+#[derive(Debug, Validate, Deserialize)]
+pub struct CreateEdgeRequest {
+    pub edge_type: String,
+    pub from: NodeRef,
+    pub to: NodeRef,
+    #[serde(default)]
+    #[validate(custom = "validate_properties")]
+    pub properties: HashMap<String, JsonValue>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateEdgeQueryParams {
+    // Reject values that would otherwise be coerced with a warning
+    pub strict: Option<bool>,
+}
+
+// Vertex id of an edge's endpoint. A node that isn't in the graph is the client's mistake
+// rather than a missing resource, so it's a 400
+async fn resolve_endpoint(
+    pool: &sqlx::PgPool,
+    graph_id: &GraphId,
+    node: &NodeRef,
+    side: &str,
+) -> Result<i64, ApiError> {
+    let vertex_id = match node {
+        NodeRef::Vertex(id) => Node::get_many(pool, graph_id, &[*id])
+            .await?
+            .first()
+            .map(Node::id),
+        NodeRef::Public(public_id) => Node::vertex_id(pool, graph_id, public_id).await?,
+    };
+    vertex_id.ok_or_else(|| ApiError::BadRequest(format!("The '{}' node does not exist", side)))
+}
+
+pub async fn create_edge(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+    Query(params): Query<CreateEdgeQueryParams>,
+    Json(request): Json<CreateEdgeRequest>,
+) -> Result<(StatusCode, Option<GraphRevision>, Json<JsonValue>), ApiError> {
+    request.validate()?;
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_write()?;
+    let graph_info = access.graph;
+
+    let edge_type_id = EdgeTypeId::parse(&request.edge_type)?;
+    let edge_type = EdgeType::from_id(&state.pool, &graph_info.graph_id, &edge_type_id)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => ApiError::BadRequest("Edge type does not exist".into()),
+            e => {
+                error!("Failed to fetch edge type: {}", e);
+                ApiError::Database(e)
+            }
+        })?;
+
+    let from_id =
+        resolve_endpoint(&state.pool, &graph_info.graph_id, &request.from, "from").await?;
+    let to_id = resolve_endpoint(&state.pool, &graph_info.graph_id, &request.to, "to").await?;
+
+    // Spent once both ends and the type are known to exist, like for nodes
+    state.write_budget.consume(&user, 1)?;
+
+    let rules = WriteRules {
+        strict: params.strict.unwrap_or(false),
+        max_value_length: state.max_property_value_length,
+    };
+    let (edge, warnings) = Edge::create(
+        &state.pool,
+        &graph_info.graph_id,
+        &edge_type,
+        (from_id, to_id),
+        request.properties,
+        user.id,
+        rules,
+    )
+    .await
+    .map_err(|e| match e {
+        CreateEdgeError::ValidationError(errors, schema_hint) => {
+            ApiError::invalid_properties(errors, schema_hint)
+        }
+        CreateEdgeError::DatabaseError(e) => ApiError::from_cypher_error(e),
+    })?;
+
+    state
+        .webhooks
+        .dispatch(
+            &state.pool,
+            &graph_info.graph_id,
+            WebhookEvent::EdgeCreated,
+            json!(edge),
+        )
+        .await;
+
+    let revision = GraphRevision::bump(&state.pool, &graph_info.graph_id).await;
+    Ok((
+        StatusCode::CREATED,
+        revision,
+        Json(json!({ "id": edge.id, "warnings": warnings })),
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetEdgesQueryParams {
+    pub page: Option<u32>,
+    // Type id or display name
+    pub edge_type: Option<String>,
+    // Graph revision returned by a write the listing must reflect
+    pub min_revision: Option<i64>,
+}
+
+// Look up an edge type by id, falling back to its name. Unknown types are a 400
+async fn resolve_edge_type(
+    pool: &sqlx::PgPool,
+    graph_id: &GraphId,
+    edge_type: &str,
+) -> Result<EdgeType, ApiError> {
+    // Only something shaped like an id is worth looking up as one
+    if let Ok(edge_type_id) = EdgeTypeId::parse(edge_type) {
+        match EdgeType::from_id(pool, graph_id, &edge_type_id).await {
+            Ok(edge_type) => return Ok(edge_type),
+            Err(sqlx::Error::RowNotFound) => {}
+            Err(e) => return Err(ApiError::Database(e)),
+        }
+    }
+    match EdgeType::from_name(pool, graph_id, edge_type).await {
+        Ok(edge_type) => Ok(edge_type),
+        Err(sqlx::Error::RowNotFound) => Err(ApiError::BadRequest(format!(
+            "Unknown edge type '{}'",
+            edge_type
+        ))),
+        Err(e) => Err(ApiError::Database(e)),
+    }
+}
+
+pub async fn get_edges(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+    Query(params): Query<GetEdgesQueryParams>,
+) -> Result<(GraphRevision, Json<JsonValue>), ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let graph_info = access.graph;
+    let revision = MinRevision::new(params.min_revision).check(&graph_info)?;
+
+    let page = params.page.unwrap_or(1);
+    if page == 0 {
+        return Err(ApiError::BadRequest("Pages start at 1".into()));
+    }
+
+    let edge_type = match params.edge_type.as_deref() {
+        Some(edge_type) => {
+            Some(resolve_edge_type(&state.pool, &graph_info.graph_id, edge_type).await?)
+        }
+        None => None,
+    };
+
+    let edges = Edge::list(&state.pool, &graph_info.graph_id, edge_type.as_ref(), page)
+        .await
+        .map_err(ApiError::from_cypher_error)?;
+
+    // Each edge's label is its edge type id, as listed by /meta/edge_types. The resolved
+    // id of the requested type is returned too, since it may have been given by name
+    Ok((
+        revision,
+        Json(json!({
+        "edge_type": edge_type.map(|edge_type| edge_type.id),
+        "page": page,
+        "page_size": LIST_PAGE_SIZE,
+        "edges": edges,
+        })),
+    ))
+}
@@ -1,9 +1,16 @@
+use crate::ag::AgLookupError;
+use crate::attribute_order::ReorderError;
+use crate::ids::InvalidId;
+use crate::org::DictionaryError;
+use crate::validation::{
+    deprecated_message, AttributeValidationError, RequiredAttributeHint, ValidationErrorList,
+};
 use axum::Json;
 use serde::Serialize;
 use sqlx::Error as SqlxError;
 use thiserror::Error;
 use tracing::{debug, error};
-use validator::ValidationErrors;
+use validator::{ValidationError, ValidationErrors};
 
 #[derive(Serialize)]
 struct ErrorResponse {
@@ -20,16 +27,224 @@ pub enum ApiError {
     Database(#[from] SqlxError),
     #[error("Internal server error")]
     InternalServerError,
-    //#[error("Not found")]
-    //NotFound,
+    #[error("Not found: {message}")]
+    NotFound { code: String, message: String },
     #[error("Invalid request: {0}")]
     BadRequest(String),
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
     #[error("Validation error: {0}")]
     Validation(#[from] ValidationErrors),
+    // Properties that don't match their type, with the type's required attributes
+    #[error("Invalid properties: {errors}")]
+    InvalidProperties {
+        errors: ValidationErrors,
+        schema_hint: Vec<RequiredAttributeHint>,
+    },
     #[error("Unauthorized")]
     Unauthorized,
+    // The user can see the resource but their role doesn't allow the action
+    #[error("Forbidden: {message}")]
+    Forbidden { code: String, message: String },
+    #[error("Invalid label: {0}")]
+    InvalidLabel(String),
+    #[error("Conflict: {message}")]
+    Conflict {
+        code: String,
+        message: String,
+        details: Option<Vec<String>>,
+    },
+    #[error("Gone: {message}")]
+    Gone { code: String, message: String },
+    #[error("Locked: {message}")]
+    Locked { code: String, message: String },
+    #[error("Rate limited until {reset_at}")]
+    RateLimited {
+        reset_at: chrono::DateTime<chrono::Utc>,
+    },
+    // The global write throttle is saturated
+    #[error("Overloaded, retry after {retry_after:?}")]
+    Overloaded { retry_after: std::time::Duration },
+}
+
+#[derive(Serialize)]
+struct ErrorResponseWithSchemaHint {
+    #[serde(flatten)]
+    error: ErrorResponse,
+    schema_hint: Vec<RequiredAttributeHint>,
+}
+
+// One "field: message" string per field error, e.g. "properties.age: must be a number"
+fn validation_details(e: &ValidationErrors) -> Vec<String> {
+    let mut details = Vec::new();
+    // Iterate through field errors and push a separate string for each error.
+    for (field, errors) in e.field_errors().iter() {
+        for error in errors.iter() {
+            // Use the error message if available; otherwise, use the error code.
+            let msg = error
+                .message
+                .clone()
+                .unwrap_or_else(|| std::borrow::Cow::from(error.code.clone()));
+            // Property errors name the offending key, e.g. "properties.age"
+            match error.params.get("key").and_then(|key| key.as_str()) {
+                Some(key) => details.push(format!("{}.{}: {}", field, key, msg)),
+                None => details.push(format!("{}: {}", field, msg)),
+            }
+        }
+    }
+    details
+}
+
+// An id given by the client that isn't shaped like one, rejected before any lookup
+impl From<InvalidId> for ApiError {
+    fn from(e: InvalidId) -> Self {
+        ApiError::BadRequest(e.to_string())
+    }
+}
+
+// SQLSTATEs raised by AGE (and Postgres) when a label name is not a valid identifier
+const INVALID_LABEL_SQLSTATES: [&str; 3] = [
+    "22023", // invalid_parameter_value, e.g. "label name is invalid"
+    "42602", // invalid_name
+    "42622", // name_too_long
+];
+
+fn is_invalid_label_error(db_err: &dyn sqlx::error::DatabaseError) -> bool {
+    db_err
+        .code()
+        .is_some_and(|code| INVALID_LABEL_SQLSTATES.contains(&code.as_ref()))
+        && db_err.message().contains("name")
+}
+
+impl ApiError {
+    pub fn forbidden(message: &str) -> Self {
+        ApiError::Forbidden {
+            code: "FORBIDDEN".into(),
+            message: message.into(),
+        }
+    }
+
+    // Properties that failed validation against a node or edge type, keyed by attribute
+    pub fn invalid_properties(
+        errors: ValidationErrorList,
+        schema_hint: Vec<RequiredAttributeHint>,
+    ) -> Self {
+        let mut validation_errors = ValidationErrors::new();
+        for error in errors {
+            match error {
+                AttributeValidationError::MissingAttribute { name } => {
+                    let mut val_error = ValidationError::new("missing");
+                    val_error.message = Some("required".into());
+                    // Convert the dynamic field name into a &'static str.
+                    validation_errors.add(Box::leak(name.into_boxed_str()), val_error);
+                }
+                AttributeValidationError::WrongType { name, expected } => {
+                    let mut val_error = ValidationError::new("wrong_type");
+                    val_error.message = Some(format!("must be of type {}", expected).into());
+                    validation_errors.add(Box::leak(name.into_boxed_str()), val_error);
+                }
+                AttributeValidationError::NotStrict { name, message } => {
+                    let mut val_error = ValidationError::new("strict");
+                    val_error.message = Some(message.into());
+                    validation_errors.add(Box::leak(name.into_boxed_str()), val_error);
+                }
+                AttributeValidationError::Deprecated { name, replaced_by } => {
+                    let mut val_error = ValidationError::new("deprecated");
+                    val_error.message = Some(deprecated_message(&replaced_by).into());
+                    if let Some(replacement) = replaced_by {
+                        val_error.add_param("replaced_by".into(), &replacement);
+                    }
+                    validation_errors.add(Box::leak(name.into_boxed_str()), val_error);
+                }
+                AttributeValidationError::TooLong { name, max_length } => {
+                    let mut val_error = ValidationError::new("too_long");
+                    val_error.message =
+                        Some(format!("must be at most {} characters", max_length).into());
+                    val_error.add_param("max_length".into(), &max_length);
+                    validation_errors.add(Box::leak(name.into_boxed_str()), val_error);
+                }
+            }
+        }
+        ApiError::InvalidProperties {
+            errors: validation_errors,
+            schema_hint,
+        }
+    }
+
+    // Map an error from creating an AGE label, naming the offending label when AGE rejected it
+    pub fn from_label_error(e: SqlxError, label: &str) -> Self {
+        if let sqlx::Error::Database(ref db_err) = e {
+            if is_invalid_label_error(db_err.as_ref()) {
+                error!("AGE rejected label '{}': {}", label, db_err.message());
+                return ApiError::InvalidLabel(label.to_string());
+            }
+        }
+        ApiError::Database(e)
+    }
+
+    // Map an error from a cypher query. A graph or label that no longer exists is a 404
+    // rather than a 500, e.g. when a type was deleted out from under a cached client
+    pub fn from_cypher_error(e: SqlxError) -> Self {
+        match AgLookupError::classify(&e) {
+            Some(AgLookupError::GraphNotFound) => ApiError::NotFound {
+                code: "GRAPH_NOT_FOUND".into(),
+                message: "The graph no longer exists".into(),
+            },
+            Some(AgLookupError::LabelNotFound) => ApiError::NotFound {
+                code: "TYPE_NOT_FOUND".into(),
+                message: "The requested type no longer exists".into(),
+            },
+            None => {
+                error!("Cypher query failed: {}", e);
+                ApiError::Database(e)
+            }
+        }
+    }
+
+    // Map an error from resolving `from_dictionary` attributes of a type creation request
+    pub fn from_dictionary_error(e: DictionaryError) -> Self {
+        match e {
+            DictionaryError::NotFound(_) => ApiError::BadRequest(e.to_string()),
+            DictionaryError::DatabaseError(e) => {
+                error!("Failed to resolve dictionary attributes: {}", e);
+                ApiError::Database(e)
+            }
+        }
+    }
+
+    // Map an error from reordering node or edge type attributes
+    pub fn from_reorder_error(e: ReorderError) -> Self {
+        match e {
+            ReorderError::Mismatch => ApiError::BadRequest(e.to_string()),
+            ReorderError::DatabaseError(e) => {
+                error!("Failed to reorder attributes: {}", e);
+                ApiError::Database(e)
+            }
+        }
+    }
+
+    // Conflict returned when a node or edge type name normalizes to an existing one
+    pub fn type_exists(code: &str, normalized_name: &str) -> Self {
+        ApiError::Conflict {
+            code: code.to_string(),
+            message: format!(
+                "A type named '{}' already exists in this graph",
+                normalized_name
+            ),
+            details: None,
+        }
+    }
+
+    // Map an error from saving a node or edge type. A taken name, whether caught by AGE's
+    // label check or the (graph_id, normalized_name) unique index, becomes a 409 with `code`
+    pub fn from_type_save_error(e: SqlxError, normalized_name: &str, code: &str) -> Self {
+        if let sqlx::Error::Database(ref db_err) = e {
+            if db_err.is_unique_violation() || db_err.message().contains("already exists") {
+                return Self::type_exists(code, normalized_name);
+            }
+        }
+        Self::from_label_error(e, normalized_name)
+    }
 }
 
 impl axum::response::IntoResponse for ApiError {
@@ -45,6 +260,19 @@ impl axum::response::IntoResponse for ApiError {
                         db_err.code()
                     );
 
+                    // For AGE's invalid label name errors
+                    if is_invalid_label_error(db_err.as_ref()) {
+                        return (
+                            axum::http::StatusCode::BAD_REQUEST,
+                            Json(ErrorResponse {
+                                code: "INVALID_LABEL".into(),
+                                message: format!("Invalid label: {}", db_err.message()),
+                                details: None,
+                            }),
+                        )
+                            .into_response();
+                    }
+
                     // For AGE's "already exists" error
                     if db_err.message().contains("already exists") {
                         return (
@@ -90,27 +318,30 @@ impl axum::response::IntoResponse for ApiError {
             ApiError::Validation(ref e) => {
                 // Log the validation error remove newlines
                 debug!("Validation error: {}", e.to_string().replace("\n", "; "));
-                let mut details = Vec::new();
-                // Iterate through field errors and push a separate string for each error.
-                for (field, errors) in e.field_errors().iter() {
-                    for error in errors.iter() {
-                        // Use the error message if available; otherwise, use the error code.
-                        let msg = error
-                            .message
-                            .clone()
-                            .unwrap_or_else(|| std::borrow::Cow::from(error.code.clone()));
-                        details.push(format!("{}: {}", field, msg));
-                    }
-                }
                 (
                     axum::http::StatusCode::BAD_REQUEST,
                     Json(ErrorResponse {
                         code: "VALIDATION_ERROR".into(),
                         message: "Invalid input data".into(),
-                        details: Some(details),
+                        details: Some(validation_details(e)),
                     }),
                 )
             }
+            ApiError::InvalidProperties {
+                ref errors,
+                schema_hint,
+            } => {
+                debug!("Validation error: {}", errors.to_string().replace("\n", "; "));
+                let body = Json(ErrorResponseWithSchemaHint {
+                    error: ErrorResponse {
+                        code: "VALIDATION_ERROR".into(),
+                        message: "Invalid input data".into(),
+                        details: Some(validation_details(errors)),
+                    },
+                    schema_hint,
+                });
+                return (axum::http::StatusCode::BAD_REQUEST, body).into_response();
+            }
             ApiError::InternalServerError => (
                 axum::http::StatusCode::INTERNAL_SERVER_ERROR,
                 Json(ErrorResponse {
@@ -119,6 +350,85 @@ impl axum::response::IntoResponse for ApiError {
                     details: None,
                 }),
             ),
+            ApiError::InvalidLabel(ref label) => (
+                axum::http::StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    code: "INVALID_LABEL".into(),
+                    message: format!(
+                        "'{}' is not a valid label: labels must start with a letter, contain only letters, numbers and underscores, and be at most {} characters long",
+                        label,
+                        crate::utils::MAX_LABEL_LENGTH
+                    ),
+                    details: None,
+                }),
+            ),
+            ApiError::Conflict {
+                code,
+                message,
+                details,
+            } => (
+                axum::http::StatusCode::CONFLICT,
+                Json(ErrorResponse {
+                    code,
+                    message,
+                    details,
+                }),
+            ),
+            ApiError::NotFound { code, message } => (
+                axum::http::StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    code,
+                    message,
+                    details: None,
+                }),
+            ),
+            ApiError::Gone { code, message } => (
+                axum::http::StatusCode::GONE,
+                Json(ErrorResponse {
+                    code,
+                    message,
+                    details: None,
+                }),
+            ),
+            ApiError::Locked { code, message } => (
+                axum::http::StatusCode::LOCKED,
+                Json(ErrorResponse {
+                    code,
+                    message,
+                    details: None,
+                }),
+            ),
+            ApiError::RateLimited { reset_at } => {
+                let retry_after = (reset_at - chrono::Utc::now()).num_seconds().max(1);
+                let body = Json(ErrorResponse {
+                    code: "RATE_LIMITED".into(),
+                    message: format!(
+                        "Write budget exceeded, resets at {}",
+                        crate::utils::rfc3339::format(&reset_at)
+                    ),
+                    details: None,
+                });
+                return (
+                    axum::http::StatusCode::TOO_MANY_REQUESTS,
+                    [(axum::http::header::RETRY_AFTER, retry_after.to_string())],
+                    body,
+                )
+                    .into_response();
+            }
+            ApiError::Overloaded { retry_after } => {
+                let retry_after = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+                let body = Json(ErrorResponse {
+                    code: "SERVER_BUSY".into(),
+                    message: "The server is handling too many writes, retry later".into(),
+                    details: None,
+                });
+                return (
+                    axum::http::StatusCode::SERVICE_UNAVAILABLE,
+                    [(axum::http::header::RETRY_AFTER, retry_after.to_string())],
+                    body,
+                )
+                    .into_response();
+            }
             ApiError::Unauthorized => (
                 axum::http::StatusCode::UNAUTHORIZED,
                 Json(ErrorResponse {
@@ -127,8 +437,164 @@ impl axum::response::IntoResponse for ApiError {
                     details: None,
                 }),
             ),
+            ApiError::Forbidden { code, message } => (
+                axum::http::StatusCode::FORBIDDEN,
+                Json(ErrorResponse {
+                    code,
+                    message,
+                    details: None,
+                }),
+            ),
         };
 
         (status, error_response).into_response()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edge::EdgeTypeAttributeDefinition;
+    use crate::ids::{EdgeTypeId, NodeTypeId};
+    use crate::node::NodeTypeAttributeDefinition;
+    use crate::validation::{ValidationOutcome, WriteRules};
+    use axum::response::IntoResponse;
+    use serde_json::{json, Value as JsonValue};
+    use std::collections::HashMap;
+
+    const RULES: WriteRules = WriteRules {
+        strict: false,
+        max_value_length: 1000,
+    };
+
+    // name, data_type, required
+    const ATTRIBUTES: [(&str, &str, bool); 3] = [
+        ("since", "date", true),
+        ("weight", "number", true),
+        ("note", "string", false),
+    ];
+
+    fn definition(name: &str, data_type: &str, required: bool) -> JsonValue {
+        json!({
+            "name": name,
+            "data_type": data_type,
+            "required": required,
+            "description": format!("The {}", name),
+        })
+    }
+
+    fn properties(value: JsonValue) -> HashMap<String, JsonValue> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    async fn body(error: ApiError) -> (axum::http::StatusCode, JsonValue) {
+        let response = error.into_response();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    // The body node and edge creation send for properties missing `since` and with a
+    // `weight` that isn't a number, validated against ATTRIBUTES
+    fn expected_body() -> JsonValue {
+        json!({
+            "code": "VALIDATION_ERROR",
+            "message": "Invalid input data",
+            "details": [
+                "since: required",
+                "weight: must be of type number"
+            ],
+            "schema_hint": [
+                {"name": "since", "data_type": "date", "description": "The since"},
+                {"name": "weight", "data_type": "number", "description": "The weight"}
+            ]
+        })
+    }
+
+    // Field errors come out of a map, so details are compared in a stable order
+    fn sorted_details(mut body: JsonValue) -> JsonValue {
+        body["details"]
+            .as_array_mut()
+            .unwrap()
+            .sort_by(|a, b| a.as_str().cmp(&b.as_str()));
+        body
+    }
+
+    #[tokio::test]
+    async fn invalid_node_properties_body() {
+        let attributes: Vec<NodeTypeAttributeDefinition> = ATTRIBUTES
+            .iter()
+            .map(|(name, data_type, required)| {
+                NodeTypeAttributeDefinition::from_request(
+                    &serde_json::from_value(definition(name, data_type, *required)).unwrap(),
+                    &NodeTypeId::parse("vPERSON01").unwrap(),
+                )
+            })
+            .collect();
+        let outcome = ValidationOutcome::validate_write(
+            &attributes,
+            properties(json!({"weight": "heavy", "note": "ok"})),
+            RULES,
+        );
+        let error = ApiError::invalid_properties(
+            ValidationErrorList(outcome.errors),
+            RequiredAttributeHint::for_attributes(&attributes),
+        );
+
+        let (status, body) = body(error).await;
+        assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+        assert_eq!(sorted_details(body), expected_body());
+    }
+
+    #[tokio::test]
+    async fn invalid_edge_properties_body() {
+        let attributes: Vec<EdgeTypeAttributeDefinition> = ATTRIBUTES
+            .iter()
+            .map(|(name, data_type, required)| {
+                EdgeTypeAttributeDefinition::from_request(
+                    &serde_json::from_value(definition(name, data_type, *required)).unwrap(),
+                    &EdgeTypeId::parse("eKNOWS001").unwrap(),
+                )
+            })
+            .collect();
+        let outcome = ValidationOutcome::validate_write(
+            &attributes,
+            properties(json!({"weight": "heavy", "note": "ok"})),
+            RULES,
+        );
+        let error = ApiError::invalid_properties(
+            ValidationErrorList(outcome.errors),
+            RequiredAttributeHint::for_attributes(&attributes),
+        );
+
+        let (status, body) = body(error).await;
+        assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+        assert_eq!(sorted_details(body), expected_body());
+    }
+
+    #[tokio::test]
+    async fn schema_hint_is_sent_without_required_attributes() {
+        let outcome = ValidationOutcome::validate_write(
+            &Vec::<NodeTypeAttributeDefinition>::new(),
+            properties(json!({"note": "x".repeat(1001)})),
+            RULES,
+        );
+        let (status, body) = body(ApiError::invalid_properties(
+            ValidationErrorList(outcome.errors),
+            Vec::new(),
+        ))
+        .await;
+        assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body,
+            json!({
+                "code": "VALIDATION_ERROR",
+                "message": "Invalid input data",
+                "details": ["note: must be at most 1000 characters"],
+                "schema_hint": []
+            })
+        );
+    }
+}
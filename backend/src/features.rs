@@ -0,0 +1,64 @@
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::str::FromStr;
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter, EnumString};
+
+// Optional features operators can turn on with FEATURES, e.g. FEATURES=public_graphs,webhooks
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Display, EnumString, EnumIter,
+)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    // Graphs marked public are readable by any signed in user
+    PublicGraphs,
+    // Webhook subscription routes and event delivery
+    Webhooks,
+}
+
+// Features enabled when FEATURES is not set, i.e. the ones that shipped before flags existed
+const DEFAULT_FEATURES: [Feature; 2] = [Feature::PublicGraphs, Feature::Webhooks];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Features {
+    enabled: BTreeSet<Feature>,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Self {
+            enabled: DEFAULT_FEATURES.into_iter().collect(),
+        }
+    }
+}
+
+impl FromStr for Features {
+    type Err = String;
+
+    // Comma separated flag names. Blank entries are skipped, unknown names are an error
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let enabled = s
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                name.parse::<Feature>()
+                    .map_err(|_| format!("unknown feature '{}'", name))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { enabled })
+    }
+}
+
+impl Features {
+    pub fn all() -> Self {
+        Self {
+            enabled: Feature::iter().collect(),
+        }
+    }
+
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        self.enabled.contains(&feature)
+    }
+}
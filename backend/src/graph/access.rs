@@ -0,0 +1,410 @@
+use crate::config::AppState;
+use crate::error::ApiError;
+use crate::features::Feature;
+use crate::graph::{GraphInfo, GraphRole};
+use crate::ids::GraphId;
+use crate::org::Role;
+use crate::user::User;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use strum_macros::Display;
+use tracing::error;
+
+// Authorization failures follow one policy for orgs, graphs and everything inside them:
+// - no signed in user: 401
+// - the resource doesn't exist, or the user can't see it: 404, with the same body either
+//   way so responses don't reveal whether an org or graph exists
+// - the user can see the resource but their role doesn't allow the action: 403
+
+// The role a user effectively has on a graph, ordered from least to most privileged.
+// This is the single source of truth for graph authorization: endpoints enforce it and
+// listings report it, so the two can't diverge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum EffectiveRole {
+    None,
+    // Can read the schema and data
+    Viewer,
+    // Can also create and modify data
+    Editor,
+    // Can also modify the schema and manage the graph
+    Admin,
+}
+
+impl EffectiveRole {
+    // Combine the user's org role, graph membership and the graph's visibility.
+    // The most privileged role granted by any of them wins. A graph membership only
+    // counts for members of the graph's org
+    pub fn compute(
+        org_role: Option<&Role>,
+        graph_role: Option<&GraphRole>,
+        is_public: bool,
+    ) -> Self {
+        let from_org = match org_role {
+            Some(Role::Admin) => EffectiveRole::Admin,
+            Some(Role::Viewer) => EffectiveRole::Viewer,
+            None => EffectiveRole::None,
+        };
+        let from_graph = match graph_role.filter(|_| org_role.is_some()) {
+            Some(GraphRole::Admin) => EffectiveRole::Admin,
+            Some(GraphRole::Member) => EffectiveRole::Editor,
+            None => EffectiveRole::None,
+        };
+        let from_visibility = if is_public {
+            EffectiveRole::Viewer
+        } else {
+            EffectiveRole::None
+        };
+
+        from_org.max(from_graph).max(from_visibility)
+    }
+
+    pub fn can_read(&self) -> bool {
+        *self >= EffectiveRole::Viewer
+    }
+
+    pub fn can_write(&self) -> bool {
+        *self >= EffectiveRole::Editor
+    }
+
+    pub fn can_admin(&self) -> bool {
+        *self >= EffectiveRole::Admin
+    }
+}
+
+// Actions the UI asks about before rendering controls for them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphCapability {
+    DataRead,
+    DataWrite,
+    SchemaWrite,
+    // Managing the members of the graph's org, graph memberships have no endpoints
+    MemberManage,
+    // Graphs are deleted together with their org
+    GraphDelete,
+    Export,
+}
+
+impl GraphCapability {
+    pub const ALL: [GraphCapability; 6] = [
+        GraphCapability::DataRead,
+        GraphCapability::DataWrite,
+        GraphCapability::SchemaWrite,
+        GraphCapability::MemberManage,
+        GraphCapability::GraphDelete,
+        GraphCapability::Export,
+    ];
+}
+
+// The actions a user is allowed to perform on a graph, derived from their effective role
+#[derive(Debug, Serialize)]
+pub struct GraphPermissions {
+    pub role: EffectiveRole,
+    pub can_read: bool,
+    pub can_write: bool,
+    pub can_admin: bool,
+    pub capabilities: BTreeMap<GraphCapability, bool>,
+}
+
+impl From<&GraphAccess> for GraphPermissions {
+    fn from(access: &GraphAccess) -> Self {
+        let role = access.role;
+        Self {
+            role,
+            can_read: role.can_read(),
+            can_write: role.can_write(),
+            can_admin: role.can_admin(),
+            capabilities: GraphCapability::ALL
+                .into_iter()
+                .map(|capability| (capability, access.can(capability)))
+                .collect(),
+        }
+    }
+}
+
+// A graph together with the requesting user's effective role on it, and their role on the
+// graph's org if they are a member
+pub struct GraphAccess {
+    pub graph: GraphInfo,
+    pub role: EffectiveRole,
+    pub org_role: Option<Role>,
+}
+
+impl GraphAccess {
+    // Graphs the user can't read are reported as not found
+    pub async fn resolve(
+        state: &AppState,
+        graph_id: &GraphId,
+        user: &User,
+    ) -> Result<Self, ApiError> {
+        let pool = &state.pool;
+        let graph = GraphInfo::from_id(pool, graph_id)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::RowNotFound => graph_not_found(graph_id),
+                e => {
+                    error!("Failed to fetch graph info: {}", e);
+                    ApiError::InternalServerError
+                }
+            })?;
+
+        // Memberships may be up to the cache TTL old, see MembershipCache
+        let memberships = state.memberships.get(pool, user).await.map_err(|e| {
+            error!("Failed to fetch memberships: {}", e);
+            ApiError::InternalServerError
+        })?;
+        let org_role = memberships.org_member(&graph.org_id).map(|m| &m.role);
+
+        let role = EffectiveRole::compute(
+            org_role,
+            memberships.graph_role(&graph.graph_id),
+            graph.is_public && state.features.is_enabled(Feature::PublicGraphs),
+        );
+
+        let access = Self {
+            org_role: org_role.cloned(),
+            graph,
+            role,
+        };
+        access.require_read()?;
+        Ok(access)
+    }
+
+    // Whether the matching require_* check would pass. Built from the same role and lock
+    // checks, so preflight answers follow what the endpoints enforce
+    pub fn can(&self, capability: GraphCapability) -> bool {
+        match capability {
+            GraphCapability::DataRead | GraphCapability::Export => self.role.can_read(),
+            GraphCapability::DataWrite => self.role.can_write() && !self.graph.locked,
+            GraphCapability::SchemaWrite => self.role.can_admin() && !self.graph.locked,
+            GraphCapability::MemberManage => self.org_role == Some(Role::Admin),
+            // Org deletion refuses protected graphs
+            GraphCapability::GraphDelete => {
+                self.org_role == Some(Role::Admin) && !self.graph.deletion_protected
+            }
+        }
+    }
+
+    pub fn require_read(&self) -> Result<(), ApiError> {
+        if !self.role.can_read() {
+            error!("User cannot read graph {}", self.graph.graph_id);
+            return Err(graph_not_found(&self.graph.graph_id));
+        }
+        Ok(())
+    }
+
+    // Creating or changing data. Rejected while the graph is locked
+    pub fn require_write(&self) -> Result<(), ApiError> {
+        if !self.role.can_write() {
+            error!("User cannot write to graph {}", self.graph.graph_id);
+            return Err(ApiError::forbidden(
+                "Writing to the graph requires the editor role",
+            ));
+        }
+        self.require_unlocked()
+    }
+
+    // Creating or changing node and edge types. Rejected while the graph is locked
+    pub fn require_schema_write(&self) -> Result<(), ApiError> {
+        self.require_admin()?;
+        self.require_unlocked()
+    }
+
+    fn require_unlocked(&self) -> Result<(), ApiError> {
+        if self.graph.locked {
+            return Err(ApiError::Locked {
+                code: "GRAPH_LOCKED".into(),
+                message: "The graph is locked and cannot be modified".into(),
+            });
+        }
+        Ok(())
+    }
+
+    // Managing the graph itself, e.g. settings, webhooks or the lock. Allowed while locked
+    pub fn require_admin(&self) -> Result<(), ApiError> {
+        if !self.role.can_admin() {
+            error!("User is not an admin of graph {}", self.graph.graph_id);
+            return Err(ApiError::forbidden(
+                "This action requires the admin role on the graph",
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn graph_not_found(graph_id: &GraphId) -> ApiError {
+    ApiError::NotFound {
+        code: "GRAPH_NOT_FOUND".into(),
+        message: format!("No graph with id '{}'", graph_id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::org::Org;
+    use GraphCapability::*;
+
+    fn access(
+        org_role: Option<Role>,
+        role: EffectiveRole,
+        locked: bool,
+        protected: bool,
+    ) -> GraphAccess {
+        let org = Org::new("Acme", "");
+        let mut graph = GraphInfo::new(&org, "Graph", None).unwrap();
+        graph.locked = locked;
+        graph.deletion_protected = protected;
+        GraphAccess {
+            graph,
+            role,
+            org_role,
+        }
+    }
+
+    #[test]
+    fn effective_role_table() {
+        use EffectiveRole as E;
+        let (viewer, admin) = (Some(&Role::Viewer), Some(&Role::Admin));
+        let (member, graph_admin) = (Some(&GraphRole::Member), Some(&GraphRole::Admin));
+        for (org_role, graph_role, is_public, expected) in [
+            (None, None, false, E::None),
+            (None, None, true, E::Viewer),
+            (None, member, false, E::None),
+            (None, graph_admin, false, E::None),
+            (None, graph_admin, true, E::Viewer),
+            (viewer, None, false, E::Viewer),
+            (viewer, None, true, E::Viewer),
+            (viewer, member, false, E::Editor),
+            (viewer, graph_admin, false, E::Admin),
+            (admin, None, false, E::Admin),
+            (admin, member, true, E::Admin),
+        ] {
+            assert_eq!(
+                EffectiveRole::compute(org_role, graph_role, is_public),
+                expected,
+                "{:?} {:?} public: {}",
+                org_role,
+                graph_role,
+                is_public
+            );
+        }
+    }
+
+    #[test]
+    fn capability_matrix() {
+        use EffectiveRole as E;
+        let (viewer, admin) = (Some(Role::Viewer), Some(Role::Admin));
+        // (org role, effective role, locked, deletion protected, allowed capabilities)
+        let table: [(Option<Role>, EffectiveRole, bool, bool, &[GraphCapability]); 16] = [
+            (None, E::None, false, false, &[]),
+            (None, E::None, true, true, &[]),
+            (None, E::Viewer, false, false, &[DataRead, Export]),
+            (None, E::Viewer, true, true, &[DataRead, Export]),
+            (viewer.clone(), E::Viewer, false, false, &[DataRead, Export]),
+            (viewer.clone(), E::Viewer, true, false, &[DataRead, Export]),
+            (viewer.clone(), E::Viewer, false, true, &[DataRead, Export]),
+            (
+                viewer.clone(),
+                E::Editor,
+                false,
+                false,
+                &[DataRead, DataWrite, Export],
+            ),
+            (viewer.clone(), E::Editor, true, false, &[DataRead, Export]),
+            (
+                viewer.clone(),
+                E::Admin,
+                false,
+                false,
+                &[DataRead, DataWrite, SchemaWrite, Export],
+            ),
+            (viewer.clone(), E::Admin, true, false, &[DataRead, Export]),
+            (
+                viewer.clone(),
+                E::Admin,
+                false,
+                true,
+                &[DataRead, DataWrite, SchemaWrite, Export],
+            ),
+            (admin.clone(), E::Admin, false, false, &GraphCapability::ALL),
+            (
+                admin.clone(),
+                E::Admin,
+                true,
+                false,
+                &[DataRead, MemberManage, GraphDelete, Export],
+            ),
+            (
+                admin.clone(),
+                E::Admin,
+                false,
+                true,
+                &[DataRead, DataWrite, SchemaWrite, MemberManage, Export],
+            ),
+            (
+                admin.clone(),
+                E::Admin,
+                true,
+                true,
+                &[DataRead, MemberManage, Export],
+            ),
+        ];
+
+        for (org_role, role, locked, protected, allowed) in table {
+            let access = access(org_role.clone(), role, locked, protected);
+            let permissions = GraphPermissions::from(&access);
+            for capability in GraphCapability::ALL {
+                let expected = allowed.contains(&capability);
+                let case = format!(
+                    "{:?} as {:?}, locked: {}, protected: {}",
+                    capability, role, locked, protected
+                );
+                assert_eq!(access.can(capability), expected, "{}", case);
+                assert_eq!(permissions.capabilities[&capability], expected, "{}", case);
+            }
+            // Preflight answers follow what the endpoints enforce
+            assert_eq!(access.require_read().is_ok(), access.can(DataRead));
+            assert_eq!(access.require_write().is_ok(), access.can(DataWrite));
+            assert_eq!(
+                access.require_schema_write().is_ok(),
+                access.can(SchemaWrite)
+            );
+            assert_eq!(permissions.can_read, role.can_read());
+            assert_eq!(permissions.can_write, role.can_write());
+            assert_eq!(permissions.can_admin, role.can_admin());
+        }
+    }
+
+    #[test]
+    fn graph_membership_needs_org_membership() {
+        let role = EffectiveRole::compute(None, Some(&GraphRole::Admin), false);
+        assert_eq!(role, EffectiveRole::None);
+        let role = EffectiveRole::compute(None, Some(&GraphRole::Member), true);
+        assert_eq!(role, EffectiveRole::Viewer);
+        let role = EffectiveRole::compute(Some(&Role::Viewer), Some(&GraphRole::Member), false);
+        assert_eq!(role, EffectiveRole::Editor);
+    }
+
+    async fn response_parts(error: ApiError) -> (axum::http::StatusCode, axum::body::Bytes) {
+        let response = axum::response::IntoResponse::into_response(error);
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn unreadable_graph_looks_like_a_missing_one() {
+        // A non-member of the graph's org, on a private graph
+        let role = EffectiveRole::compute(None, None, false);
+        let access = access(None, role, false, false);
+        let hidden = response_parts(access.require_read().unwrap_err()).await;
+        let missing = response_parts(graph_not_found(&access.graph.graph_id)).await;
+        assert_eq!(hidden.0, axum::http::StatusCode::NOT_FOUND);
+        assert_eq!(hidden, missing);
+    }
+}
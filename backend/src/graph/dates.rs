@@ -0,0 +1,140 @@
+use crate::ag::Vertex;
+use crate::cypher::Cypher;
+use crate::graph::GraphInfo;
+use crate::node::{
+    Node, NodeScope, NodeTypeAttributeDataType, NodeTypeAttributeDefinition,
+    NodeTypeInheritanceError,
+};
+use crate::utils::generate_set_clause;
+use crate::validation::canonical_date;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use tracing::info;
+
+const NORMALIZE_PAGE_SIZE: usize = 1000;
+
+// A stored value of a date attribute that couldn't be read as a date
+#[derive(Debug, Serialize)]
+pub struct UnparseableDate {
+    pub node_id: i64,
+    pub node_type: String,
+    pub attribute: String,
+    pub value: JsonValue,
+}
+
+// Outcome of rewriting a graph's stored dates in their canonical form
+#[derive(Debug, Serialize)]
+pub struct DateNormalizationReport {
+    pub graph_id: String,
+    pub nodes_updated: usize,
+    pub values_normalized: usize,
+    pub unparseable: Vec<UnparseableDate>,
+}
+
+impl DateNormalizationReport {
+    // Rewrite every date attribute value of the graph's nodes as UTC RFC3339, the form
+    // new writes are stored in. Values that can't be parsed are left alone and reported.
+    // Maintenance rewrites are not recorded in the node history
+    pub async fn run(
+        pool: &sqlx::PgPool,
+        graph: &GraphInfo,
+    ) -> Result<Self, NodeTypeInheritanceError> {
+        info!("Normalizing dates of graph: {}", graph.graph_id);
+
+        // Date attribute names per node type, inherited ones included
+        let mut date_attributes: HashMap<String, Vec<String>> = HashMap::new();
+        for node_type in graph.get_node_types(pool).await? {
+            let node_type_id = node_type.id.to_string();
+            let lineage = node_type.lineage(pool).await?;
+            let names: Vec<String> = NodeTypeAttributeDefinition::resolve(pool, &lineage)
+                .await?
+                .into_iter()
+                .filter(|attr| attr.data_type == NodeTypeAttributeDataType::Date)
+                .map(|attr| attr.name)
+                .collect();
+            if !names.is_empty() {
+                date_attributes.insert(node_type_id, names);
+            }
+        }
+
+        let mut report = Self {
+            graph_id: graph.graph_id.to_string(),
+            nodes_updated: 0,
+            values_normalized: 0,
+            unparseable: Vec::new(),
+        };
+        if date_attributes.is_empty() {
+            return Ok(report);
+        }
+
+        let scope = NodeScope::all();
+        let mut offset = 0;
+        loop {
+            let query = Cypher::new(
+                &graph.graph_id,
+                format!(
+                    "MATCH {} {} RETURN v ORDER BY id(v) SKIP {} LIMIT {}",
+                    scope.pattern("v"),
+                    scope.where_clause("v"),
+                    offset,
+                    NORMALIZE_PAGE_SIZE
+                ),
+            );
+            let ag_rows = query.fetch_all(pool).await?;
+            let page_len = ag_rows.len();
+            offset += page_len;
+
+            for ag_row in ag_rows {
+                let node = Vertex::try_from(ag_row)
+                    .and_then(|vertex| Node::from_vertex(vertex, &graph.graph_id))
+                    .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+                let Some((node_type, names)) = node
+                    .node_type()
+                    .and_then(|node_type| date_attributes.get_key_value(node_type.as_str()))
+                else {
+                    continue;
+                };
+
+                let mut changes: HashMap<String, JsonValue> = HashMap::new();
+                for name in names {
+                    let value = match node.properties().get(name) {
+                        None | Some(JsonValue::Null) => continue,
+                        Some(value) => value,
+                    };
+                    match value.as_str().and_then(canonical_date) {
+                        Some(canonical) if canonical == *value => {}
+                        Some(canonical) => {
+                            changes.insert(name.clone(), JsonValue::String(canonical));
+                        }
+                        None => report.unparseable.push(UnparseableDate {
+                            node_id: node.id(),
+                            node_type: node_type.to_string(),
+                            attribute: name.clone(),
+                            value: value.clone(),
+                        }),
+                    }
+                }
+                if changes.is_empty() {
+                    continue;
+                }
+
+                let update = Cypher::new(
+                    &graph.graph_id,
+                    format!(
+                        "MATCH (n) WHERE id(n) = {} {}",
+                        node.id(),
+                        generate_set_clause("n", &changes)
+                    ),
+                );
+                update.execute(pool).await?;
+                report.nodes_updated += 1;
+                report.values_normalized += changes.len();
+            }
+
+            if page_len < NORMALIZE_PAGE_SIZE {
+                return Ok(report);
+            }
+        }
+    }
+}
@@ -1,35 +1,60 @@
 use crate::auth::Auth;
+use crate::catalog::{graph_labels, GraphLabel};
 use crate::config::AppState;
+use crate::db::with_retry;
 use crate::error::ApiError;
-use crate::graph::{GraphError, GraphInfo};
-use crate::org::{Org, Role};
+use crate::features::Feature;
+use crate::graph::{
+    graph_not_found, CreateGraphError, DateNormalizationReport, EdgeTypePairCount, EffectiveRole,
+    GraphAccess, GraphError, GraphExport, GraphInfo, GraphPermissions, GraphPing, GraphRevision,
+    GraphRole, GraphValidationReport, MinRevision, NodeNameUniqueness, PropertyKey,
+    StoredValidationReport,
+};
+use crate::ids::GraphId;
+use crate::job::{Job, JobKind};
+use crate::node::{Node, NodeVisibility};
+use crate::org::OrgAccess;
 use axum::{
-    extract::{Extension, Path, State},
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
     Json,
 };
-use lazy_static::lazy_static;
-use regex::Regex;
 use serde::Deserialize;
+use std::collections::HashMap;
 use tracing::{error, info};
 use uuid::Uuid;
-use validator::Validate;
+use validator::{Validate, ValidationError};
+
+// The DTO rules delegate to GraphInfo so every creation path shares them
+fn to_validation_error(e: GraphError) -> ValidationError {
+    let GraphError::ValidationError(message) = e;
+    let mut error = ValidationError::new("invalid");
+    error.message = Some(message.into());
+    error
+}
 
-lazy_static! {
-    // This regex matches only letters (both cases) and numbers.
-    static ref NAME_REGEX: Regex = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
+fn validate_graph_name(name: &str) -> Result<(), ValidationError> {
+    GraphInfo::validate_name(name).map_err(to_validation_error)
+}
+
+fn validate_graph_description(description: &str) -> Result<(), ValidationError> {
+    GraphInfo::validate_description(description).map_err(to_validation_error)
 }
 
 #[derive(Debug, Validate, Deserialize)]
 pub struct CreateGraphRequest {
-    #[validate(regex(
-        path = "NAME_REGEX",
-        message = "Name must contain only letters and numbers"
-    ))]
-    #[validate(length(max = 30, message = "Name must be at most 30 characters long"))]
+    #[validate(custom = "validate_graph_name")]
     name: String,
 
-    #[validate(length(max = 100, message = "Description must be at most 100 characters long"))]
+    #[validate(custom = "validate_graph_description")]
     description: Option<String>,
+
+    // Defaults to names being unique per node type
+    node_name_uniqueness: Option<NodeNameUniqueness>,
+
+    // Defaults to case-sensitive names
+    #[serde(default)]
+    case_insensitive_names: bool,
 }
 
 pub async fn create_graph(
@@ -45,49 +70,49 @@ pub async fn create_graph(
         ApiError::Unauthorized
     })?;
 
-    let org = Org::from_id(&state.pool, &org_id).await.map_err(|e| {
-        error!("Failed to fetch organization: {:?}", e);
-        ApiError::Unauthorized
-    })?;
-
-    // Check that the user is a member of the organization
-    let org_member = org
-        .get_member(&state.pool, user.id)
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch org member: {:?}", e);
-            ApiError::Unauthorized
-        })?
-        .map_or_else(
-            || {
-                error!("User is not a member of the organization");
-                Err(ApiError::Unauthorized)
-            },
-            |m| Ok(m),
-        )?;
-
-    // Check that the user is an admin of the organization
-    if org_member.role != Role::Admin {
-        error!("User is not an admin of the organization");
-        return Err(ApiError::Unauthorized);
-    }
+    let access = OrgAccess::resolve(&state, &org_id, &user).await?;
+    access.require_admin()?;
+    let org = access.org;
 
     // Convert description which is Option<String> to Option<&str>
     let description = request.description.as_deref();
 
     // TODO: Handle different error types
-    let graph_info = GraphInfo::new(&org, &request.name, description).map_err(|e| match e {
+    let mut graph_info = GraphInfo::new(&org, &request.name, description).map_err(|e| match e {
         GraphError::ValidationError(msg) => {
             error!("Validation error when creating graph: {}", msg);
             ApiError::BadRequest(msg)
         }
     })?;
+    if let Some(node_name_uniqueness) = request.node_name_uniqueness {
+        graph_info.node_name_uniqueness = node_name_uniqueness;
+    }
+    graph_info.case_insensitive_names = request.case_insensitive_names;
 
     info!("Creating graph with name: {}", graph_info.name);
-    graph_info.persist(&state.pool, user).await.map_err(|e| {
-        info!("Failed to persist graph info: {:?}", e);
-        ApiError::InternalServerError
+    with_retry(&state.db_retry, "create_graph", || {
+        graph_info.persist(&state.pool, user.clone(), state.org_max_graphs)
+    })
+    .await
+    .map_err(|e| match e {
+        CreateGraphError::LimitReached(quota) => {
+            info!("Org {} is at its limit of {} graphs", org.id, quota.limit);
+            ApiError::Conflict {
+                code: "GRAPH_LIMIT_REACHED".into(),
+                message: e.to_string(),
+                details: Some(vec![
+                    format!("count: {}", quota.count),
+                    format!("limit: {}", quota.limit),
+                ]),
+            }
+        }
+        CreateGraphError::Database(e) => {
+            info!("Failed to persist graph info: {:?}", e);
+            ApiError::InternalServerError
+        }
     })?;
+    // The creator is made a graph admin
+    state.memberships.invalidate([user.id]);
 
     // Return Graph ID
     Ok(Json(serde_json::json!({
@@ -106,31 +131,8 @@ pub async fn get_graphs(
         ApiError::Unauthorized
     })?;
 
-    let org = Org::from_id(&state.pool, &org_id).await.map_err(|e| {
-        error!("Failed to fetch organization: {:?}", e);
-        ApiError::Unauthorized
-    })?;
-
-    // Check that the user is a member of the organization
-    let org_member = org
-        .get_member(&state.pool, user.id)
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch org member: {:?}", e);
-            ApiError::Unauthorized
-        })?
-        .map_or_else(
-            || {
-                error!("User is not a member of the organization");
-                Err(ApiError::Unauthorized)
-            },
-            |m| Ok(m),
-        )?;
-
-    if org_member.role != Role::Admin && org_member.role != Role::Viewer {
-        error!("User is not an admin of the organization");
-        return Err(ApiError::Unauthorized);
-    }
+    let access = OrgAccess::resolve(&state, &org_id, &user).await?;
+    let (org, org_member) = (access.org, access.member);
 
     // Get all graphs for the organization
     let graphs = GraphInfo::get_all(&state.pool, org.id).await.map_err(|e| {
@@ -138,13 +140,39 @@ pub async fn get_graphs(
         ApiError::InternalServerError
     })?;
 
+    let pinned_ids = GraphInfo::get_pinned_ids(&state.pool, user.id)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch pinned graphs: {:?}", e);
+            ApiError::InternalServerError
+        })?;
+
+    let graph_memberships = GraphInfo::get_memberships_in_org(&state.pool, org.id, user.id)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch graph memberships: {:?}", e);
+            ApiError::InternalServerError
+        })?;
+    let graph_roles: HashMap<&str, &GraphRole> = graph_memberships
+        .iter()
+        .map(|m| (m.graph_id.as_str(), &m.role))
+        .collect();
+
     let response = graphs
         .iter()
         .map(|g| {
+            let effective_role = EffectiveRole::compute(
+                Some(&org_member.role),
+                graph_roles.get(g.graph_id.as_str()).copied(),
+                g.is_public && state.features.is_enabled(Feature::PublicGraphs),
+            );
             serde_json::json!({
                 "id": g.graph_id,
                 "name": g.name,
                 "description": g.description.as_deref().unwrap_or(""),
+                "is_pinned": pinned_ids.iter().any(|id| g.graph_id == *id),
+                "deletion_protected": g.deletion_protected,
+                "effective_role": effective_role,
             })
         })
         .collect::<Vec<_>>();
@@ -155,56 +183,673 @@ pub async fn get_graphs(
 pub async fn get_graph(
     State(state): State<AppState>,
     Extension(auth): Extension<Auth>,
-    Path(graph_id): Path<String>,
+    Path(graph_id): Path<GraphId>,
 ) -> Result<Json<serde_json::Value>, ApiError> {
-    // TODO: Add functionality to allow public graphs to be viewed by anyone
     // Anonymous users cannot be part of any organizations
     let user = auth.user.ok_or_else(|| {
         error!("Unauthorized access: no valid user found in middleware");
         ApiError::Unauthorized
     })?;
 
-    // Get the graph by id
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let graph = access.graph;
+
+    let response = serde_json::json!({
+        "id": graph.graph_id,
+        "name": graph.name,
+        "description": graph.description.as_deref().unwrap_or(""),
+        "node_name_uniqueness": graph.node_name_uniqueness,
+        "case_insensitive_names": graph.case_insensitive_names,
+        "locked": graph.locked,
+        "deletion_protected": graph.deletion_protected,
+    });
+
+    Ok(Json(response))
+}
+
+pub async fn pin_graph(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+) -> Result<StatusCode, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let graph = access.graph;
+
+    graph.pin(&state.pool, user.id).await.map_err(|e| {
+        error!("Failed to pin graph: {:?}", e);
+        ApiError::InternalServerError
+    })?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn unpin_graph(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+) -> Result<StatusCode, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
     let graph = GraphInfo::from_id(&state.pool, &graph_id)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => graph_not_found(&graph_id),
+            e => {
+                error!("Failed to fetch graph: {:?}", e);
+                ApiError::InternalServerError
+            }
+        })?;
+
+    // Removing a pin does not require access to the graph, so users can
+    // clean up pins to graphs they can no longer see
+    graph.unpin(&state.pool, user.id).await.map_err(|e| {
+        error!("Failed to unpin graph: {:?}", e);
+        ApiError::InternalServerError
+    })?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_pinned_graphs(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let pinned = GraphInfo::get_pinned(&state.pool, user.id)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch pinned graphs: {:?}", e);
+            ApiError::InternalServerError
+        })?;
+
+    // Pins are kept while the user can read the graph, by the same rule pin_graph and the
+    // graph endpoints enforce. Pins to graphs they can no longer read are removed
+    let memberships = state
+        .memberships
+        .get(&state.pool, &user)
         .await
         .map_err(|e| {
-            error!("Failed to fetch graph: {:?}", e);
+            error!("Failed to fetch memberships: {:?}", e);
             ApiError::InternalServerError
         })?;
+    let (graphs, stale): (Vec<GraphInfo>, Vec<GraphInfo>) = pinned.into_iter().partition(|g| {
+        EffectiveRole::compute(
+            memberships.org_member(&g.org_id).map(|m| &m.role),
+            memberships.graph_role(&g.graph_id),
+            g.is_public && state.features.is_enabled(Feature::PublicGraphs),
+        )
+        .can_read()
+    });
+    if !stale.is_empty() {
+        let stale_ids: Vec<GraphId> = stale.into_iter().map(|g| g.graph_id).collect();
+        GraphInfo::unpin_all(&state.pool, user.id, &stale_ids)
+            .await
+            .map_err(|e| {
+                error!("Failed to remove stale pins: {:?}", e);
+                ApiError::InternalServerError
+            })?;
+    }
+
+    let response = graphs
+        .iter()
+        .map(|g| {
+            serde_json::json!({
+                "id": g.graph_id,
+                "org_id": g.org_id,
+                "name": g.name,
+                "description": g.description.as_deref().unwrap_or(""),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Json(serde_json::json!(response)))
+}
+
+pub async fn get_graph_permissions(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+) -> Result<Json<GraphPermissions>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+
+    Ok(Json(GraphPermissions::from(&access)))
+}
+
+#[derive(Deserialize)]
+pub struct ExportGraphQueryParams {
+    // Read all pages from a single consistent snapshot
+    pub snapshot: Option<bool>,
+}
+
+pub async fn export_graph(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+    Query(params): Query<ExportGraphQueryParams>,
+) -> Result<Json<GraphExport>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let role = access.role;
+    let graph = access.graph;
 
-    let org = Org::from_id(&state.pool, &graph.org_id)
+    let visibility = NodeVisibility::load(&state.pool, &graph.graph_id, role)
         .await
         .map_err(|e| {
-            error!("Failed to fetch organization: {:?}", e);
+            error!("Failed to fetch restricted node types: {:?}", e);
             ApiError::InternalServerError
         })?;
+    let export = GraphExport::collect(
+        &state.pool,
+        &graph.graph_id,
+        params.snapshot.unwrap_or(false),
+        &visibility,
+    )
+    .await
+    .map_err(|e| {
+        error!("Failed to export graph: {:?}", e);
+        ApiError::InternalServerError
+    })?;
+
+    Ok(Json(export))
+}
+
+pub async fn get_property_keys(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+) -> Result<Json<Vec<PropertyKey>>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let graph = access.graph;
+
+    let keys = graph.get_property_keys(&state.pool).await.map_err(|e| {
+        error!("Failed to fetch property keys: {:?}", e);
+        ApiError::InternalServerError
+    })?;
+
+    Ok(Json(keys))
+}
+
+// Labels AGE holds data under, mapped back to node and edge types. Labels no type accounts
+// for are flagged, showing where the data has drifted from the schema
+pub async fn get_graph_labels(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+) -> Result<Json<Vec<GraphLabel>>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let graph = access.graph;
 
-    // Check that the user is a member of the organization
-    let org_member = org
-        .get_member(&state.pool, user.id)
+    let labels = graph_labels(&state.pool, &graph.graph_id)
         .await
         .map_err(|e| {
-            error!("Failed to fetch org member: {:?}", e);
+            error!(
+                "Failed to fetch the labels of graph {}: {}",
+                graph.graph_id, e
+            );
             ApiError::InternalServerError
-        })?
-        .map_or_else(
-            || {
-                error!("User is not a member of the organization");
-                Err(ApiError::Unauthorized)
-            },
-            |m| Ok(m),
-        )?;
+        })?;
 
-    if org_member.role != Role::Admin && org_member.role != Role::Viewer {
-        error!("User is not an admin of the organization");
-        return Err(ApiError::Unauthorized);
+    Ok(Json(labels))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateGraphSettingsRequest {
+    node_name_uniqueness: Option<NodeNameUniqueness>,
+    case_insensitive_names: Option<bool>,
+}
+
+pub async fn update_graph_settings(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+    Json(request): Json<UpdateGraphSettingsRequest>,
+) -> Result<StatusCode, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_admin()?;
+    let mut graph = access.graph;
+
+    if let Some(node_name_uniqueness) = request.node_name_uniqueness {
+        // Existing duplicates are left as they are, the new scope applies to nodes created from now on
+        graph
+            .set_node_name_uniqueness(&state.pool, node_name_uniqueness)
+            .await
+            .map_err(|e| {
+                error!("Failed to update node name uniqueness: {:?}", e);
+                ApiError::InternalServerError
+            })?;
+    }
+    if let Some(case_insensitive_names) = request.case_insensitive_names {
+        // Nodes from before the lowercased name was kept need it to be found. Names that
+        // only differ in case are left as they are, like existing duplicates above
+        if case_insensitive_names {
+            Node::backfill_name_lower(&state.pool, &graph.graph_id)
+                .await
+                .map_err(|e| {
+                    error!("Failed to backfill lowercased node names: {:?}", e);
+                    ApiError::InternalServerError
+                })?;
+        }
+        graph
+            .set_case_insensitive_names(&state.pool, case_insensitive_names)
+            .await
+            .map_err(|e| {
+                error!("Failed to update case-insensitive names: {:?}", e);
+                ApiError::InternalServerError
+            })?;
     }
 
-    let response = serde_json::json!({
-        "id": graph.graph_id,
-        "name": graph.name,
-        "description": graph.description.as_deref().unwrap_or(""),
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn lock_graph(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+) -> Result<StatusCode, ApiError> {
+    set_graph_locked(state, auth, graph_id, true).await
+}
+
+pub async fn unlock_graph(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+) -> Result<StatusCode, ApiError> {
+    set_graph_locked(state, auth, graph_id, false).await
+}
+
+async fn set_graph_locked(
+    state: AppState,
+    auth: Auth,
+    graph_id: GraphId,
+    locked: bool,
+) -> Result<StatusCode, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_admin()?;
+    let mut graph = access.graph;
+
+    graph.set_locked(&state.pool, locked).await.map_err(|e| {
+        error!("Failed to update graph lock: {:?}", e);
+        ApiError::InternalServerError
+    })?;
+    info!(
+        "Graph {} {} by {}",
+        graph.graph_id,
+        if locked { "locked" } else { "unlocked" },
+        user.id
+    );
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateGraphProtectionRequest {
+    deletion_protected: bool,
+}
+
+pub async fn update_graph_protection(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+    Json(request): Json<UpdateGraphProtectionRequest>,
+) -> Result<StatusCode, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_admin()?;
+    let mut graph = access.graph;
+
+    graph
+        .set_deletion_protected(&state.pool, request.deletion_protected, user.id)
+        .await
+        .map_err(|e| {
+            error!("Failed to update graph protection: {:?}", e);
+            ApiError::InternalServerError
+        })?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GraphStatsQueryParams {
+    // Revision returned by a write the stats have to reflect
+    pub min_revision: Option<i64>,
+}
+
+// Served from the stats cache unless min_revision is ahead of the revision the cached
+// counts were read at
+pub async fn get_edges_by_type_pair(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+    Query(params): Query<GraphStatsQueryParams>,
+) -> Result<(GraphRevision, Json<Vec<EdgeTypePairCount>>), ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let role = access.role;
+    let graph = access.graph;
+    let min_revision = MinRevision::new(params.min_revision);
+    let revision = min_revision.check(&graph)?;
+
+    let visibility = NodeVisibility::load(&state.pool, &graph.graph_id, role)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch restricted node types: {:?}", e);
+            ApiError::InternalServerError
+        })?;
+    let key = (graph.graph_id.clone(), visibility.clone());
+    let (revision, counts) = state
+        .edge_stats
+        .get_or_load(key, revision, min_revision, || {
+            EdgeTypePairCount::for_graph(&state.pool, &graph, &visibility)
+        })
+        .await
+        .map_err(|e| {
+            error!("Failed to count edges by type pair: {:?}", e);
+            ApiError::InternalServerError
+        })?;
+
+    Ok((revision, Json(counts.to_vec())))
+}
+
+// Check that the graph's AGE graph exists and can be queried. Reported with a 200 either
+// way, the outcome is in the body
+pub async fn ping_graph(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+) -> Result<Json<GraphPing>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_admin()?;
+
+    let ping = GraphPing::run(&state.pool, &access.graph.graph_id).await;
+    if !ping.ok {
+        error!(
+            "Ping of graph {} failed: {:?}",
+            access.graph.graph_id, ping.message
+        );
+    }
+
+    Ok(Json(ping))
+}
+
+// Run a snapshot export in the background. The result is downloaded from /jobs/:id/result
+pub async fn start_export_job(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+) -> Result<(StatusCode, Json<serde_json::Value>), ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let role = access.role;
+    let graph = access.graph;
+
+    // Resolved now, so the export covers what the requesting user could see
+    let visibility = NodeVisibility::load(&state.pool, &graph.graph_id, role)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch restricted node types: {:?}", e);
+            ApiError::InternalServerError
+        })?;
+    let mut job = Job::new(
+        graph.org_id,
+        Some(graph.graph_id.to_string()),
+        JobKind::GraphExport,
+        user.id,
+    );
+    job.persist(&state.pool).await.map_err(|e| {
+        error!("Failed to create export job: {:?}", e);
+        ApiError::InternalServerError
+    })?;
+    let job_id = job.id;
+
+    let pool = state.pool.clone();
+    tokio::spawn(async move {
+        if let Err(e) = job.mark_running(&pool).await {
+            error!("Failed to start export job {}: {:?}", job.id, e);
+            return;
+        }
+
+        let result = GraphExport::collect(&pool, &graph.graph_id, true, &visibility)
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|export| serde_json::to_vec(&export).map_err(|e| e.to_string()));
+
+        let outcome = match result {
+            Ok(bytes) => {
+                let filename = format!("{}-export.json", graph.graph_id);
+                job.complete(&pool, &bytes, "application/json", &filename)
+                    .await
+            }
+            Err(e) => {
+                error!("Export job {} failed: {}", job.id, e);
+                job.fail(&pool, &e).await
+            }
+        };
+        if let Err(e) = outcome {
+            error!("Failed to record export job {} result: {:?}", job.id, e);
+        }
     });
 
-    Ok(Json(response))
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({ "id": job_id })),
+    ))
+}
+
+// Rewrite the graph's stored date values in their canonical form in the background. The
+// job result lists the values that couldn't be parsed
+pub async fn start_date_normalization_job(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+) -> Result<(StatusCode, Json<serde_json::Value>), ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_admin()?;
+    access.require_write()?;
+    let graph = access.graph;
+
+    let mut job = Job::new(
+        graph.org_id,
+        Some(graph.graph_id.to_string()),
+        JobKind::DateNormalization,
+        user.id,
+    );
+    job.persist(&state.pool).await.map_err(|e| {
+        error!("Failed to create date normalization job: {:?}", e);
+        ApiError::InternalServerError
+    })?;
+    let job_id = job.id;
+
+    let pool = state.pool.clone();
+    tokio::spawn(async move {
+        if let Err(e) = job.mark_running(&pool).await {
+            error!("Failed to start date normalization job {}: {:?}", job.id, e);
+            return;
+        }
+
+        let result = DateNormalizationReport::run(&pool, &graph)
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|report| serde_json::to_vec(&report).map_err(|e| e.to_string()));
+
+        let outcome = match result {
+            Ok(bytes) => {
+                let filename = format!("{}-date-normalization.json", graph.graph_id);
+                job.complete(&pool, &bytes, "application/json", &filename)
+                    .await
+            }
+            Err(e) => {
+                error!("Date normalization job {} failed: {}", job.id, e);
+                job.fail(&pool, &e).await
+            }
+        };
+        if let Err(e) = outcome {
+            error!(
+                "Failed to record date normalization job {} result: {:?}",
+                job.id, e
+            );
+        }
+    });
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({ "id": job_id })),
+    ))
+}
+
+// Check every node and edge against the current schema in the background. The report is
+// the job result and also becomes the graph's latest validation report
+pub async fn start_validation_job(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+) -> Result<(StatusCode, Json<serde_json::Value>), ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let graph = access.graph;
+
+    let mut job = Job::new(
+        graph.org_id,
+        Some(graph.graph_id.to_string()),
+        JobKind::GraphValidation,
+        user.id,
+    );
+    job.persist(&state.pool).await.map_err(|e| {
+        error!("Failed to create validation job: {:?}", e);
+        ApiError::InternalServerError
+    })?;
+    let job_id = job.id;
+
+    let pool = state.pool.clone();
+    tokio::spawn(async move {
+        if let Err(e) = job.mark_running(&pool).await {
+            error!("Failed to start validation job {}: {:?}", job.id, e);
+            return;
+        }
+
+        let result = match GraphValidationReport::run(&pool, &graph).await {
+            Ok(report) => match report.save(&pool, job.id).await {
+                Ok(()) => serde_json::to_vec(&report).map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            },
+            Err(e) => Err(e.to_string()),
+        };
+
+        let outcome = match result {
+            Ok(bytes) => {
+                let filename = format!("{}-validation.json", graph.graph_id);
+                job.complete(&pool, &bytes, "application/json", &filename)
+                    .await
+            }
+            Err(e) => {
+                error!("Validation job {} failed: {}", job.id, e);
+                job.fail(&pool, &e).await
+            }
+        };
+        if let Err(e) = outcome {
+            error!("Failed to record validation job {} result: {:?}", job.id, e);
+        }
+    });
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({ "id": job_id })),
+    ))
+}
+
+// The report of the graph's most recent completed validation job
+pub async fn get_validation_report(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+) -> Result<Json<StoredValidationReport>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let graph = access.graph;
+
+    let report = GraphValidationReport::latest(&state.pool, &graph.graph_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch validation report: {:?}", e);
+            ApiError::InternalServerError
+        })?
+        .ok_or_else(|| ApiError::NotFound {
+            code: "VALIDATION_REPORT_NOT_FOUND".into(),
+            message: format!(
+                "Graph {} has not been validated yet. Start a validation with POST /graphs/{}/validate",
+                graph.graph_id, graph.graph_id
+            ),
+        })?;
+
+    Ok(Json(report))
 }
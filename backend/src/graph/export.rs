@@ -0,0 +1,133 @@
+use crate::ag::{self, Vertex};
+use crate::cypher::Cypher;
+use crate::edge::Edge;
+use crate::ids::GraphId;
+use crate::node::{where_all, Node, NodeScope, NodeVisibility};
+use serde::Serialize;
+use sqlx::{PgConnection, PgPool};
+use tracing::info;
+
+const EXPORT_PAGE_SIZE: usize = 1000;
+
+// Full dump of a graph's nodes and edges, read page by page
+#[derive(Debug, Serialize)]
+pub struct GraphExport {
+    pub graph_id: String,
+    pub snapshot: bool,
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+impl GraphExport {
+    // Read every node and edge of the graph that `visibility` allows, edges only when both
+    // ends are. With `snapshot` set all pages are read inside one REPEATABLE READ
+    // transaction, so writes that land while the export is running can't produce a torn dump
+    pub async fn collect(
+        pool: &PgPool,
+        graph_id: &GraphId,
+        snapshot: bool,
+        visibility: &NodeVisibility,
+    ) -> Result<Self, sqlx::Error> {
+        info!("Exporting graph: {}, snapshot: {}", graph_id, snapshot);
+
+        if !snapshot {
+            // Each page is its own statement here and sees whatever is committed at the time
+            let mut conn = pool.acquire().await?;
+            let nodes = Self::collect_nodes(&mut conn, graph_id, visibility).await?;
+            let edges = Self::collect_edges(&mut conn, graph_id, visibility).await?;
+            return Ok(Self {
+                graph_id: graph_id.to_string(),
+                snapshot,
+                nodes,
+                edges,
+            });
+        }
+
+        let mut transaction = pool.begin().await?;
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ READ ONLY")
+            .execute(&mut *transaction)
+            .await?;
+
+        let nodes = Self::collect_nodes(&mut transaction, graph_id, visibility).await?;
+        let edges = Self::collect_edges(&mut transaction, graph_id, visibility).await?;
+        transaction.commit().await?;
+
+        Ok(Self {
+            graph_id: graph_id.to_string(),
+            snapshot,
+            nodes,
+            edges,
+        })
+    }
+
+    async fn collect_nodes(
+        conn: &mut PgConnection,
+        graph_id: &GraphId,
+        visibility: &NodeVisibility,
+    ) -> Result<Vec<Node>, sqlx::Error> {
+        let scope = NodeScope::visible(visibility);
+        let mut nodes = Vec::new();
+        loop {
+            let query = Cypher::new(
+                graph_id,
+                format!(
+                    "MATCH {} {} RETURN v ORDER BY id(v) SKIP {} LIMIT {}",
+                    scope.pattern("v"),
+                    scope.where_clause("v"),
+                    nodes.len(),
+                    EXPORT_PAGE_SIZE
+                ),
+            );
+            let ag_rows = query.fetch_all(&mut *conn).await?;
+            let page_len = ag_rows.len();
+
+            for ag_row in ag_rows {
+                let node = Vertex::try_from(ag_row)
+                    .and_then(|vertex| Node::from_vertex(vertex, graph_id))
+                    .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+                nodes.push(node);
+            }
+
+            if page_len < EXPORT_PAGE_SIZE {
+                return Ok(nodes);
+            }
+        }
+    }
+
+    async fn collect_edges(
+        conn: &mut PgConnection,
+        graph_id: &GraphId,
+        visibility: &NodeVisibility,
+    ) -> Result<Vec<Edge>, sqlx::Error> {
+        let scope = NodeScope::visible(visibility);
+        let mut conditions = scope.conditions("a");
+        conditions.extend(scope.conditions("b"));
+        let mut edges = Vec::new();
+        loop {
+            let query = Cypher::new(
+                graph_id,
+                format!(
+                    "MATCH {}-[r]->{} {} RETURN r ORDER BY id(r) SKIP {} LIMIT {}",
+                    scope.pattern("a"),
+                    scope.pattern("b"),
+                    where_all(&conditions),
+                    edges.len(),
+                    EXPORT_PAGE_SIZE
+                ),
+            );
+            let ag_rows = query.fetch_all(&mut *conn).await?;
+            let page_len = ag_rows.len();
+
+            for ag_row in ag_rows {
+                let edge = ag::Edge::try_from(ag_row)
+                    .and_then(Edge::try_from)
+                    .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+                edges.push(edge);
+            }
+
+            if page_len < EXPORT_PAGE_SIZE {
+                return Ok(edges);
+            }
+        }
+    }
+}
@@ -1,4 +1,10 @@
-use crate::{node::NodeType, org::Org, user::User, utils::create_id};
+use crate::auth::{SecurityEvent, SecurityEventKind};
+use crate::catalog;
+use crate::db::Retryable;
+use crate::ids::GraphId;
+use crate::{node::NodeType, org::Org, user::User};
+use lazy_static::lazy_static;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use sqlx::{postgres::PgRow, FromRow, Row};
 use strum_macros::{Display, EnumString};
@@ -11,6 +17,17 @@ pub enum GraphRole {
     Member,
 }
 
+// Scope within which node names must be unique
+#[derive(PartialEq, Clone, Copy, Default, Serialize, Deserialize, Debug, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum NodeNameUniqueness {
+    None,
+    #[default]
+    PerType,
+    PerGraph,
+}
+
 pub struct GraphMember {
     pub graph_id: String,
     pub user_id: Uuid,
@@ -19,6 +36,23 @@ pub struct GraphMember {
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
+impl<'r> FromRow<'r, PgRow> for GraphMember {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        let role: String = row.try_get("role")?;
+        let role = role
+            .parse::<GraphRole>()
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+        Ok(Self {
+            graph_id: row.try_get("graph_id")?,
+            user_id: row.try_get("user_id")?,
+            role,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+}
+
 impl GraphMember {
     pub fn new(graph_id: String, user_id: Uuid, role: GraphRole) -> Self {
         let now = chrono::Utc::now();
@@ -32,14 +66,44 @@ impl GraphMember {
     }
 }
 
+// A property key defined by at least one node or edge type attribute in a graph
+#[derive(Debug, Serialize)]
+pub struct PropertyKey {
+    pub key: String,
+    pub data_types: Vec<String>,
+    pub node_type_count: i64,
+    pub edge_type_count: i64,
+}
+
+impl<'r> FromRow<'r, PgRow> for PropertyKey {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            key: row.try_get("key")?,
+            data_types: row.try_get("data_types")?,
+            node_type_count: row.try_get("node_type_count")?,
+            edge_type_count: row.try_get("edge_type_count")?,
+        })
+    }
+}
+
 pub struct GraphInfo {
     // Unique randomly generated identifier for the graph name to pass to AGE
     // AGE graph names are unique. This allows us to have multiple graphs with the same name
     // Has to start with a letter
-    pub graph_id: String,
+    pub graph_id: GraphId,
     pub org_id: Uuid,
     pub name: String,
     pub description: Option<String>,
+    pub is_public: bool,
+    pub node_name_uniqueness: NodeNameUniqueness,
+    // Node names that differ only in case are the same name
+    pub case_insensitive_names: bool,
+    // Writes are rejected while set
+    pub locked: bool,
+    // The graph can't be deleted while set
+    pub deletion_protected: bool,
+    // Bumped after every data write, see GraphRevision
+    pub revision: i64,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
@@ -47,17 +111,36 @@ pub struct GraphInfo {
 // Implement FromRow for GraphInfo to convert from PgRow to GraphInfo
 impl<'r> FromRow<'r, PgRow> for GraphInfo {
     fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        let node_name_uniqueness: String = row.try_get("node_name_uniqueness")?;
+        let node_name_uniqueness = node_name_uniqueness
+            .parse::<NodeNameUniqueness>()
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
         Ok(Self {
             graph_id: row.try_get("graph_id")?,
             org_id: row.try_get("org_id")?,
             name: row.try_get("name")?,
             description: row.try_get("description")?,
+            is_public: row.try_get("is_public")?,
+            node_name_uniqueness,
+            case_insensitive_names: row.try_get("case_insensitive_names")?,
+            locked: row.try_get("locked")?,
+            deletion_protected: row.try_get("deletion_protected")?,
+            revision: row.try_get("revision")?,
             created_at: row.try_get("created_at")?,
             updated_at: row.try_get("updated_at")?,
         })
     }
 }
 
+lazy_static! {
+    // This regex matches only letters (both cases) and numbers.
+    static ref NAME_REGEX: Regex = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
+}
+
+pub const MAX_GRAPH_NAME_LENGTH: usize = 30;
+pub const MAX_GRAPH_DESCRIPTION_LENGTH: usize = 100;
+
 // Create error enum for graph creation
 // TODO: Handle duplicate graph id's, and add retry logic
 #[derive(Debug)]
@@ -66,55 +149,156 @@ pub enum GraphError {
     ValidationError(String),
 }
 
-impl GraphInfo {
-    pub fn new(org: &Org, name: &str, description: Option<&str>) -> Result<Self, GraphError> {
-        let now = chrono::Utc::now();
-        // Prefix g to the random id. Required by AGE to start with a letter
-        let graph_id = "g".to_string() + &create_id(8);
+// Graphs an org has against how many it may have, 0 meaning no limit
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct GraphQuota {
+    pub count: i64,
+    pub limit: i64,
+}
+
+impl GraphQuota {
+    pub fn is_reached(&self) -> bool {
+        self.limit > 0 && self.count >= self.limit
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CreateGraphError {
+    #[error("The org already has {} of at most {} graphs", .0.count, .0.limit)]
+    LimitReached(GraphQuota),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl Retryable for CreateGraphError {
+    fn is_retryable(&self) -> bool {
+        matches!(self, CreateGraphError::Database(e) if e.is_retryable())
+    }
+}
 
-        // If name is empty, return a validation error
+impl GraphInfo {
+    pub fn validate_name(name: &str) -> Result<(), GraphError> {
         if name.is_empty() {
             return Err(GraphError::ValidationError(
                 "Name cannot be empty".to_string(),
             ));
         }
+        if !NAME_REGEX.is_match(name) {
+            return Err(GraphError::ValidationError(
+                "Name must contain only letters and numbers".to_string(),
+            ));
+        }
+        if name.chars().count() > MAX_GRAPH_NAME_LENGTH {
+            return Err(GraphError::ValidationError(format!(
+                "Name must be at most {} characters long",
+                MAX_GRAPH_NAME_LENGTH
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn validate_description(description: &str) -> Result<(), GraphError> {
+        if description.chars().count() > MAX_GRAPH_DESCRIPTION_LENGTH {
+            return Err(GraphError::ValidationError(format!(
+                "Description must be at most {} characters long",
+                MAX_GRAPH_DESCRIPTION_LENGTH
+            )));
+        }
+        Ok(())
+    }
+
+    // All graph creation paths go through here, so the name and description rules
+    // are enforced whether or not the caller validated a request DTO first
+    pub fn new(org: &Org, name: &str, description: Option<&str>) -> Result<Self, GraphError> {
+        let now = chrono::Utc::now();
+        // The g prefix is required by AGE, graph names start with a letter
+        let graph_id = GraphId::generate();
+
+        Self::validate_name(name)?;
+        if let Some(description) = description {
+            Self::validate_description(description)?;
+        }
 
         Ok(Self {
             graph_id,
             org_id: org.id,
             name: name.to_string(),
             description: description.map(|s| s.to_string()),
+            is_public: false,
+            node_name_uniqueness: NodeNameUniqueness::default(),
+            case_insensitive_names: false,
+            locked: false,
+            deletion_protected: false,
+            revision: 0,
             created_at: now,
             updated_at: now,
         })
     }
 
-    pub async fn persist(&self, pool: &sqlx::PgPool, admin_user: User) -> Result<(), sqlx::Error> {
+    // Create the graph unless its org is at its graph limit. `default_max_graphs` applies
+    // to orgs without their own limit
+    pub async fn persist(
+        &self,
+        pool: &sqlx::PgPool,
+        admin_user: User,
+        default_max_graphs: u32,
+    ) -> Result<(), CreateGraphError> {
+        catalog::with_graph_cleanup(
+            pool,
+            &self.graph_id,
+            self.insert(pool, admin_user, default_max_graphs),
+        )
+        .await
+    }
+
+    async fn insert(
+        &self,
+        pool: &sqlx::PgPool,
+        admin_user: User,
+        default_max_graphs: u32,
+    ) -> Result<(), CreateGraphError> {
         // Start a transaction
         let mut transaction = pool.begin().await?;
 
-        // Create the graph in AGE
-        let age_query = "SELECT ag_catalog.create_graph($1)";
-        sqlx::query(age_query)
-            .bind(&self.graph_id)
-            .execute(&mut *transaction)
+        // Lock the org so graphs created at the same time are counted one after the other
+        let quota_query = "
+        SELECT COALESCE(o.max_graphs, $2) AS max_graphs,
+            (SELECT count(*) FROM app_data.graph_info g WHERE g.org_id = o.id) AS graph_count
+        FROM app_data.org o
+        WHERE o.id = $1
+        FOR UPDATE
+        ";
+        let row = sqlx::query(quota_query)
+            .bind(self.org_id)
+            .bind(default_max_graphs as i32)
+            .fetch_one(&mut *transaction)
             .await?;
+        let quota = GraphQuota {
+            count: row.try_get("graph_count")?,
+            limit: row.try_get::<i32, _>("max_graphs")? as i64,
+        };
+        if quota.is_reached() {
+            return Err(CreateGraphError::LimitReached(quota));
+        }
 
         // Insert the graph info into the database
         let graph_info_query =
-            "INSERT INTO app_data.graph_info (graph_id, org_id, name, description, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5, $6)";
+            "INSERT INTO app_data.graph_info (graph_id, org_id, name, description, node_name_uniqueness, case_insensitive_names, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)";
         sqlx::query(graph_info_query)
             .bind(&self.graph_id)
             .bind(&self.org_id)
             .bind(&self.name)
             .bind(&self.description)
+            .bind(self.node_name_uniqueness.to_string())
+            .bind(self.case_insensitive_names)
             .bind(&self.created_at)
             .bind(&self.updated_at)
             .execute(&mut *transaction)
             .await?;
 
-        let graph_member = GraphMember::new(self.graph_id.clone(), admin_user.id, GraphRole::Admin);
+        let graph_member =
+            GraphMember::new(self.graph_id.to_string(), admin_user.id, GraphRole::Admin);
 
         let graph_member_query =
             "INSERT INTO app_data.graph_member (graph_id, user_id, role, created_at, updated_at)
@@ -128,12 +312,115 @@ impl GraphInfo {
             .execute(&mut *transaction)
             .await?;
 
+        // Create the graph in AGE last, so a failed metadata insert never gets as far as
+        // creating it
+        let age_query = "SELECT ag_catalog.create_graph($1)";
+        sqlx::query(age_query)
+            .bind(&self.graph_id)
+            .execute(&mut *transaction)
+            .await?;
+
         transaction.commit().await?;
         Ok(())
     }
 
+    pub async fn set_node_name_uniqueness(
+        &mut self,
+        pool: &sqlx::PgPool,
+        node_name_uniqueness: NodeNameUniqueness,
+    ) -> Result<(), sqlx::Error> {
+        let query = "UPDATE app_data.graph_info SET node_name_uniqueness = $1, updated_at = now() WHERE graph_id = $2";
+        sqlx::query(query)
+            .bind(node_name_uniqueness.to_string())
+            .bind(&self.graph_id)
+            .execute(pool)
+            .await?;
+        self.node_name_uniqueness = node_name_uniqueness;
+        Ok(())
+    }
+
+    pub async fn set_case_insensitive_names(
+        &mut self,
+        pool: &sqlx::PgPool,
+        case_insensitive_names: bool,
+    ) -> Result<(), sqlx::Error> {
+        let query = "UPDATE app_data.graph_info SET case_insensitive_names = $1, updated_at = now() WHERE graph_id = $2";
+        sqlx::query(query)
+            .bind(case_insensitive_names)
+            .bind(&self.graph_id)
+            .execute(pool)
+            .await?;
+        self.case_insensitive_names = case_insensitive_names;
+        Ok(())
+    }
+
+    pub async fn set_locked(
+        &mut self,
+        pool: &sqlx::PgPool,
+        locked: bool,
+    ) -> Result<(), sqlx::Error> {
+        let query =
+            "UPDATE app_data.graph_info SET locked = $1, updated_at = now() WHERE graph_id = $2";
+        sqlx::query(query)
+            .bind(locked)
+            .bind(&self.graph_id)
+            .execute(pool)
+            .await?;
+        self.locked = locked;
+        Ok(())
+    }
+
+    // Turn deletion protection on or off, recording who changed it
+    pub async fn set_deletion_protected(
+        &mut self,
+        pool: &sqlx::PgPool,
+        deletion_protected: bool,
+        changed_by: Uuid,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let query = "UPDATE app_data.graph_info SET deletion_protected = $1, updated_at = now() WHERE graph_id = $2";
+        sqlx::query(query)
+            .bind(deletion_protected)
+            .bind(&self.graph_id)
+            .execute(&mut *tx)
+            .await?;
+        SecurityEvent::record(
+            &mut tx,
+            changed_by,
+            SecurityEventKind::GraphProtectionChanged,
+            serde_json::json!({
+                "graph_id": self.graph_id,
+                "deletion_protected": deletion_protected,
+            }),
+        )
+        .await?;
+        tx.commit().await?;
+
+        self.deletion_protected = deletion_protected;
+        Ok(())
+    }
+
+    // Drop the AGE graph and remove its metadata. Types and members are removed by cascade
+    pub async fn delete(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), sqlx::Error> {
+        let age_query = "SELECT ag_catalog.drop_graph($1, true)";
+        sqlx::query(age_query)
+            .bind(&self.graph_id)
+            .execute(&mut **transaction)
+            .await?;
+
+        let query = "DELETE FROM app_data.graph_info WHERE graph_id = $1";
+        sqlx::query(query)
+            .bind(&self.graph_id)
+            .execute(&mut **transaction)
+            .await?;
+        Ok(())
+    }
+
     pub async fn get_all(pool: &sqlx::PgPool, org_id: Uuid) -> Result<Vec<GraphInfo>, sqlx::Error> {
-        let query = "SELECT * FROM app_data.graph_info WHERE org_id = $1";
+        let query = "SELECT * FROM app_data.graph_info WHERE org_id = $1 ORDER BY name, graph_id";
         let rows = sqlx::query(query).bind(&org_id).fetch_all(pool).await?;
         let graphs: Vec<GraphInfo> = rows
             .iter()
@@ -142,7 +429,13 @@ impl GraphInfo {
         Ok(graphs)
     }
 
-    pub async fn from_id(pool: &sqlx::PgPool, graph_id: &str) -> Result<Self, sqlx::Error> {
+    // Ids of every graph across all orgs, for maintenance run at startup
+    pub async fn all_ids(pool: &sqlx::PgPool) -> Result<Vec<GraphId>, sqlx::Error> {
+        let query = "SELECT graph_id FROM app_data.graph_info ORDER BY graph_id";
+        sqlx::query_scalar(query).fetch_all(pool).await
+    }
+
+    pub async fn from_id(pool: &sqlx::PgPool, graph_id: &GraphId) -> Result<Self, sqlx::Error> {
         let query = "SELECT * FROM app_data.graph_info WHERE graph_id = $1";
         sqlx::query_as::<_, GraphInfo>(query)
             .bind(graph_id)
@@ -151,11 +444,224 @@ impl GraphInfo {
     }
 
     pub async fn get_node_types(&self, pool: &sqlx::PgPool) -> Result<Vec<NodeType>, sqlx::Error> {
-        let query = "SELECT * FROM app_data.node_types WHERE graph_id = $1";
+        let query = "SELECT * FROM app_data.node_types WHERE graph_id = $1 ORDER BY name, id";
         let rows = sqlx::query_as::<_, NodeType>(query)
             .bind(&self.graph_id)
             .fetch_all(pool)
             .await?;
         Ok(rows)
     }
+
+    // Distinct attribute keys across all node and edge types of the graph
+    pub async fn get_property_keys(
+        &self,
+        pool: &sqlx::PgPool,
+    ) -> Result<Vec<PropertyKey>, sqlx::Error> {
+        let query = r#"
+            SELECT key,
+                   array_agg(DISTINCT data_type ORDER BY data_type) AS data_types,
+                   COUNT(*) FILTER (WHERE kind = 'node') AS node_type_count,
+                   COUNT(*) FILTER (WHERE kind = 'edge') AS edge_type_count
+            FROM (
+                SELECT a.normalized_name AS key, a.data_type, 'node' AS kind
+                FROM app_data.node_type_attributes a
+                JOIN app_data.node_types t ON t.id = a.type_id
+                WHERE t.graph_id = $1
+                UNION ALL
+                SELECT a.normalized_name AS key, a.data_type, 'edge' AS kind
+                FROM app_data.edge_type_attribute a
+                JOIN app_data.edge_type t ON t.id = a.type_id
+                WHERE t.graph_id = $1
+            ) keys
+            GROUP BY key
+            ORDER BY key
+        "#;
+        sqlx::query_as::<_, PropertyKey>(query)
+            .bind(&self.graph_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    // Get the user's graph memberships across all orgs
+    pub async fn get_memberships_of_user(
+        pool: &sqlx::PgPool,
+        user_id: Uuid,
+    ) -> Result<Vec<GraphMember>, sqlx::Error> {
+        let query = "SELECT * FROM app_data.graph_member WHERE user_id = $1";
+        sqlx::query_as::<_, GraphMember>(query)
+            .bind(user_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    // Get the user's graph memberships for all graphs in an org
+    pub async fn get_memberships_in_org(
+        pool: &sqlx::PgPool,
+        org_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Vec<GraphMember>, sqlx::Error> {
+        let query = "
+        SELECT gm.*
+        FROM app_data.graph_member gm
+        JOIN app_data.graph_info g ON g.graph_id = gm.graph_id
+        WHERE g.org_id = $1 AND gm.user_id = $2
+        ";
+        sqlx::query_as::<_, GraphMember>(query)
+            .bind(org_id)
+            .bind(user_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    // Pin the graph for the given user. Pinning an already pinned graph is a no-op
+    pub async fn pin(&self, pool: &sqlx::PgPool, user_id: Uuid) -> Result<(), sqlx::Error> {
+        let query = "INSERT INTO app_data.user_graph_pin (user_id, graph_id) VALUES ($1, $2)
+            ON CONFLICT (user_id, graph_id) DO NOTHING";
+        sqlx::query(query)
+            .bind(user_id)
+            .bind(&self.graph_id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn unpin(&self, pool: &sqlx::PgPool, user_id: Uuid) -> Result<(), sqlx::Error> {
+        let query = "DELETE FROM app_data.user_graph_pin WHERE user_id = $1 AND graph_id = $2";
+        sqlx::query(query)
+            .bind(user_id)
+            .bind(&self.graph_id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    // Get the ids of the graphs pinned by the user
+    pub async fn get_pinned_ids(
+        pool: &sqlx::PgPool,
+        user_id: Uuid,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let query = "SELECT graph_id FROM app_data.user_graph_pin WHERE user_id = $1";
+        sqlx::query_scalar::<_, String>(query)
+            .bind(user_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    // Get the graphs pinned by the user, most recently pinned first. Whether the user can
+    // still read them is up to the caller, see unpin_all
+    pub async fn get_pinned(
+        pool: &sqlx::PgPool,
+        user_id: Uuid,
+    ) -> Result<Vec<GraphInfo>, sqlx::Error> {
+        let query = "
+        SELECT g.*
+        FROM app_data.user_graph_pin p
+        JOIN app_data.graph_info g ON g.graph_id = p.graph_id
+        WHERE p.user_id = $1
+        ORDER BY p.pinned_at DESC, g.graph_id
+        ";
+        sqlx::query_as::<_, GraphInfo>(query)
+            .bind(user_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    // Remove the user's pins to the given graphs, e.g. ones they can no longer read
+    pub async fn unpin_all(
+        pool: &sqlx::PgPool,
+        user_id: Uuid,
+        graph_ids: &[GraphId],
+    ) -> Result<(), sqlx::Error> {
+        let query = "DELETE FROM app_data.user_graph_pin WHERE user_id = $1 AND graph_id = ANY($2)";
+        sqlx::query(query)
+            .bind(user_id)
+            .bind(graph_ids)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_message(result: Result<(), GraphError>) -> Option<String> {
+        result
+            .err()
+            .map(|GraphError::ValidationError(message)| message)
+    }
+
+    #[test]
+    fn names_are_letters_and_numbers() {
+        assert_eq!(error_message(GraphInfo::validate_name("Roads2024")), None);
+        assert_eq!(
+            error_message(GraphInfo::validate_name("")).as_deref(),
+            Some("Name cannot be empty")
+        );
+        for name in [
+            "road network",
+            "roads_2024",
+            "roads-2024",
+            "Straße",
+            " roads",
+        ] {
+            assert_eq!(
+                error_message(GraphInfo::validate_name(name)).as_deref(),
+                Some("Name must contain only letters and numbers"),
+                "{:?}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn name_length_limit() {
+        let longest = "a".repeat(MAX_GRAPH_NAME_LENGTH);
+        assert_eq!(error_message(GraphInfo::validate_name(&longest)), None);
+        assert_eq!(
+            error_message(GraphInfo::validate_name(&format!("{}a", longest))).as_deref(),
+            Some("Name must be at most 30 characters long")
+        );
+    }
+
+    #[test]
+    fn description_length_limit_counts_characters() {
+        let longest = "é".repeat(MAX_GRAPH_DESCRIPTION_LENGTH);
+        assert_eq!(
+            error_message(GraphInfo::validate_description(&longest)),
+            None
+        );
+        assert_eq!(error_message(GraphInfo::validate_description("")), None);
+        assert_eq!(
+            error_message(GraphInfo::validate_description(&format!("{}é", longest))).as_deref(),
+            Some("Description must be at most 100 characters long")
+        );
+    }
+
+    #[test]
+    fn new_graph() {
+        let org = Org::new("Acme", "");
+        let graph = GraphInfo::new(&org, "Roads", Some("Road network")).unwrap();
+
+        assert_eq!(graph.org_id, org.id);
+        assert_eq!(graph.name, "Roads");
+        assert_eq!(graph.description.as_deref(), Some("Road network"));
+        assert!(graph.graph_id.as_str().starts_with('g'));
+        assert_eq!(graph.graph_id.as_str().len(), 9);
+        assert!(!graph.is_public && !graph.locked && !graph.deletion_protected);
+        assert_eq!(graph.node_name_uniqueness, NodeNameUniqueness::PerType);
+        assert_eq!(graph.revision, 0);
+
+        let other = GraphInfo::new(&org, "Roads", None).unwrap();
+        assert_ne!(other.graph_id, graph.graph_id);
+        assert_eq!(other.description, None);
+    }
+
+    #[test]
+    fn new_graph_is_validated() {
+        let org = Org::new("Acme", "");
+        assert!(GraphInfo::new(&org, "Road network", None).is_err());
+        assert!(GraphInfo::new(&org, "Roads", Some(&"a".repeat(101))).is_err());
+    }
 }
@@ -1,6 +1,19 @@
+mod access;
+mod dates;
 mod endpoints;
+mod export;
 mod graph;
+mod ping;
+mod revision;
+mod stats;
+mod validation_report;
 
+pub use access::*;
+pub use dates::*;
 pub use endpoints::*;
+pub use export::*;
 pub use graph::*;
-
+pub use ping::*;
+pub use revision::*;
+pub use stats::*;
+pub use validation_report::*;
@@ -0,0 +1,57 @@
+use crate::ag::AgLookupError;
+use crate::cypher::Cypher;
+use crate::ids::GraphId;
+use serde::Serialize;
+use std::time::Instant;
+
+// Result of running a trivial cypher query against a graph, to tell whether its AGE graph
+// exists and answers
+#[derive(Debug, Serialize)]
+pub struct GraphPing {
+    pub ok: bool,
+    // Round trip of the query, failures included
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_count: Option<i64>,
+    // graph_not_found, query_failed or decode_failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl GraphPing {
+    // Never fails: problems with the graph are what the ping reports
+    pub async fn run(pool: &sqlx::PgPool, graph_id: &GraphId) -> Self {
+        let query = Cypher::new(graph_id, "MATCH (n) RETURN count(n) LIMIT 1")
+            .returning("node_count agtype");
+        let started = Instant::now();
+        let result = query.fetch_one(pool).await;
+        let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+        let failed = |error, message: String| Self {
+            ok: false,
+            latency_ms,
+            node_count: None,
+            error: Some(error),
+            message: Some(message),
+        };
+        match result {
+            Ok(count) => match count.0.into_scalar() {
+                Ok(count) => Self {
+                    ok: true,
+                    latency_ms,
+                    node_count: count.as_i64(),
+                    error: None,
+                    message: None,
+                },
+                Err(e) => failed("decode_failed", e.to_string()),
+            },
+            Err(e) if AgLookupError::classify(&e) == Some(AgLookupError::GraphNotFound) => failed(
+                "graph_not_found",
+                "The AGE graph does not exist".to_string(),
+            ),
+            Err(e) => failed("query_failed", e.to_string()),
+        }
+    }
+}
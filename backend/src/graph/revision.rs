@@ -0,0 +1,308 @@
+use super::GraphInfo;
+use crate::error::ApiError;
+use crate::ids::GraphId;
+use axum::http::HeaderValue;
+use axum::response::{IntoResponseParts, ResponseParts};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::error;
+
+// Response header with the graph's revision. Writes return the revision they bumped the
+// graph to, listings the revision the graph was at when they were read
+pub const GRAPH_REVISION_HEADER: &str = "graph-revision";
+
+// Count of a graph's data writes. It only goes up, so a client holding the revision of its
+// own write can ask a later read to reflect at least that write
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GraphRevision(pub i64);
+
+impl GraphRevision {
+    // Record a write to the graph and return the new revision. Called once the write has
+    // committed, so a failure is only logged and the response goes out without a revision
+    pub async fn bump(pool: &sqlx::PgPool, graph_id: &GraphId) -> Option<Self> {
+        let query = "UPDATE app_data.graph_info SET revision = revision + 1, last_activity_at = now() WHERE graph_id = $1 RETURNING revision";
+        match sqlx::query_scalar(query)
+            .bind(graph_id)
+            .fetch_one(pool)
+            .await
+        {
+            Ok(revision) => Some(Self(revision)),
+            Err(e) => {
+                error!("Failed to bump the revision of graph {}: {}", graph_id, e);
+                None
+            }
+        }
+    }
+}
+
+impl IntoResponseParts for GraphRevision {
+    type Error = Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        res.headers_mut()
+            .insert(GRAPH_REVISION_HEADER, HeaderValue::from(self.0));
+        Ok(res)
+    }
+}
+
+// The revision a read has to reflect, from its min_revision parameter
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MinRevision(Option<i64>);
+
+impl MinRevision {
+    pub fn new(min_revision: Option<i64>) -> Self {
+        Self(min_revision)
+    }
+
+    // Whether data as of `revision` is too old for the read. Cached listings and stats
+    // must be bypassed and read fresh then
+    pub fn requires_refresh(&self, revision: GraphRevision) -> bool {
+        self.0.is_some_and(|min| revision.0 < min)
+    }
+
+    // Check the read against the graph's revision, which was read with the access check,
+    // and return that revision. Listings query the database directly and see every write
+    // up to it, so they meet any revision a write returned. Cached reads are checked again
+    // by RevisionCache. One beyond the graph's revision was never issued
+    pub fn check(&self, graph: &GraphInfo) -> Result<GraphRevision, ApiError> {
+        let revision = GraphRevision(graph.revision);
+        if self.requires_refresh(revision) {
+            return Err(ApiError::BadRequest(format!(
+                "min_revision is ahead of the graph's revision {}",
+                revision.0
+            )));
+        }
+        Ok(revision)
+    }
+}
+
+#[derive(Debug)]
+struct CachedRead<T> {
+    read_at: Instant,
+    revision: GraphRevision,
+    value: Arc<T>,
+}
+
+#[derive(Debug)]
+struct CachedReads<K, T> {
+    by_key: HashMap<K, CachedRead<T>>,
+    last_pruned: Option<Instant>,
+}
+
+// Results of expensive reads of a graph, such as stats, kept for up to a TTL. An entry is
+// served even when the graph was written since it was read, unless the read's min_revision
+// is ahead of the revision the entry was read at. Then the entry is bypassed and the result
+// read fresh, so a client sees its own writes. A TTL of zero disables the cache
+#[derive(Debug)]
+pub struct RevisionCache<K, T> {
+    ttl: Duration,
+    entries: Mutex<CachedReads<K, T>>,
+}
+
+impl<K: Eq + Hash, T> RevisionCache<K, T> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(CachedReads {
+                by_key: HashMap::new(),
+                last_pruned: None,
+            }),
+        }
+    }
+
+    // The cached result for `key`, or the one `load` returns. `revision` is the graph's
+    // revision as read before the load, so the loaded result reflects at least it. Returns
+    // the revision the result reflects
+    pub async fn get_or_load<F, Fut, E>(
+        &self,
+        key: K,
+        revision: GraphRevision,
+        min_revision: MinRevision,
+        load: F,
+    ) -> Result<(GraphRevision, Arc<T>), E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        self.get_or_load_at(key, revision, min_revision, Instant::now, load)
+            .await
+    }
+
+    // get_or_load with time read from `clock`, so tests can move it
+    async fn get_or_load_at<F, Fut, E>(
+        &self,
+        key: K,
+        revision: GraphRevision,
+        min_revision: MinRevision,
+        clock: impl Fn() -> Instant,
+        load: F,
+    ) -> Result<(GraphRevision, Arc<T>), E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        if self.ttl.is_zero() {
+            return Ok((revision, Arc::new(load().await?)));
+        }
+
+        {
+            let entries = self.entries.lock().unwrap();
+            if let Some(cached) = entries.by_key.get(&key) {
+                if clock().duration_since(cached.read_at) < self.ttl
+                    && !min_revision.requires_refresh(cached.revision)
+                {
+                    return Ok((cached.revision, Arc::clone(&cached.value)));
+                }
+            }
+        }
+
+        // Entries age from the start of the load, so none is served past the TTL
+        let read_at = clock();
+        let value = Arc::new(load().await?);
+
+        let now = clock();
+        let mut entries = self.entries.lock().unwrap();
+        // Drop expired entries at most once per TTL, so graphs nobody reads any more don't
+        // stay in memory
+        if entries
+            .last_pruned
+            .is_none_or(|at| now.duration_since(at) >= self.ttl)
+        {
+            let ttl = self.ttl;
+            entries
+                .by_key
+                .retain(|_, cached| now.duration_since(cached.read_at) < ttl);
+            entries.last_pruned = Some(now);
+        }
+        // A slower load that read an older revision doesn't replace a newer entry
+        let newer_cached = entries
+            .by_key
+            .get(&key)
+            .is_some_and(|cached| cached.revision > revision);
+        if !newer_cached {
+            entries.by_key.insert(
+                key,
+                CachedRead {
+                    read_at,
+                    revision,
+                    value: Arc::clone(&value),
+                },
+            );
+        }
+        Ok((revision, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    const TTL: Duration = Duration::from_secs(30);
+
+    // Reads through the cache, counting the loads. The loaded value is the revision read
+    struct Reads {
+        cache: RevisionCache<&'static str, i64>,
+        start: Instant,
+        elapsed: Cell<Duration>,
+        loads: Cell<u32>,
+    }
+
+    impl Reads {
+        fn new(ttl: Duration) -> Self {
+            Self {
+                cache: RevisionCache::new(ttl),
+                start: Instant::now(),
+                elapsed: Cell::new(Duration::ZERO),
+                loads: Cell::new(0),
+            }
+        }
+
+        async fn read(&self, graph_revision: i64, min_revision: Option<i64>) -> (i64, i64) {
+            let (revision, value) = self
+                .cache
+                .get_or_load_at(
+                    "graph",
+                    GraphRevision(graph_revision),
+                    MinRevision::new(min_revision),
+                    || self.start + self.elapsed.get(),
+                    || async {
+                        self.loads.set(self.loads.get() + 1);
+                        Ok::<_, ()>(graph_revision)
+                    },
+                )
+                .await
+                .unwrap();
+            (revision.0, *value)
+        }
+
+        fn advance(&self, by: Duration) {
+            self.elapsed.set(self.elapsed.get() + by);
+        }
+    }
+
+    #[tokio::test]
+    async fn cached_reads_are_served_within_the_ttl() {
+        let reads = Reads::new(TTL);
+        assert_eq!(reads.read(1, None).await, (1, 1));
+        // Written since, but nobody asked for the write to be seen
+        assert_eq!(reads.read(2, None).await, (1, 1));
+        assert_eq!(reads.read(2, Some(1)).await, (1, 1));
+        assert_eq!(reads.loads.get(), 1);
+
+        reads.advance(TTL);
+        assert_eq!(reads.read(2, None).await, (2, 2));
+        assert_eq!(reads.loads.get(), 2);
+    }
+
+    #[tokio::test]
+    async fn min_revision_ahead_of_the_cache_bypasses_it() {
+        let reads = Reads::new(TTL);
+        assert_eq!(reads.read(1, None).await, (1, 1));
+
+        // The client wrote revision 2 and asks to see it
+        assert_eq!(reads.read(2, Some(2)).await, (2, 2));
+        assert_eq!(reads.loads.get(), 2);
+
+        // The fresh read replaced the entry, so it is served from the cache again
+        assert_eq!(reads.read(2, Some(2)).await, (2, 2));
+        assert_eq!(reads.read(3, None).await, (2, 2));
+        assert_eq!(reads.loads.get(), 2);
+    }
+
+    #[tokio::test]
+    async fn older_reads_do_not_replace_newer_entries() {
+        let reads = Reads::new(TTL);
+        // A read of revision 6 is still loading when one of revision 7 completes
+        let (revision, _) = reads
+            .cache
+            .get_or_load_at(
+                "graph",
+                GraphRevision(6),
+                MinRevision::default(),
+                || reads.start,
+                || async {
+                    reads.read(7, Some(7)).await;
+                    Ok::<_, ()>(6)
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(revision, GraphRevision(6));
+
+        assert_eq!(reads.read(7, Some(7)).await, (7, 7));
+        assert_eq!(reads.loads.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn zero_ttl_always_loads() {
+        let reads = Reads::new(Duration::ZERO);
+        reads.read(1, None).await;
+        reads.read(1, None).await;
+        assert_eq!(reads.loads.get(), 2);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::ag::AgType;
+use crate::cypher::Cypher;
+use crate::edge::EdgeType;
+use crate::graph::{GraphInfo, RevisionCache};
+use crate::ids::GraphId;
+use crate::node::{where_all, NodeScope, NodeVisibility};
+use serde::Serialize;
+use sqlx::Row;
+use std::collections::HashMap;
+
+// Edge type pair counts per graph and visibility
+pub type EdgeStatsCache = RevisionCache<(GraphId, NodeVisibility), Vec<EdgeTypePairCount>>;
+
+// Number of edges of one type between nodes of two given types
+#[derive(Debug, Clone, Serialize)]
+pub struct EdgeTypePairCount {
+    pub from_type: String,
+    pub from_type_name: Option<String>,
+    pub edge_type: String,
+    pub edge_type_name: Option<String>,
+    pub to_type: String,
+    pub to_type_name: Option<String>,
+    pub count: i64,
+}
+
+impl EdgeTypePairCount {
+    // Count edges grouped by (from label, edge label, to label) in a single cypher
+    // aggregation. Labels are type ids, the names are resolved from the type tables
+    pub async fn for_graph(
+        pool: &sqlx::PgPool,
+        graph: &GraphInfo,
+        visibility: &NodeVisibility,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        // Both ends are limited to the nodes listings cover, so counts match what is listed
+        let scope = NodeScope::visible(visibility);
+        let mut conditions = scope.conditions("a");
+        conditions.extend(scope.conditions("b"));
+        let query = Cypher::new(
+            &graph.graph_id,
+            format!(
+                "MATCH {}-[r]->{} {} RETURN label(a), label(r), label(b), count(*)",
+                scope.pattern("a"),
+                scope.pattern("b"),
+                where_all(&conditions)
+            ),
+        )
+        .returning("from_label agtype, edge_label agtype, to_label agtype, edge_count agtype");
+        let rows = query.fetch_rows(pool).await?;
+
+        let node_type_names: HashMap<String, String> = graph
+            .get_node_types(pool)
+            .await?
+            .into_iter()
+            .map(|t| (t.id.into(), t.name))
+            .collect();
+        let edge_type_names: HashMap<String, String> = EdgeType::list(pool, &graph.graph_id)
+            .await?
+            .into_iter()
+            .map(|t| (t.id.into(), t.name))
+            .collect();
+
+        let mut counts = Vec::with_capacity(rows.len());
+        for row in rows {
+            let decode = |column: &str| -> Result<serde_json::Value, sqlx::Error> {
+                let value: AgType = row.try_get(column)?;
+                value
+                    .0
+                    .into_scalar()
+                    .map_err(|e| sqlx::Error::Decode(Box::new(e)))
+            };
+            let as_string =
+                |value: serde_json::Value| value.as_str().unwrap_or_default().to_string();
+
+            let from_type = as_string(decode("from_label")?);
+            let edge_type = as_string(decode("edge_label")?);
+            let to_type = as_string(decode("to_label")?);
+            let count = decode("edge_count")?.as_i64().unwrap_or_default();
+
+            counts.push(Self {
+                from_type_name: node_type_names.get(&from_type).cloned(),
+                edge_type_name: edge_type_names.get(&edge_type).cloned(),
+                to_type_name: node_type_names.get(&to_type).cloned(),
+                from_type,
+                edge_type,
+                to_type,
+                count,
+            });
+        }
+
+        counts.sort_by_key(|c| std::cmp::Reverse(c.count));
+        Ok(counts)
+    }
+}
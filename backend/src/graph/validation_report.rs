@@ -0,0 +1,304 @@
+use crate::ag::{self, Vertex};
+use crate::cypher::Cypher;
+use crate::edge::{Edge, EdgeType, EdgeTypeAttributeDefinition};
+use crate::graph::GraphInfo;
+use crate::ids::GraphId;
+use crate::node::{Node, NodeScope, NodeTypeAttributeDefinition, NodeTypeInheritanceError};
+use crate::validation::{AttributeRule, AttributeValidationError, ValidationOutcome};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use sqlx::{postgres::PgRow, FromRow, Row};
+use std::collections::{BTreeMap, HashMap};
+use tracing::info;
+use uuid::Uuid;
+
+const VALIDATION_PAGE_SIZE: usize = 1000;
+
+// Offending element ids kept per violation, enough for the UI to link to a few of them
+pub const MAX_VIOLATION_SAMPLES: usize = 10;
+
+// Elements of one type that break the same rule for the same attribute. Samples are
+// public ids for nodes, as used in node URLs, and edge ids for edges
+#[derive(Debug, Serialize)]
+pub struct Violation {
+    pub kind: String,
+    pub attribute: String,
+    pub count: usize,
+    pub sample_ids: Vec<String>,
+}
+
+// Violations of the nodes or edges of one type
+#[derive(Debug, Serialize)]
+pub struct TypeViolations {
+    pub type_id: String,
+    pub type_name: String,
+    pub checked: usize,
+    pub invalid: usize,
+    pub violations: Vec<Violation>,
+}
+
+// Nodes whose label has no node type, e.g. left behind when their type was deleted. They
+// aren't checked, and need to be moved to another type or deleted
+#[derive(Debug, Default, Serialize)]
+pub struct UntypedNodes {
+    pub count: usize,
+    // Number of nodes per label without a type
+    pub labels: BTreeMap<String, usize>,
+    pub sample_ids: Vec<String>,
+}
+
+// Outcome of checking every node and edge of a graph against the current schema.
+// Stored values are checked strictly, so values that writes would coerce are reported too.
+// Edges whose label has no type definition are not checked, nodes are listed as untyped
+#[derive(Debug, Serialize)]
+pub struct GraphValidationReport {
+    pub graph_id: String,
+    pub nodes_checked: usize,
+    pub edges_checked: usize,
+    // Number of violations per kind, e.g. {"missing_attribute": 3}
+    pub counts: BTreeMap<String, usize>,
+    pub node_types: Vec<TypeViolations>,
+    pub edge_types: Vec<TypeViolations>,
+    pub untyped_nodes: UntypedNodes,
+}
+
+// The latest report of a graph as stored by the validation job
+#[derive(Debug, Serialize)]
+pub struct StoredValidationReport {
+    pub job_id: Uuid,
+    #[serde(with = "crate::utils::rfc3339")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub report: JsonValue,
+}
+
+impl<'r> FromRow<'r, PgRow> for StoredValidationReport {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            job_id: row.try_get("job_id")?,
+            created_at: row.try_get("created_at")?,
+            report: row.try_get("report")?,
+        })
+    }
+}
+
+// Running tally for one type while elements are checked
+struct TypeTally<A> {
+    type_name: String,
+    attributes: Vec<A>,
+    checked: usize,
+    invalid: usize,
+    // Keyed by (kind, attribute) so the report order is stable
+    violations: BTreeMap<(String, String), Violation>,
+}
+
+impl<A: AttributeRule> TypeTally<A> {
+    fn new(type_name: String, attributes: Vec<A>) -> Self {
+        Self {
+            type_name,
+            attributes,
+            checked: 0,
+            invalid: 0,
+            violations: BTreeMap::new(),
+        }
+    }
+
+    fn check(
+        &mut self,
+        id: &str,
+        properties: &HashMap<String, JsonValue>,
+        counts: &mut BTreeMap<String, usize>,
+    ) {
+        self.checked += 1;
+        let outcome = ValidationOutcome::validate(&self.attributes, properties.clone(), true);
+        if outcome.is_valid() {
+            return;
+        }
+        self.invalid += 1;
+        for error in &outcome.errors {
+            self.record(id, error);
+            *counts.entry(error.code().to_string()).or_default() += 1;
+        }
+    }
+
+    fn record(&mut self, id: &str, error: &AttributeValidationError) {
+        let key = (error.code().to_string(), error.attribute().to_string());
+        let violation = self.violations.entry(key).or_insert_with(|| Violation {
+            kind: error.code().to_string(),
+            attribute: error.attribute().to_string(),
+            count: 0,
+            sample_ids: Vec::new(),
+        });
+        violation.count += 1;
+        if violation.sample_ids.len() < MAX_VIOLATION_SAMPLES {
+            violation.sample_ids.push(id.to_string());
+        }
+    }
+
+    // Only types with at least one invalid element end up in the report
+    fn into_report(self, type_id: String) -> Option<TypeViolations> {
+        if self.invalid == 0 {
+            return None;
+        }
+        Some(TypeViolations {
+            type_id,
+            type_name: self.type_name,
+            checked: self.checked,
+            invalid: self.invalid,
+            violations: self.violations.into_values().collect(),
+        })
+    }
+}
+
+fn type_reports<A: AttributeRule>(tallies: HashMap<String, TypeTally<A>>) -> Vec<TypeViolations> {
+    let mut reports: Vec<TypeViolations> = tallies
+        .into_iter()
+        .filter_map(|(type_id, tally)| tally.into_report(type_id))
+        .collect();
+    reports.sort_by(|a, b| b.invalid.cmp(&a.invalid).then(a.type_id.cmp(&b.type_id)));
+    reports
+}
+
+impl GraphValidationReport {
+    // Check every node and edge of the graph, page by page
+    pub async fn run(
+        pool: &sqlx::PgPool,
+        graph: &GraphInfo,
+    ) -> Result<Self, NodeTypeInheritanceError> {
+        info!("Validating graph: {}", graph.graph_id);
+
+        // Node attributes per node type, inherited ones included
+        let mut node_tallies: HashMap<String, TypeTally<NodeTypeAttributeDefinition>> =
+            HashMap::new();
+        for node_type in graph.get_node_types(pool).await? {
+            let (node_type_id, node_type_name) = (node_type.id.to_string(), node_type.name.clone());
+            let lineage = node_type.lineage(pool).await?;
+            let attributes = NodeTypeAttributeDefinition::resolve(pool, &lineage).await?;
+            node_tallies.insert(node_type_id, TypeTally::new(node_type_name, attributes));
+        }
+
+        let mut edge_tallies: HashMap<String, TypeTally<EdgeTypeAttributeDefinition>> =
+            HashMap::new();
+        for edge_type in EdgeType::list(pool, &graph.graph_id).await? {
+            let attributes =
+                EdgeTypeAttributeDefinition::from_edge_type(pool, &edge_type.id).await?;
+            edge_tallies.insert(
+                edge_type.id.into(),
+                TypeTally::new(edge_type.name, attributes),
+            );
+        }
+
+        let mut counts = BTreeMap::new();
+        let mut nodes_checked = 0;
+        let mut untyped_nodes = UntypedNodes::default();
+        let scope = NodeScope::all();
+        let mut offset = 0;
+        loop {
+            let query = Cypher::new(
+                &graph.graph_id,
+                format!(
+                    "MATCH {} {} RETURN v ORDER BY id(v) SKIP {} LIMIT {}",
+                    scope.pattern("v"),
+                    scope.where_clause("v"),
+                    offset,
+                    VALIDATION_PAGE_SIZE
+                ),
+            );
+            let ag_rows = query.fetch_all(pool).await?;
+            let page_len = ag_rows.len();
+            offset += page_len;
+
+            for ag_row in ag_rows {
+                let vertex =
+                    Vertex::try_from(ag_row).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+                // Kept as stored, a label that isn't a type id is reported as it is
+                let label = vertex.label.clone();
+                let node = Node::from_vertex(vertex, &graph.graph_id)
+                    .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+                let id = node
+                    .public_id()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| node.id().to_string());
+                match node_tallies.get_mut(label.as_str()) {
+                    Some(tally) => {
+                        tally.check(&id, node.properties(), &mut counts);
+                        nodes_checked += 1;
+                    }
+                    None => {
+                        untyped_nodes.count += 1;
+                        *untyped_nodes.labels.entry(label.to_string()).or_default() += 1;
+                        if untyped_nodes.sample_ids.len() < MAX_VIOLATION_SAMPLES {
+                            untyped_nodes.sample_ids.push(id);
+                        }
+                    }
+                }
+            }
+
+            if page_len < VALIDATION_PAGE_SIZE {
+                break;
+            }
+        }
+
+        let mut edges_checked = 0;
+        let mut offset = 0;
+        loop {
+            let query = Cypher::new(
+                &graph.graph_id,
+                format!(
+                    "MATCH ()-[r]->() RETURN r ORDER BY id(r) SKIP {} LIMIT {}",
+                    offset, VALIDATION_PAGE_SIZE
+                ),
+            );
+            let ag_rows = query.fetch_all(pool).await?;
+            let page_len = ag_rows.len();
+            offset += page_len;
+
+            for ag_row in ag_rows {
+                let edge = ag::Edge::try_from(ag_row)
+                    .and_then(Edge::try_from)
+                    .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+                if let Some(tally) = edge_tallies.get_mut(&edge.label) {
+                    tally.check(&edge.id.to_string(), &edge.properties, &mut counts);
+                    edges_checked += 1;
+                }
+            }
+
+            if page_len < VALIDATION_PAGE_SIZE {
+                break;
+            }
+        }
+
+        Ok(Self {
+            graph_id: graph.graph_id.to_string(),
+            nodes_checked,
+            edges_checked,
+            counts,
+            node_types: type_reports(node_tallies),
+            edge_types: type_reports(edge_tallies),
+            untyped_nodes,
+        })
+    }
+
+    // Store the report as the graph's latest, replacing the previous one
+    pub async fn save(&self, pool: &sqlx::PgPool, job_id: Uuid) -> Result<(), sqlx::Error> {
+        let report = serde_json::to_value(self).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+        let query = "INSERT INTO app_data.graph_validation_report (graph_id, job_id, report, created_at) VALUES ($1, $2, $3, now()) ON CONFLICT (graph_id) DO UPDATE SET job_id = EXCLUDED.job_id, report = EXCLUDED.report, created_at = EXCLUDED.created_at";
+        sqlx::query(query)
+            .bind(&self.graph_id)
+            .bind(job_id)
+            .bind(report)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn latest(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+    ) -> Result<Option<StoredValidationReport>, sqlx::Error> {
+        let query = "SELECT * FROM app_data.graph_validation_report WHERE graph_id = $1";
+        sqlx::query_as::<_, StoredValidationReport>(query)
+            .bind(graph_id)
+            .fetch_optional(pool)
+            .await
+    }
+}
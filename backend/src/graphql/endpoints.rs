@@ -0,0 +1,43 @@
+use super::build_schema;
+use crate::auth::Auth;
+use crate::config::AppState;
+use crate::error::ApiError;
+use crate::graph::GraphAccess;
+use crate::ids::GraphId;
+use crate::node::NodeVisibility;
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use tracing::error;
+
+// Execute a read-only GraphQL query against a graph. The schema is generated from the graph's
+// type metadata on each request, so it always reflects the current node and edge types
+pub async fn graphql(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+    Json(request): Json<async_graphql::Request>,
+) -> Result<Json<async_graphql::Response>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+
+    let visibility = NodeVisibility::load(&state.pool, &access.graph.graph_id, access.role)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch restricted node types: {}", e);
+            ApiError::InternalServerError
+        })?;
+    let schema = build_schema(&state.pool, &access.graph, visibility)
+        .await
+        .map_err(|e| {
+            error!("Failed to build GraphQL schema: {}", e);
+            ApiError::InternalServerError
+        })?;
+
+    Ok(Json(schema.execute(request).await))
+}
@@ -0,0 +1,5 @@
+mod endpoints;
+mod schema;
+
+pub use endpoints::*;
+pub use schema::*;
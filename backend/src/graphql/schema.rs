@@ -0,0 +1,641 @@
+use crate::edge::{Edge, EdgeType, Subgraph};
+use crate::graph::GraphInfo;
+use crate::ids::{GraphId, NodeTypeId};
+use crate::node::{
+    resolve_node_type, Node, NodeScope, NodeSort, NodeType, NodeTypeAttributeDataType,
+    NodeTypeAttributeDefinition, NodeVisibility, PropertyFilter, MAX_INHERITANCE_DEPTH,
+};
+use crate::utils::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use async_graphql::dynamic::{
+    Enum, Field, FieldFuture, FieldValue, InputObject, InputValue, Interface, InterfaceField,
+    Object, ResolverContext, Scalar, Schema, SchemaError, TypeRef,
+};
+use async_graphql::Value;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde_json::Value as JsonValue;
+use std::collections::{HashMap, HashSet};
+
+// Limits applied to every query. Depth bounds neighbor traversals, complexity bounds the
+// number of fields resolved
+pub const MAX_DEPTH: usize = 8;
+pub const MAX_COMPLEXITY: usize = 500;
+
+// Object type for nodes whose label has no node type, e.g. after the type was deleted
+const UNTYPED_NODE: &str = "UntypedNode";
+
+// Fields every node object has, from the Node interface
+const NODE_FIELDS: [&str; 5] = ["id", "nodeType", "name", "properties", "neighbors"];
+
+// Data shared by the resolvers of one schema
+struct GraphContext {
+    pool: sqlx::PgPool,
+    graph_id: GraphId,
+    // Node type id -> name of its object type
+    objects: HashMap<String, String>,
+    node_types: Vec<Value>,
+    visibility: NodeVisibility,
+}
+
+impl GraphContext {
+    fn node_value(&self, node: Node) -> FieldValue<'static> {
+        let object = self
+            .objects
+            .get(node.node_type().map_or("", NodeTypeId::as_str))
+            .cloned()
+            .unwrap_or_else(|| UNTYPED_NODE.to_string());
+        FieldValue::owned_any(node).with_type(object)
+    }
+}
+
+// An edge of a node together with the node at its other end
+struct Neighbor {
+    edge: Edge,
+    node: Node,
+}
+
+// Result of the `nodes` query
+struct Connection {
+    nodes: Vec<Node>,
+    page_info: Value,
+}
+
+// Build the read-only schema of a graph. Each node type becomes an object implementing the
+// Node interface, with a typed field per attribute, inherited ones included. Node listings
+// only return the nodes `visibility` allows
+pub async fn build_schema(
+    pool: &sqlx::PgPool,
+    graph: &GraphInfo,
+    visibility: NodeVisibility,
+) -> Result<Schema, BuildError> {
+    let node_types = graph.get_node_types(pool).await?;
+    let attributes = NodeTypeAttributeDefinition::for_graph(pool, &graph.graph_id).await?;
+    Ok(assemble(
+        pool,
+        &graph.graph_id,
+        &node_types,
+        &attributes,
+        visibility,
+    )?)
+}
+
+fn assemble(
+    pool: &sqlx::PgPool,
+    graph_id: &GraphId,
+    node_types: &[NodeType],
+    attributes: &[NodeTypeAttributeDefinition],
+    visibility: NodeVisibility,
+) -> Result<Schema, SchemaError> {
+    let mut objects = HashMap::new();
+    let mut node_objects = Vec::new();
+    for node_type in node_types {
+        let name = object_name(node_type);
+        let mut object = node_object(&name);
+        let mut taken: HashSet<String> = NODE_FIELDS.iter().map(|f| f.to_string()).collect();
+        for attribute in inherited_attributes(node_type, node_types, attributes) {
+            // Attributes without a usable field name are still readable through `properties`
+            let Some(field) = field_name(&attribute.name) else {
+                continue;
+            };
+            if !taken.insert(field.clone()) {
+                continue;
+            }
+            object = object.field(attribute_field(field, attribute));
+        }
+        objects.insert(node_type.id.to_string(), name);
+        node_objects.push(object);
+    }
+
+    let mut builder = Schema::build("Query", None, None)
+        .register(Scalar::new("JSON"))
+        .register(Enum::new("Direction").items(["IN", "OUT", "BOTH"]))
+        .register(
+            InputObject::new("PropertyFilter")
+                .field(InputValue::new(
+                    "attribute",
+                    TypeRef::named_nn(TypeRef::STRING),
+                ))
+                .field(InputValue::new("op", TypeRef::named(TypeRef::STRING)))
+                .field(InputValue::new("value", TypeRef::named_nn(TypeRef::STRING))),
+        )
+        .register(node_interface())
+        .register(node_object(UNTYPED_NODE))
+        .register(edge_object())
+        .register(
+            Object::new("Neighbor")
+                .field(Field::new("edge", TypeRef::named_nn("Edge"), |ctx| {
+                    FieldFuture::new(async move {
+                        let neighbor = ctx.parent_value.try_downcast_ref::<Neighbor>()?;
+                        Ok(Some(FieldValue::borrowed_any(&neighbor.edge)))
+                    })
+                }))
+                .field(Field::new("node", TypeRef::named_nn("Node"), |ctx| {
+                    FieldFuture::new(async move {
+                        let graph = ctx.data::<GraphContext>()?;
+                        let neighbor = ctx.parent_value.try_downcast_ref::<Neighbor>()?;
+                        Ok(Some(graph.node_value(neighbor.node.clone())))
+                    })
+                })),
+        )
+        .register(
+            Object::new("PageInfo")
+                .field(value_field("hasNextPage", TypeRef::BOOLEAN, true))
+                .field(value_field("endCursor", TypeRef::STRING, false)),
+        )
+        .register(
+            Object::new("NodeConnection")
+                .field(Field::new(
+                    "nodes",
+                    TypeRef::named_nn_list_nn("Node"),
+                    |ctx| {
+                        FieldFuture::new(async move {
+                            let graph = ctx.data::<GraphContext>()?;
+                            let connection = ctx.parent_value.try_downcast_ref::<Connection>()?;
+                            Ok(Some(FieldValue::list(
+                                connection.nodes.iter().map(|n| graph.node_value(n.clone())),
+                            )))
+                        })
+                    },
+                ))
+                .field(Field::new(
+                    "pageInfo",
+                    TypeRef::named_nn("PageInfo"),
+                    |ctx| {
+                        FieldFuture::new(async move {
+                            let connection = ctx.parent_value.try_downcast_ref::<Connection>()?;
+                            Ok(Some(FieldValue::value(connection.page_info.clone())))
+                        })
+                    },
+                )),
+        )
+        .register(
+            Object::new("NodeType")
+                .field(value_field("id", TypeRef::STRING, true))
+                .field(value_field("name", TypeRef::STRING, true))
+                .field(value_field("description", TypeRef::STRING, true))
+                .field(value_field("extends", TypeRef::STRING, false)),
+        )
+        .register(
+            Object::new("EdgeType")
+                .field(value_field("id", TypeRef::STRING, true))
+                .field(value_field("name", TypeRef::STRING, true))
+                .field(value_field("description", TypeRef::STRING, true)),
+        )
+        .register(query_object());
+    for object in node_objects {
+        builder = builder.register(object);
+    }
+
+    let node_types = node_types
+        .iter()
+        .map(|node_type| {
+            json_value(serde_json::json!({
+                "id": node_type.id,
+                "name": node_type.name,
+                "description": node_type.description,
+                "extends": node_type.parent_id,
+            }))
+        })
+        .collect();
+    builder
+        .data(GraphContext {
+            pool: pool.clone(),
+            graph_id: graph_id.clone(),
+            objects,
+            node_types,
+            visibility,
+        })
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BuildError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Invalid schema: {0}")]
+    Schema(#[from] SchemaError),
+}
+
+fn query_object() -> Object {
+    Object::new("Query")
+        .field(
+            Field::new("nodes", TypeRef::named_nn("NodeConnection"), |ctx| {
+                FieldFuture::new(resolve_nodes(ctx))
+            })
+            // Node type id or name
+            .argument(InputValue::new("type", TypeRef::named_nn(TypeRef::STRING)))
+            .argument(InputValue::new(
+                "filter",
+                TypeRef::named_nn_list("PropertyFilter"),
+            ))
+            .argument(InputValue::new("first", TypeRef::named(TypeRef::INT)))
+            .argument(InputValue::new("after", TypeRef::named(TypeRef::STRING))),
+        )
+        .field(
+            Field::new("node", TypeRef::named("Node"), |ctx| {
+                FieldFuture::new(async move {
+                    let graph = ctx.data::<GraphContext>()?;
+                    let id = ctx.args.try_get("id")?.i64()?;
+                    let node = Node::get_many(&graph.pool, &graph.graph_id, &[id])
+                        .await?
+                        .into_iter()
+                        .next();
+                    Ok(node.map(|node| graph.node_value(node)))
+                })
+            })
+            .argument(InputValue::new("id", TypeRef::named_nn(TypeRef::INT))),
+        )
+        .field(Field::new(
+            "nodeTypes",
+            TypeRef::named_nn_list_nn("NodeType"),
+            |ctx| {
+                FieldFuture::new(async move {
+                    let graph = ctx.data::<GraphContext>()?;
+                    Ok(Some(FieldValue::list(
+                        graph.node_types.iter().cloned().map(FieldValue::value),
+                    )))
+                })
+            },
+        ))
+        .field(Field::new(
+            "edgeTypes",
+            TypeRef::named_nn_list_nn("EdgeType"),
+            |ctx| {
+                FieldFuture::new(async move {
+                    let graph = ctx.data::<GraphContext>()?;
+                    let edge_types = EdgeType::list(&graph.pool, &graph.graph_id).await?;
+                    Ok(Some(FieldValue::list(edge_types.into_iter().map(
+                        |edge_type| {
+                            FieldValue::value(json_value(serde_json::json!({
+                                "id": edge_type.id,
+                                "name": edge_type.name,
+                                "description": edge_type.description,
+                            })))
+                        },
+                    ))))
+                })
+            },
+        ))
+}
+
+// Same validation as the node listing: the type must exist and filters are parsed against
+// its attributes. The cursor is the offset of the next node
+async fn resolve_nodes(ctx: ResolverContext<'_>) -> async_graphql::Result<Option<FieldValue<'_>>> {
+    let graph = ctx.data::<GraphContext>()?;
+    let node_type = ctx.args.try_get("type")?.string()?;
+    let node_type = resolve_node_type(&graph.pool, &graph.graph_id, node_type).await?;
+    let mut lineage = node_type.lineage(&graph.pool).await?;
+    let attributes = NodeTypeAttributeDefinition::resolve(&graph.pool, &lineage).await?;
+
+    let mut params = Vec::new();
+    if let Some(filters) = ctx.args.get("filter") {
+        for filter in filters.list()?.iter() {
+            let filter = filter.object()?;
+            let attribute = filter.try_get("attribute")?.string()?;
+            let key = match filter.get("op") {
+                Some(op) if !op.is_null() => format!("filter[{}][{}]", attribute, op.string()?),
+                _ => format!("filter[{}]", attribute),
+            };
+            params.push((key, filter.try_get("value")?.string()?.to_string()));
+        }
+    }
+    let filters = PropertyFilter::parse_all(&params, &attributes)?;
+
+    let first = match ctx.args.get("first") {
+        Some(first) => first.u64()?.clamp(1, MAX_PAGE_SIZE as u64) as u32,
+        None => DEFAULT_PAGE_SIZE,
+    };
+    let offset = match ctx.args.get("after") {
+        Some(after) => decode_cursor(after.string()?)?,
+        None => 0,
+    };
+
+    // One extra node tells whether there is a next page
+    let node_type = lineage.swap_remove(0);
+    let scope = NodeScope::new(Some(&node_type), &filters, &graph.visibility);
+    let mut nodes = Node::list_window(
+        &graph.pool,
+        &graph.graph_id,
+        &scope,
+        &NodeSort::default(),
+        offset,
+        first + 1,
+    )
+    .await?;
+    let has_next_page = nodes.len() > first as usize;
+    nodes.truncate(first as usize);
+    let end_cursor = match nodes.len() {
+        0 => None,
+        n => Some(encode_cursor(offset + n as u32)),
+    };
+
+    let page_info = json_value(serde_json::json!({
+        "hasNextPage": has_next_page,
+        "endCursor": end_cursor,
+    }));
+
+    Ok(Some(FieldValue::owned_any(Connection { nodes, page_info })))
+}
+
+fn encode_cursor(offset: u32) -> String {
+    URL_SAFE_NO_PAD.encode(offset.to_string())
+}
+
+fn decode_cursor(cursor: &str) -> async_graphql::Result<u32> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .and_then(|offset| offset.parse().ok())
+        .ok_or_else(|| async_graphql::Error::new(format!("Invalid cursor '{}'", cursor)))
+}
+
+fn node_interface() -> Interface {
+    Interface::new("Node")
+        .field(InterfaceField::new("id", TypeRef::named_nn(TypeRef::INT)))
+        .field(InterfaceField::new(
+            "nodeType",
+            TypeRef::named_nn(TypeRef::STRING),
+        ))
+        .field(InterfaceField::new("name", TypeRef::named(TypeRef::STRING)))
+        .field(InterfaceField::new("properties", TypeRef::named_nn("JSON")))
+        .field(
+            InterfaceField::new("neighbors", TypeRef::named_nn_list_nn("Neighbor"))
+                .argument(InputValue::new("edgeType", TypeRef::named(TypeRef::STRING)))
+                .argument(InputValue::new("direction", TypeRef::named("Direction")))
+                .argument(InputValue::new("first", TypeRef::named(TypeRef::INT))),
+        )
+}
+
+// An object implementing Node, before any attribute fields are added
+fn node_object(name: &str) -> Object {
+    Object::new(name)
+        .implement("Node")
+        .field(node_field("id", TypeRef::named_nn(TypeRef::INT), |node| {
+            Value::from(node.id())
+        }))
+        .field(node_field(
+            "nodeType",
+            TypeRef::named(TypeRef::STRING),
+            |node| {
+                node.node_type()
+                    .map(|id| Value::from(id.as_str()))
+                    .unwrap_or(Value::Null)
+            },
+        ))
+        .field(node_field(
+            "name",
+            TypeRef::named(TypeRef::STRING),
+            |node| match node.properties().get("name") {
+                Some(JsonValue::String(name)) => Value::from(name.as_str()),
+                _ => Value::Null,
+            },
+        ))
+        .field(node_field(
+            "properties",
+            TypeRef::named_nn("JSON"),
+            |node| json_value(serde_json::json!(node.properties())),
+        ))
+        .field(
+            Field::new("neighbors", TypeRef::named_nn_list_nn("Neighbor"), |ctx| {
+                FieldFuture::new(resolve_neighbors(ctx))
+            })
+            // Edge type id
+            .argument(InputValue::new("edgeType", TypeRef::named(TypeRef::STRING)))
+            .argument(InputValue::new("direction", TypeRef::named("Direction")))
+            .argument(InputValue::new("first", TypeRef::named(TypeRef::INT))),
+        )
+}
+
+async fn resolve_neighbors(
+    ctx: ResolverContext<'_>,
+) -> async_graphql::Result<Option<FieldValue<'_>>> {
+    let graph = ctx.data::<GraphContext>()?;
+    let node = ctx.parent_value.try_downcast_ref::<Node>()?;
+    let edge_type = match ctx.args.get("edgeType") {
+        Some(edge_type) => Some(edge_type.string()?),
+        None => None,
+    };
+    let direction = match ctx.args.get("direction") {
+        Some(direction) => direction.enum_name()?.to_string(),
+        None => "BOTH".to_string(),
+    };
+    let first = match ctx.args.get("first") {
+        Some(first) => first.u64()?.clamp(1, MAX_PAGE_SIZE as u64) as usize,
+        None => DEFAULT_PAGE_SIZE as usize,
+    };
+
+    let subgraph = Subgraph::neighbors(&graph.pool, &graph.graph_id, node.id(), true).await?;
+    let nodes: HashMap<i64, Node> = subgraph
+        .nodes
+        .unwrap_or_default()
+        .into_iter()
+        .map(|n| (n.id(), n))
+        .collect();
+
+    let neighbors: Vec<FieldValue> = subgraph
+        .edges
+        .into_iter()
+        .filter(|edge| edge_type.is_none_or(|edge_type| edge.label == edge_type))
+        .filter(|edge| match direction.as_str() {
+            "OUT" => edge.from_id == node.id(),
+            "IN" => edge.to_id == node.id(),
+            _ => true,
+        })
+        .take(first)
+        .filter_map(|edge| {
+            let other = if edge.from_id == node.id() {
+                edge.to_id
+            } else {
+                edge.from_id
+            };
+            let neighbor = nodes.get(&other)?.clone();
+            Some(FieldValue::owned_any(Neighbor {
+                edge,
+                node: neighbor,
+            }))
+        })
+        .collect();
+
+    Ok(Some(FieldValue::list(neighbors)))
+}
+
+fn edge_object() -> Object {
+    Object::new("Edge")
+        .field(edge_field("id", TypeRef::named_nn(TypeRef::INT), |edge| {
+            Value::from(edge.id)
+        }))
+        .field(edge_field(
+            "edgeType",
+            TypeRef::named_nn(TypeRef::STRING),
+            |edge| Value::from(edge.label.as_str()),
+        ))
+        .field(edge_field(
+            "fromId",
+            TypeRef::named_nn(TypeRef::INT),
+            |edge| Value::from(edge.from_id),
+        ))
+        .field(edge_field(
+            "toId",
+            TypeRef::named_nn(TypeRef::INT),
+            |edge| Value::from(edge.to_id),
+        ))
+        .field(edge_field(
+            "properties",
+            TypeRef::named_nn("JSON"),
+            |edge| json_value(serde_json::json!(edge.properties)),
+        ))
+}
+
+fn node_field(
+    name: &str,
+    ty: TypeRef,
+    value: impl Fn(&Node) -> Value + Send + Sync + 'static,
+) -> Field {
+    Field::new(name, ty, move |ctx| {
+        let node = ctx.parent_value.try_downcast_ref::<Node>().map(&value);
+        FieldFuture::new(async move { Ok(Some(FieldValue::value(node?))) })
+    })
+}
+
+fn edge_field(
+    name: &str,
+    ty: TypeRef,
+    value: impl Fn(&Edge) -> Value + Send + Sync + 'static,
+) -> Field {
+    Field::new(name, ty, move |ctx| {
+        let edge = ctx.parent_value.try_downcast_ref::<Edge>().map(&value);
+        FieldFuture::new(async move { Ok(Some(FieldValue::value(edge?))) })
+    })
+}
+
+// A field read from a parent that is a plain object value
+fn value_field(name: &'static str, ty: &str, non_null: bool) -> Field {
+    let ty = if non_null {
+        TypeRef::named_nn(ty)
+    } else {
+        TypeRef::named(ty)
+    };
+    Field::new(name, ty, move |ctx| {
+        FieldFuture::new(async move {
+            match ctx.parent_value.try_to_value()? {
+                Value::Object(object) => Ok(object.get(name).cloned().map(FieldValue::value)),
+                _ => Ok(None),
+            }
+        })
+    })
+}
+
+// Attribute field typed by the attribute's data type. Stored values of another type read as null
+fn attribute_field(field: String, attribute: &NodeTypeAttributeDefinition) -> Field {
+    let (ty, data_type) = match attribute.data_type {
+        NodeTypeAttributeDataType::Boolean => (TypeRef::BOOLEAN, attribute.data_type.clone()),
+        NodeTypeAttributeDataType::Number => (TypeRef::FLOAT, attribute.data_type.clone()),
+        // Dates are RFC3339 strings, URLs plain strings
+        _ => (TypeRef::STRING, attribute.data_type.clone()),
+    };
+    let key = attribute.name.clone();
+    Field::new(field, TypeRef::named(ty), move |ctx| {
+        let value = ctx.parent_value.try_downcast_ref::<Node>().map(|node| {
+            match (&data_type, node.properties().get(&key)) {
+                (NodeTypeAttributeDataType::Boolean, Some(JsonValue::Bool(b))) => Value::from(*b),
+                (NodeTypeAttributeDataType::Number, Some(JsonValue::Number(n))) => {
+                    n.as_f64().map(Value::from).unwrap_or(Value::Null)
+                }
+                (
+                    NodeTypeAttributeDataType::String
+                    | NodeTypeAttributeDataType::Date
+                    | NodeTypeAttributeDataType::Url,
+                    Some(JsonValue::String(s)),
+                ) => Value::from(s.as_str()),
+                _ => Value::Null,
+            }
+        });
+        FieldFuture::new(async move { Ok(Some(FieldValue::value(value?))) })
+    })
+}
+
+// Attributes of a node type including inherited ones, nearer types overriding ancestors
+fn inherited_attributes<'a>(
+    node_type: &NodeType,
+    node_types: &[NodeType],
+    attributes: &'a [NodeTypeAttributeDefinition],
+) -> Vec<&'a NodeTypeAttributeDefinition> {
+    let by_id: HashMap<&str, &NodeType> = node_types.iter().map(|t| (t.id.as_str(), t)).collect();
+    let mut lineage = vec![node_type.id.as_str()];
+    let mut parent = node_type.parent_id.as_ref().map(NodeTypeId::as_str);
+    while let Some(parent_id) = parent {
+        if lineage.len() > MAX_INHERITANCE_DEPTH || lineage.contains(&parent_id) {
+            break;
+        }
+        lineage.push(parent_id);
+        parent = by_id
+            .get(parent_id)
+            .and_then(|t| t.parent_id.as_ref().map(NodeTypeId::as_str));
+    }
+
+    let mut resolved: Vec<&NodeTypeAttributeDefinition> = Vec::new();
+    for type_id in lineage.iter().rev() {
+        for attribute in attributes.iter().filter(|a| a.type_id == *type_id) {
+            match resolved
+                .iter_mut()
+                .find(|a| a.normalized_name == attribute.normalized_name)
+            {
+                Some(existing) => *existing = attribute,
+                None => resolved.push(attribute),
+            }
+        }
+    }
+    resolved
+}
+
+// GraphQL object name for a node type: its normalized name in PascalCase, e.g. BOOK_AUTHOR
+// becomes BookAuthorNode. Falls back to the type id for names outside [A-Za-z]
+fn object_name(node_type: &NodeType) -> String {
+    let pascal: String = node_type
+        .normalized_name
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first
+                    .to_uppercase()
+                    .chain(chars.flat_map(char::to_lowercase))
+                    .collect(),
+                None => String::new(),
+            }
+        })
+        .collect();
+    if !pascal.is_empty() && pascal.chars().all(|c| c.is_ascii_alphabetic()) {
+        format!("{}Node", pascal)
+    } else {
+        format!("{}Node", node_type.id.as_str().to_uppercase())
+    }
+}
+
+// Attribute name in camelCase, e.g. "Date of birth" becomes dateOfBirth. None when the name
+// can't be a GraphQL field name
+fn field_name(attribute: &str) -> Option<String> {
+    let mut field = String::new();
+    for (i, word) in attribute.split_whitespace().enumerate() {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            if i == 0 {
+                field.extend(first.to_lowercase());
+            } else {
+                field.extend(first.to_uppercase());
+            }
+            field.extend(chars);
+        }
+    }
+    let valid = field.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && !field.starts_with("__")
+        && field.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    valid.then_some(field)
+}
+
+fn json_value(json: JsonValue) -> Value {
+    Value::from_json(json).unwrap_or(Value::Null)
+}
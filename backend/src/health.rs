@@ -0,0 +1,81 @@
+use crate::config::AppState;
+use crate::features::Features;
+use crate::rate_limit::ThrottleStats;
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+use sqlx::migrate::Migrator;
+use tracing::{error, warn};
+
+// Migrations compiled into this binary
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+#[derive(Debug, Serialize)]
+pub struct MigrationStatus {
+    // Latest migration applied to the database, None when none have run
+    pub current: Option<i64>,
+    // Latest migration this binary was built with
+    pub expected: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    pub database: bool,
+    pub migrations: MigrationStatus,
+}
+
+pub async fn live() -> StatusCode {
+    StatusCode::OK
+}
+
+// Ready once the database is reachable and has every migration this binary expects.
+// During rolling deploys this keeps a new binary out of rotation until migrations have run
+pub async fn ready(State(state): State<AppState>) -> (StatusCode, Json<ReadinessResponse>) {
+    let expected = MIGRATOR.iter().map(|m| m.version).max();
+
+    let current = sqlx::query_scalar::<_, Option<i64>>(
+        "SELECT MAX(version) FROM _sqlx_migrations WHERE success",
+    )
+    .fetch_one(&*state.pool)
+    .await;
+
+    let (database, current) = match current {
+        Ok(current) => (true, current),
+        Err(e) => {
+            error!("Readiness check failed to query migrations: {}", e);
+            (false, None)
+        }
+    };
+
+    let ready = database && current >= expected;
+    if !ready {
+        warn!(
+            "Not ready: database reachable: {}, migration {:?} of {:?}",
+            database, current, expected
+        );
+    }
+
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(ReadinessResponse {
+            ready,
+            database,
+            migrations: MigrationStatus { current, expected },
+        }),
+    )
+}
+
+// Optional features enabled on this deployment, so clients can hide what isn't available
+pub async fn features(State(state): State<AppState>) -> Json<Features> {
+    Json(state.features)
+}
+
+// Counters of the global write throttle since the process started
+pub async fn throttle(State(state): State<AppState>) -> Json<Vec<ThrottleStats>> {
+    Json(state.write_throttle.stats())
+}
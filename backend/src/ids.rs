@@ -0,0 +1,195 @@
+// Typed wrappers around the string ids of graphs and types, so one kind of id can't be
+// passed where another is expected. An id is only made by `parse`, which checks its form,
+// or by `generate`. Wherever one comes from, a request path or body or a database row, it
+// is parsed, so the text of an id is safe to format into a query. They are stored and
+// serialized as plain strings
+use crate::utils::{create_id, is_type_id, TYPE_ID_LENGTH};
+use serde::{Deserialize, Serialize};
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::postgres::{PgArgumentBuffer, PgHasArrayType, PgTypeInfo, PgValueRef, Postgres};
+use std::fmt;
+use thiserror::Error;
+
+// Text that isn't an id of the expected kind
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("'{id}' is not a valid {kind} id")]
+pub struct InvalidId {
+    pub kind: &'static str,
+    pub id: String,
+}
+
+macro_rules! typed_id {
+    ($(#[$meta:meta])* $name:ident, $prefix:literal, $kind:literal) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+        #[serde(try_from = "String", into = "String")]
+        pub struct $name(String);
+
+        impl $name {
+            pub const PREFIX: char = $prefix;
+
+            // The id `id` spells: PREFIX followed by TYPE_ID_LENGTH uppercase letters or
+            // digits
+            pub fn parse(id: &str) -> Result<Self, InvalidId> {
+                if is_type_id(Self::PREFIX, id) {
+                    return Ok(Self(id.to_string()));
+                }
+                Err(InvalidId {
+                    kind: $kind,
+                    id: id.to_string(),
+                })
+            }
+
+            // A new random id
+            pub fn generate() -> Self {
+                Self(format!("{}{}", Self::PREFIX, create_id(TYPE_ID_LENGTH)))
+            }
+
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = InvalidId;
+
+            fn try_from(id: String) -> Result<Self, InvalidId> {
+                Self::parse(&id)
+            }
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = InvalidId;
+
+            fn from_str(id: &str) -> Result<Self, InvalidId> {
+                Self::parse(id)
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl std::borrow::Borrow<str> for $name {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(&self, other: &&str) -> bool {
+                self.0 == *other
+            }
+        }
+
+        impl PartialEq<String> for $name {
+            fn eq(&self, other: &String) -> bool {
+                &self.0 == other
+            }
+        }
+
+        impl sqlx::Type<Postgres> for $name {
+            fn type_info() -> PgTypeInfo {
+                <String as sqlx::Type<Postgres>>::type_info()
+            }
+
+            fn compatible(ty: &PgTypeInfo) -> bool {
+                <String as sqlx::Type<Postgres>>::compatible(ty)
+            }
+        }
+
+        impl PgHasArrayType for $name {
+            fn array_type_info() -> PgTypeInfo {
+                <String as PgHasArrayType>::array_type_info()
+            }
+        }
+
+        impl sqlx::Encode<'_, Postgres> for $name {
+            fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+                <String as sqlx::Encode<Postgres>>::encode_by_ref(&self.0, buf)
+            }
+        }
+
+        impl<'r> sqlx::Decode<'r, Postgres> for $name {
+            fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+                let id = <&str as sqlx::Decode<Postgres>>::decode(value)?;
+                Ok(Self::parse(id)?)
+            }
+        }
+    };
+}
+
+typed_id!(
+    // Id of a graph, also the name of its AGE graph
+    GraphId, 'g', "graph"
+);
+
+typed_id!(
+    // Id of a node type. Also the AGE label of the type's nodes
+    NodeTypeId,
+    'v',
+    "node type"
+);
+
+typed_id!(
+    // Id of an edge type. Also the AGE label of the type's edges
+    EdgeTypeId,
+    'e',
+    "edge type"
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_parsed_by_kind() {
+        assert_eq!(
+            NodeTypeId::parse("vPERSON01").unwrap().as_str(),
+            "vPERSON01"
+        );
+        assert_eq!(
+            NodeTypeId::parse("ePERSON01").unwrap_err().to_string(),
+            "'ePERSON01' is not a valid node type id"
+        );
+        for id in ["", "v", "vperson01", "vPERSON0", "vPERSON012", "vPERSON-1"] {
+            assert!(NodeTypeId::parse(id).is_err(), "{:?}", id);
+        }
+    }
+
+    #[test]
+    fn generated_ids_parse() {
+        let id = GraphId::generate();
+        assert_eq!(GraphId::parse(id.as_str()), Ok(id));
+        let id = EdgeTypeId::generate();
+        assert_eq!(EdgeTypeId::parse(id.as_str()), Ok(id));
+    }
+
+    #[test]
+    fn deserializing_validates() {
+        let id: GraphId = serde_json::from_str("\"gROADS001\"").unwrap();
+        assert_eq!(serde_json::to_string(&id).unwrap(), "\"gROADS001\"");
+        assert!(serde_json::from_str::<GraphId>("\"g'); DROP\"").is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use super::{Job, JobStatus};
+use crate::auth::Auth;
+use crate::config::AppState;
+use crate::error::ApiError;
+use crate::user::User;
+use axum::{
+    body::{Body, Bytes},
+    extract::{Extension, Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::Response,
+    Json,
+};
+use futures::stream;
+use std::sync::Arc;
+use tracing::error;
+use uuid::Uuid;
+
+// Size of each read from the stored result while streaming it out
+const RESULT_CHUNK_SIZE: i64 = 64 * 1024;
+
+async fn fetch_job(state: &AppState, job_id: Uuid, user: &User) -> Result<Job, ApiError> {
+    let job = Job::from_id(&state.pool, job_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch job: {:?}", e);
+            ApiError::InternalServerError
+        })?
+        .ok_or_else(|| job_not_found(job_id))?;
+
+    // Jobs the user can't access are reported as not found, like graphs and orgs
+    let allowed = job.can_access(&state.pool, user).await.map_err(|e| {
+        error!("Failed to check job access: {:?}", e);
+        ApiError::InternalServerError
+    })?;
+    if !allowed {
+        error!("User is neither the job creator nor an org admin");
+        return Err(job_not_found(job_id));
+    }
+
+    Ok(job)
+}
+
+fn job_not_found(job_id: Uuid) -> ApiError {
+    ApiError::NotFound {
+        code: "JOB_NOT_FOUND".into(),
+        message: format!("No job with id '{}'", job_id),
+    }
+}
+
+pub async fn get_job(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(job_id): Path<Uuid>,
+) -> Result<Json<Job>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let job = fetch_job(&state, job_id, &user).await?;
+    Ok(Json(job))
+}
+
+// Parse a single `bytes=` range into inclusive start and end offsets
+fn parse_range(value: &str, size: i64) -> Option<(i64, i64)> {
+    let spec = value.strip_prefix("bytes=")?;
+    // Multiple ranges are not supported
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: i64 = suffix.parse().ok()?;
+            if suffix == 0 {
+                return None;
+            }
+            ((size - suffix).max(0), size - 1)
+        }
+        (start, "") => (start.parse().ok()?, size - 1),
+        (start, end) => (start.parse().ok()?, end.parse::<i64>().ok()?.min(size - 1)),
+    };
+    if start > end || start >= size {
+        return None;
+    }
+    Some((start, end))
+}
+
+// Stream a job's result. Supports single range requests so downloads can be resumed
+pub async fn get_job_result(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(job_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let job = fetch_job(&state, job_id, &user).await?;
+
+    if job.status != JobStatus::Completed {
+        return Err(ApiError::Conflict {
+            code: "JOB_NOT_COMPLETED".into(),
+            message: format!("Job is {}", job.status),
+            details: job.error.map(|e| vec![e]),
+        });
+    }
+
+    let (Some(oid), Some(size)) = (job.result_oid, job.result_size) else {
+        return Err(ApiError::Gone {
+            code: "JOB_RESULT_EXPIRED".into(),
+            message: "The job result is no longer available, run the export again".into(),
+        });
+    };
+    if job.is_expired() {
+        return Err(ApiError::Gone {
+            code: "JOB_RESULT_EXPIRED".into(),
+            message: "The job result has expired, run the export again".into(),
+        });
+    }
+
+    let content_type = job
+        .result_content_type
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+    let filename = job
+        .result_filename
+        .unwrap_or_else(|| format!("{}.bin", job.id));
+
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok());
+    let (status, start, end) = match range {
+        None => (StatusCode::OK, 0, size - 1),
+        Some(range) => match parse_range(range, size) {
+            Some((start, end)) => (StatusCode::PARTIAL_CONTENT, start, end),
+            None => {
+                return Response::builder()
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(header::CONTENT_RANGE, format!("bytes */{}", size))
+                    .body(Body::empty())
+                    .map_err(|e| {
+                        error!("Failed to build response: {:?}", e);
+                        ApiError::InternalServerError
+                    });
+            }
+        },
+    };
+
+    // Chunks are only read from the database as the client consumes the body
+    let pool = Arc::clone(&state.pool);
+    let chunks = stream::try_unfold(start, move |offset| {
+        let pool = Arc::clone(&pool);
+        async move {
+            if offset > end {
+                return Ok(None);
+            }
+            let length = RESULT_CHUNK_SIZE.min(end - offset + 1);
+            let chunk = Job::read_result_chunk(&pool, oid, offset, length as i32)
+                .await
+                .map_err(std::io::Error::other)?;
+            Ok::<_, std::io::Error>(Some((Bytes::from(chunk), offset + length)))
+        }
+    });
+
+    let mut response = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", filename),
+        )
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CONTENT_LENGTH, end - start + 1);
+    if status == StatusCode::PARTIAL_CONTENT {
+        response = response.header(
+            header::CONTENT_RANGE,
+            format!("bytes {}-{}/{}", start, end, size),
+        );
+    }
+
+    response.body(Body::from_stream(chunks)).map_err(|e| {
+        error!("Failed to build response: {:?}", e);
+        ApiError::InternalServerError
+    })
+}
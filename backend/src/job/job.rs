@@ -0,0 +1,241 @@
+use crate::org::{Org, Role};
+use crate::user::User;
+use serde::Serialize;
+use sqlx::postgres::types::Oid;
+use sqlx::{postgres::PgRow, FromRow, Row};
+use strum_macros::{Display, EnumString};
+use uuid::Uuid;
+
+// How long a job result can be downloaded after the job completes
+pub const RESULT_TTL_HOURS: i64 = 24;
+
+#[derive(PartialEq, Clone, Copy, Serialize, Debug, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    GraphExport,
+    DateNormalization,
+    OrgExport,
+    GraphValidation,
+}
+
+#[derive(PartialEq, Clone, Copy, Serialize, Debug, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Job {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub graph_id: Option<String>,
+    pub kind: JobKind,
+    pub status: JobStatus,
+    pub created_by: Uuid,
+    #[serde(with = "crate::utils::rfc3339")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(with = "crate::utils::rfc3339::option")]
+    pub completed_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub error: Option<String>,
+    #[serde(skip)]
+    pub result_oid: Option<Oid>,
+    pub result_content_type: Option<String>,
+    pub result_filename: Option<String>,
+    pub result_size: Option<i64>,
+    #[serde(with = "crate::utils::rfc3339::option")]
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl<'r> FromRow<'r, PgRow> for Job {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        let kind: String = row.try_get("kind")?;
+        let status: String = row.try_get("status")?;
+
+        Ok(Self {
+            id: row.try_get("id")?,
+            org_id: row.try_get("org_id")?,
+            graph_id: row.try_get("graph_id")?,
+            kind: kind.parse().map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+            status: status
+                .parse()
+                .map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+            created_by: row.try_get("created_by")?,
+            created_at: row.try_get("created_at")?,
+            completed_at: row.try_get("completed_at")?,
+            error: row.try_get("error")?,
+            result_oid: row.try_get("result_oid")?,
+            result_content_type: row.try_get("result_content_type")?,
+            result_filename: row.try_get("result_filename")?,
+            result_size: row.try_get("result_size")?,
+            expires_at: row.try_get("expires_at")?,
+        })
+    }
+}
+
+impl Job {
+    pub fn new(org_id: Uuid, graph_id: Option<String>, kind: JobKind, created_by: Uuid) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            org_id,
+            graph_id,
+            kind,
+            status: JobStatus::Pending,
+            created_by,
+            created_at: chrono::Utc::now(),
+            completed_at: None,
+            error: None,
+            result_oid: None,
+            result_content_type: None,
+            result_filename: None,
+            result_size: None,
+            expires_at: None,
+        }
+    }
+
+    pub async fn persist(&self, pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+        let query = "INSERT INTO app_data.job (id, org_id, graph_id, kind, status, created_by, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7)";
+        sqlx::query(query)
+            .bind(self.id)
+            .bind(self.org_id)
+            .bind(&self.graph_id)
+            .bind(self.kind.to_string())
+            .bind(self.status.to_string())
+            .bind(self.created_by)
+            .bind(self.created_at)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn from_id(pool: &sqlx::PgPool, id: Uuid) -> Result<Option<Self>, sqlx::Error> {
+        let query = "SELECT * FROM app_data.job WHERE id = $1";
+        sqlx::query_as::<_, Job>(query)
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    // Most recent job of the org with the given kind that completed after `since`
+    pub async fn latest_completed(
+        pool: &sqlx::PgPool,
+        org_id: Uuid,
+        kind: JobKind,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let query = "SELECT * FROM app_data.job WHERE org_id = $1 AND kind = $2 AND status = $3 AND completed_at >= $4 ORDER BY completed_at DESC LIMIT 1";
+        sqlx::query_as::<_, Job>(query)
+            .bind(org_id)
+            .bind(kind.to_string())
+            .bind(JobStatus::Completed.to_string())
+            .bind(since)
+            .fetch_optional(pool)
+            .await
+    }
+
+    pub async fn mark_running(&mut self, pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+        let query = "UPDATE app_data.job SET status = $1 WHERE id = $2";
+        sqlx::query(query)
+            .bind(JobStatus::Running.to_string())
+            .bind(self.id)
+            .execute(pool)
+            .await?;
+        self.status = JobStatus::Running;
+        Ok(())
+    }
+
+    // Store the result as a large object and mark the job completed
+    pub async fn complete(
+        &mut self,
+        pool: &sqlx::PgPool,
+        result: &[u8],
+        content_type: &str,
+        filename: &str,
+    ) -> Result<(), sqlx::Error> {
+        let now = chrono::Utc::now();
+        let expires_at = now + chrono::Duration::hours(RESULT_TTL_HOURS);
+
+        let mut transaction = pool.begin().await?;
+        let oid: Oid = sqlx::query_scalar("SELECT lo_from_bytea(0, $1)")
+            .bind(result)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        let query = "UPDATE app_data.job SET status = $1, completed_at = $2, result_oid = $3, result_content_type = $4, result_filename = $5, result_size = $6, expires_at = $7 WHERE id = $8";
+        sqlx::query(query)
+            .bind(JobStatus::Completed.to_string())
+            .bind(now)
+            .bind(oid)
+            .bind(content_type)
+            .bind(filename)
+            .bind(result.len() as i64)
+            .bind(expires_at)
+            .bind(self.id)
+            .execute(&mut *transaction)
+            .await?;
+        transaction.commit().await?;
+
+        self.status = JobStatus::Completed;
+        self.completed_at = Some(now);
+        self.result_oid = Some(oid);
+        self.result_content_type = Some(content_type.to_string());
+        self.result_filename = Some(filename.to_string());
+        self.result_size = Some(result.len() as i64);
+        self.expires_at = Some(expires_at);
+        Ok(())
+    }
+
+    pub async fn fail(&mut self, pool: &sqlx::PgPool, error: &str) -> Result<(), sqlx::Error> {
+        let now = chrono::Utc::now();
+        let query =
+            "UPDATE app_data.job SET status = $1, completed_at = $2, error = $3 WHERE id = $4";
+        sqlx::query(query)
+            .bind(JobStatus::Failed.to_string())
+            .bind(now)
+            .bind(error)
+            .bind(self.id)
+            .execute(pool)
+            .await?;
+        self.status = JobStatus::Failed;
+        self.completed_at = Some(now);
+        self.error = Some(error.to_string());
+        Ok(())
+    }
+
+    // Results can be fetched by the job creator and by admins of the job's org
+    pub async fn can_access(&self, pool: &sqlx::PgPool, user: &User) -> Result<bool, sqlx::Error> {
+        if self.created_by == user.id {
+            return Ok(true);
+        }
+        let org = Org::from_id(pool, &self.org_id).await?;
+        let member = org.get_member(pool, user.id).await?;
+        Ok(member.is_some_and(|m| m.role == Role::Admin))
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= chrono::Utc::now())
+    }
+
+    // Read part of the stored result. Large objects must be read inside a transaction
+    pub async fn read_result_chunk(
+        pool: &sqlx::PgPool,
+        oid: Oid,
+        offset: i64,
+        length: i32,
+    ) -> Result<Vec<u8>, sqlx::Error> {
+        let mut transaction = pool.begin().await?;
+        let chunk: Vec<u8> = sqlx::query_scalar("SELECT lo_get($1, $2, $3)")
+            .bind(oid)
+            .bind(offset)
+            .bind(length)
+            .fetch_one(&mut *transaction)
+            .await?;
+        transaction.commit().await?;
+        Ok(chunk)
+    }
+}
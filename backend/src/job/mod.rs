@@ -0,0 +1,5 @@
+mod endpoints;
+mod job;
+
+pub use endpoints::*;
+pub use job::*;
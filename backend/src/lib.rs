@@ -0,0 +1,448 @@
+mod admin;
+mod ag;
+mod attribute_order;
+pub mod auth;
+mod catalog;
+pub mod config;
+mod csv;
+pub mod cypher;
+pub mod db;
+mod edge;
+mod error;
+pub mod features;
+pub mod graph;
+#[cfg(feature = "graphql")]
+mod graphql;
+mod health;
+mod ids;
+mod job;
+pub mod membership;
+mod node;
+mod normalized_names;
+pub mod notification;
+mod org;
+pub mod rate_limit;
+mod selection;
+mod share;
+mod user;
+mod utils;
+mod validation;
+pub mod webhook;
+
+use crate::config::AppState;
+use crate::features::Feature;
+use crate::rate_limit::WriteClass;
+
+use axum::{
+    body::Body,
+    http::{HeaderName, Method, Request},
+    middleware,
+    routing::{delete, get, patch, post, put},
+    Router,
+};
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tower_http::timeout::TimeoutLayer;
+use tower_http::trace::{self, TraceLayer};
+use tracing::Level;
+
+// Give nodes created before public ids existed one. Run at startup, after the migrations
+pub async fn backfill_node_public_ids(pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+    for graph_id in graph::GraphInfo::all_ids(pool).await? {
+        let updated = node::Node::backfill_public_ids(pool, &graph_id).await?;
+        if updated > 0 {
+            tracing::info!(
+                "Assigned public ids to {} node(s) of graph {}",
+                updated,
+                graph_id
+            );
+        }
+    }
+    Ok(())
+}
+
+// Bring stored normalized names in line with `utils::normalize`, logging the ones that
+// changed and the ones that would collide. Run at startup, after the migrations
+pub async fn sync_normalized_names(pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+    for mismatch in normalized_names::sync_normalized_names(pool).await? {
+        if mismatch.conflict {
+            tracing::warn!(
+                "{} {} named '{}' normalizes to '{}', which another name already uses. Kept '{}', rename one of them",
+                mismatch.table,
+                mismatch.id,
+                mismatch.name,
+                mismatch.expected,
+                mismatch.stored
+            );
+        } else {
+            tracing::info!(
+                "Renormalized {} {} named '{}' from '{}' to '{}'",
+                mismatch.table,
+                mismatch.id,
+                mismatch.name,
+                mismatch.stored,
+                mismatch.expected
+            );
+        }
+    }
+    Ok(())
+}
+
+// Authenticated routes of features that can be turned off
+fn optional_routes(state: &AppState) -> Router<AppState> {
+    let mut router = Router::new();
+    if state.features.is_enabled(Feature::Webhooks) {
+        let webhook_writes = Router::new()
+            .route("/graphs/:graph_id/webhooks", post(webhook::create_webhook))
+            .route(
+                "/graphs/:graph_id/webhooks/:webhook_id",
+                delete(webhook::delete_webhook),
+            );
+        router = router
+            .route("/graphs/:graph_id/webhooks", get(webhook::get_webhooks))
+            .merge(throttled(webhook_writes, state, WriteClass::Metadata));
+    }
+    // Read only, despite being a POST
+    #[cfg(feature = "graphql")]
+    {
+        router = router.route("/graphs/:graph_id/graphql", post(graphql::graphql));
+    }
+    router
+}
+
+// Count the requests of a route group against the global rate of its class. Layered
+// inside auth, so only signed in users' requests use up the rate
+fn throttled(router: Router<AppState>, state: &AppState, class: WriteClass) -> Router<AppState> {
+    router.route_layer(middleware::from_fn_with_state(
+        (state.clone(), class),
+        rate_limit::throttle_middleware,
+    ))
+}
+
+// Writes to orgs, graphs, node and edge types, settings and everything else that isn't
+// graph data
+fn metadata_write_routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin/catalog/repair", post(admin::repair_catalog))
+        .route("/orgs", post(org::create_org))
+        .route("/orgs/:id", delete(org::delete_org))
+        .route("/orgs/:id/members", post(org::add_org_member))
+        .route("/orgs/:id/members", patch(org::update_org_members))
+        .route("/orgs/:id/members/bulk", post(org::bulk_add_org_members))
+        .route("/orgs/:id/attributes", post(org::create_org_attribute))
+        .route(
+            "/orgs/:id/attributes/:attribute_id",
+            put(org::update_org_attribute),
+        )
+        .route("/orgs/:id/graphs", post(graph::create_graph))
+        .route("/graphs/:graph_id/pin", post(graph::pin_graph))
+        .route("/graphs/:graph_id/pin", delete(graph::unpin_graph))
+        .route("/graphs/:graph_id/lock", post(graph::lock_graph))
+        .route(
+            "/graphs/:graph_id/protection",
+            put(graph::update_graph_protection),
+        )
+        .route("/graphs/:graph_id/lock", delete(graph::unlock_graph))
+        .route(
+            "/graphs/:graph_id/settings",
+            put(graph::update_graph_settings),
+        )
+        .route(
+            "/graphs/:graph_id/dates/normalize/jobs",
+            post(graph::start_date_normalization_job),
+        )
+        .route(
+            "/graphs/:graph_id/validate",
+            post(graph::start_validation_job),
+        )
+        .route("/auth/device/approve", post(auth::approve_device))
+        .route("/auth/logout", post(auth::logout))
+        .route("/auth/refresh", post(auth::refresh))
+        .route(
+            "/me/notifications/:notification_id/read",
+            post(notification::mark_notification_read),
+        )
+        // Node endpoints
+        .route(
+            "/graphs/:graph_id/meta/node_types",
+            post(node::create_node_type),
+        )
+        .route(
+            "/graphs/:graph_id/meta/node_types/:node_type_id/attributes",
+            put(node::replace_node_type_attributes),
+        )
+        .route(
+            "/graphs/:graph_id/meta/node_types/:node_type_id/attributes/order",
+            put(node::reorder_node_type_attributes),
+        )
+        .route(
+            "/graphs/:graph_id/meta/node_types/:node_type_id",
+            delete(node::delete_node_type),
+        )
+        // Edge endpoints
+        .route(
+            "/graphs/:graph_id/meta/edge_types",
+            post(edge::create_edge_type),
+        )
+        .route(
+            "/graphs/:graph_id/meta/edge_types/:edge_type_id/attributes/order",
+            put(edge::reorder_edge_type_attributes),
+        )
+        .route(
+            "/graphs/:graph_id/meta/edge_types/:edge_type_id",
+            delete(edge::delete_edge_type),
+        )
+        // Selection endpoints
+        .route(
+            "/graphs/:graph_id/selections",
+            post(selection::create_selection),
+        )
+        .route(
+            "/graphs/:graph_id/selections/:selection_id",
+            patch(selection::update_selection),
+        )
+        .route(
+            "/graphs/:graph_id/selections/:selection_id",
+            delete(selection::delete_selection),
+        )
+        .route(
+            "/graphs/:graph_id/selections/:selection_id/nodes",
+            post(selection::add_selection_nodes),
+        )
+        .route(
+            "/graphs/:graph_id/selections/:selection_id/nodes/:node_id",
+            delete(selection::remove_selection_node),
+        )
+        // Share endpoints
+        .route("/graphs/:graph_id/shares", post(share::create_share))
+        .route(
+            "/graphs/:graph_id/shares/:share_id",
+            delete(share::revoke_share),
+        )
+}
+
+// Writes that create or change nodes and edges
+fn data_write_routes() -> Router<AppState> {
+    Router::new()
+        .route("/graphs/:graph_id/nodes", post(node::create_node))
+        .route(
+            "/graphs/:graph_id/nodes/import.csv",
+            post(node::import_nodes_csv),
+        )
+        .route(
+            "/graphs/:graph_id/nodes/:node_type/:name/duplicate",
+            post(node::duplicate_node),
+        )
+        .route(
+            "/graphs/:graph_id/nodes/:node_type/:name/history/:version/restore",
+            post(node::restore_node_version),
+        )
+        .route(
+            "/graphs/:graph_id/nodes/:node_id/protection",
+            put(node::update_node_protection),
+        )
+        .route("/graphs/:graph_id/edges", post(edge::create_edge))
+}
+
+// Exports read a whole graph or org, whatever their method
+fn export_routes() -> Router<AppState> {
+    Router::new()
+        .route("/orgs/:id/export", post(org::start_org_export_job))
+        .route("/graphs/:graph_id/export", get(graph::export_graph))
+        .route(
+            "/graphs/:graph_id/export/jobs",
+            post(graph::start_export_job),
+        )
+}
+
+// Build the application router. Shared by the binary and tests so both exercise the same routes
+pub fn build_app(state: AppState) -> Router {
+    let cors = CorsLayer::new()
+        .allow_origin(AllowOrigin::list(state.cors_origins.clone()))
+        .allow_methods(vec![
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+        ])
+        .allow_headers(Any)
+        // Read by the SPA to pass a write's revision to the reads after it
+        .expose_headers([HeaderName::from_static(graph::GRAPH_REVISION_HEADER)]);
+
+    // Create router with all endpoints
+    Router::new()
+        .route("/profile", get(user::profile))
+        .route("/admin/config", get(admin::get_config))
+        .route("/admin/catalog", get(admin::get_catalog_report))
+        .route("/orgs", get(org::get_orgs))
+        .route("/orgs/:id/members", get(org::get_org_members))
+        .route("/orgs/:id/attributes", get(org::get_org_attributes))
+        .route("/orgs/:id/permissions", get(org::get_org_permissions))
+        .route("/orgs/:id/overview", get(org::get_org_overview))
+        .route("/orgs/:id/schema_report", get(org::get_schema_report))
+        .route("/orgs/:id/graphs", get(graph::get_graphs))
+        .route("/graphs/:graph_id", get(graph::get_graph))
+        .route(
+            "/graphs/:graph_id/permissions",
+            get(graph::get_graph_permissions),
+        )
+        .route(
+            "/graphs/:graph_id/property-keys",
+            get(graph::get_property_keys),
+        )
+        .route(
+            "/graphs/:graph_id/stats/edges-by-type-pair",
+            get(graph::get_edges_by_type_pair),
+        )
+        .route("/graphs/:graph_id/labels", get(graph::get_graph_labels))
+        .route("/graphs/:graph_id/ping", get(graph::ping_graph))
+        .route(
+            "/graphs/:graph_id/validation_report",
+            get(graph::get_validation_report),
+        )
+        .route("/jobs/:job_id", get(job::get_job))
+        .route("/jobs/:job_id/result", get(job::get_job_result))
+        .route("/me/pinned_graphs", get(graph::get_pinned_graphs))
+        .route("/me/notifications", get(notification::get_notifications))
+        // Node endpoints
+        .route(
+            "/graphs/:graph_id/meta/node_types",
+            get(node::get_node_types),
+        )
+        .route(
+            "/graphs/:graph_id/meta/node_types/:node_type_id",
+            get(node::get_node_type),
+        )
+        .route("/graphs/:graph_id/nodes", get(node::get_nodes))
+        // Edge endpoints
+        .route("/graphs/:graph_id/edges", get(edge::get_edges))
+        .route(
+            "/graphs/:graph_id/nodes/orphans",
+            get(node::get_orphan_nodes),
+        )
+        .route(
+            "/graphs/:graph_id/nodes/batch-get",
+            post(node::batch_get_nodes),
+        )
+        .route(
+            "/graphs/:graph_id/nodes/:node_type/:name/edge-summary",
+            get(node::get_node_edge_summary),
+        )
+        .route(
+            "/graphs/:graph_id/nodes/:node_type/:name/history",
+            get(node::get_node_history),
+        )
+        .route(
+            "/graphs/:graph_id/nodes/:node_id/full",
+            get(node::get_node_detail),
+        )
+        .route(
+            "/graphs/:graph_id/nodes/:node_id/neighbors",
+            get(node::get_neighbors),
+        )
+        .route(
+            "/graphs/:graph_id/meta/edge_types",
+            get(edge::get_edge_types),
+        )
+        .route(
+            "/graphs/:graph_id/meta/edge_types/:edge_type_id",
+            get(edge::get_edge_type),
+        )
+        .route(
+            "/graphs/:graph_id/meta/edge_types/:edge_type_id/attributes",
+            get(edge::get_edge_type_attributes),
+        )
+        .route(
+            "/graphs/:graph_id/edge-types/applicable",
+            get(edge::get_applicable_edge_types),
+        )
+        // Selection endpoints
+        .route(
+            "/graphs/:graph_id/selections",
+            get(selection::get_selections),
+        )
+        .route(
+            "/graphs/:graph_id/selections/:selection_id",
+            get(selection::get_selection),
+        )
+        // Share endpoints
+        .route("/graphs/:graph_id/shares", get(share::get_shares))
+        .merge(throttled(
+            metadata_write_routes(),
+            &state,
+            WriteClass::Metadata,
+        ))
+        .merge(throttled(data_write_routes(), &state, WriteClass::Data))
+        .merge(throttled(export_routes(), &state, WriteClass::Exports))
+        .merge(optional_routes(&state))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::auth_middleware,
+        ))
+        .route("/auth/url", post(auth::authorize))
+        .route("/oidc/callback", post(auth::callback))
+        .route("/auth/device", post(auth::device_authorize))
+        .route("/auth/device/token", post(auth::device_token))
+        .route("/shares/:token", get(share::get_shared_subgraph))
+        .route("/health/live", get(health::live))
+        .route("/health/ready", get(health::ready))
+        .route("/features", get(health::features))
+        .route("/health/throttle", get(health::throttle))
+        .with_state(state)
+        .layer(TimeoutLayer::new(Duration::from_secs(10)))
+        .layer(cors)
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(|request: &Request<Body>| {
+                    // Same fields as DefaultMakeSpan, plus the user id filled in by auth_middleware
+                    // and a request id, taken from x-request-id when a proxy set one
+                    let request_id = request
+                        .headers()
+                        .get("x-request-id")
+                        .and_then(|value| value.to_str().ok())
+                        .map(str::to_string)
+                        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+                    tracing::info_span!(
+                        "request",
+                        method = %request.method(),
+                        uri = %request.uri(),
+                        version = ?request.version(),
+                        request_id = %request_id,
+                        user_id = tracing::field::Empty,
+                    )
+                })
+                .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rate_limit::WriteThrottle;
+    use axum::http::StatusCode;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn anonymous_writes_do_not_use_up_the_throttle() {
+        let pool = sqlx::PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        let mut state = AppState::for_tests(pool);
+        let rates = HashMap::from([(WriteClass::Metadata, 1)]);
+        state.write_throttle = Arc::new(WriteThrottle::new(&rates, Duration::ZERO));
+        let app = build_app(state.clone());
+
+        for _ in 0..3 {
+            let request = Request::post("/orgs")
+                .header("authorization", "Bearer not-a-session")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"name": "Acme", "description": ""}"#))
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+        let stats = state.write_throttle.stats();
+        assert_eq!(stats[0].consumed + stats[0].rejected, 0);
+    }
+}
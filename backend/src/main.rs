@@ -1,33 +1,20 @@
-mod ag;
-mod auth;
-mod config;
-mod edge;
-mod error;
-mod graph;
-//mod label;
-mod node;
-mod org;
-mod user;
-mod utils;
+use backend::auth::{self, OidcProviderApi};
+use backend::config::{AppState, Config};
+use backend::db::RetryPolicy;
+use backend::features::Feature;
+use backend::graph::EdgeStatsCache;
+use backend::membership::MembershipCache;
+use backend::notification::{LogChannel, Notifier};
+use backend::rate_limit::{WriteBudget, WriteThrottle};
+use backend::webhook::WebhookDispatcher;
+use backend::{backfill_node_public_ids, build_app, sync_normalized_names};
 
-use crate::config::{AppState, Config};
-
-use axum::{
-    http::{HeaderValue, Method},
-    middleware,
-    routing::{get, post},
-    Router,
-};
 use dotenvy::dotenv;
 use maplit::hashmap;
 use sqlx::{postgres::PgPoolOptions, Executor};
-use std::env;
 use std::sync::Arc;
-use std::time::Duration;
-use tower_http::cors::{Any, CorsLayer};
-use tower_http::timeout::TimeoutLayer;
-use tower_http::trace::{self, TraceLayer};
-use tracing::{info, Level};
+use tracing::{info, warn};
+use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
 async fn main() {
@@ -35,14 +22,23 @@ async fn main() {
     tracing_subscriber::fmt()
         .with_ansi(false)
         .with_level(true)
-        .with_env_filter("info,sqlx=warn")
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info,sqlx=warn")),
+        )
         .init();
 
     // Load .env file if it exists
     dotenv().ok();
 
     // Initialize configuration
-    let config = Config::from_env().expect("Failed to load configuration from environment");
+    let config = Config::from_env().unwrap_or_else(|errors| {
+        eprintln!("{}", errors);
+        std::process::exit(1);
+    });
+    if config.log_cypher_values {
+        warn!("LOG_CYPHER_VALUES is set, cypher statements are logged with their values");
+    }
+    backend::cypher::log_values(config.log_cypher_values);
 
     // Create the connection pool with configuration
     // Requires the AGE extension to be installed in the database
@@ -68,80 +64,72 @@ async fn main() {
         .await
         .expect("Failed to run migrations");
 
-    // Initialize OIDC providers (Only Google for now)
+    backfill_node_public_ids(pool.as_ref())
+        .await
+        .expect("Failed to backfill node public ids");
+
+    sync_normalized_names(pool.as_ref())
+        .await
+        .expect("Failed to check normalized names");
+
+    // Initialize OIDC providers. Google is always configured, Microsoft when its client is set
     let google_oidc_config = auth::OidcConfig::from_env(auth::AuthProvider::Google)
         .expect("Failed to load OIDC configuration from environment");
     let google_oidc_provider = auth::OidcProvider::new(google_oidc_config).await.unwrap();
+    let mut oidc_providers = hashmap! {
+        "google".to_string() => Arc::new(google_oidc_provider) as Arc<dyn OidcProviderApi>,
+    };
+    if config.microsoft_client_id.is_some() {
+        let microsoft_oidc_config = auth::OidcConfig::from_env(auth::AuthProvider::Microsoft)
+            .expect("Failed to load Microsoft OIDC configuration from environment");
+        let microsoft_oidc_provider = auth::OidcProvider::new(microsoft_oidc_config)
+            .await
+            .unwrap();
+        oidc_providers.insert(
+            "microsoft".to_string(),
+            Arc::new(microsoft_oidc_provider) as Arc<dyn OidcProviderApi>,
+        );
+    }
 
     // Initialize AppState
     let state = AppState {
         pool: Arc::clone(&pool),
-        oidc_providers: hashmap! {
-            "google".to_string() => google_oidc_provider,
+        oidc_providers,
+        write_budget: Arc::new(WriteBudget::new(
+            config.write_budget_limit,
+            config.write_budget_window,
+        )),
+        memberships: Arc::new(MembershipCache::new(config.membership_cache_ttl)),
+        edge_stats: Arc::new(EdgeStatsCache::new(config.stats_cache_ttl)),
+        write_throttle: Arc::new(WriteThrottle::new(
+            &config.write_throttle_rates,
+            config.write_throttle_max_wait,
+        )),
+        notifier: Arc::new(Notifier::new(vec![Arc::new(LogChannel)])),
+        db_retry: RetryPolicy {
+            max_attempts: config.db_retry_attempts.max(1),
+            ..RetryPolicy::default()
         },
+        webhooks: Arc::new(WebhookDispatcher::new(
+            config.features.is_enabled(Feature::Webhooks),
+        )),
+        device_verification_url: config.device_verification_url.clone(),
+        features: config.features.clone(),
+        org_max_graphs: config.org_max_graphs,
+        max_property_value_length: config.max_property_value_length,
+        cors_origins: config
+            .cors_origins
+            .iter()
+            .map(|origin| origin.parse().expect("CORS origins are checked by Config"))
+            .collect(),
+        redacted_config: Arc::new(config.redacted()),
     };
 
-    let cors = CorsLayer::new()
-        .allow_origin("http://localhost:3000".parse::<HeaderValue>().unwrap())
-        .allow_methods(vec![Method::GET, Method::POST, Method::PUT, Method::DELETE])
-        .allow_headers(Any);
+    let app = build_app(state);
 
-    // Create router with all endpoints
-    let app = Router::new()
-        .route("/profile", get(user::profile))
-        .route("/orgs", post(org::create_org))
-        .route("/orgs", get(org::get_orgs))
-        .route("/orgs/:id/members", post(org::add_org_member))
-        .route("/orgs/:id/members", get(org::get_org_members))
-        .route("/orgs/:id/graphs", post(graph::create_graph))
-        .route("/orgs/:id/graphs", get(graph::get_graphs))
-        .route("/graphs/:graph_id", get(graph::get_graph))
-        // Node endpoints
-        .route(
-            "/graphs/:graph_id/meta/node_types",
-            post(node::create_node_type),
-        )
-        .route(
-            "/graphs/:graph_id/meta/node_types",
-            get(node::get_node_types),
-        )
-        .route(
-            "/graphs/:graph_id/meta/node_types/:node_type_id",
-            get(node::get_node_type),
-        )
-        .route("/graphs/:graph_id/nodes", post(node::create_node))
-        .route("/graphs/:graph_id/nodes", get(node::get_nodes))
-        // Edge endpoints
-        .route(
-            "/graphs/:graph_id/meta/edge_types",
-            post(edge::create_edge_type),
-        )
-        .route(
-            "/graphs/:graph_id/meta/edge_types",
-            get(edge::get_edge_types),
-        )
-        .route(
-            "/graphs/:graph_id/meta/edge_types/:edge_type_id",
-            get(edge::get_edge_type),
-        )
-        .layer(middleware::from_fn_with_state(
-            state.clone(),
-            auth::auth_middleware,
-        ))
-        .route("/auth/url", post(auth::authorize))
-        .route("/oidc/callback", post(auth::callback))
-        .with_state(state)
-        .layer(TimeoutLayer::new(Duration::from_secs(10)))
-        .layer(cors)
-        .layer(
-            TraceLayer::new_for_http()
-                .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
-                .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
-        );
-
-    let bind_address = env::var("BIND_ADDRESS").unwrap_or_else(|_| "127.0.0.1:3210".to_string());
-
-    let listener = tokio::net::TcpListener::bind(bind_address).await.unwrap();
+    let listener = tokio::net::TcpListener::bind(config.bind_address)
+        .await
+        .unwrap();
     info!(
         "axum: starting service on {}",
         listener.local_addr().unwrap()
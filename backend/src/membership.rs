@@ -0,0 +1,272 @@
+use crate::graph::{GraphInfo, GraphRole};
+use crate::ids::GraphId;
+use crate::org::OrgMember;
+use crate::user::User;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+// A user's org memberships and graph roles, as read by the authorization helpers
+#[derive(Debug)]
+pub struct Memberships {
+    orgs: HashMap<Uuid, OrgMember>,
+    graphs: HashMap<String, GraphRole>,
+}
+
+impl Memberships {
+    async fn load(pool: &sqlx::PgPool, user: &User) -> Result<Self, sqlx::Error> {
+        let orgs = user
+            .get_org_memberships(pool)
+            .await?
+            .into_iter()
+            .map(|member| (member.org_id, member))
+            .collect();
+
+        let graphs = GraphInfo::get_memberships_of_user(pool, user.id)
+            .await?
+            .into_iter()
+            .map(|member| (member.graph_id, member.role))
+            .collect();
+
+        Ok(Self { orgs, graphs })
+    }
+
+    pub fn org_member(&self, org_id: &Uuid) -> Option<&OrgMember> {
+        self.orgs.get(org_id)
+    }
+
+    pub fn graph_role(&self, graph_id: &GraphId) -> Option<&GraphRole> {
+        self.graphs.get(graph_id.as_str())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    by_user: HashMap<Uuid, (Instant, Arc<Memberships>)>,
+    // Bumped by every invalidation, so a load that raced one is not cached
+    generation: u64,
+    last_pruned: Option<Instant>,
+}
+
+// Memberships per user, kept for a short TTL so authorizing a request doesn't query them
+// every time. Endpoints that change memberships or roles invalidate the users they touch,
+// which takes effect on the next request. Changes made by another instance of the service,
+// or directly in the database, are seen once the entry expires, so a revoked role can
+// still be used for up to the TTL there. A TTL of zero disables the cache.
+// Memberships of deleted orgs and graphs need no invalidation, access checks look the org
+// or graph up first.
+#[derive(Debug)]
+pub struct MembershipCache {
+    ttl: Duration,
+    entries: Mutex<Entries>,
+}
+
+impl MembershipCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    pub async fn get(
+        &self,
+        pool: &sqlx::PgPool,
+        user: &User,
+    ) -> Result<Arc<Memberships>, sqlx::Error> {
+        self.get_or_load(user.id, Instant::now, || Memberships::load(pool, user))
+            .await
+    }
+
+    // The cached memberships of the user, or the ones `load` returns. Time is read from
+    // `clock` so tests can move it
+    async fn get_or_load<F, Fut>(
+        &self,
+        user_id: Uuid,
+        clock: impl Fn() -> Instant,
+        load: F,
+    ) -> Result<Arc<Memberships>, sqlx::Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Memberships, sqlx::Error>>,
+    {
+        if self.ttl.is_zero() {
+            return Ok(Arc::new(load().await?));
+        }
+
+        let generation = {
+            let entries = self.entries.lock().unwrap();
+            if let Some((loaded_at, memberships)) = entries.by_user.get(&user_id) {
+                if clock().duration_since(*loaded_at) < self.ttl {
+                    return Ok(Arc::clone(memberships));
+                }
+            }
+            entries.generation
+        };
+
+        // Entries age from the start of the load, so none is used past the TTL
+        let loaded_at = clock();
+        let memberships = Arc::new(load().await?);
+
+        let now = clock();
+        let mut entries = self.entries.lock().unwrap();
+        // Drop expired entries at most once per TTL, so users who stopped making requests
+        // don't stay in memory
+        if entries
+            .last_pruned
+            .is_none_or(|at| now.duration_since(at) >= self.ttl)
+        {
+            let ttl = self.ttl;
+            entries
+                .by_user
+                .retain(|_, (loaded_at, _)| now.duration_since(*loaded_at) < ttl);
+            entries.last_pruned = Some(now);
+        }
+        if entries.generation == generation {
+            entries
+                .by_user
+                .insert(user_id, (loaded_at, Arc::clone(&memberships)));
+        }
+        Ok(memberships)
+    }
+
+    // Forget the memberships of the users, after their memberships or roles changed
+    pub fn invalidate(&self, user_ids: impl IntoIterator<Item = Uuid>) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.generation += 1;
+        for user_id in user_ids {
+            entries.by_user.remove(&user_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::org::Role;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    const TTL: Duration = Duration::from_secs(30);
+
+    // A clock that only moves when told to
+    struct TestClock {
+        start: Instant,
+        elapsed_ms: AtomicU64,
+    }
+
+    impl TestClock {
+        fn new() -> Self {
+            Self {
+                start: Instant::now(),
+                elapsed_ms: AtomicU64::new(0),
+            }
+        }
+
+        fn now(&self) -> Instant {
+            self.start + Duration::from_millis(self.elapsed_ms.load(Ordering::SeqCst))
+        }
+
+        fn advance(&self, by: Duration) {
+            self.elapsed_ms
+                .fetch_add(by.as_millis() as u64, Ordering::SeqCst);
+        }
+    }
+
+    fn memberships(user_id: Uuid, org_id: Uuid, role: Option<Role>) -> Memberships {
+        Memberships {
+            orgs: role
+                .map(|role| (org_id, OrgMember::new(org_id, user_id, role)))
+                .into_iter()
+                .collect(),
+            graphs: HashMap::new(),
+        }
+    }
+
+    async fn org_role(
+        cache: &MembershipCache,
+        clock: &TestClock,
+        user_id: Uuid,
+        org_id: Uuid,
+        stored: Option<Role>,
+    ) -> Option<Role> {
+        cache
+            .get_or_load(
+                user_id,
+                || clock.now(),
+                || async { Ok(memberships(user_id, org_id, stored)) },
+            )
+            .await
+            .unwrap()
+            .org_member(&org_id)
+            .map(|member| member.role.clone())
+    }
+
+    #[tokio::test]
+    async fn revocation_takes_effect_after_invalidation() {
+        let (cache, clock) = (MembershipCache::new(TTL), TestClock::new());
+        let (user_id, org_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        let role = org_role(&cache, &clock, user_id, org_id, Some(Role::Admin)).await;
+        assert_eq!(role, Some(Role::Admin));
+
+        // Revoked in the database, the cached role is still used within the TTL
+        clock.advance(Duration::from_secs(1));
+        let role = org_role(&cache, &clock, user_id, org_id, None).await;
+        assert_eq!(role, Some(Role::Admin));
+
+        cache.invalidate([user_id]);
+        let role = org_role(&cache, &clock, user_id, org_id, None).await;
+        assert_eq!(role, None);
+    }
+
+    #[tokio::test]
+    async fn staleness_is_bounded_by_the_ttl() {
+        let (cache, clock) = (MembershipCache::new(TTL), TestClock::new());
+        let (user_id, org_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        org_role(&cache, &clock, user_id, org_id, Some(Role::Admin)).await;
+        clock.advance(TTL - Duration::from_millis(1));
+        let role = org_role(&cache, &clock, user_id, org_id, Some(Role::Viewer)).await;
+        assert_eq!(role, Some(Role::Admin));
+
+        clock.advance(Duration::from_millis(1));
+        let role = org_role(&cache, &clock, user_id, org_id, Some(Role::Viewer)).await;
+        assert_eq!(role, Some(Role::Viewer));
+    }
+
+    #[tokio::test]
+    async fn load_racing_an_invalidation_is_not_cached() {
+        let (cache, clock) = (MembershipCache::new(TTL), TestClock::new());
+        let (user_id, org_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        // The role is revoked and invalidated while the load that read it is in flight
+        let stale = cache
+            .get_or_load(
+                user_id,
+                || clock.now(),
+                || async {
+                    let read = memberships(user_id, org_id, Some(Role::Admin));
+                    cache.invalidate([user_id]);
+                    Ok(read)
+                },
+            )
+            .await
+            .unwrap();
+        assert!(stale.org_member(&org_id).is_some());
+
+        let role = org_role(&cache, &clock, user_id, org_id, None).await;
+        assert_eq!(role, None);
+    }
+
+    #[tokio::test]
+    async fn zero_ttl_always_loads() {
+        let (cache, clock) = (MembershipCache::new(Duration::ZERO), TestClock::new());
+        let (user_id, org_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        org_role(&cache, &clock, user_id, org_id, Some(Role::Admin)).await;
+        let role = org_role(&cache, &clock, user_id, org_id, None).await;
+        assert_eq!(role, None);
+    }
+}
@@ -0,0 +1,194 @@
+use super::Node;
+use crate::ag::{self, AgType, AgValue, Vertex};
+use crate::cypher::Cypher;
+use crate::edge::Edge;
+use crate::ids::{GraphId, NodeTypeId};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use sqlx::Row;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+// Default and maximum number of edges returned per relationship group
+pub const DEFAULT_GROUP_CAP: usize = 25;
+pub const MAX_GROUP_CAP: usize = 100;
+
+// The other end of an edge, without its properties
+#[derive(Debug, Serialize)]
+pub struct NeighborSummary {
+    pub id: i64,
+    pub node_type: String,
+    pub name: Option<JsonValue>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RelatedEdge {
+    pub edge: Edge,
+    pub neighbor: NeighborSummary,
+}
+
+// Edges of one type in one direction. `count` is the number of such edges, of which
+// at most the cap are listed
+#[derive(Debug, Serialize)]
+pub struct EdgeGroup {
+    pub edge_type: String,
+    pub count: usize,
+    pub truncated: bool,
+    pub edges: Vec<RelatedEdge>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TypeSummary {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DetailTypes {
+    pub node_types: Vec<TypeSummary>,
+    pub edge_types: Vec<TypeSummary>,
+}
+
+// Everything the node detail page shows, loaded in two round trips: one cypher query
+// for the node, its edges and neighbors, and one for the metadata of the types involved
+#[derive(Debug, Serialize)]
+pub struct NodeDetail {
+    pub node: Node,
+    pub incoming: Vec<EdgeGroup>,
+    pub outgoing: Vec<EdgeGroup>,
+    pub types: DetailTypes,
+}
+
+impl NodeDetail {
+    // None when the graph has no node with the id
+    pub async fn load(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        node_id: i64,
+        cap: usize,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let query = Cypher::new(
+            graph_id,
+            format!(
+                "MATCH (n) WHERE id(n) = {} OPTIONAL MATCH (n)-[r]-(m) RETURN n, r, m ORDER BY id(r)",
+                node_id
+            ),
+        )
+        .returning("n agtype, r agtype, m agtype");
+        let rows = query.fetch_rows(pool).await?;
+        let Some(first) = rows.first() else {
+            return Ok(None);
+        };
+
+        let n: AgType = first.try_get("n")?;
+        let node = Vertex::try_from(n)
+            .and_then(|vertex| Node::from_vertex(vertex, graph_id))
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+        // Direction and edge type -> edges, in edge id order
+        let mut groups: BTreeMap<(bool, String), Vec<RelatedEdge>> = BTreeMap::new();
+        let mut seen: HashSet<i64> = HashSet::new();
+        for row in &rows {
+            // The optional match yields a null edge and neighbor when the node has no edges
+            let (Some(r), Some(m)) = (
+                row.try_get::<Option<AgType>, _>("r")?,
+                row.try_get::<Option<AgType>, _>("m")?,
+            ) else {
+                continue;
+            };
+            if matches!(r.0, AgValue::Scalar(_)) {
+                continue;
+            }
+
+            let edge = ag::Edge::try_from(r)
+                .and_then(Edge::try_from)
+                .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+            // A self loop matches once per direction, list it once as outgoing
+            if !seen.insert(edge.id) {
+                continue;
+            }
+            let neighbor = Vertex::try_from(m).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+            let incoming = edge.from_id != node.id();
+            groups
+                .entry((incoming, edge.label.clone()))
+                .or_default()
+                .push(RelatedEdge {
+                    edge,
+                    neighbor: NeighborSummary {
+                        id: neighbor.id,
+                        node_type: neighbor.label,
+                        name: neighbor.properties.get("name").cloned(),
+                    },
+                });
+        }
+
+        let mut node_type_ids: BTreeSet<String> = BTreeSet::new();
+        node_type_ids.extend(node.node_type().map(NodeTypeId::to_string));
+        let mut edge_type_ids: BTreeSet<String> = BTreeSet::new();
+        let (mut incoming, mut outgoing) = (Vec::new(), Vec::new());
+        for ((is_incoming, edge_type), mut edges) in groups {
+            let count = edges.len();
+            edges.truncate(cap);
+            node_type_ids.extend(edges.iter().map(|e| e.neighbor.node_type.clone()));
+            edge_type_ids.insert(edge_type.clone());
+
+            let group = EdgeGroup {
+                edge_type,
+                count,
+                truncated: count > cap,
+                edges,
+            };
+            if is_incoming {
+                incoming.push(group);
+            } else {
+                outgoing.push(group);
+            }
+        }
+
+        let types = Self::load_types(pool, graph_id, &node_type_ids, &edge_type_ids).await?;
+        Ok(Some(Self {
+            node,
+            incoming,
+            outgoing,
+            types,
+        }))
+    }
+
+    async fn load_types(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        node_type_ids: &BTreeSet<String>,
+        edge_type_ids: &BTreeSet<String>,
+    ) -> Result<DetailTypes, sqlx::Error> {
+        let query = r#"
+            SELECT 'node' AS kind, id, name, description FROM app_data.node_types
+            WHERE graph_id = $1 AND id = ANY($2)
+            UNION ALL
+            SELECT 'edge' AS kind, id, name, description FROM app_data.edge_type
+            WHERE graph_id = $1 AND id = ANY($3)
+            ORDER BY kind, name, id
+        "#;
+        let rows = sqlx::query(query)
+            .bind(graph_id)
+            .bind(node_type_ids.iter().collect::<Vec<_>>())
+            .bind(edge_type_ids.iter().collect::<Vec<_>>())
+            .fetch_all(pool)
+            .await?;
+
+        let mut types = DetailTypes::default();
+        for row in rows {
+            let kind: String = row.try_get("kind")?;
+            let summary = TypeSummary {
+                id: row.try_get("id")?,
+                name: row.try_get("name")?,
+                description: row.try_get("description")?,
+            };
+            match kind.as_str() {
+                "node" => types.node_types.push(summary),
+                _ => types.edge_types.push(summary),
+            }
+        }
+        Ok(types)
+    }
+}
@@ -0,0 +1,85 @@
+use crate::ag::AgType;
+use crate::cypher::Cypher;
+use crate::edge::EdgeType;
+use crate::ids::GraphId;
+use serde::Serialize;
+use sqlx::Row;
+use std::collections::HashMap;
+
+// Number of edges of one type in one direction of a node
+#[derive(Debug, Serialize)]
+pub struct EdgeTypeCount {
+    pub edge_type: String,
+    pub edge_type_name: Option<String>,
+    pub count: i64,
+}
+
+// Edge types incident to a node with their counts, without loading the edges
+#[derive(Debug, Serialize)]
+pub struct EdgeSummary {
+    pub incoming: Vec<EdgeTypeCount>,
+    pub outgoing: Vec<EdgeTypeCount>,
+}
+
+impl EdgeSummary {
+    // Count the edges of the node grouped by direction and edge label in a single cypher
+    // aggregation. A self loop counts once, as outgoing, like in the node detail
+    pub async fn load(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        node_id: i64,
+    ) -> Result<Self, sqlx::Error> {
+        let query = Cypher::new(
+            graph_id,
+            format!(
+                "MATCH (n)-[r]->() WHERE id(n) = {id} \
+                 RETURN 'outgoing' AS direction, label(r) AS edge_label, count(*) AS edge_count \
+                 UNION ALL \
+                 MATCH (n)<-[r]-(m) WHERE id(n) = {id} AND id(m) <> {id} \
+                 RETURN 'incoming' AS direction, label(r) AS edge_label, count(*) AS edge_count",
+                id = node_id
+            ),
+        )
+        .returning("direction agtype, edge_label agtype, edge_count agtype");
+        let rows = query.fetch_rows(pool).await?;
+
+        let edge_type_names: HashMap<String, String> = EdgeType::list(pool, graph_id)
+            .await?
+            .into_iter()
+            .map(|t| (t.id.into(), t.name))
+            .collect();
+
+        let (mut incoming, mut outgoing) = (Vec::new(), Vec::new());
+        for row in rows {
+            let decode = |column: &str| -> Result<serde_json::Value, sqlx::Error> {
+                let value: AgType = row.try_get(column)?;
+                value
+                    .0
+                    .into_scalar()
+                    .map_err(|e| sqlx::Error::Decode(Box::new(e)))
+            };
+            let direction = decode("direction")?;
+            let edge_type = decode("edge_label")?
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            let count = decode("edge_count")?.as_i64().unwrap_or_default();
+
+            let edge_count = EdgeTypeCount {
+                edge_type_name: edge_type_names.get(&edge_type).cloned(),
+                edge_type,
+                count,
+            };
+            if direction.as_str() == Some("incoming") {
+                incoming.push(edge_count);
+            } else {
+                outgoing.push(edge_count);
+            }
+        }
+
+        for counts in [&mut incoming, &mut outgoing] {
+            counts.sort_by(|a, b| b.count.cmp(&a.count).then(a.edge_type.cmp(&b.edge_type)));
+        }
+        Ok(Self { incoming, outgoing })
+    }
+}
@@ -1,24 +1,36 @@
 use super::node_types;
 use super::{
-    Node, NodeType, NodeTypeAttributeDataType, NodeTypeAttributeDefinition, NodeTypeSummary,
+    EdgeSummary, Node, NodeCsvImport, NodeDetail, NodeFields, NodeHistoryEntry, NodeImportReport,
+    NodeScope, NodeSort, NodeType, NodeTypeAttributeDataType, NodeTypeAttributeDefinition,
+    NodeTypeSummary, NodeVisibility, PropertyFilter, SortDirection, DEFAULT_GROUP_CAP,
+    MAX_GROUP_CAP, PROTECTED_PROPERTY, RESERVED_PROPERTIES,
 };
+use crate::attribute_order::{self, AttributeTable, ReorderAttributesRequest};
 use crate::auth::Auth;
+use crate::catalog::{self, LabelKind};
 use crate::config::AppState;
+use crate::db::with_retry;
+use crate::edge::Subgraph;
 use crate::error::ApiError;
-use crate::graph::GraphInfo;
-use crate::node::{AttributeValidationError, CreateNodeError};
-use crate::org::Org;
-use crate::org::Role;
+use crate::graph::{GraphAccess, GraphInfo, GraphRevision, MinRevision, NodeNameUniqueness};
+use crate::ids::{GraphId, NodeTypeId};
+use crate::node::{CreateNodeError, NodeTypeInheritanceError, RestoreNodeError};
+use crate::org::AttributeSpec;
+use crate::utils::{validate_node_type_id, validate_properties, validate_property_key, Page};
+use crate::validation::{validate_example, WriteRules};
+use crate::webhook::WebhookEvent;
+use axum::body::Bytes;
 use axum::extract::Query;
-//use crate::utils::{generate_props_clause, validate_label, validate_properties};
 use axum::{
     extract::{Extension, Path, State},
+    http::StatusCode,
     Json,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value as JsonValue};
 use sqlx::{Postgres, Transaction};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
 use tracing::{error, info, warn};
 use uuid::Uuid;
 use validator::Validate;
@@ -29,19 +41,93 @@ pub struct NewAttributeDefinition {
     pub data_type: NodeTypeAttributeDataType,
     pub required: bool,
     pub description: String,
+    // Placeholder value for forms, must match data_type
+    pub example: Option<JsonValue>,
+    // Phase the attribute out: new writes are rejected, existing values are kept
+    #[serde(default)]
+    pub deprecated: bool,
+    pub deprecated_reason: Option<String>,
+    // Another attribute of the type to use instead
+    pub replaced_by: Option<String>,
+    // Longest string value accepted, below the server-wide cap
+    pub max_length: Option<i32>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct CreateNodeTypeRequest {
     pub name: String,
     pub description: String,
-    pub attributes: Vec<NewAttributeDefinition>,
+    // Id of a node type to inherit attribute definitions from
+    pub extends: Option<String>,
+    // Only list, count and export nodes of the type for graph admins
+    #[serde(default)]
+    pub restricted: bool,
+    pub attributes: Vec<AttributeSpec<NewAttributeDefinition>>,
+}
+
+// Check attribute names as property keys: not empty, valid identifier characters, not one of
+// the reserved properties and unique by normalized name. Errors are reported against
+// `attributes.<name>`
+fn validate_attribute_names<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+) -> Result<(), ValidationErrors> {
+    let mut errors = ValidationErrors::new();
+    let mut seen = HashSet::new();
+    for name in names {
+        let result = if name.trim().is_empty() {
+            Err(attribute_error(
+                "empty_attribute_name",
+                name,
+                "Attribute name cannot be empty",
+            ))
+        } else if RESERVED_PROPERTIES.contains(&name) {
+            Err(attribute_error(
+                "reserved_attribute_name",
+                name,
+                "Attribute name is reserved",
+            ))
+        } else if !seen.insert(crate::utils::normalize(name)) {
+            Err(attribute_error(
+                "duplicate_attribute_name",
+                name,
+                "Attribute name is listed more than once",
+            ))
+        } else {
+            validate_property_key(name)
+        };
+        if let Err(error) = result {
+            errors.add("attributes", error);
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn attribute_error(code: &'static str, name: &str, message: &'static str) -> ValidationError {
+    let mut error = ValidationError::new(code);
+    error.add_param("key".into(), &name);
+    error.message = Some(message.into());
+    error
+}
+
+impl CreateNodeTypeRequest {
+    // Checks the inline attributes. Names of dictionary attributes are only known once they
+    // are resolved, so create_node_type checks the resolved list again
+    pub fn validate(&self) -> Result<(), ValidationErrors> {
+        validate_attribute_names(self.attributes.iter().filter_map(|spec| match spec {
+            AttributeSpec::Inline(definition) => Some(definition.name.as_str()),
+            AttributeSpec::FromDictionary { .. } => None,
+        }))
+    }
 }
 
 pub async fn create_node_type(
     State(state): State<AppState>,
     Extension(auth): Extension<Auth>,
-    Path(graph_id): Path<String>,
+    Path(graph_id): Path<GraphId>,
     Json(payload): Json<CreateNodeTypeRequest>,
 ) -> Result<Json<JsonValue>, ApiError> {
     let user = auth.user.ok_or_else(|| {
@@ -49,53 +135,27 @@ pub async fn create_node_type(
         ApiError::Unauthorized
     })?;
 
-    // TODO: Add validation for the request payload
-    // Validate the label name before proceeding
-    //payload.validate()?;
-
-    let graph_info = GraphInfo::from_id(&state.pool, &graph_id)
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch graph info: {}", e);
-            ApiError::InternalServerError
-        })?;
-
-    let org = Org::from_id(&state.pool, &graph_info.org_id)
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch organization: {}", e);
-            ApiError::InternalServerError
-        })?;
-
-    // Check if the user is a member of the org
-    let org_member = org
-        .get_member(&state.pool, user.id)
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch org member: {}", e);
-            ApiError::InternalServerError
-        })?
-        .ok_or_else(|| {
-            error!("User is not a member of the organization");
-            ApiError::Unauthorized
-        })?;
+    payload.validate()?;
 
-    // Check if the user is an admin of the org
-    if org_member.role != Role::Admin {
-        return Err(ApiError::Unauthorized);
-    }
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_schema_write()?;
+    let graph_info = access.graph;
 
     //
     // User is an admin of the org, proceed with creating the node type
     //
 
-    let node_type = node_types::NodeType::new(
+    let mut node_type = node_types::NodeType::new(
         &graph_info.graph_id,
         &payload.name,
         payload.description,
         user.id,
     )
-    .unwrap();
+    .map_err(|e| {
+        error!("Failed to create node type: {}", e);
+        ApiError::BadRequest(e)
+    })?;
+    node_type.restricted = payload.restricted;
 
     // Check if the node type already exists
     let existing_node_type = NodeType::from_name(
@@ -105,34 +165,127 @@ pub async fn create_node_type(
     )
     .await;
     if existing_node_type.is_ok() {
-        return Err(ApiError::BadRequest("Node type already exists".into()));
+        return Err(ApiError::type_exists(
+            "NODE_TYPE_EXISTS",
+            &node_type.normalized_name,
+        ));
     }
 
-    info!("Creating node type for graph: {}", graph_info.name);
+    if let Some(parent_id) = &payload.extends {
+        let parent_id = NodeTypeId::parse(parent_id)?;
+        let parent = NodeType::from_id(&state.pool, &graph_info.graph_id, &parent_id)
+            .await
+            .map_err(|e| {
+                error!("Failed to fetch parent node type: {}", e);
+                ApiError::BadRequest("Parent node type does not exist".into())
+            })?;
 
-    // Start a transaction
-    let mut transaction: Transaction<Postgres> = state.pool.begin().await.map_err(|e| {
-        error!("Failed to start transaction for create_node_label: {}", e);
-        ApiError::InternalServerError
-    })?;
+        // The parent's lineage starts with the parent, so it lists every ancestor the new
+        // type will have
+        let lineage = parent.lineage(&state.pool).await.map_err(|e| {
+            error!("Invalid parent node type: {}", e);
+            ApiError::BadRequest(e.to_string())
+        })?;
+        if lineage.len() > node_types::MAX_INHERITANCE_DEPTH {
+            return Err(ApiError::BadRequest(format!(
+                "Node types can have at most {} ancestors",
+                node_types::MAX_INHERITANCE_DEPTH
+            )));
+        }
+        node_type.parent_id = Some(lineage[0].id.clone());
+    }
 
-    node_type.save(&mut transaction).await.map_err(|e| {
-        error!("Failed to save node type: {}", e);
-        ApiError::InternalServerError
-    })?;
+    info!("Creating node type for graph: {}", graph_info.name);
+
+    let attributes = AttributeSpec::resolve_all(
+        &state.pool,
+        graph_info.org_id,
+        payload.attributes,
+        |entry, required| NewAttributeDefinition {
+            name: entry.name.clone(),
+            data_type: entry.data_type.clone(),
+            required,
+            description: entry.description.clone(),
+            example: None,
+            deprecated: false,
+            deprecated_reason: None,
+            replaced_by: None,
+            max_length: entry.max_length(),
+        },
+    )
+    .await
+    .map_err(ApiError::from_dictionary_error)?;
+    validate_attribute_names(
+        attributes
+            .iter()
+            .map(|(definition, _)| definition.name.as_str()),
+    )?;
+    let attr_defs: Vec<NodeTypeAttributeDefinition> = attributes
+        .iter()
+        .enumerate()
+        .map(|(position, (new_attr_def, dictionary_id))| {
+            let mut attr_def =
+                NodeTypeAttributeDefinition::from_request(new_attr_def, &node_type.id);
+            attr_def.dictionary_id = *dictionary_id;
+            attr_def.position = position as i32;
+            attr_def
+        })
+        .collect();
+    for attr_def in &attr_defs {
+        if attr_def.max_length.is_some_and(|n| n < 1) {
+            return Err(ApiError::BadRequest(format!(
+                "Attribute '{}' needs a max_length of at least 1",
+                attr_def.name
+            )));
+        }
+        if let Some(example) = &attr_def.example {
+            validate_example(attr_def, example)
+                .map_err(|e| ApiError::BadRequest(format!("Invalid example: {}", e)))?;
+        }
+    }
 
-    // Store attributes for this node type
-    for new_attr_def in &payload.attributes {
-        let attr_def = NodeTypeAttributeDefinition::from_request(new_attr_def, &node_type.id);
+    // The label and metadata inserts can deadlock with concurrent writers, in which case
+    // the whole transaction is run again
+    let (pool, node_type, attr_defs) = (&state.pool, &node_type, &attr_defs);
+    let create = with_retry(&state.db_retry, "create_node_type", move || async move {
+        let mut transaction: Transaction<Postgres> = pool.begin().await?;
 
-        attr_def.save(&mut transaction).await.map_err(|e| {
-            error!("Failed to save attribute: {}", e);
-            ApiError::InternalServerError
+        node_type.save(&mut transaction).await.map_err(|e| {
+            error!("Failed to save node type: {}", e);
+            ApiError::from_type_save_error(e, &node_type.normalized_name, "NODE_TYPE_EXISTS")
         })?;
-    }
 
-    // Commit the transaction
-    transaction.commit().await?;
+        // Store attributes for this node type
+        for attr_def in attr_defs {
+            attr_def.save(&mut transaction).await.map_err(|e| {
+                error!("Failed to save attribute: {}", e);
+                ApiError::Database(e)
+            })?;
+        }
+
+        transaction.commit().await?;
+        Ok::<_, ApiError>(())
+    });
+    // The label is created in the same transaction and should roll back with it, one
+    // left behind by a failure is dropped
+    catalog::with_label_cleanup(
+        pool,
+        &graph_info.graph_id,
+        &node_type.normalized_name,
+        LabelKind::Vertex,
+        create,
+    )
+    .await?;
+
+    state
+        .webhooks
+        .dispatch(
+            pool,
+            &graph_info.graph_id,
+            WebhookEvent::NodeTypeCreated,
+            json!(NodeTypeResponse::from(node_type, attr_defs)),
+        )
+        .await;
 
     // Return Node Type ID
     Ok(Json(json!({"id": node_type.id})))
@@ -141,7 +294,7 @@ pub async fn create_node_type(
 pub async fn get_node_types(
     State(state): State<AppState>,
     Extension(auth): Extension<Auth>,
-    Path(graph_id): Path<String>,
+    Path(graph_id): Path<GraphId>,
 ) -> Result<Json<serde_json::Value>, ApiError> {
     // TODO: Add functionality to allow public graphs to be viewed by anyone
     let user = auth.user.ok_or_else(|| {
@@ -149,37 +302,8 @@ pub async fn get_node_types(
         ApiError::Unauthorized
     })?;
 
-    let graph_info = GraphInfo::from_id(&state.pool, &graph_id)
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch graph info: {}", e);
-            ApiError::InternalServerError
-        })?;
-
-    let org = Org::from_id(&state.pool, &graph_info.org_id)
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch organization: {}", e);
-            ApiError::InternalServerError
-        })?;
-
-    // Check if the user is a member of the org
-    let org_member = org
-        .get_member(&state.pool, user.id)
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch org member: {}", e);
-            ApiError::InternalServerError
-        })?
-        .ok_or_else(|| {
-            error!("User is not a member of the organization");
-            ApiError::Unauthorized
-        })?;
-
-    // Check if the user is an admin or viewer of the org
-    if org_member.role != Role::Admin && org_member.role != Role::Viewer {
-        return Err(ApiError::Unauthorized);
-    }
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let graph_info = access.graph;
 
     let node_types = graph_info.get_node_types(&state.pool).await.map_err(|e| {
         error!("Failed to fetch node types: {}", e);
@@ -202,6 +326,16 @@ pub struct NodeTypeAttributeResponse {
     pub data_type: NodeTypeAttributeDataType,
     pub required: bool,
     pub description: String,
+    pub example: Option<JsonValue>,
+    pub dictionary_id: Option<Uuid>,
+    pub position: i32,
+    // Forms should hide deprecated attributes, nodes may still carry them
+    pub deprecated: bool,
+    pub deprecated_reason: Option<String>,
+    pub replaced_by: Option<String>,
+    pub max_length: Option<i32>,
+    // Values are validated https URLs the UI may render, e.g. as an image
+    pub renderable: bool,
 }
 
 impl NodeTypeAttributeResponse {
@@ -212,6 +346,14 @@ impl NodeTypeAttributeResponse {
             data_type: attr.data_type.clone(),
             required: attr.required,
             description: attr.description.clone(),
+            example: attr.example.clone(),
+            dictionary_id: attr.dictionary_id,
+            position: attr.position,
+            deprecated: attr.deprecated,
+            deprecated_reason: attr.deprecated_reason.clone(),
+            replaced_by: attr.replaced_by.clone(),
+            max_length: attr.max_length,
+            renderable: attr.data_type == NodeTypeAttributeDataType::Url,
         }
     }
 }
@@ -222,23 +364,26 @@ pub struct NodeTypeResponse {
     pub graph_id: String,
     pub name: String,
     pub description: String,
+    pub extends: Option<String>,
+    #[serde(with = "crate::utils::rfc3339")]
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub created_by: Uuid,
     pub attributes: Vec<NodeTypeAttributeResponse>,
 }
 
 impl NodeTypeResponse {
-    pub fn from(node_type: &NodeType, attributes: Vec<NodeTypeAttributeDefinition>) -> Self {
+    pub fn from(node_type: &NodeType, attributes: &[NodeTypeAttributeDefinition]) -> Self {
         // Convert the attributes to the response type
         let attributes: Vec<NodeTypeAttributeResponse> = attributes
             .iter()
             .map(|attr| NodeTypeAttributeResponse::from(attr))
             .collect();
         Self {
-            id: node_type.id.clone(),
-            graph_id: node_type.graph_id.clone(),
+            id: node_type.id.to_string(),
+            graph_id: node_type.graph_id.to_string(),
             name: node_type.name.clone(),
             description: node_type.description.clone(),
+            extends: node_type.parent_id.as_ref().map(NodeTypeId::to_string),
             created_at: node_type.created_at,
             created_by: node_type.created_by,
             attributes,
@@ -249,7 +394,7 @@ impl NodeTypeResponse {
 pub async fn get_node_type(
     State(state): State<AppState>,
     Extension(auth): Extension<Auth>,
-    Path((graph_id, node_type_id)): Path<(String, String)>,
+    Path((graph_id, node_type_id)): Path<(GraphId, NodeTypeId)>,
 ) -> Result<Json<serde_json::Value>, ApiError> {
     // TODO: Add functionality to allow public graphs to be viewed by anyone
     let user = auth.user.ok_or_else(|| {
@@ -257,235 +402,1400 @@ pub async fn get_node_type(
         ApiError::Unauthorized
     })?;
 
-    let graph_info = GraphInfo::from_id(&state.pool, &graph_id)
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch graph info: {}", e);
-            ApiError::InternalServerError
-        })?;
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let graph_info = access.graph;
 
-    let org = Org::from_id(&state.pool, &graph_info.org_id)
+    let node_type = NodeType::from_id(&state.pool, &graph_info.graph_id, &node_type_id)
         .await
         .map_err(|e| {
-            error!("Failed to fetch organization: {}", e);
+            error!("Failed to fetch node type: {}", e);
             ApiError::InternalServerError
         })?;
 
-    // Check if the user is a member of the org
-    let org_member = org
-        .get_member(&state.pool, user.id)
+    let node_type_attributes = NodeTypeAttributeDefinition::from_node_type(&state.pool, &node_type)
         .await
         .map_err(|e| {
-            error!("Failed to fetch org member: {}", e);
+            error!("Failed to fetch node type attributes: {}", e);
             ApiError::InternalServerError
-        })?
-        .ok_or_else(|| {
-            error!("User is not a member of the organization");
-            ApiError::Unauthorized
         })?;
 
-    // Check if the user is an admin or viewer of the org
-    if org_member.role != Role::Admin && org_member.role != Role::Viewer {
-        return Err(ApiError::Unauthorized);
-    }
+    let response = NodeTypeResponse::from(&node_type, &node_type_attributes);
 
-    let node_type = NodeType::from_id(&state.pool, &graph_info.graph_id, &node_type_id)
+    Ok(Json(serde_json::json!(response)))
+}
+
+// Look up a node type addressed by id in the path, 404 when the graph has no such type
+async fn find_node_type(
+    pool: &sqlx::PgPool,
+    graph_id: &GraphId,
+    node_type_id: &NodeTypeId,
+) -> Result<NodeType, ApiError> {
+    NodeType::from_id(pool, graph_id, node_type_id)
         .await
-        .map_err(|e| {
-            error!("Failed to fetch node type: {}", e);
-            ApiError::InternalServerError
-        })?;
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => ApiError::NotFound {
+                code: "TYPE_NOT_FOUND".into(),
+                message: format!("Node type '{}' does not exist", node_type_id),
+            },
+            e => {
+                error!("Failed to fetch node type: {}", e);
+                ApiError::Database(e)
+            }
+        })
+}
 
-    let node_type_attributes = NodeTypeAttributeDefinition::from_node_type(&state.pool, &node_type)
+// Reorder the node type's own attributes. Inherited ones keep their place ahead of them
+pub async fn reorder_node_type_attributes(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path((graph_id, node_type_id)): Path<(GraphId, NodeTypeId)>,
+    Json(payload): Json<ReorderAttributesRequest>,
+) -> Result<Json<Vec<NodeTypeAttributeResponse>>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_schema_write()?;
+    let graph_info = access.graph;
+
+    let node_type = find_node_type(&state.pool, &graph_info.graph_id, &node_type_id).await?;
+
+    attribute_order::reorder(
+        &state.pool,
+        AttributeTable::Node,
+        node_type.id.as_str(),
+        &payload.attribute_ids,
+    )
+    .await
+    .map_err(ApiError::from_reorder_error)?;
+
+    let attributes = NodeTypeAttributeDefinition::from_node_type(&state.pool, &node_type)
         .await
         .map_err(|e| {
             error!("Failed to fetch node type attributes: {}", e);
             ApiError::InternalServerError
         })?;
 
-    let response = NodeTypeResponse::from(&node_type, node_type_attributes);
+    Ok(Json(
+        attributes
+            .iter()
+            .map(NodeTypeAttributeResponse::from)
+            .collect(),
+    ))
+}
 
-    Ok(Json(serde_json::json!(response)))
+// Delete a node type that no other type extends. Its nodes are kept under a label no type
+// has, its AGE label is dropped when it holds none
+pub async fn delete_node_type(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path((graph_id, node_type_id)): Path<(GraphId, NodeTypeId)>,
+) -> Result<StatusCode, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_schema_write()?;
+    let graph_info = access.graph;
+
+    let node_type = find_node_type(&state.pool, &graph_info.graph_id, &node_type_id).await?;
+
+    let lineage = node_type.with_descendants(&state.pool).await?;
+    if lineage.len() > 1 {
+        return Err(ApiError::Conflict {
+            code: "NODE_TYPE_EXTENDED".into(),
+            message: format!("Node type '{}' is extended by other types", node_type.name),
+            details: Some(
+                lineage
+                    .into_iter()
+                    .filter(|id| *id != node_type.id)
+                    .map(String::from)
+                    .collect(),
+            ),
+        });
+    }
+
+    // The definition as it was, for subscribers
+    let attributes = NodeTypeAttributeDefinition::from_node_type(&state.pool, &node_type).await?;
+
+    info!(
+        "Deleting node type {} of graph {}",
+        node_type.id, graph_info.name
+    );
+    node_type.delete(&state.pool).await.map_err(|e| {
+        error!("Failed to delete node type: {}", e);
+        ApiError::Database(e)
+    })?;
+    catalog::drop_label_of_deleted_type(
+        &state.pool,
+        &graph_info.graph_id,
+        &node_type.normalized_name,
+        LabelKind::Vertex,
+    )
+    .await;
+
+    state
+        .webhooks
+        .dispatch(
+            &state.pool,
+            &graph_info.graph_id,
+            WebhookEvent::NodeTypeDeleted,
+            json!(NodeTypeResponse::from(&node_type, &attributes)),
+        )
+        .await;
+
+    Ok(StatusCode::NO_CONTENT)
 }
 
-#[derive(Debug, Validate, Deserialize)]
-pub struct CreateNodeRequest {
-    pub node_type: String,
-    pub properties: HashMap<String, JsonValue>,
+#[derive(Debug, Deserialize)]
+pub struct ReplaceAttributeDefinition {
+    #[serde(flatten)]
+    pub definition: NewAttributeDefinition,
+    // Value given to existing nodes without one when the attribute becomes required
+    pub backfill: Option<JsonValue>,
 }
 
-#[derive(Serialize)]
-pub struct FieldError {
-    pub field: String,
-    pub message: String,
+#[derive(Debug, Deserialize)]
+pub struct ReplaceAttributesRequest {
+    pub attributes: Vec<ReplaceAttributeDefinition>,
 }
 
-use validator::{ValidationError, ValidationErrors};
-pub async fn create_node(
+// One attribute of a replacement, with the definition it replaces if any
+struct AttributeChange {
+    attribute: NodeTypeAttributeDefinition,
+    previous: Option<NodeTypeAttributeDefinition>,
+    backfill: Option<JsonValue>,
+}
+
+// Replace the node type's own attributes with the given list in one transaction. Entries
+// are matched to existing attributes by normalized name: unmatched ones are added,
+// attributes missing from the list are removed and the rest are updated in place.
+// Positions follow the list order.
+//
+// Nodes of the type and its subtypes are checked against the changes. An attribute that
+// becomes required needs a `backfill` value if any of them lack it, and an attribute's
+// data type can only change while none of them have a value for it
+pub async fn replace_node_type_attributes(
     State(state): State<AppState>,
     Extension(auth): Extension<Auth>,
-    Path(graph_id): Path<String>,
-    Json(request): Json<CreateNodeRequest>,
-) -> Result<Json<serde_json::Value>, ApiError> {
-    // TODO: Remove this, use a custom validation function
-    request.validate()?;
+    Path((graph_id, node_type_id)): Path<(GraphId, NodeTypeId)>,
+    Json(payload): Json<ReplaceAttributesRequest>,
+) -> Result<Json<Vec<NodeTypeAttributeResponse>>, ApiError> {
     let user = auth.user.ok_or_else(|| {
         error!("Unauthorized access: no valid user found in middleware");
         ApiError::Unauthorized
     })?;
 
-    let graph_info = GraphInfo::from_id(&state.pool, &graph_id)
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch graph info: {}", e);
-            ApiError::InternalServerError
-        })?;
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_schema_write()?;
+    let graph_info = access.graph;
 
-    let org = Org::from_id(&state.pool, &graph_info.org_id)
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch organization: {}", e);
-            ApiError::InternalServerError
-        })?;
+    let node_type = find_node_type(&state.pool, &graph_info.graph_id, &node_type_id).await?;
+    let mut existing: HashMap<String, NodeTypeAttributeDefinition> =
+        NodeTypeAttributeDefinition::from_node_type(&state.pool, &node_type)
+            .await?
+            .into_iter()
+            .map(|attr| (attr.normalized_name.clone(), attr))
+            .collect();
 
-    // Check if the user is a member of the org
-    let org_member = org
-        .get_member(&state.pool, user.id)
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch org member: {}", e);
-            ApiError::InternalServerError
-        })?
-        .ok_or_else(|| {
-            error!("User is not a member of the organization");
-            ApiError::Unauthorized
-        })?;
+    validate_attribute_names(
+        payload
+            .attributes
+            .iter()
+            .map(|entry| entry.definition.name.as_str()),
+    )?;
+    let mut changes: Vec<AttributeChange> = Vec::with_capacity(payload.attributes.len());
+    for (position, entry) in payload.attributes.into_iter().enumerate() {
+        let mut attribute =
+            NodeTypeAttributeDefinition::from_request(&entry.definition, &node_type.id);
+        attribute.position = position as i32;
+        if attribute.deprecated && attribute.required {
+            return Err(ApiError::BadRequest(format!(
+                "Attribute '{}' can't be both required and deprecated",
+                attribute.name
+            )));
+        }
+        if attribute.replaced_by.is_some() && !attribute.deprecated {
+            return Err(ApiError::BadRequest(format!(
+                "Attribute '{}' can only name a replacement once deprecated",
+                attribute.name
+            )));
+        }
+        if attribute.max_length.is_some_and(|n| n < 1) {
+            return Err(ApiError::BadRequest(format!(
+                "Attribute '{}' needs a max_length of at least 1",
+                attribute.name
+            )));
+        }
+        if let Some(example) = &attribute.example {
+            validate_example(&attribute, example)
+                .map_err(|e| ApiError::BadRequest(format!("Invalid example: {}", e)))?;
+        }
+        if let Some(backfill) = &entry.backfill {
+            validate_example(&attribute, backfill)
+                .map_err(|e| ApiError::BadRequest(format!("Invalid backfill: {}", e)))?;
+        }
 
-    // Check if the user is an admin of the org
-    if org_member.role != Role::Admin {
-        return Err(ApiError::Unauthorized);
+        let previous = existing.remove(&attribute.normalized_name);
+        if let Some(previous) = &previous {
+            attribute.id = previous.id;
+            attribute.dictionary_id = previous.dictionary_id;
+        }
+        changes.push(AttributeChange {
+            attribute,
+            previous,
+            backfill: entry.backfill,
+        });
+    }
+    // A replacement has to be one of the type's attributes that is still in use
+    for change in &changes {
+        let Some(replacement) = &change.attribute.replaced_by else {
+            continue;
+        };
+        let usable = changes.iter().any(|c| {
+            c.attribute.name == *replacement
+                && c.attribute.normalized_name != change.attribute.normalized_name
+                && !c.attribute.deprecated
+        });
+        if !usable {
+            return Err(ApiError::BadRequest(format!(
+                "Attribute '{}' is replaced by '{}', which is not a current attribute of the type",
+                change.attribute.name, replacement
+            )));
+        }
     }
+    let removed: Vec<Uuid> = existing.into_values().map(|attr| attr.id).collect();
 
-    // Check if the node type exists
-    NodeType::from_id(&state.pool, &graph_info.graph_id, &request.node_type)
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch node type: {}", e);
-            ApiError::BadRequest("Node type does not exist".into())
-        })?;
+    let type_ids = node_type.with_descendants(&state.pool).await?;
 
-    // Fail if name is not provided
-    if !request.properties.contains_key("name") {
-        return Err(ApiError::BadRequest("Name property is required".into()));
-    }
+    info!(
+        "Replacing attributes of node type {} in graph: {}",
+        node_type.id, graph_info.graph_id
+    );
+    let (pool, graph_id, type_ids, changes, removed) = (
+        &state.pool,
+        &graph_info.graph_id,
+        &type_ids,
+        &changes,
+        &removed,
+    );
+    with_retry(
+        &state.db_retry,
+        "replace_node_type_attributes",
+        move || async move {
+            let mut transaction: Transaction<Postgres> = pool.begin().await?;
 
-    // Do not allow creation of nodes with the same name
-    let name = request.properties.get("name").unwrap().as_str().unwrap();
-    let node_type = &request.node_type;
+            let mut conflicts = Vec::new();
+            for change in changes {
+                let attribute = &change.attribute;
+                let (was_required, retyped) = match &change.previous {
+                    Some(previous) => (
+                        previous.required,
+                        previous.data_type != attribute.data_type,
+                    ),
+                    None => (false, false),
+                };
 
-    // Check if a node of the same type with the same name already exists
-    let existing_node =
-        Node::get_by_name(&state.pool, &graph_info.graph_id, &node_type, name).await;
-    if existing_node.is_ok() {
-        warn!("Existing node: {:?}", existing_node);
-        return Err(ApiError::BadRequest(
-            "Node with the same name already exists".into(),
-        ));
-    }
+                if retyped
+                    && Node::count_with_property(
+                        &mut transaction,
+                        graph_id,
+                        type_ids,
+                        &attribute.name,
+                        true,
+                    )
+                    .await?
+                        > 0
+                {
+                    conflicts.push(format!(
+                        "{}: the data type can't change while nodes have a value for it",
+                        attribute.name
+                    ));
+                }
 
-    Node::create(&state.pool, request, user.id, graph_info.graph_id)
-        .await
-        .map_err(|e| match e {
-            CreateNodeError::ValidationError(errors) => {
-                let mut validation_errors = ValidationErrors::new();
-                for error in errors {
-                    match error {
-                        AttributeValidationError::MissingAttribute { name } => {
-                            let mut val_error = ValidationError::new("missing");
-                            val_error.message = Some("required".into());
-                            // Convert the dynamic field name into a &'static str.
-                            validation_errors.add(Box::leak(name.into_boxed_str()), val_error);
-                        }
-                        AttributeValidationError::WrongType { name, expected } => {
-                            let mut val_error = ValidationError::new("wrong_type");
-                            val_error.message =
-                                Some(format!("must be of type {}", expected).into());
-                            validation_errors.add(Box::leak(name.into_boxed_str()), val_error);
+                if attribute.required && !was_required {
+                    let missing = Node::count_with_property(
+                        &mut transaction,
+                        graph_id,
+                        type_ids,
+                        &attribute.name,
+                        false,
+                    )
+                    .await?;
+                    match &change.backfill {
+                        _ if missing == 0 => {}
+                        Some(value) => {
+                            Node::backfill_property(
+                                &mut transaction,
+                                graph_id,
+                                type_ids,
+                                &attribute.name,
+                                value,
+                            )
+                            .await?
                         }
+                        None => conflicts.push(format!(
+                            "{}: {} node(s) have no value, give a backfill value to make it required",
+                            attribute.name, missing
+                        )),
                     }
                 }
-                ApiError::Validation(validation_errors)
             }
-            CreateNodeError::DatabaseError(_) => {
-                error!("Database error when creating node: {}", e);
-                ApiError::InternalServerError
+            if !conflicts.is_empty() {
+                return Err(ApiError::Conflict {
+                    code: "ATTRIBUTE_CHANGE_CONFLICT".into(),
+                    message: "Existing nodes don't allow some of the attribute changes".into(),
+                    details: Some(conflicts),
+                });
             }
-        })?;
 
-    Ok(Json(json!({})))
+            NodeTypeAttributeDefinition::delete_many(&mut transaction, removed).await?;
+            for change in changes {
+                match change.previous {
+                    Some(_) => change.attribute.update(&mut transaction).await?,
+                    None => change.attribute.save(&mut transaction).await?,
+                }
+            }
+
+            transaction.commit().await?;
+            Ok::<_, ApiError>(())
+        },
+    )
+    .await?;
+
+    let attributes = NodeTypeAttributeDefinition::from_node_type(pool, &node_type).await?;
+
+    state
+        .webhooks
+        .dispatch(
+            pool,
+            graph_id,
+            WebhookEvent::NodeTypeUpdated,
+            json!(NodeTypeResponse::from(&node_type, &attributes)),
+        )
+        .await;
+
+    Ok(Json(
+        attributes
+            .iter()
+            .map(NodeTypeAttributeResponse::from)
+            .collect(),
+    ))
+}
+
+#[derive(Debug, Validate, Deserialize)]
+pub struct CreateNodeRequest {
+    #[validate(custom = "validate_node_type_id")]
+    pub node_type: String,
+    #[validate(custom = "validate_properties")]
+    pub properties: HashMap<String, JsonValue>,
+}
+
+use validator::{ValidationError, ValidationErrors};
+// Reject a node name that is already taken within the graph's uniqueness scope
+async fn ensure_name_available(
+    pool: &sqlx::PgPool,
+    graph_info: &GraphInfo,
+    node_type: &NodeTypeId,
+    name: &str,
+) -> Result<(), ApiError> {
+    let name_taken = match graph_info.node_name_uniqueness {
+        NodeNameUniqueness::None => false,
+        NodeNameUniqueness::PerType => {
+            // Check if a node of the same type with the same name already exists
+            let existing_node = Node::get_by_name_opt(
+                pool,
+                &graph_info.graph_id,
+                node_type,
+                name,
+                graph_info.case_insensitive_names,
+            )
+            .await
+            .map_err(|e| {
+                error!("Failed to check for an existing node name: {}", e);
+                ApiError::InternalServerError
+            })?;
+            if existing_node.is_some() {
+                warn!("Existing node: {:?}", existing_node);
+            }
+            existing_node.is_some()
+        }
+        NodeNameUniqueness::PerGraph => Node::name_exists(
+            pool,
+            &graph_info.graph_id,
+            name,
+            graph_info.case_insensitive_names,
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to check for an existing node name: {}", e);
+            ApiError::InternalServerError
+        })?,
+    };
+    if name_taken {
+        return Err(ApiError::BadRequest(
+            "Node with the same name already exists".into(),
+        ));
+    }
+    Ok(())
 }
 
 #[derive(Deserialize)]
-pub struct GetNodesQueryParams {
-    pub page: Option<u32>,
-    pub node_type: Option<String>,
+pub struct CreateNodeQueryParams {
+    // Reject values that would otherwise be coerced with a warning
+    pub strict: Option<bool>,
 }
 
-pub async fn get_nodes(
+pub async fn create_node(
     State(state): State<AppState>,
     Extension(auth): Extension<Auth>,
-    Path(graph_id): Path<String>,
-    Query(params): Query<GetNodesQueryParams>,
-) -> Result<Json<serde_json::Value>, ApiError> {
-    // TODO: Allow public graphs to be viewed by anyone
+    Path(graph_id): Path<GraphId>,
+    Query(params): Query<CreateNodeQueryParams>,
+    Json(request): Json<CreateNodeRequest>,
+) -> Result<(Option<GraphRevision>, Json<serde_json::Value>), ApiError> {
+    // TODO: Remove this, use a custom validation function
+    request.validate()?;
     let user = auth.user.ok_or_else(|| {
         error!("Unauthorized access: no valid user found in middleware");
         ApiError::Unauthorized
     })?;
 
-    let graph_info = GraphInfo::from_id(&state.pool, &graph_id)
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch graph info: {}", e);
-            ApiError::InternalServerError
-        })?;
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_write()?;
+    let graph_info = access.graph;
 
-    let org = Org::from_id(&state.pool, &graph_info.org_id)
+    // Check if the node type exists
+    let node_type_id = NodeTypeId::parse(&request.node_type)?;
+    NodeType::from_id(&state.pool, &graph_info.graph_id, &node_type_id)
         .await
         .map_err(|e| {
-            error!("Failed to fetch organization: {}", e);
-            ApiError::InternalServerError
+            error!("Failed to fetch node type: {}", e);
+            ApiError::BadRequest("Node type does not exist".into())
         })?;
 
-    // Check if the user is a member of the org
-    let org_member = org
-        .get_member(&state.pool, user.id)
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch org member: {}", e);
-            ApiError::InternalServerError
-        })?
-        .ok_or_else(|| {
-            error!("User is not a member of the organization");
-            ApiError::Unauthorized
-        })?;
+    if request.properties.contains_key(PROTECTED_PROPERTY) {
+        return Err(ApiError::BadRequest(format!(
+            "'{}' is set through the node protection endpoint",
+            PROTECTED_PROPERTY
+        )));
+    }
 
-    // Check if the user is an admin or viewer of the org
-    if org_member.role != Role::Admin && org_member.role != Role::Viewer {
-        return Err(ApiError::Unauthorized);
+    // Fail if name is not provided
+    if !request.properties.contains_key("name") {
+        return Err(ApiError::BadRequest("Name property is required".into()));
     }
 
-    let nodes = Node::list(
+    // Do not allow creation of nodes with the same name, within the graph's uniqueness scope
+    let name = request.properties.get("name").unwrap().as_str().unwrap();
+    ensure_name_available(&state.pool, &graph_info, &node_type_id, name).await?;
+
+    // Spent once the request is known to be well formed, so rejected ones cost nothing
+    state.write_budget.consume(&user, 1)?;
+
+    let rules = WriteRules {
+        strict: params.strict.unwrap_or(false),
+        max_value_length: state.max_property_value_length,
+    };
+    let (node, warnings) = Node::create(
         &state.pool,
         &graph_info.graph_id,
-        params.node_type.as_deref(),
-        params.page,
+        &node_type_id,
+        request.properties,
+        user.id,
+        rules,
     )
     .await
-    .map_err(|e| {
-        error!("Failed to fetch nodes: {}", e);
-        ApiError::InternalServerError
-    })?;
-
+    .map_err(|e| match e {
+        CreateNodeError::ValidationError(errors, schema_hint) => {
+            ApiError::invalid_properties(errors, schema_hint)
+        }
+        // A broken type hierarchy is a problem with the type the request names
+        CreateNodeError::Inheritance(
+            NodeTypeInheritanceError::Cycle(_) | NodeTypeInheritanceError::TooDeep(_),
+        ) => {
+            warn!("Node type of new node has an invalid hierarchy: {}", e);
+            ApiError::BadRequest(e.to_string())
+        }
+        CreateNodeError::DatabaseError(_)
+        | CreateNodeError::Inheritance(NodeTypeInheritanceError::DatabaseError(_)) => {
+            error!("Database error when creating node: {}", e);
+            ApiError::InternalServerError
+        }
+    })?;
+
+    state
+        .webhooks
+        .dispatch(
+            &state.pool,
+            node.graph_id(),
+            WebhookEvent::NodeCreated,
+            json!(node),
+        )
+        .await;
+
+    let revision = GraphRevision::bump(&state.pool, node.graph_id()).await;
+    Ok((
+        revision,
+        Json(json!({ "id": node.id(), "warnings": warnings })),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct ImportNodesQueryParams {
+    // Id or name of the node type every row is created as
+    pub node_type: String,
+    // Only check the rows and report which would fail
+    pub dry_run: Option<bool>,
+}
+
+// Create nodes from a CSV upload whose header names the node type's attributes. Valid
+// rows are created, the others are reported with their row number. With dry_run set the
+// rows are only checked
+pub async fn import_nodes_csv(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+    Query(params): Query<ImportNodesQueryParams>,
+    body: Bytes,
+) -> Result<(Option<GraphRevision>, Json<NodeImportReport>), ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_write()?;
+    let graph_info = access.graph;
+
+    let node_type = resolve_node_type(&state.pool, &graph_info.graph_id, &params.node_type).await?;
+    let mut lineage = node_type.lineage(&state.pool).await.map_err(|e| {
+        error!("Failed to resolve node type lineage: {}", e);
+        ApiError::InternalServerError
+    })?;
+    let attributes = NodeTypeAttributeDefinition::resolve(&state.pool, &lineage)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch node type attributes: {}", e);
+            ApiError::InternalServerError
+        })?;
+    // The lineage starts with the type itself
+    let node_type = lineage.swap_remove(0);
+
+    let body = std::str::from_utf8(&body)
+        .map_err(|_| ApiError::BadRequest("CSV body must be UTF-8".into()))?;
+    let import =
+        NodeCsvImport::prepare(body, node_type, attributes, state.max_property_value_length)
+            .map_err(ApiError::BadRequest)?;
+
+    if params.dry_run.unwrap_or(false) {
+        let report = import
+            .dry_run(&state.pool, &graph_info)
+            .await
+            .map_err(|e| {
+                error!("Failed to check node import: {:?}", e);
+                ApiError::InternalServerError
+            })?;
+        return Ok((None, Json(report)));
+    }
+
+    state
+        .write_budget
+        .consume(&user, import.row_count() as u32)?;
+
+    let (report, nodes) = import
+        .run(&state.pool, &graph_info, user.id)
+        .await
+        .map_err(|e| {
+            error!("Failed to import nodes: {:?}", e);
+            ApiError::InternalServerError
+        })?;
+    info!(
+        "Imported {} node(s) into graph {} by {}, {} row error(s)",
+        report.created,
+        graph_info.graph_id,
+        user.id,
+        report.errors.len()
+    );
+
+    for node in nodes {
+        state
+            .webhooks
+            .dispatch(
+                &state.pool,
+                node.graph_id(),
+                WebhookEvent::NodeCreated,
+                json!(node),
+            )
+            .await;
+    }
+
+    let revision = GraphRevision::bump(&state.pool, &graph_info.graph_id).await;
+    Ok((revision, Json(report)))
+}
+
+#[derive(Deserialize)]
+pub struct GetNodesQueryParams {
+    pub page: Option<u32>,
+    pub node_type: Option<String>,
+    // name (default), created_at or an attribute of node_type
+    pub sort: Option<String>,
+    pub dir: Option<SortDirection>,
+    // Comma separated properties to return, e.g. name,status. Defaults to all of them
+    pub fields: Option<String>,
+    // Graph revision returned by a write the listing must reflect
+    pub min_revision: Option<i64>,
+}
+
+// Look up a node type by id, falling back to its name. Unknown types are a 400
+pub(crate) async fn resolve_node_type(
+    pool: &sqlx::PgPool,
+    graph_id: &GraphId,
+    node_type: &str,
+) -> Result<NodeType, ApiError> {
+    resolve_node_type_with(
+        node_type,
+        |id| async move { NodeType::from_id(pool, graph_id, &id).await },
+        |name| async move { NodeType::from_name(pool, graph_id, &name).await },
+    )
+    .await
+}
+
+// resolve_node_type with the lookups passed in, so tests can stand in for the database.
+// The value is only ever bound as a query parameter, never interpolated
+async fn resolve_node_type_with<ById, ByName>(
+    node_type: &str,
+    by_id: impl FnOnce(NodeTypeId) -> ById,
+    by_name: impl FnOnce(String) -> ByName,
+) -> Result<NodeType, ApiError>
+where
+    ById: Future<Output = Result<NodeType, sqlx::Error>>,
+    ByName: Future<Output = Result<NodeType, sqlx::Error>>,
+{
+    // Only something shaped like an id is worth looking up as one
+    if let Ok(node_type_id) = NodeTypeId::parse(node_type) {
+        match by_id(node_type_id).await {
+            Ok(node_type) => return Ok(node_type),
+            Err(sqlx::Error::RowNotFound) => {}
+            Err(e) => return Err(ApiError::Database(e)),
+        }
+    }
+    match by_name(node_type.to_string()).await {
+        Ok(node_type) => Ok(node_type),
+        Err(sqlx::Error::RowNotFound) => Err(ApiError::BadRequest(format!(
+            "Unknown node type '{}'",
+            node_type
+        ))),
+        Err(e) => Err(ApiError::Database(e)),
+    }
+}
+
+pub async fn get_nodes(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+    Query(params): Query<GetNodesQueryParams>,
+    // filter[<attribute>]=<value> and filter[<attribute>][<op>]=<value> pairs
+    Query(raw_params): Query<Vec<(String, String)>>,
+) -> Result<(GraphRevision, Json<serde_json::Value>), ApiError> {
+    // TODO: Allow public graphs to be viewed by anyone
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let role = access.role;
+    let graph_info = access.graph;
+    let revision = MinRevision::new(params.min_revision).check(&graph_info)?;
+
+    // Accept either the type id or its display name. Filter values are parsed according to
+    // the attribute's data type, so filtering needs the node type
+    let (node_type, filters, sort, fields) = match params.node_type.as_deref() {
+        Some(node_type) => {
+            let node_type = resolve_node_type(&state.pool, &graph_info.graph_id, node_type).await?;
+            let mut lineage = node_type.lineage(&state.pool).await.map_err(|e| {
+                error!("Failed to resolve node type lineage: {}", e);
+                ApiError::InternalServerError
+            })?;
+            let attributes = NodeTypeAttributeDefinition::resolve(&state.pool, &lineage).await?;
+            let filters = PropertyFilter::parse_all(&raw_params, &attributes)?;
+            let sort = NodeSort::parse(params.sort.as_deref(), params.dir, Some(&attributes))?;
+            let fields = NodeFields::parse(params.fields.as_deref(), Some(&attributes))?;
+            (Some(lineage.swap_remove(0)), filters, sort, fields)
+        }
+        None if raw_params.iter().any(|(key, _)| key.starts_with("filter[")) => {
+            return Err(ApiError::BadRequest(
+                "Property filters require a node_type".into(),
+            ));
+        }
+        None => {
+            let sort = NodeSort::parse(params.sort.as_deref(), params.dir, None)?;
+            let fields = NodeFields::parse(params.fields.as_deref(), None)?;
+            (None, Vec::new(), sort, fields)
+        }
+    };
+
+    let visibility = NodeVisibility::load(&state.pool, &graph_info.graph_id, role)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch restricted node types: {}", e);
+            ApiError::InternalServerError
+        })?;
+    let scope = NodeScope::new(node_type.as_ref(), &filters, &visibility);
+    let mut nodes = Node::list(
+        &state.pool,
+        &graph_info.graph_id,
+        &scope,
+        &sort,
+        params.page,
+    )
+    .await
+    .map_err(ApiError::from_cypher_error)?;
+
+    if let Some(fields) = &fields {
+        nodes.iter_mut().for_each(|node| node.project(fields));
+    }
+
+    Ok((revision, Json(serde_json::json!(nodes))))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetOrphanNodesQueryParams {
+    pub page: Option<u32>,
+    pub page_size: Option<u32>,
+    // Type id or display name
+    pub node_type: Option<String>,
+}
+
+pub async fn get_orphan_nodes(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+    Query(params): Query<GetOrphanNodesQueryParams>,
+) -> Result<Json<Page<Node>>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let role = access.role;
+    let graph_info = access.graph;
+
+    let node_type = match params.node_type.as_deref() {
+        Some(node_type) => {
+            Some(resolve_node_type(&state.pool, &graph_info.graph_id, node_type).await?)
+        }
+        None => None,
+    };
+
+    let (page, page_size) = Page::<Node>::bounds(params.page, params.page_size);
+    let visibility = NodeVisibility::load(&state.pool, &graph_info.graph_id, role)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch restricted node types: {}", e);
+            ApiError::InternalServerError
+        })?;
+    let scope = NodeScope::new(node_type.as_ref(), &[], &visibility);
+    let (items, total) = Node::orphans(&state.pool, &graph_info.graph_id, &scope, page, page_size)
+        .await
+        .map_err(ApiError::from_cypher_error)?;
+
+    Ok(Json(Page {
+        items,
+        page,
+        page_size,
+        total,
+    }))
+}
+
+#[derive(Debug, Validate, Deserialize)]
+pub struct BatchGetNodesRequest {
+    #[validate(length(min = 1, max = 100, message = "Between 1 and 100 ids must be provided"))]
+    pub ids: Vec<i64>,
+}
+
+pub async fn batch_get_nodes(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+    Json(request): Json<BatchGetNodesRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    request.validate()?;
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let graph_info = access.graph;
+
+    let nodes = Node::get_many(&state.pool, &graph_info.graph_id, &request.ids)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch nodes: {}", e);
+            ApiError::InternalServerError
+        })?;
+
     Ok(Json(serde_json::json!(nodes)))
 }
+
+// Map the public id of a node in the path to its vertex id. Unknown ids are a 404
+pub(crate) async fn find_node_id(
+    pool: &sqlx::PgPool,
+    graph_id: &GraphId,
+    public_id: &str,
+) -> Result<i64, ApiError> {
+    Node::vertex_id(pool, graph_id, public_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound {
+            code: "NODE_NOT_FOUND".into(),
+            message: format!("No node with id {}", public_id),
+        })
+}
+
+#[derive(Deserialize)]
+pub struct GetNeighborsQueryParams {
+    // Include the endpoint nodes of each edge in the response
+    pub expand: Option<bool>,
+}
+
+pub async fn get_neighbors(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path((graph_id, public_id)): Path<(GraphId, String)>,
+    Query(params): Query<GetNeighborsQueryParams>,
+) -> Result<Json<Subgraph>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let graph_info = access.graph;
+
+    let node_id = find_node_id(&state.pool, &graph_info.graph_id, &public_id).await?;
+    let subgraph = Subgraph::neighbors(
+        &state.pool,
+        &graph_info.graph_id,
+        node_id,
+        params.expand.unwrap_or(false),
+    )
+    .await
+    .map_err(|e| {
+        error!("Failed to fetch neighbors: {}", e);
+        ApiError::InternalServerError
+    })?;
+
+    Ok(Json(subgraph))
+}
+
+#[derive(Deserialize)]
+pub struct GetNodeDetailQueryParams {
+    // Maximum number of edges listed per edge type and direction
+    pub limit: Option<usize>,
+}
+
+pub async fn get_node_detail(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path((graph_id, public_id)): Path<(GraphId, String)>,
+    Query(params): Query<GetNodeDetailQueryParams>,
+) -> Result<Json<NodeDetail>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let graph_info = access.graph;
+
+    let cap = params
+        .limit
+        .unwrap_or(DEFAULT_GROUP_CAP)
+        .clamp(1, MAX_GROUP_CAP);
+    let node_id = find_node_id(&state.pool, &graph_info.graph_id, &public_id).await?;
+    let detail = NodeDetail::load(&state.pool, &graph_info.graph_id, node_id, cap)
+        .await
+        .map_err(ApiError::from_cypher_error)?
+        .ok_or_else(|| ApiError::NotFound {
+            code: "NODE_NOT_FOUND".into(),
+            message: format!("No node with id {}", public_id),
+        })?;
+
+    Ok(Json(detail))
+}
+
+// Counts of the edge types around a node, for showing its relationships without the edges
+pub async fn get_node_edge_summary(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path((graph_id, node_type, name)): Path<(GraphId, NodeTypeId, String)>,
+) -> Result<Json<EdgeSummary>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let graph_info = access.graph;
+
+    let node = Node::get_by_name_opt(
+        &state.pool,
+        &graph_info.graph_id,
+        &node_type,
+        &name,
+        graph_info.case_insensitive_names,
+    )
+    .await
+    .map_err(|e| {
+        error!("Failed to fetch node: {}", e);
+        ApiError::InternalServerError
+    })?
+    .ok_or_else(|| ApiError::NotFound {
+        code: "NODE_NOT_FOUND".into(),
+        message: format!("No {} node named '{}'", node_type, name),
+    })?;
+
+    let summary = EdgeSummary::load(&state.pool, &graph_info.graph_id, node.id())
+        .await
+        .map_err(ApiError::from_cypher_error)?;
+
+    Ok(Json(summary))
+}
+
+#[derive(Debug, Validate, Deserialize)]
+pub struct DuplicateNodeRequest {
+    #[validate(length(
+        min = 1,
+        max = 1000,
+        message = "Name must be between 1 and 1000 characters"
+    ))]
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct DuplicateNodeQueryParams {
+    // Also copy the edges into and out of the node, attached to the copy
+    pub copy_edges: Option<bool>,
+}
+
+pub async fn duplicate_node(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path((graph_id, node_type, name)): Path<(GraphId, NodeTypeId, String)>,
+    Query(params): Query<DuplicateNodeQueryParams>,
+    Json(request): Json<DuplicateNodeRequest>,
+) -> Result<(StatusCode, Option<GraphRevision>, Json<JsonValue>), ApiError> {
+    request.validate()?;
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_write()?;
+    let graph_info = access.graph;
+
+    let source = Node::get_by_name_opt(
+        &state.pool,
+        &graph_info.graph_id,
+        &node_type,
+        &name,
+        graph_info.case_insensitive_names,
+    )
+    .await
+    .map_err(|e| {
+        error!("Failed to fetch node to duplicate: {}", e);
+        ApiError::InternalServerError
+    })?
+    .ok_or_else(|| ApiError::NotFound {
+        code: "NODE_NOT_FOUND".into(),
+        message: format!("No {} node named '{}'", node_type, name),
+    })?;
+
+    ensure_name_available(&state.pool, &graph_info, &node_type, &request.name).await?;
+
+    state.write_budget.consume(&user, 1)?;
+
+    let copy_edges = params.copy_edges.unwrap_or(false);
+    let (node, copied_edges) =
+        Node::duplicate(&state.pool, &source, &request.name, user.id, copy_edges)
+            .await
+            .map_err(|e| {
+                error!("Failed to duplicate node {}: {}", source.id(), e);
+                ApiError::InternalServerError
+            })?;
+
+    state
+        .webhooks
+        .dispatch(
+            &state.pool,
+            node.graph_id(),
+            WebhookEvent::NodeCreated,
+            json!(node),
+        )
+        .await;
+
+    let revision = GraphRevision::bump(&state.pool, node.graph_id()).await;
+    Ok((
+        StatusCode::CREATED,
+        revision,
+        Json(json!({ "id": node.id(), "copied_edges": copied_edges })),
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NodeHistoryQueryParams {
+    pub page: Option<u32>,
+    pub page_size: Option<u32>,
+}
+
+pub async fn get_node_history(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path((graph_id, node_type, name)): Path<(GraphId, NodeTypeId, String)>,
+    Query(params): Query<NodeHistoryQueryParams>,
+) -> Result<Json<Page<NodeHistoryEntry>>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let graph_info = access.graph;
+
+    let node = Node::get_by_name_opt(
+        &state.pool,
+        &graph_info.graph_id,
+        &node_type,
+        &name,
+        graph_info.case_insensitive_names,
+    )
+    .await
+    .map_err(|e| {
+        error!("Failed to fetch node: {}", e);
+        ApiError::InternalServerError
+    })?
+    .ok_or_else(|| ApiError::NotFound {
+        code: "NODE_NOT_FOUND".into(),
+        message: format!("No {} node named '{}'", node_type, name),
+    })?;
+
+    let (page, page_size) = Page::<NodeHistoryEntry>::bounds(params.page, params.page_size);
+    let (items, total) = NodeHistoryEntry::list(
+        &state.pool,
+        &graph_info.graph_id,
+        node.id(),
+        page,
+        page_size,
+    )
+    .await
+    .map_err(|e| {
+        error!("Failed to fetch node history: {}", e);
+        ApiError::InternalServerError
+    })?;
+
+    Ok(Json(Page {
+        items,
+        page,
+        page_size,
+        total,
+    }))
+}
+
+// Reapply the properties of an earlier history entry as a new change
+pub async fn restore_node_version(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path((graph_id, node_type, name, version)): Path<(GraphId, NodeTypeId, String, i64)>,
+) -> Result<(Option<GraphRevision>, Json<Node>), ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_write()?;
+    let graph_info = access.graph;
+
+    let node = Node::get_by_name_opt(
+        &state.pool,
+        &graph_info.graph_id,
+        &node_type,
+        &name,
+        graph_info.case_insensitive_names,
+    )
+    .await
+    .map_err(|e| {
+        error!("Failed to fetch node: {}", e);
+        ApiError::InternalServerError
+    })?
+    .ok_or_else(|| ApiError::NotFound {
+        code: "NODE_NOT_FOUND".into(),
+        message: format!("No {} node named '{}'", node_type, name),
+    })?;
+
+    let entry = NodeHistoryEntry::version(&state.pool, &graph_info.graph_id, node.id(), version)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch node history: {}", e);
+            ApiError::InternalServerError
+        })?
+        .ok_or_else(|| ApiError::NotFound {
+            code: "VERSION_NOT_FOUND".into(),
+            message: format!("Node has no version {}", version),
+        })?;
+
+    // Going back to an earlier name must not clash with a node created since
+    if let Some(JsonValue::String(restored_name)) = entry.after.get("name") {
+        if *restored_name != name {
+            ensure_name_available(&state.pool, &graph_info, &node_type, restored_name).await?;
+        }
+    }
+
+    state.write_budget.consume(&user, 1)?;
+
+    let restored = node
+        .restore(&state.pool, &entry.after, user.id)
+        .await
+        .map_err(|e| match e {
+            RestoreNodeError::Conflict(errors) => ApiError::Conflict {
+                code: "SCHEMA_CONFLICT".into(),
+                message: format!(
+                    "Version {} does not match the current definition of the node type",
+                    version
+                ),
+                details: Some(errors.into_iter().map(|e| e.to_string()).collect()),
+            },
+            RestoreNodeError::InvalidSnapshot => {
+                error!("History entry {} is not a property map", entry.id);
+                ApiError::InternalServerError
+            }
+            RestoreNodeError::DatabaseError(_) | RestoreNodeError::Inheritance(_) => {
+                error!("Failed to restore node {}: {}", node.id(), e);
+                ApiError::InternalServerError
+            }
+        })?;
+
+    let revision = GraphRevision::bump(&state.pool, &graph_info.graph_id).await;
+    Ok((revision, Json(restored)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateNodeProtectionRequest {
+    pub protected: bool,
+}
+
+// Mark a reference node as protected from deletion, or remove the protection
+pub async fn update_node_protection(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path((graph_id, public_id)): Path<(GraphId, String)>,
+    Json(request): Json<UpdateNodeProtectionRequest>,
+) -> Result<(Option<GraphRevision>, Json<Node>), ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_admin()?;
+    let graph_info = access.graph;
+
+    let node_id = find_node_id(&state.pool, &graph_info.graph_id, &public_id).await?;
+    let node = Node::get_many(&state.pool, &graph_info.graph_id, &[node_id])
+        .await
+        .map_err(ApiError::from_cypher_error)?
+        .pop()
+        .ok_or_else(|| ApiError::NotFound {
+            code: "NODE_NOT_FOUND".into(),
+            message: format!("No node with id {}", public_id),
+        })?;
+
+    let node = node
+        .set_protected(&state.pool, request.protected, user.id)
+        .await
+        .map_err(|e| {
+            error!("Failed to update node protection: {}", e);
+            ApiError::InternalServerError
+        })?;
+
+    let revision = GraphRevision::bump(&state.pool, &graph_info.graph_id).await;
+    Ok((revision, Json(node)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_request(attribute_names: &[&str]) -> CreateNodeTypeRequest {
+        let attributes: Vec<JsonValue> = attribute_names
+            .iter()
+            .map(|name| {
+                json!({
+                    "name": name,
+                    "data_type": "string",
+                    "required": false,
+                    "description": "",
+                })
+            })
+            .collect();
+        serde_json::from_value(json!({
+            "name": "Person",
+            "description": "",
+            "attributes": attributes,
+        }))
+        .unwrap()
+    }
+
+    fn error_codes(errors: &ValidationErrors) -> Vec<String> {
+        errors.field_errors()["attributes"]
+            .iter()
+            .map(|error| error.code.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn distinct_attribute_names_are_accepted() {
+        assert!(create_request(&["first_name", "last_name"])
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn duplicate_attribute_names_are_rejected() {
+        let errors = create_request(&["email", "phone", "email"])
+            .validate()
+            .unwrap_err();
+        assert_eq!(error_codes(&errors), ["duplicate_attribute_name"]);
+    }
+
+    #[test]
+    fn attribute_names_are_compared_normalized() {
+        let errors = create_request(&["Email", "email "]).validate().unwrap_err();
+        assert_eq!(error_codes(&errors), ["duplicate_attribute_name"]);
+    }
+
+    #[test]
+    fn dictionary_attributes_are_checked_once_resolved() {
+        let request: CreateNodeTypeRequest = serde_json::from_value(json!({
+            "name": "Person",
+            "description": "",
+            "attributes": [
+                { "from_dictionary": Uuid::new_v4() },
+                { "from_dictionary": Uuid::new_v4() },
+            ],
+        }))
+        .unwrap();
+        assert!(request.validate().is_ok());
+    }
+
+    // Lookups over an in-memory list of types, recording what was asked for
+    struct TypeLookups {
+        types: Vec<NodeType>,
+        asked: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl TypeLookups {
+        fn new() -> Self {
+            let graph_id = GraphId::parse("gTEST0001").unwrap();
+            let person = NodeType::new(&graph_id, "Person", String::new(), Uuid::new_v4()).unwrap();
+            let city = NodeType::new(&graph_id, "City", String::new(), Uuid::new_v4()).unwrap();
+            Self {
+                types: vec![person, city],
+                asked: Default::default(),
+            }
+        }
+
+        async fn resolve(&self, node_type: &str) -> Result<NodeType, ApiError> {
+            resolve_node_type_with(
+                node_type,
+                |id| async move {
+                    self.asked.lock().unwrap().push(format!("id:{}", id));
+                    self.find(|t| t.id == id)
+                },
+                |name| async move {
+                    self.asked.lock().unwrap().push(format!("name:{}", name));
+                    let normalized = crate::utils::normalize(&name);
+                    self.find(|t| t.normalized_name == normalized)
+                },
+            )
+            .await
+        }
+
+        fn find(&self, matches: impl Fn(&NodeType) -> bool) -> Result<NodeType, sqlx::Error> {
+            let found = self.types.iter().find(|t| matches(t));
+            let found = found.ok_or(sqlx::Error::RowNotFound)?;
+            let graph_id = found.graph_id.clone();
+            // NodeType isn't Clone, a copy of the stored row
+            let mut copy = NodeType::new(&graph_id, &found.name, String::new(), found.created_by)
+                .map_err(sqlx::Error::Protocol)?;
+            copy.id = found.id.clone();
+            Ok(copy)
+        }
+
+        fn asked(&self) -> Vec<String> {
+            self.asked.lock().unwrap().clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn node_types_resolve_by_id() {
+        let lookups = TypeLookups::new();
+        let city_id = lookups.types[1].id.clone();
+        let resolved = lookups.resolve(city_id.as_str()).await.unwrap();
+        assert_eq!(resolved.id, city_id);
+        assert_eq!(lookups.asked(), [format!("id:{}", city_id)]);
+    }
+
+    #[tokio::test]
+    async fn node_types_resolve_by_display_name() {
+        let lookups = TypeLookups::new();
+        let resolved = lookups.resolve("Person").await.unwrap();
+        assert_eq!(resolved.id, lookups.types[0].id);
+        // A name isn't shaped like an id, so it is only looked up by name
+        assert_eq!(lookups.asked(), ["name:Person"]);
+        // The pattern uses the stored id as the label, not the name that was sent
+        let visibility = NodeVisibility::default();
+        let scope = NodeScope::new(Some(&resolved), &[], &visibility);
+        assert_eq!(scope.pattern("v"), format!("(v:{})", lookups.types[0].id));
+    }
+
+    #[tokio::test]
+    async fn unknown_node_types_are_a_bad_request() {
+        let lookups = TypeLookups::new();
+        // Shaped like an id but not one of the graph's types, then tried as a name
+        for node_type in ["vZZZZ9999", "Planet"] {
+            let error = lookups.resolve(node_type).await.unwrap_err();
+            assert!(matches!(error, ApiError::BadRequest(_)), "{}", node_type);
+        }
+        assert_eq!(
+            lookups.asked(),
+            ["id:vZZZZ9999", "name:vZZZZ9999", "name:Planet"]
+        );
+    }
+
+    #[tokio::test]
+    async fn hostile_node_types_are_only_looked_up_by_name() {
+        let lookups = TypeLookups::new();
+        for node_type in [
+            "Person) DETACH DELETE (n",
+            "Person' OR '1'='1",
+            "x $$) as (v agtype); DROP TABLE app_data.users; --",
+            "`Person`",
+            "v$$ RETURN 1 $$",
+        ] {
+            let error = lookups.resolve(node_type).await.unwrap_err();
+            assert!(matches!(error, ApiError::BadRequest(_)), "{}", node_type);
+        }
+        assert!(lookups
+            .asked()
+            .iter()
+            .all(|asked| asked.starts_with("name:")));
+    }
+}
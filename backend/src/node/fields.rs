@@ -0,0 +1,51 @@
+use super::{NodeTypeAttributeDefinition, PROTECTED_PROPERTY};
+use validator::{ValidationError, ValidationErrors};
+
+// Properties every node can have, whatever its type declares
+pub const BUILT_IN_FIELDS: [&str; 4] = ["name", "created_by", "created_at", PROTECTED_PROPERTY];
+
+// Properties a listing should return, from `fields=name,status`. Nodes keep their id and
+// type either way
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeFields(Vec<String>);
+
+impl NodeFields {
+    // Parse a comma separated field list. Fields must be built in or attributes of the
+    // listed node type, so attributes can only be requested together with a node_type.
+    // No list, or an empty one, means every property
+    pub fn parse(
+        fields: Option<&str>,
+        attributes: Option<&[NodeTypeAttributeDefinition]>,
+    ) -> Result<Option<Self>, ValidationErrors> {
+        let Some(fields) = fields else {
+            return Ok(None);
+        };
+
+        let mut names: Vec<String> = Vec::new();
+        let mut errors = ValidationErrors::new();
+        for field in fields.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            let known = BUILT_IN_FIELDS.contains(&field)
+                || attributes.is_some_and(|attributes| attributes.iter().any(|a| a.name == field));
+            if !known {
+                let mut error = ValidationError::new("unknown_field");
+                error.add_param("key".into(), &field);
+                error.message = Some(match attributes {
+                    Some(_) => "Not an attribute of the node type".into(),
+                    None => "Attribute fields require a node_type".into(),
+                });
+                errors.add("fields", error);
+            } else if !names.iter().any(|name| name == field) {
+                names.push(field.to_string());
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok((!names.is_empty()).then_some(Self(names)))
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.0.iter().any(|field| field == key)
+    }
+}
@@ -0,0 +1,312 @@
+use super::{NodeTypeAttributeDataType, NodeTypeAttributeDefinition};
+use crate::utils::{cypher_key, cypher_literal, rfc3339};
+use chrono::DateTime;
+use serde_json::Value as JsonValue;
+use strum_macros::{Display, EnumString};
+use validator::{ValidationError, ValidationErrors};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum FilterOp {
+    Eq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    Contains,
+    StartsWith,
+}
+
+impl FilterOp {
+    fn cypher(&self) -> &'static str {
+        match self {
+            FilterOp::Eq => "=",
+            FilterOp::Gt => ">",
+            FilterOp::Gte => ">=",
+            FilterOp::Lt => "<",
+            FilterOp::Lte => "<=",
+            FilterOp::Contains => "CONTAINS",
+            FilterOp::StartsWith => "STARTS WITH",
+        }
+    }
+
+    // Operators that make sense for a data type
+    fn allowed_for(data_type: &NodeTypeAttributeDataType) -> &'static [FilterOp] {
+        match data_type {
+            NodeTypeAttributeDataType::String | NodeTypeAttributeDataType::Url => {
+                &[FilterOp::Eq, FilterOp::Contains, FilterOp::StartsWith]
+            }
+            NodeTypeAttributeDataType::Boolean => &[FilterOp::Eq],
+            NodeTypeAttributeDataType::Number | NodeTypeAttributeDataType::Date => &[
+                FilterOp::Eq,
+                FilterOp::Gt,
+                FilterOp::Gte,
+                FilterOp::Lt,
+                FilterOp::Lte,
+            ],
+        }
+    }
+}
+
+// A condition on one property, with the value already parsed for the attribute's data type
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyFilter {
+    pub attribute: String,
+    pub op: FilterOp,
+    pub value: JsonValue,
+}
+
+impl PropertyFilter {
+    // Parse `filter[<attribute>]=<value>` and `filter[<attribute>][<op>]=<value>` query
+    // parameters against the node type's attributes. Other parameters are ignored. Every
+    // bad filter is reported against `filter.<attribute>`
+    pub fn parse_all(
+        params: &[(String, String)],
+        attributes: &[NodeTypeAttributeDefinition],
+    ) -> Result<Vec<Self>, ValidationErrors> {
+        let mut filters = Vec::new();
+        let mut errors = ValidationErrors::new();
+        for (key, raw) in params {
+            let Some(spec) = key
+                .strip_prefix("filter[")
+                .and_then(|rest| rest.strip_suffix(']'))
+            else {
+                continue;
+            };
+            let (attribute, op) = match spec.split_once("][") {
+                Some((attribute, op)) => (attribute, Some(op)),
+                None => (spec, None),
+            };
+
+            match Self::parse(attribute, op, raw, attributes) {
+                Ok(filter) => filters.push(filter),
+                Err(error) => errors.add("filter", error),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(filters)
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn parse(
+        attribute: &str,
+        op: Option<&str>,
+        raw: &str,
+        attributes: &[NodeTypeAttributeDefinition],
+    ) -> Result<Self, ValidationError> {
+        // Every node has a name, whether or not the type declares it
+        let data_type = match attributes.iter().find(|a| a.name == attribute) {
+            Some(definition) => definition.data_type.clone(),
+            None if attribute == "name" => NodeTypeAttributeDataType::String,
+            None => {
+                return Err(filter_error(
+                    "unknown_attribute",
+                    attribute,
+                    "is not an attribute of the node type".into(),
+                ))
+            }
+        };
+
+        let op = match op {
+            None => FilterOp::Eq,
+            Some(op) => op.parse::<FilterOp>().map_err(|_| {
+                filter_error(
+                    "unknown_operator",
+                    attribute,
+                    format!("has unknown operator '{}'", op),
+                )
+            })?,
+        };
+        if !FilterOp::allowed_for(&data_type).contains(&op) {
+            return Err(filter_error(
+                "unsupported_operator",
+                attribute,
+                format!("does not support '{}' on a {} attribute", op, data_type),
+            ));
+        }
+
+        let value = parse_value(&data_type, raw).ok_or_else(|| {
+            filter_error(
+                "invalid_value",
+                attribute,
+                format!("'{}' is not a valid {}", raw, data_type),
+            )
+        })?;
+
+        Ok(Self {
+            attribute: attribute.to_string(),
+            op,
+            value,
+        })
+    }
+
+    // Render as a cypher condition on `variable`
+    pub fn to_cypher(&self, variable: &str) -> String {
+        format!(
+            "{}.{} {} {}",
+            variable,
+            cypher_key(&self.attribute),
+            self.op.cypher(),
+            cypher_literal(&self.value)
+        )
+    }
+}
+
+fn filter_error(code: &'static str, attribute: &str, message: String) -> ValidationError {
+    let mut error = ValidationError::new(code);
+    error.add_param("key".into(), &attribute);
+    error.message = Some(message.into());
+    error
+}
+
+fn parse_value(data_type: &NodeTypeAttributeDataType, raw: &str) -> Option<JsonValue> {
+    match data_type {
+        NodeTypeAttributeDataType::String | NodeTypeAttributeDataType::Url => {
+            Some(JsonValue::String(raw.to_string()))
+        }
+        NodeTypeAttributeDataType::Boolean => match raw {
+            "true" => Some(JsonValue::Bool(true)),
+            "false" => Some(JsonValue::Bool(false)),
+            _ => None,
+        },
+        NodeTypeAttributeDataType::Number => match raw.parse::<i64>() {
+            Ok(integer) => Some(JsonValue::from(integer)),
+            Err(_) => raw
+                .parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+                .map(JsonValue::Number),
+        },
+        // Dates are stored as RFC3339 strings, compared in the UTC form they are written in
+        NodeTypeAttributeDataType::Date => DateTime::parse_from_rfc3339(raw)
+            .ok()
+            .map(|date| JsonValue::String(rfc3339::format(&date.to_utc()))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn attributes() -> Vec<NodeTypeAttributeDefinition> {
+        [
+            ("title", "string"),
+            ("homepage", "url"),
+            ("active", "boolean"),
+            ("age", "number"),
+            ("born", "date"),
+        ]
+        .into_iter()
+        .map(|(name, data_type)| {
+            serde_json::from_value(json!({
+                "id": uuid::Uuid::new_v4(),
+                "type_id": "vaaaaaaaaaa",
+                "name": name,
+                "normalized_name": name,
+                "data_type": data_type,
+                "required": false,
+                "description": "",
+                "example": null,
+                "dictionary_id": null,
+                "position": 0,
+            }))
+            .unwrap()
+        })
+        .collect()
+    }
+
+    fn parse(key: &str, raw: &str) -> Result<Vec<PropertyFilter>, ValidationErrors> {
+        PropertyFilter::parse_all(&[(key.to_string(), raw.to_string())], &attributes())
+    }
+
+    fn error_code(key: &str, raw: &str) -> String {
+        let errors = parse(key, raw).unwrap_err();
+        errors.field_errors()["filter"][0].code.to_string()
+    }
+
+    #[test]
+    fn operators_per_data_type() {
+        // (filter key, value, operator accepted)
+        for (key, raw, accepted) in [
+            ("filter[title][contains]", "Ada", true),
+            ("filter[title][gt]", "Ada", false),
+            ("filter[homepage][starts_with]", "https://", true),
+            ("filter[homepage][lte]", "https://", false),
+            ("filter[active]", "true", true),
+            ("filter[active][contains]", "true", false),
+            ("filter[age][gte]", "36", true),
+            ("filter[age][starts_with]", "3", false),
+            ("filter[born][lt]", "2000-01-01T00:00:00Z", true),
+            ("filter[born][contains]", "2000", false),
+            // Every node has a name, compared as a string
+            ("filter[name][starts_with]", "A", true),
+            ("filter[name][gt]", "A", false),
+        ] {
+            match accepted {
+                true => assert!(parse(key, raw).is_ok(), "{}", key),
+                false => assert_eq!(error_code(key, raw), "unsupported_operator", "{}", key),
+            }
+        }
+        assert_eq!(error_code("filter[age][between]", "1"), "unknown_operator");
+        assert_eq!(error_code("filter[height]", "1"), "unknown_attribute");
+    }
+
+    #[test]
+    fn values_are_parsed_for_the_data_type() {
+        let filters = parse("filter[age][gt]", "36").unwrap();
+        assert_eq!(filters[0].value, json!(36));
+        assert_eq!(filters[0].to_cypher("v"), "v.`age` > 36");
+        assert_eq!(parse("filter[age]", "1.5").unwrap()[0].value, json!(1.5));
+        assert_eq!(
+            parse("filter[active]", "false").unwrap()[0].value,
+            json!(false)
+        );
+        for (key, raw) in [
+            ("filter[active]", "yes"),
+            ("filter[active]", "True"),
+            ("filter[age]", "36 years"),
+        ] {
+            assert_eq!(error_code(key, raw), "invalid_value", "{} = {}", key, raw);
+        }
+    }
+
+    #[test]
+    fn non_finite_numbers_are_rejected() {
+        for raw in ["NaN", "nan", "inf", "-inf", "infinity", "1e999"] {
+            assert_eq!(error_code("filter[age]", raw), "invalid_value", "{}", raw);
+        }
+    }
+
+    #[test]
+    fn dates_must_be_rfc3339() {
+        // Offsets are accepted and compared in the UTC form dates are stored in
+        let filters = parse("filter[born][gte]", "2024-01-01T12:00:00+02:00").unwrap();
+        assert_eq!(filters[0].value, json!("2024-01-01T10:00:00.000Z"));
+
+        for raw in [
+            "2024-01-01",
+            "2024-01-01T12:00:00",
+            "2024-1-1T12:00:00Z",
+            "01/02/2024",
+            "2024-13-01T00:00:00Z",
+            "1704110400",
+        ] {
+            assert_eq!(error_code("filter[born]", raw), "invalid_value", "{}", raw);
+        }
+    }
+
+    #[test]
+    fn other_parameters_are_ignored_and_errors_are_collected() {
+        let params = [
+            ("page".to_string(), "2".to_string()),
+            ("filter[age]".to_string(), "x".to_string()),
+            ("filter[active]".to_string(), "y".to_string()),
+        ];
+        let errors = PropertyFilter::parse_all(&params, &attributes()).unwrap_err();
+        assert_eq!(errors.field_errors()["filter"].len(), 2);
+    }
+}
@@ -0,0 +1,141 @@
+use super::Node;
+use crate::ids::GraphId;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use sqlx::{postgres::PgRow, FromRow, PgConnection, Row};
+use std::collections::HashMap;
+use strum_macros::{Display, EnumString};
+use uuid::Uuid;
+
+#[derive(PartialEq, Clone, Copy, Serialize, Debug, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum NodeChange {
+    Created,
+    // Created as a copy of another node
+    Duplicated,
+    // Properties set back to those of an earlier version
+    Restored,
+    // Deletion protection turned on or off
+    ProtectionChanged,
+}
+
+// One change to a node's properties. `before` is None for the change that created the node
+#[derive(Debug, Serialize)]
+pub struct NodeHistoryEntry {
+    pub id: Uuid,
+    // Position of the entry in the node's history, starting at 1
+    pub version: i64,
+    pub node_id: i64,
+    pub change: NodeChange,
+    pub before: Option<JsonValue>,
+    pub after: JsonValue,
+    pub changed_by: Uuid,
+    #[serde(with = "crate::utils::rfc3339")]
+    pub changed_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl<'r> FromRow<'r, PgRow> for NodeHistoryEntry {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        let change: String = row.try_get("change")?;
+
+        Ok(Self {
+            id: row.try_get("id")?,
+            version: row.try_get("version")?,
+            node_id: row.try_get("node_id")?,
+            change: change
+                .parse()
+                .map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+            before: row.try_get("before")?,
+            after: row.try_get("after")?,
+            changed_by: row.try_get("changed_by")?,
+            changed_at: row.try_get("changed_at")?,
+        })
+    }
+}
+
+impl NodeHistoryEntry {
+    // Append an entry for the node's current properties. Takes a connection so the entry
+    // is written in the same transaction as the change it records
+    pub async fn record(
+        conn: &mut PgConnection,
+        node: &Node,
+        change: NodeChange,
+        before: Option<&HashMap<String, JsonValue>>,
+        changed_by: Uuid,
+    ) -> Result<(), sqlx::Error> {
+        let query = r#"
+            INSERT INTO app_data.node_history (id, graph_id, node_id, change, before, after, changed_by, changed_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#;
+        sqlx::query(query)
+            .bind(Uuid::new_v4())
+            .bind(node.graph_id())
+            .bind(node.id())
+            .bind(change.to_string())
+            .bind(before.map(|before| serde_json::json!(before)))
+            .bind(serde_json::json!(node.properties()))
+            .bind(changed_by)
+            .bind(chrono::Utc::now())
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+
+    // Oldest first, with the total number of entries for the node
+    pub async fn list(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        node_id: i64,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<Self>, i64), sqlx::Error> {
+        let query = r#"
+            SELECT *, ROW_NUMBER() OVER (ORDER BY changed_at, id) AS version,
+                   COUNT(*) OVER () AS total
+            FROM app_data.node_history
+            WHERE graph_id = $1 AND node_id = $2
+            ORDER BY changed_at, id
+            LIMIT $3 OFFSET $4
+        "#;
+        let rows = sqlx::query(query)
+            .bind(graph_id)
+            .bind(node_id)
+            .bind(page_size as i64)
+            .bind(((page - 1) * page_size) as i64)
+            .fetch_all(pool)
+            .await?;
+
+        let total = match rows.first() {
+            Some(row) => row.try_get("total")?,
+            None => 0,
+        };
+        let entries = rows
+            .iter()
+            .map(NodeHistoryEntry::from_row)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((entries, total))
+    }
+
+    pub async fn version(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        node_id: i64,
+        version: i64,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let query = r#"
+            SELECT * FROM (
+                SELECT *, ROW_NUMBER() OVER (ORDER BY changed_at, id) AS version
+                FROM app_data.node_history
+                WHERE graph_id = $1 AND node_id = $2
+            ) h
+            WHERE version = $3
+        "#;
+        sqlx::query_as::<_, NodeHistoryEntry>(query)
+            .bind(graph_id)
+            .bind(node_id)
+            .bind(version)
+            .fetch_optional(pool)
+            .await
+    }
+}
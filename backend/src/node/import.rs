@@ -0,0 +1,354 @@
+use super::{Node, NodeChange, NodeHistoryEntry, NodeType, NodeTypeAttributeDefinition};
+use crate::csv;
+use crate::graph::{GraphInfo, NodeNameUniqueness};
+use crate::utils::{normalize, rfc3339};
+use crate::validation::{
+    deprecated_message, AttributeValidationError, ValidationOutcome, WriteRules,
+};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use sqlx::PgConnection;
+use std::collections::{HashMap, HashSet};
+use tracing::error;
+use uuid::Uuid;
+
+// Most data rows accepted by one CSV import
+pub const MAX_IMPORT_ROWS: usize = 5000;
+// Nodes created per transaction
+const IMPORT_CHUNK: usize = 200;
+
+// A row that was not imported. Rows are numbered like spreadsheet lines, the header
+// being row 1
+#[derive(Debug, Serialize)]
+pub struct ImportRowError {
+    pub row: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<String>,
+    pub message: String,
+}
+
+impl ImportRowError {
+    fn new(row: usize, column: Option<&str>, message: impl Into<String>) -> Self {
+        Self {
+            row,
+            column: column.map(str::to_string),
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct NodeImportReport {
+    // Nothing was written, the report only tells which rows would fail
+    pub dry_run: bool,
+    // Rows that passed validation
+    pub valid: usize,
+    pub created: usize,
+    // Public ids of the created nodes, in row order
+    pub ids: Vec<String>,
+    pub errors: Vec<ImportRowError>,
+}
+
+// A CSV upload whose header has been checked against a node type
+pub struct NodeCsvImport {
+    node_type: NodeType,
+    attributes: Vec<NodeTypeAttributeDefinition>,
+    // Property each column is stored as
+    columns: Vec<String>,
+    records: Vec<csv::CsvRecord>,
+    // Server-wide cap on string values
+    max_value_length: usize,
+}
+
+struct ValidRow {
+    row: usize,
+    properties: HashMap<String, JsonValue>,
+}
+
+impl NodeCsvImport {
+    // Parse the upload and map its header to the node type's attributes, by name or
+    // normalized name. Unknown, repeated and missing required columns are reported here,
+    // before any row is looked at
+    pub fn prepare(
+        body: &str,
+        node_type: NodeType,
+        attributes: Vec<NodeTypeAttributeDefinition>,
+        max_value_length: usize,
+    ) -> Result<Self, String> {
+        let mut records = csv::parse(body).map_err(|e| e.to_string())?.into_iter();
+        let header = records
+            .next()
+            .ok_or_else(|| "The CSV is empty, a header row is required".to_string())?;
+        let records: Vec<csv::CsvRecord> = records.collect();
+        if records.is_empty() {
+            return Err("The CSV has no rows below its header".into());
+        }
+        if records.len() > MAX_IMPORT_ROWS {
+            return Err(format!(
+                "At most {} rows can be imported at once",
+                MAX_IMPORT_ROWS
+            ));
+        }
+
+        let mut columns = Vec::with_capacity(header.fields.len());
+        let mut unknown = Vec::new();
+        for field in &header.fields {
+            let field = field.trim();
+            let property = if field.eq_ignore_ascii_case("name") {
+                Some("name")
+            } else {
+                attributes
+                    .iter()
+                    .find(|a| a.name == field)
+                    .or_else(|| {
+                        attributes
+                            .iter()
+                            .find(|a| a.normalized_name == normalize(field))
+                    })
+                    .map(|a| a.name.as_str())
+            };
+            match property {
+                Some(property) if columns.iter().any(|c| c == property) => {
+                    return Err(format!("Column '{}' is given more than once", property));
+                }
+                Some(property) => columns.push(property.to_string()),
+                None => unknown.push(format!("'{}'", field)),
+            }
+        }
+        if !unknown.is_empty() {
+            return Err(format!(
+                "Unknown column(s) for node type '{}': {}",
+                node_type.name,
+                unknown.join(", ")
+            ));
+        }
+
+        let missing: Vec<String> = std::iter::once("name")
+            .chain(
+                attributes
+                    .iter()
+                    .filter(|a| a.required)
+                    .map(|a| a.name.as_str()),
+            )
+            .filter(|name| !columns.iter().any(|c| c == name))
+            .map(|name| format!("'{}'", name))
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!(
+                "Missing required column(s): {}",
+                missing.join(", ")
+            ));
+        }
+
+        Ok(Self {
+            node_type,
+            attributes,
+            columns,
+            records,
+            max_value_length,
+        })
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.records.len()
+    }
+
+    // Check every row, then create the valid ones in chunks of transactions. A chunk that
+    // fails to save reports its rows as errors without undoing the chunks before it
+    pub async fn run(
+        self,
+        pool: &sqlx::PgPool,
+        graph: &GraphInfo,
+        created_by: Uuid,
+    ) -> Result<(NodeImportReport, Vec<Node>), sqlx::Error> {
+        let (valid, mut errors) = self.check(pool, graph).await?;
+
+        let mut nodes = Vec::with_capacity(valid.len());
+        for chunk in valid.chunks(IMPORT_CHUNK) {
+            let mut tx = pool.begin().await?;
+            let created = match self.insert_chunk(&mut tx, graph, chunk, created_by).await {
+                Ok(created) => tx.commit().await.map(|_| created),
+                Err(e) => Err(e),
+            };
+            match created {
+                Ok(created) => nodes.extend(created),
+                Err(e) => {
+                    error!("Failed to import nodes into {}: {:?}", graph.graph_id, e);
+                    errors.extend(
+                        chunk
+                            .iter()
+                            .map(|row| ImportRowError::new(row.row, None, "Could not be saved")),
+                    );
+                }
+            }
+        }
+        errors.sort_by_key(|e| e.row);
+
+        let report = NodeImportReport {
+            dry_run: false,
+            valid: valid.len(),
+            created: nodes.len(),
+            ids: nodes
+                .iter()
+                .filter_map(|node| node.public_id().map(str::to_string))
+                .collect(),
+            errors,
+        };
+        Ok((report, nodes))
+    }
+
+    // Check every row like `run` does, without writing anything
+    pub async fn dry_run(
+        self,
+        pool: &sqlx::PgPool,
+        graph: &GraphInfo,
+    ) -> Result<NodeImportReport, sqlx::Error> {
+        let (valid, errors) = self.check(pool, graph).await?;
+        Ok(NodeImportReport {
+            dry_run: true,
+            valid: valid.len(),
+            created: 0,
+            ids: Vec::new(),
+            errors,
+        })
+    }
+
+    // Split the rows into those that can be created and the errors of the others. Cells
+    // are coerced to the attribute types, empty cells are left out
+    async fn check(
+        &self,
+        pool: &sqlx::PgPool,
+        graph: &GraphInfo,
+    ) -> Result<(Vec<ValidRow>, Vec<ImportRowError>), sqlx::Error> {
+        let mut errors = Vec::new();
+        let mut valid = Vec::new();
+        let mut names = HashSet::new();
+        for record in &self.records {
+            let row = record.line;
+            if record.fields.len() != self.columns.len() {
+                errors.push(ImportRowError::new(
+                    row,
+                    None,
+                    format!(
+                        "Expected {} columns, found {}",
+                        self.columns.len(),
+                        record.fields.len()
+                    ),
+                ));
+                continue;
+            }
+
+            let properties: HashMap<String, JsonValue> = self
+                .columns
+                .iter()
+                .zip(&record.fields)
+                .filter(|(_, cell)| !cell.trim().is_empty())
+                .map(|(column, cell)| (column.clone(), JsonValue::String(cell.clone())))
+                .collect();
+
+            let name = match properties.get("name") {
+                Some(JsonValue::String(name)) => name.trim().to_string(),
+                _ => {
+                    errors.push(ImportRowError::new(row, Some("name"), "required"));
+                    continue;
+                }
+            };
+            if let Some(message) = self.name_conflict(pool, graph, &name, &mut names).await? {
+                errors.push(ImportRowError::new(row, Some("name"), message));
+                continue;
+            }
+
+            let rules = WriteRules {
+                strict: false,
+                max_value_length: self.max_value_length,
+            };
+            let outcome = ValidationOutcome::validate_write(&self.attributes, properties, rules);
+            if !outcome.is_valid() {
+                errors.extend(outcome.errors.iter().map(|e| {
+                    let message = match e {
+                        AttributeValidationError::MissingAttribute { .. } => "required".into(),
+                        AttributeValidationError::WrongType { expected, .. } => {
+                            format!("must be of type {}", expected)
+                        }
+                        AttributeValidationError::NotStrict { message, .. } => message.clone(),
+                        AttributeValidationError::Deprecated { replaced_by, .. } => {
+                            deprecated_message(replaced_by)
+                        }
+                        AttributeValidationError::TooLong { max_length, .. } => {
+                            format!("must be at most {} characters", max_length)
+                        }
+                    };
+                    ImportRowError::new(row, Some(e.attribute()), message)
+                }));
+                continue;
+            }
+            let mut properties = outcome.coerced_properties;
+            properties.insert("name".to_string(), JsonValue::String(name));
+            valid.push(ValidRow { row, properties });
+        }
+        Ok((valid, errors))
+    }
+
+    // Why the name can't be used, following the graph's uniqueness setting. Names earlier
+    // in the file count as taken
+    async fn name_conflict(
+        &self,
+        pool: &sqlx::PgPool,
+        graph: &GraphInfo,
+        name: &str,
+        names: &mut HashSet<String>,
+    ) -> Result<Option<String>, sqlx::Error> {
+        let taken = match graph.node_name_uniqueness {
+            NodeNameUniqueness::None => return Ok(None),
+            NodeNameUniqueness::PerType => Node::get_by_name_opt(
+                pool,
+                &graph.graph_id,
+                &self.node_type.id,
+                name,
+                graph.case_insensitive_names,
+            )
+            .await?
+            .is_some(),
+            NodeNameUniqueness::PerGraph => {
+                Node::name_exists(pool, &graph.graph_id, name, graph.case_insensitive_names).await?
+            }
+        };
+        if taken {
+            return Ok(Some(format!("A node named '{}' already exists", name)));
+        }
+        let key = if graph.case_insensitive_names {
+            name.to_lowercase()
+        } else {
+            name.to_string()
+        };
+        if !names.insert(key) {
+            return Ok(Some(format!("'{}' is used by an earlier row", name)));
+        }
+        Ok(None)
+    }
+
+    async fn insert_chunk(
+        &self,
+        conn: &mut PgConnection,
+        graph: &GraphInfo,
+        chunk: &[ValidRow],
+        created_by: Uuid,
+    ) -> Result<Vec<Node>, sqlx::Error> {
+        let created_at = JsonValue::String(rfc3339::format(&chrono::Utc::now()));
+        let mut nodes = Vec::with_capacity(chunk.len());
+        for row in chunk {
+            let mut properties = row.properties.clone();
+            properties.insert(
+                "created_by".to_string(),
+                JsonValue::String(created_by.to_string()),
+            );
+            properties.insert("created_at".to_string(), created_at.clone());
+            let node =
+                Node::insert(&mut *conn, &graph.graph_id, &self.node_type.id, &properties).await?;
+            NodeHistoryEntry::record(&mut *conn, &node, NodeChange::Created, None, created_by)
+                .await?;
+            nodes.push(node);
+        }
+        Ok(nodes)
+    }
+}
@@ -1,7 +1,23 @@
+mod detail;
+mod edge_summary;
 mod endpoints;
+mod fields;
+mod filter;
+mod history;
+mod import;
 mod node;
 mod node_types;
+mod scope;
+mod sort;
 
+pub use detail::*;
+pub use edge_summary::*;
 pub use endpoints::*;
+pub use fields::*;
+pub use filter::*;
+pub use history::*;
+pub use import::*;
 pub use node::*;
 pub use node_types::*;
+pub use scope::*;
+pub use sort::*;
@@ -1,144 +1,255 @@
-use super::{CreateNodeRequest, NodeType};
-use crate::ag::{AgType, Vertex};
-use crate::node::{NodeTypeAttributeDataType, NodeTypeAttributeDefinition};
-use crate::utils::generate_props_clause;
+use super::{where_all, NodeChange, NodeFields, NodeHistoryEntry, NodeScope, NodeSort, NodeType};
+use crate::ag::{AgLookupError, Vertex};
+use crate::auth::{SecurityEvent, SecurityEventKind};
+use crate::cypher::Cypher;
+use crate::edge::Edge;
+use crate::ids::{EdgeTypeId, GraphId, NodeTypeId};
+use crate::node::{NodeTypeAttributeDefinition, NodeTypeInheritanceError};
+use crate::utils::{
+    create_id, cypher_key, cypher_literal, cypher_string, generate_props_clause,
+    generate_set_clause, rfc3339, TYPE_ID_LENGTH,
+};
+use crate::validation::{
+    RequiredAttributeHint, ValidationErrorList, ValidationOutcome, ValidationWarning, WriteRules,
+};
 use futures::future::try_join_all;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
+use sqlx::PgConnection;
 use std::collections::HashMap;
-use std::fmt;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct CreateNodeResponse {
-    pub id: i64,
+// Property marking a node as protected from deletion. Only set through Node::set_protected
+pub const PROTECTED_PROPERTY: &str = "protected";
+
+// Lowercased copy of the name kept on every node, matched instead of the name when the
+// graph has case-insensitive names. Internal, so it is left out of Node's properties
+pub const NAME_LOWER_PROPERTY: &str = "name_lower";
+
+// Short stable id of the node, "n" followed by TYPE_ID_LENGTH characters like type ids.
+// Set once at creation and used in URLs instead of the vertex id, which changes whenever
+// the node is recreated. Surfaced as Node::public_id rather than as a property
+pub const PUBLIC_ID_PROPERTY: &str = "public_id";
+
+// Page size of node listings, which edge listings share
+pub const LIST_PAGE_SIZE: u32 = 5;
+
+// Properties managed by the backend, which can't be declared as attributes
+pub const RESERVED_PROPERTIES: [&str; 3] =
+    [PROTECTED_PROPERTY, NAME_LOWER_PROPERTY, PUBLIC_ID_PROPERTY];
+
+// How many nodes without a public id are given one per transaction
+const PUBLIC_ID_BACKFILL_PAGE_SIZE: usize = 1000;
+
+pub fn new_public_id() -> String {
+    format!("n{}", create_id(TYPE_ID_LENGTH))
+}
+
+// Take the internal properties out of a vertex's properties, returning the public id
+fn split_internal_properties(properties: &mut HashMap<String, JsonValue>) -> Option<String> {
+    properties.remove(NAME_LOWER_PROPERTY);
+    match properties.remove(PUBLIC_ID_PROPERTY) {
+        Some(JsonValue::String(public_id)) => Some(public_id),
+        _ => None,
+    }
+}
+
+// Set the lowercased name next to the name, if there is one
+fn with_name_lower(properties: &mut HashMap<String, JsonValue>) {
+    if let Some(JsonValue::String(name)) = properties.get("name") {
+        let name_lower = JsonValue::String(name.to_lowercase());
+        properties.insert(NAME_LOWER_PROPERTY.to_string(), name_lower);
+    }
+}
+
+// Property map pattern matching a node's name
+fn name_pattern(name: &str, case_insensitive: bool) -> String {
+    if case_insensitive {
+        format!(
+            "{{{}: {}}}",
+            NAME_LOWER_PROPERTY,
+            cypher_string(&name.to_lowercase())
+        )
+    } else {
+        format!("{{name: {}}}", cypher_string(name))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Node {
-    graph_id: String,
-    node_type: String,
+    id: i64,
+    // None only for nodes the startup backfill hasn't reached yet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    public_id: Option<String>,
+    graph_id: GraphId,
+    // None when the vertex's label has no node type any more, e.g. its type was deleted
+    // while nodes remained. `type_missing` is set then, so clients can tell such nodes apart
+    node_type: Option<NodeTypeId>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    type_missing: bool,
     properties: HashMap<String, JsonValue>,
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum CreateNodeError {
     #[error("Validation error: {0}")]
-    ValidationError(ValidationErrorList),
+    ValidationError(ValidationErrorList, Vec<RequiredAttributeHint>),
 
     #[error("Database error: {0}")]
     DatabaseError(#[from] sqlx::Error),
+
+    #[error("Inheritance error: {0}")]
+    Inheritance(#[from] NodeTypeInheritanceError),
 }
 
-#[derive(Debug)]
-pub enum AttributeValidationError {
-    MissingAttribute {
-        name: String,
-    },
-    WrongType {
-        name: String,
-        expected: &'static str,
-    },
+#[derive(Debug, thiserror::Error)]
+pub enum RestoreNodeError {
+    // The snapshot doesn't satisfy the node type as it is defined now
+    #[error("Snapshot conflicts with the current schema: {0}")]
+    Conflict(ValidationErrorList),
+
+    #[error("Snapshot is not a property map")]
+    InvalidSnapshot,
+
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Inheritance error: {0}")]
+    Inheritance(#[from] NodeTypeInheritanceError),
 }
 
-impl fmt::Display for AttributeValidationError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            AttributeValidationError::MissingAttribute { name } => {
-                write!(f, "Missing attribute: {}", name)
-            }
-            AttributeValidationError::WrongType { name, expected } => {
-                write!(f, "Attribute '{}' must be of type {}", name, expected)
-            }
-        }
+impl Node {
+    pub fn id(&self) -> i64 {
+        self.id
     }
-}
 
-#[derive(Debug)]
-pub struct ValidationErrorList(pub Vec<AttributeValidationError>);
+    pub fn public_id(&self) -> Option<&str> {
+        self.public_id.as_deref()
+    }
 
-impl fmt::Display for ValidationErrorList {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let messages: Vec<String> = self.0.iter().map(|e| e.to_string()).collect();
-        write!(f, "{}", messages.join("; "))
+    pub fn graph_id(&self) -> &GraphId {
+        &self.graph_id
     }
-}
 
-impl IntoIterator for ValidationErrorList {
-    type Item = AttributeValidationError;
-    type IntoIter = std::vec::IntoIter<AttributeValidationError>;
+    pub fn node_type(&self) -> Option<&NodeTypeId> {
+        self.node_type.as_ref()
+    }
 
-    fn into_iter(self) -> Self::IntoIter {
-        self.0.into_iter()
+    pub fn properties(&self) -> &HashMap<String, JsonValue> {
+        &self.properties
     }
-}
 
-impl Node {
-    async fn try_from(
+    // Drop every property not in `fields`
+    pub fn project(&mut self, fields: &NodeFields) {
+        self.properties.retain(|key, _| fields.contains(key));
+    }
+
+    pub async fn try_from(
         pool: &sqlx::PgPool,
         vertex: Vertex,
-        graph_id: &str,
+        graph_id: &GraphId,
     ) -> Result<Self, serde_json::Error> {
-        let node_type = NodeType::from_id(pool, graph_id, &vertex.label)
-            .await
-            .map_err(|e| {
+        // A node whose type is gone is still returned, so one of them doesn't fail a listing.
+        // A label that isn't shaped like a type id can't belong to a type
+        let found = match NodeTypeId::parse(&vertex.label) {
+            Ok(node_type_id) => NodeType::from_id(pool, graph_id, &node_type_id).await,
+            Err(_) => Err(sqlx::Error::RowNotFound),
+        };
+        let node_type = match found {
+            Ok(node_type) => Some(node_type.id),
+            Err(sqlx::Error::RowNotFound) => {
+                warn!(
+                    "Node {} of graph {} has label '{}', which has no node type",
+                    vertex.id, graph_id, vertex.label
+                );
+                None
+            }
+            Err(e) => {
                 // Create a JSON error with a custom message
-                serde_json::Error::io(std::io::Error::new(
+                return Err(serde_json::Error::io(std::io::Error::new(
                     std::io::ErrorKind::Other,
                     format!(
                         "Error fetching node type for label '{}': {}",
                         &vertex.label, e
                     ),
-                ))
-            })?;
+                )));
+            }
+        };
 
-        let properties: HashMap<String, JsonValue> = serde_json::from_value(vertex.properties)?;
+        let mut properties: HashMap<String, JsonValue> = serde_json::from_value(vertex.properties)?;
+        let public_id = split_internal_properties(&mut properties);
         let node = Node {
-            graph_id: graph_id.to_string(),
-            node_type: node_type.id,
+            id: vertex.id,
+            public_id,
+            graph_id: graph_id.clone(),
+            type_missing: node_type.is_none(),
+            node_type,
             properties,
         };
         Ok(node)
     }
 
-    fn from_request(
-        // TODO: Add validation
-        request: CreateNodeRequest,
-        graph_id: String,
-    ) -> Result<Self, serde_json::Error> {
-        Ok(Self {
-            graph_id,
-            node_type: request.node_type,
-            properties: request.properties,
+    // Build a node straight from a vertex. Vertex labels are node type ids, so this
+    // skips the node type lookup for callers that must stay on a single connection. The
+    // label is taken as the type without checking that the type still exists, unless it
+    // isn't shaped like a type id
+    pub fn from_vertex(vertex: Vertex, graph_id: &GraphId) -> Result<Self, serde_json::Error> {
+        let mut properties: HashMap<String, JsonValue> = serde_json::from_value(vertex.properties)?;
+        let public_id = split_internal_properties(&mut properties);
+        let node_type = NodeTypeId::parse(&vertex.label).ok();
+        Ok(Node {
+            id: vertex.id,
+            public_id,
+            graph_id: graph_id.clone(),
+            type_missing: node_type.is_none(),
+            node_type,
+            properties,
         })
     }
 
     pub async fn list(
         pool: &sqlx::PgPool,
-        graph_id: &str,
-        node_type: Option<&str>,
+        graph_id: &GraphId,
+        scope: &NodeScope<'_>,
+        sort: &NodeSort,
         page: Option<u32>,
     ) -> Result<Vec<Self>, sqlx::Error> {
         let page = page.unwrap_or(1);
-        let page_size = 5;
+        let page_size = LIST_PAGE_SIZE;
         let offset = (page - 1) * page_size;
+        Self::list_window(pool, graph_id, scope, sort, offset, page_size).await
+    }
 
-        let query = if node_type.is_some() {
-            format!(
-                "SELECT * FROM cypher('{}', $$ MATCH (v:{}) RETURN v ORDER BY v.name SKIP {} LIMIT {} $$) as (row agtype)",
-                graph_id, node_type.unwrap(), offset, page_size
-            )
-        } else {
+    // Nodes in `sort` order, skipping the first `offset` and returning at most `limit`
+    pub async fn list_window(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        scope: &NodeScope<'_>,
+        sort: &NodeSort,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let query = Cypher::new(
+            graph_id,
             format!(
-                "SELECT * FROM cypher('{}', $$ MATCH (v) RETURN v ORDER BY v.name SKIP {} LIMIT {} $$) as (row agtype)",
-                graph_id, offset, page_size
-            )
-        };
+                "MATCH {} {} RETURN v ORDER BY {} SKIP {} LIMIT {}",
+                scope.pattern("v"),
+                scope.where_clause("v"),
+                sort.order_by("v"),
+                offset,
+                limit
+            ),
+        );
 
-        let ag_rows = sqlx::query_as::<_, AgType>(&query)
-            .fetch_all(&*pool)
-            .await?;
+        // A label that no longer exists (e.g. its type was deleted) has no nodes to list
+        let ag_rows = match query.fetch_all(&*pool).await {
+            Ok(rows) => rows,
+            Err(e) if AgLookupError::classify(&e) == Some(AgLookupError::LabelNotFound) => {
+                return Ok(Vec::new())
+            }
+            Err(e) => return Err(e),
+        };
 
         let vertices: Vec<Vertex> = ag_rows
             .iter()
@@ -157,128 +268,605 @@ impl Node {
         Ok(nodes)
     }
 
-    pub async fn get_by_name(
+    // Nodes without any edges, ordered by name, with the total number of such nodes
+    pub async fn orphans(
         pool: &sqlx::PgPool,
-        graph_id: &str,
-        node_type: &str,
+        graph_id: &GraphId,
+        scope: &NodeScope<'_>,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<Self>, i64), sqlx::Error> {
+        // Counted and listed with the same pattern and conditions
+        let mut conditions = scope.conditions("n");
+        conditions.push("NOT (n)--()".to_string());
+        let (pattern, where_clause) = (scope.pattern("n"), where_all(&conditions));
+        let count_query = Cypher::new(
+            graph_id,
+            format!("MATCH {} {} RETURN count(n)", pattern, where_clause),
+        )
+        .returning("total agtype");
+        let page_query = Cypher::new(
+            graph_id,
+            format!(
+                "MATCH {} {} RETURN n ORDER BY n.name, id(n) SKIP {} LIMIT {}",
+                pattern,
+                where_clause,
+                (page - 1) * page_size,
+                page_size
+            ),
+        );
+
+        // A label that no longer exists has no nodes
+        let total = match count_query.fetch_one(pool).await {
+            Ok(total) => total
+                .0
+                .into_scalar()
+                .map_err(|e| sqlx::Error::Decode(Box::new(e)))?
+                .as_i64()
+                .unwrap_or_default(),
+            Err(e) if AgLookupError::classify(&e) == Some(AgLookupError::LabelNotFound) => {
+                return Ok((Vec::new(), 0))
+            }
+            Err(e) => return Err(e),
+        };
+        if total == 0 {
+            return Ok((Vec::new(), 0));
+        }
+
+        let ag_rows = page_query.fetch_all(pool).await?;
+        let nodes = ag_rows
+            .into_iter()
+            .map(|ag_row| {
+                Vertex::try_from(ag_row)
+                    .and_then(|vertex| Node::from_vertex(vertex, graph_id))
+                    .map_err(|e| sqlx::Error::Decode(Box::new(e)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((nodes, total))
+    }
+
+    // Look up a node by type and name, ignoring case if `case_insensitive` is set. A missing
+    // node type, label or node is None; only unexpected database errors are returned as errors
+    pub async fn get_by_name_opt(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        node_type_id: &NodeTypeId,
         name: &str,
-    ) -> Result<Self, sqlx::Error> {
-        let node_type = NodeType::from_id(pool, graph_id, node_type).await?;
-        let escaped_name = name.replace("'", "''");
-        let query = format!(
-            "SELECT * FROM cypher('{}', $$ MATCH (n:{} {{name: '{}'}}) RETURN n $$) as (row agtype)",
+        case_insensitive: bool,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let node_type = match NodeType::from_id(pool, graph_id, node_type_id).await {
+            Ok(node_type) => node_type,
+            Err(sqlx::Error::RowNotFound) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let query = Cypher::new(
             graph_id,
-            &node_type.id,
-            &escaped_name
+            format!(
+                "MATCH (n:{} {}) RETURN n LIMIT 1",
+                &node_type.id,
+                name_pattern(name, case_insensitive)
+            ),
         );
 
-        let ag_row = sqlx::query_as::<_, AgType>(&query)
-            .fetch_one(&*pool)
-            .await?;
+        let ag_row = match query.fetch_optional(pool).await {
+            Ok(Some(ag_row)) => ag_row,
+            Ok(None) => return Ok(None),
+            Err(e) if AgLookupError::classify(&e) == Some(AgLookupError::LabelNotFound) => {
+                return Ok(None)
+            }
+            Err(e) => return Err(e),
+        };
 
-        let vertex: Vertex =
-            Vertex::try_from(ag_row).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+        Vertex::try_from(ag_row)
+            .and_then(|vertex| Node::from_vertex(vertex, graph_id))
+            .map(Some)
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))
+    }
 
-        let node = Node::try_from(pool, vertex, graph_id)
-            .await
-            .map_err(|e| sqlx::Error::Decode(Box::new(e)));
-        node
+    // Check whether a node of any type in the graph already has the given name, ignoring
+    // case if `case_insensitive` is set
+    pub async fn name_exists(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        name: &str,
+        case_insensitive: bool,
+    ) -> Result<bool, sqlx::Error> {
+        let query = Cypher::new(
+            graph_id,
+            format!(
+                "MATCH (n {}) RETURN n LIMIT 1",
+                name_pattern(name, case_insensitive)
+            ),
+        );
+
+        let ag_row = query.fetch_optional(pool).await?;
+        Ok(ag_row.is_some())
+    }
+
+    // Set the lowercased name on nodes created before it was kept
+    pub async fn backfill_name_lower(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+    ) -> Result<(), sqlx::Error> {
+        let query = Cypher::new(
+            graph_id,
+            format!(
+                "MATCH (n) WHERE n.name IS NOT NULL AND n.{} IS NULL SET n.{} = toLower(n.name)",
+                NAME_LOWER_PROPERTY, NAME_LOWER_PROPERTY
+            ),
+        );
+        query.execute(pool).await?;
+        Ok(())
+    }
+
+    // Fetch the nodes matching the given vertex ids, returned in the order the ids were given
+    pub async fn get_many(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        ids: &[i64],
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let id_list = ids
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let query = Cypher::new(
+            graph_id,
+            format!("MATCH (n) WHERE id(n) IN [{}] RETURN n", id_list),
+        );
+
+        let ag_rows = query.fetch_all(pool).await?;
+
+        let node_futures = ag_rows.into_iter().map(|ag_row| async move {
+            let vertex = Vertex::try_from(ag_row).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+            Node::try_from(pool, vertex, graph_id)
+                .await
+                .map_err(|e| sqlx::Error::Decode(Box::new(e)))
+        });
+        let nodes = try_join_all(node_futures).await?;
+
+        // Preserve the order of the requested ids
+        let mut nodes_by_id: HashMap<i64, Node> =
+            nodes.into_iter().map(|node| (node.id, node)).collect();
+        let ordered = ids.iter().filter_map(|id| nodes_by_id.remove(id)).collect();
+        Ok(ordered)
     }
 
     pub async fn create(
         pool: &sqlx::PgPool,
-        create_node_request: CreateNodeRequest,
+        graph_id: &GraphId,
+        node_type_id: &NodeTypeId,
+        properties: HashMap<String, JsonValue>,
         created_by: Uuid,
-        graph_id: String,
-    ) -> Result<(), CreateNodeError> {
+        rules: WriteRules,
+    ) -> Result<(Self, Vec<ValidationWarning>), CreateNodeError> {
         // First, fetch the NodeType
-        let node_type = NodeType::from_id(pool, &graph_id, &create_node_request.node_type).await?;
-
-        // Then, fetch all attribute definitions for this node type
-        let attributes = NodeTypeAttributeDefinition::from_node_type(pool, &node_type).await?;
-
-        let mut errors = Vec::new();
-        // Validate that all required attributes are present and valid
-        for attr in &attributes {
-            if attr.required {
-                match create_node_request.properties.get(&attr.name) {
-                    None => {
-                        errors.push(AttributeValidationError::MissingAttribute {
-                            name: attr.name.clone(),
-                        });
-                    }
-                    Some(value) => match attr.data_type {
-                        NodeTypeAttributeDataType::Number => {
-                            if !value.is_number() {
-                                errors.push(AttributeValidationError::WrongType {
-                                    name: attr.name.clone(),
-                                    expected: "number",
-                                });
-                            }
-                        }
-                        NodeTypeAttributeDataType::Boolean => {
-                            if !value.is_boolean() {
-                                errors.push(AttributeValidationError::WrongType {
-                                    name: attr.name.clone(),
-                                    expected: "boolean",
-                                });
-                            }
-                        }
-                        NodeTypeAttributeDataType::Date => {
-                            if let Some(str_val) = value.as_str() {
-                                if chrono::DateTime::parse_from_rfc3339(str_val).is_err() {
-                                    errors.push(AttributeValidationError::WrongType {
-                                        name: attr.name.clone(),
-                                        expected: "RFC3339 date string",
-                                    });
-                                }
-                            } else {
-                                errors.push(AttributeValidationError::WrongType {
-                                    name: attr.name.clone(),
-                                    expected: "RFC3339 date string",
-                                });
-                            }
-                        }
-                        NodeTypeAttributeDataType::String => {
-                            debug!("No validation needed for string type");
-                        }
-                    },
-                }
-            }
-        }
-        // If any errors were collected, return them as a typed error
-        if !errors.is_empty() {
-            return Err(CreateNodeError::ValidationError(ValidationErrorList(
-                errors,
-            )));
+        let node_type = NodeType::from_id(pool, graph_id, node_type_id).await?;
+
+        // Then, fetch all attribute definitions for this node type, including inherited ones
+        let lineage = node_type.lineage(pool).await?;
+        let attributes = NodeTypeAttributeDefinition::resolve(pool, &lineage).await?;
+        let node_type = &lineage[0];
+
+        let outcome = ValidationOutcome::validate_write(&attributes, properties, rules);
+        if !outcome.is_valid() {
+            return Err(CreateNodeError::ValidationError(
+                ValidationErrorList(outcome.errors),
+                RequiredAttributeHint::for_attributes(&attributes),
+            ));
         }
 
         debug!("All attributes are valid for node type: {}", &node_type.id);
 
-        let mut node = Node::from_request(create_node_request, graph_id)
-            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+        let mut properties = outcome.coerced_properties;
 
         // Add created_by and created_at to properties
-        node.properties.insert(
+        properties.insert(
             "created_by".to_string(),
             JsonValue::String(created_by.to_string()),
         );
-        node.properties.insert(
+        properties.insert(
             "created_at".to_string(),
-            JsonValue::String(chrono::Utc::now().to_rfc3339()),
+            JsonValue::String(rfc3339::format(&chrono::Utc::now())),
         );
 
-        let props_clause = generate_props_clause(&node.properties);
-        let query = format!(
-            "SELECT * FROM cypher('{}', $$ CREATE (n:{} {}) RETURN n $$) as (row agtype)",
-            &node.graph_id, &node.node_type, &props_clause
+        info!("Creating node in graph: {}, by: {}", &graph_id, created_by);
+        let mut transaction = pool.begin().await?;
+        let node = Node::insert(&mut transaction, graph_id, &node_type.id, &properties).await?;
+        NodeHistoryEntry::record(
+            &mut transaction,
+            &node,
+            NodeChange::Created,
+            None,
+            created_by,
+        )
+        .await?;
+        transaction.commit().await?;
+        Ok((node, outcome.warnings))
+    }
+
+    // Copy a node's properties into a new node with the given name and fresh creation
+    // stamps, optionally copying its edges too. Edges the source has to itself are
+    // copied as edges from the copy to itself. Returns the copy and the number of edges copied
+    pub async fn duplicate(
+        pool: &sqlx::PgPool,
+        source: &Node,
+        name: &str,
+        created_by: Uuid,
+        copy_edges: bool,
+    ) -> Result<(Self, usize), sqlx::Error> {
+        let mut properties = source.properties.clone();
+        properties.remove(PROTECTED_PROPERTY);
+        properties.insert("name".to_string(), JsonValue::String(name.to_string()));
+        properties.insert(
+            "created_by".to_string(),
+            JsonValue::String(created_by.to_string()),
+        );
+        properties.insert(
+            "created_at".to_string(),
+            JsonValue::String(rfc3339::format(&chrono::Utc::now())),
         );
 
         info!(
-            "Creating node in graph: {}, by: {}",
-            &node.graph_id, created_by
+            "Duplicating node {} in graph: {}, by: {}",
+            source.id, &source.graph_id, created_by
+        );
+        // Nodes are looked up by type before being duplicated, so they have one
+        let node_type = source.node_type().ok_or(sqlx::Error::RowNotFound)?;
+        let mut transaction = pool.begin().await?;
+        let node = Node::insert(&mut transaction, &source.graph_id, node_type, &properties).await?;
+        NodeHistoryEntry::record(
+            &mut transaction,
+            &node,
+            NodeChange::Duplicated,
+            None,
+            created_by,
+        )
+        .await?;
+
+        let mut copied_edges = 0;
+        if copy_edges {
+            let edges = Edge::incident(&mut transaction, &source.graph_id, source.id).await?;
+            let swap = |id: i64| if id == source.id { node.id } else { id };
+            for edge in edges {
+                // Edges are only created with a type id as their label
+                let label =
+                    EdgeTypeId::parse(&edge.label).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+                Edge::insert(
+                    &mut transaction,
+                    &source.graph_id,
+                    &label,
+                    swap(edge.from_id),
+                    swap(edge.to_id),
+                    &edge.properties,
+                )
+                .await?;
+                copied_edges += 1;
+            }
+        }
+
+        transaction.commit().await?;
+        Ok((node, copied_edges))
+    }
+
+    // Set the node's properties back to a snapshot from its history, recorded as a new
+    // history entry. The snapshot must be valid for the node type as it is defined now;
+    // the node keeps its current creation stamps and protection
+    pub async fn restore(
+        &self,
+        pool: &sqlx::PgPool,
+        snapshot: &JsonValue,
+        changed_by: Uuid,
+    ) -> Result<Self, RestoreNodeError> {
+        let mut properties: HashMap<String, JsonValue> =
+            serde_json::from_value(snapshot.clone())
+                .map_err(|_| RestoreNodeError::InvalidSnapshot)?;
+        for key in ["created_by", "created_at", PROTECTED_PROPERTY] {
+            match self.properties.get(key) {
+                Some(value) => properties.insert(key.to_string(), value.clone()),
+                None => properties.remove(key),
+            };
+        }
+
+        let node_type_id = self.node_type().ok_or(sqlx::Error::RowNotFound)?;
+        let node_type = NodeType::from_id(pool, &self.graph_id, node_type_id).await?;
+        let lineage = node_type.lineage(pool).await?;
+        let attributes = NodeTypeAttributeDefinition::resolve(pool, &lineage).await?;
+        let outcome = ValidationOutcome::validate(&attributes, properties, true);
+        if !outcome.is_valid() {
+            return Err(RestoreNodeError::Conflict(ValidationErrorList(
+                outcome.errors,
+            )));
+        }
+
+        // Properties the snapshot doesn't have are removed by setting them to null
+        let mut changes = outcome.coerced_properties;
+        for key in self.properties.keys() {
+            changes.entry(key.clone()).or_insert(JsonValue::Null);
+        }
+        with_name_lower(&mut changes);
+
+        info!(
+            "Restoring node {} in graph: {}, by: {}",
+            self.id, &self.graph_id, changed_by
+        );
+        let mut transaction = pool.begin().await?;
+        let query = Cypher::new(
+            &self.graph_id,
+            format!(
+                "MATCH (n) WHERE id(n) = {} {} RETURN n",
+                self.id,
+                generate_set_clause("n", &changes)
+            ),
+        );
+        let ag_row = query.fetch_one(&mut *transaction).await?;
+        let node = Vertex::try_from(ag_row)
+            .and_then(|vertex| Node::from_vertex(vertex, &self.graph_id))
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+        NodeHistoryEntry::record(
+            &mut transaction,
+            &node,
+            NodeChange::Restored,
+            Some(&self.properties),
+            changed_by,
+        )
+        .await?;
+        transaction.commit().await?;
+        Ok(node)
+    }
+
+    // Turn deletion protection on or off. The change is kept in the node's history and
+    // recorded as a security event
+    pub async fn set_protected(
+        &self,
+        pool: &sqlx::PgPool,
+        protected: bool,
+        changed_by: Uuid,
+    ) -> Result<Self, sqlx::Error> {
+        let changes = HashMap::from([(PROTECTED_PROPERTY.to_string(), JsonValue::Bool(protected))]);
+        let query = Cypher::new(
+            &self.graph_id,
+            format!(
+                "MATCH (n) WHERE id(n) = {} {} RETURN n",
+                self.id,
+                generate_set_clause("n", &changes)
+            ),
+        );
+
+        let mut transaction = pool.begin().await?;
+        let ag_row = query.fetch_one(&mut *transaction).await?;
+        let node = Vertex::try_from(ag_row)
+            .and_then(|vertex| Node::from_vertex(vertex, &self.graph_id))
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+        NodeHistoryEntry::record(
+            &mut transaction,
+            &node,
+            NodeChange::ProtectionChanged,
+            Some(&self.properties),
+            changed_by,
+        )
+        .await?;
+        SecurityEvent::record(
+            &mut transaction,
+            changed_by,
+            SecurityEventKind::NodeProtectionChanged,
+            serde_json::json!({
+                "graph_id": self.graph_id,
+                "node_id": self.id,
+                "protected": protected,
+            }),
+        )
+        .await?;
+        transaction.commit().await?;
+        Ok(node)
+    }
+
+    // Number of nodes of the given types that have (`present`) or lack a value for `key`.
+    // Labels are matched in a condition so types without any nodes yet don't fail the query
+    pub async fn count_with_property(
+        conn: &mut PgConnection,
+        graph_id: &GraphId,
+        node_type_ids: &[NodeTypeId],
+        key: &str,
+        present: bool,
+    ) -> Result<i64, sqlx::Error> {
+        let query = Cypher::new(
+            graph_id,
+            format!(
+                "MATCH (n) WHERE label(n) IN {} AND n.{} IS {} RETURN count(n)",
+                cypher_literal(&node_type_ids.iter().map(NodeTypeId::as_str).collect()),
+                cypher_key(key),
+                if present { "NOT NULL" } else { "NULL" }
+            ),
+        )
+        .returning("total agtype");
+        let total = query.fetch_one(&mut *conn).await?;
+        Ok(total
+            .0
+            .into_scalar()
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?
+            .as_i64()
+            .unwrap_or_default())
+    }
+
+    // Set `key` to `value` on every node of the given types that has no value for it
+    pub async fn backfill_property(
+        conn: &mut PgConnection,
+        graph_id: &GraphId,
+        node_type_ids: &[NodeTypeId],
+        key: &str,
+        value: &JsonValue,
+    ) -> Result<(), sqlx::Error> {
+        let query = Cypher::new(
+            graph_id,
+            format!(
+                "MATCH (n) WHERE label(n) IN {} AND n.{} IS NULL SET n.{} = {}",
+                cypher_literal(&node_type_ids.iter().map(NodeTypeId::as_str).collect()),
+                cypher_key(key),
+                cypher_key(key),
+                cypher_literal(value)
+            ),
         );
-        sqlx::query(&query).fetch_one(&*pool).await?;
+        query.execute(&mut *conn).await?;
         Ok(())
     }
+
+    // Insert an already validated node with a new public id. Takes a connection so callers
+    // can group it with related writes, e.g. edges to the new node, in one transaction
+    pub async fn insert(
+        conn: &mut PgConnection,
+        graph_id: &GraphId,
+        node_type_id: &NodeTypeId,
+        properties: &HashMap<String, JsonValue>,
+    ) -> Result<Self, sqlx::Error> {
+        let mut properties = properties.clone();
+        with_name_lower(&mut properties);
+        let public_id = new_public_id();
+        properties.insert(
+            PUBLIC_ID_PROPERTY.to_string(),
+            JsonValue::String(public_id.clone()),
+        );
+        let props_clause = generate_props_clause(&properties);
+        let query = Cypher::new(
+            graph_id,
+            format!("CREATE (n:{} {}) RETURN n", node_type_id, &props_clause),
+        );
+
+        let ag_row = query.fetch_one(&mut *conn).await?;
+        let node = Vertex::try_from(ag_row)
+            .and_then(|vertex| Node::from_vertex(vertex, graph_id))
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+        Self::register_public_id(conn, graph_id, &public_id, node.id).await?;
+        Ok(node)
+    }
+
+    // Record a public id in the lookup table, which also rejects duplicates within the graph
+    async fn register_public_id(
+        conn: &mut PgConnection,
+        graph_id: &GraphId,
+        public_id: &str,
+        vertex_id: i64,
+    ) -> Result<(), sqlx::Error> {
+        let query = "INSERT INTO app_data.node_public_id (graph_id, public_id, vertex_id) VALUES ($1, $2, $3)";
+        sqlx::query(query)
+            .bind(graph_id)
+            .bind(public_id)
+            .bind(vertex_id)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    // Vertex id of the node with the given public id, if the graph has one
+    pub async fn vertex_id(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        public_id: &str,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        let query =
+            "SELECT vertex_id FROM app_data.node_public_id WHERE graph_id = $1 AND public_id = $2";
+        sqlx::query_scalar(query)
+            .bind(graph_id)
+            .bind(public_id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    // Vertex ids of the nodes with the given public ids. Public ids the graph doesn't have
+    // are left out
+    pub async fn vertex_ids(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        public_ids: &[String],
+    ) -> Result<HashMap<String, i64>, sqlx::Error> {
+        let query = "SELECT public_id, vertex_id FROM app_data.node_public_id WHERE graph_id = $1 AND public_id = ANY($2)";
+        let rows: Vec<(String, i64)> = sqlx::query_as(query)
+            .bind(graph_id)
+            .bind(public_ids)
+            .fetch_all(pool)
+            .await?;
+        Ok(rows.into_iter().collect())
+    }
+
+    // Nodes by public id, in the order of `public_ids`. Ones that don't exist are left out
+    pub async fn get_many_by_public_id(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        public_ids: &[String],
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let vertex_ids = Self::vertex_ids(pool, graph_id, public_ids).await?;
+        let ids: Vec<i64> = public_ids
+            .iter()
+            .filter_map(|public_id| vertex_ids.get(public_id).copied())
+            .collect();
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        Self::get_many(pool, graph_id, &ids).await
+    }
+
+    // Give every node of the graph created before public ids existed one. Returns the
+    // number of nodes updated
+    pub async fn backfill_public_ids(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+    ) -> Result<usize, sqlx::Error> {
+        let mut updated = 0;
+        loop {
+            let mut transaction = pool.begin().await?;
+            let query = Cypher::new(
+                graph_id,
+                format!(
+                    "MATCH (n) WHERE n.{} IS NULL RETURN id(n) LIMIT {}",
+                    PUBLIC_ID_PROPERTY, PUBLIC_ID_BACKFILL_PAGE_SIZE
+                ),
+            )
+            .returning("id agtype");
+            let ids = query.fetch_all(&mut *transaction).await?;
+            let page_len = ids.len();
+
+            for id in ids {
+                let vertex_id =
+                    id.0.into_scalar()
+                        .map_err(|e| sqlx::Error::Decode(Box::new(e)))?
+                        .as_i64()
+                        .unwrap_or_default();
+                let public_id = new_public_id();
+                let update = Cypher::new(
+                    graph_id,
+                    format!(
+                        "MATCH (n) WHERE id(n) = {} SET n.{} = {}",
+                        vertex_id,
+                        PUBLIC_ID_PROPERTY,
+                        cypher_literal(&JsonValue::String(public_id.clone()))
+                    ),
+                );
+                update.execute(&mut *transaction).await?;
+                Self::register_public_id(&mut transaction, graph_id, &public_id, vertex_id).await?;
+            }
+            transaction.commit().await?;
+            updated += page_len;
+
+            if page_len < PUBLIC_ID_BACKFILL_PAGE_SIZE {
+                return Ok(updated);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_pattern_escapes_quotes_and_dollar_quotes() {
+        assert_eq!(name_pattern("O'Brien", false), r"{name: 'O\'Brien'}");
+        assert_eq!(
+            name_pattern("O'Brien", true),
+            format!(r"{{{}: 'o\'brien'}}", NAME_LOWER_PROPERTY)
+        );
+        // $$ would end the dollar quoted cypher in the generated SQL
+        let pattern = name_pattern("a$$ b", false);
+        assert!(!pattern.contains('$'), "{}", pattern);
+    }
 }
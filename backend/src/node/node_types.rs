@@ -1,19 +1,42 @@
 use super::NewAttributeDefinition;
-use crate::utils::create_id;
+use crate::ids::{GraphId, NodeTypeId};
+use crate::utils::validate_label;
 use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
 use sqlx::postgres::PgRow;
 use sqlx::{FromRow, Postgres, Row, Transaction};
+use std::collections::HashMap;
 use strum_macros::{AsRefStr, Display, EnumString};
 use uuid::Uuid;
 
+// Maximum number of ancestors a node type can have
+pub const MAX_INHERITANCE_DEPTH: usize = 8;
+
+#[derive(Debug, thiserror::Error)]
+pub enum NodeTypeInheritanceError {
+    #[error("Node type '{0}' is part of an inheritance cycle")]
+    Cycle(String),
+
+    #[error("Node type '{0}' has more than {MAX_INHERITANCE_DEPTH} ancestors")]
+    TooDeep(String),
+
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NodeType {
-    pub id: String,
-    pub graph_id: String,
+    pub id: NodeTypeId,
+    pub graph_id: GraphId,
     pub name: String,
     pub normalized_name: String,
     pub description: String,
+    // Id of the node type this type extends
+    pub parent_id: Option<NodeTypeId>,
+    // Nodes of the type are only visible to graph admins, see NodeVisibility
+    pub restricted: bool,
     pub created_by: Uuid,
+    #[serde(with = "crate::utils::rfc3339")]
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
@@ -22,21 +45,26 @@ pub struct NodeTypeSummary {
     pub id: String,
     pub name: String,
     pub description: String,
+    pub extends: Option<String>,
+    #[serde(default)]
+    pub restricted: bool,
 }
 
 impl From<&NodeType> for NodeTypeSummary {
     fn from(node_type: &NodeType) -> Self {
         Self {
-            id: node_type.id.clone(),
+            id: node_type.id.to_string(),
             name: node_type.name.clone(),
             description: node_type.description.clone(),
+            extends: node_type.parent_id.as_ref().map(NodeTypeId::to_string),
+            restricted: node_type.restricted,
         }
     }
 }
 
 impl NodeType {
     pub fn new(
-        graph_id: &str,
+        graph_id: &GraphId,
         name: &str,
         description: String,
         created_by: Uuid,
@@ -51,14 +79,25 @@ impl NodeType {
             return Err("Node name cannot be empty.".to_string());
         }
 
+        // The normalized name is used as the AGE label, so it must be a valid AGE identifier
+        let normalized_name = crate::utils::normalize(name);
+        validate_label(&normalized_name).map_err(|e| {
+            format!(
+                "Node type name '{}' cannot be used as a label '{}': {}",
+                name, normalized_name, e.code
+            )
+        })?;
+
         Ok(Self {
-            id: format!("v{}", create_id(8)),
-            graph_id: graph_id.to_string(),
+            id: NodeTypeId::generate(),
+            graph_id: graph_id.clone(),
             name: name.to_string(),
-            normalized_name: crate::utils::normalize(name),
+            normalized_name,
             created_by,
             created_at: chrono::Utc::now(),
             description,
+            parent_id: None,
+            restricted: false,
         })
     }
 
@@ -66,14 +105,6 @@ impl NodeType {
         &self,
         transaction: &mut Transaction<'_, Postgres>,
     ) -> Result<(), sqlx::Error> {
-        // In AGE, node types are implemented as vertex labels
-        let age_query = "SELECT ag_catalog.create_vlabel($1, $2)";
-        sqlx::query(age_query)
-            .bind(&self.graph_id)
-            .bind(&self.normalized_name)
-            .execute(&mut **transaction)
-            .await?;
-
         // Store node type metadata
         let insert_node_type_meta = "
         INSERT INTO app_data.node_types (
@@ -82,9 +113,11 @@ impl NodeType {
             name, 
             normalized_name,
             description, 
+            parent_id,
+            restricted,
             created_by, 
             created_at
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7)";
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)";
 
         sqlx::query(insert_node_type_meta)
             .bind(&self.id)
@@ -92,18 +125,30 @@ impl NodeType {
             .bind(&self.name)
             .bind(&self.normalized_name)
             .bind(&self.description)
+            .bind(&self.parent_id)
+            .bind(self.restricted)
             .bind(&self.created_by)
             .bind(&self.created_at)
             .execute(&mut **transaction)
             .await?;
 
+        // In AGE, node types are implemented as vertex labels. Created after the metadata,
+        // so a taken name fails before AGE is touched. create_node_type drops a label left
+        // behind if the transaction fails
+        let age_query = "SELECT ag_catalog.create_vlabel($1, $2)";
+        sqlx::query(age_query)
+            .bind(&self.graph_id)
+            .bind(&self.normalized_name)
+            .execute(&mut **transaction)
+            .await?;
+
         Ok(())
     }
 
     pub async fn from_id(
         pool: &sqlx::PgPool,
-        graph_id: &str,
-        node_type_id: &str,
+        graph_id: &GraphId,
+        node_type_id: &NodeTypeId,
     ) -> Result<Self, sqlx::Error> {
         let query = r#"
             SELECT * FROM app_data.node_types
@@ -121,7 +166,7 @@ impl NodeType {
 
     pub async fn from_name(
         pool: &sqlx::PgPool,
-        graph_id: &str,
+        graph_id: &GraphId,
         name: &str,
     ) -> Result<Self, sqlx::Error> {
         let query = r#"
@@ -137,6 +182,72 @@ impl NodeType {
 
         Ok(node_type)
     }
+
+    // Remove the type's metadata, its own attributes go with it. Nodes keep their label
+    pub async fn delete(&self, pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+        let query = "DELETE FROM app_data.node_types WHERE graph_id = $1 AND id = $2";
+        sqlx::query(query)
+            .bind(&self.graph_id)
+            .bind(&self.id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    // Ids of the graph's restricted types
+    pub async fn restricted_ids(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+    ) -> Result<Vec<NodeTypeId>, sqlx::Error> {
+        let query = "SELECT id FROM app_data.node_types WHERE graph_id = $1 AND restricted";
+        sqlx::query_scalar(query)
+            .bind(graph_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    // Ids of this type and every type inheriting from it, directly or not. Nodes of all of
+    // them carry this type's attributes
+    pub async fn with_descendants(
+        &self,
+        pool: &sqlx::PgPool,
+    ) -> Result<Vec<NodeTypeId>, sqlx::Error> {
+        let query = r#"
+            WITH RECURSIVE tree AS (
+                SELECT id FROM app_data.node_types WHERE graph_id = $1 AND id = $2
+                UNION
+                SELECT t.id FROM app_data.node_types t
+                JOIN tree ON t.parent_id = tree.id
+                WHERE t.graph_id = $1
+            )
+            SELECT id FROM tree
+        "#;
+        sqlx::query_scalar(query)
+            .bind(&self.graph_id)
+            .bind(&self.id)
+            .fetch_all(pool)
+            .await
+    }
+
+    // Walk the parent chain, returning this type followed by its ancestors nearest first.
+    // Fails on a cycle or when the chain is longer than MAX_INHERITANCE_DEPTH
+    pub async fn lineage(
+        self,
+        pool: &sqlx::PgPool,
+    ) -> Result<Vec<NodeType>, NodeTypeInheritanceError> {
+        let mut lineage = vec![self];
+        while let Some(parent_id) = lineage.last().and_then(|t| t.parent_id.clone()) {
+            if lineage.iter().any(|t| t.id == parent_id) {
+                return Err(NodeTypeInheritanceError::Cycle(parent_id.to_string()));
+            }
+            if lineage.len() > MAX_INHERITANCE_DEPTH {
+                return Err(NodeTypeInheritanceError::TooDeep(lineage[0].id.to_string()));
+            }
+            let parent = NodeType::from_id(pool, &lineage[0].graph_id, &parent_id).await?;
+            lineage.push(parent);
+        }
+        Ok(lineage)
+    }
 }
 
 // Implement FromRow for NodeType
@@ -148,6 +259,8 @@ impl<'r> FromRow<'r, PgRow> for NodeType {
             name: row.try_get("name")?,
             normalized_name: row.try_get("normalized_name")?,
             description: row.try_get("description")?,
+            parent_id: row.try_get("parent_id")?,
+            restricted: row.try_get("restricted")?,
             created_by: row.try_get("created_by")?,
             created_at: row.try_get("created_at")?,
         })
@@ -163,10 +276,26 @@ pub struct NodeTypeAttributeDefinition {
     pub data_type: NodeTypeAttributeDataType,
     pub required: bool,
     pub description: String,
+    pub example: Option<JsonValue>,
+    // Org dictionary entry the attribute was created from
+    pub dictionary_id: Option<Uuid>,
+    // Place among the type's own attributes, starting at 0
+    pub position: i32,
+    // Rejected on new writes, still returned where nodes already have it
+    #[serde(default)]
+    pub deprecated: bool,
+    #[serde(default)]
+    pub deprecated_reason: Option<String>,
+    // Attribute to use instead of a deprecated one
+    #[serde(default)]
+    pub replaced_by: Option<String>,
+    // Longest string value accepted on new writes, below the server-wide cap
+    #[serde(default)]
+    pub max_length: Option<i32>,
 }
 
 impl NodeTypeAttributeDefinition {
-    pub fn from_request(req: &NewAttributeDefinition, type_id: &str) -> Self {
+    pub fn from_request(req: &NewAttributeDefinition, type_id: &NodeTypeId) -> Self {
         Self {
             id: Uuid::new_v4(),
             type_id: type_id.to_string(),
@@ -175,6 +304,13 @@ impl NodeTypeAttributeDefinition {
             data_type: req.data_type.clone(),
             required: req.required,
             description: req.description.clone(),
+            example: req.example.clone(),
+            dictionary_id: None,
+            position: 0,
+            deprecated: req.deprecated,
+            deprecated_reason: req.deprecated_reason.clone(),
+            replaced_by: req.replaced_by.clone(),
+            max_length: req.max_length,
         }
     }
 
@@ -190,8 +326,15 @@ impl NodeTypeAttributeDefinition {
                 normalized_name,
                 data_type,
                 required,
-                description
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7)
+                description,
+                example,
+                dictionary_id,
+                position,
+                deprecated,
+                deprecated_reason,
+                replaced_by,
+                max_length
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
         "#;
 
         sqlx::query(insert_query)
@@ -202,6 +345,13 @@ impl NodeTypeAttributeDefinition {
             .bind(&self.data_type.to_string())
             .bind(&self.required)
             .bind(&self.description)
+            .bind(&self.example)
+            .bind(self.dictionary_id)
+            .bind(self.position)
+            .bind(self.deprecated)
+            .bind(&self.deprecated_reason)
+            .bind(&self.replaced_by)
+            .bind(self.max_length)
             .execute(&mut **transaction)
             .await?;
 
@@ -215,6 +365,7 @@ impl NodeTypeAttributeDefinition {
         let query = r#"
             SELECT * FROM app_data.node_type_attributes
             WHERE type_id = $1
+            ORDER BY position, name
         "#;
 
         let rows = sqlx::query(query)
@@ -229,9 +380,120 @@ impl NodeTypeAttributeDefinition {
 
         Ok(attributes)
     }
+
+    // Overwrite the stored definition with this one, keeping its id and dictionary link
+    pub async fn update(
+        &self,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), sqlx::Error> {
+        let update_query = r#"
+            UPDATE app_data.node_type_attributes
+            SET name = $2,
+                normalized_name = $3,
+                data_type = $4,
+                required = $5,
+                description = $6,
+                example = $7,
+                position = $8,
+                deprecated = $9,
+                deprecated_reason = $10,
+                replaced_by = $11,
+                max_length = $12
+            WHERE id = $1
+        "#;
+
+        sqlx::query(update_query)
+            .bind(self.id)
+            .bind(&self.name)
+            .bind(&self.normalized_name)
+            .bind(self.data_type.to_string())
+            .bind(self.required)
+            .bind(&self.description)
+            .bind(&self.example)
+            .bind(self.position)
+            .bind(self.deprecated)
+            .bind(&self.deprecated_reason)
+            .bind(&self.replaced_by)
+            .bind(self.max_length)
+            .execute(&mut **transaction)
+            .await?;
+
+        Ok(())
+    }
+
+    // Remove definitions. Values already stored on nodes are left in place
+    pub async fn delete_many(
+        transaction: &mut Transaction<'_, Postgres>,
+        ids: &[Uuid],
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM app_data.node_type_attributes WHERE id = ANY($1)")
+            .bind(ids)
+            .execute(&mut **transaction)
+            .await?;
+        Ok(())
+    }
+
+    // Attribute definitions of a node type including the ones inherited from its
+    // ancestors. A type's own definition wins over an inherited one with the same name
+    pub async fn resolve(
+        pool: &sqlx::PgPool,
+        lineage: &[NodeType],
+    ) -> Result<Vec<NodeTypeAttributeDefinition>, sqlx::Error> {
+        let type_ids: Vec<&str> = lineage.iter().map(|t| t.id.as_str()).collect();
+        let query = r#"
+            SELECT * FROM app_data.node_type_attributes
+            WHERE type_id = ANY($1)
+            ORDER BY position, name
+        "#;
+
+        let rows = sqlx::query(query).bind(&type_ids).fetch_all(pool).await?;
+        let mut by_type: HashMap<String, Vec<NodeTypeAttributeDefinition>> = HashMap::new();
+        for row in rows.iter() {
+            let attribute = NodeTypeAttributeDefinition::from_row(row)?;
+            by_type
+                .entry(attribute.type_id.clone())
+                .or_default()
+                .push(attribute);
+        }
+
+        // Walk from the root ancestor down so nearer types override. An overriding definition
+        // keeps the slot of the one it replaces
+        let mut attributes: Vec<NodeTypeAttributeDefinition> = Vec::new();
+        for node_type in lineage.iter().rev() {
+            for attribute in by_type.remove(node_type.id.as_str()).unwrap_or_default() {
+                match attributes
+                    .iter_mut()
+                    .find(|a| a.normalized_name == attribute.normalized_name)
+                {
+                    Some(existing) => *existing = attribute,
+                    None => attributes.push(attribute),
+                }
+            }
+        }
+
+        Ok(attributes)
+    }
+
+    // Own attribute definitions of every node type in a graph, without inheritance applied
+    #[cfg(feature = "graphql")]
+    pub async fn for_graph(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+    ) -> Result<Vec<NodeTypeAttributeDefinition>, sqlx::Error> {
+        let query = r#"
+            SELECT a.* FROM app_data.node_type_attributes a
+            JOIN app_data.node_types t ON t.id = a.type_id
+            WHERE t.graph_id = $1
+            ORDER BY a.type_id, a.position, a.name
+        "#;
+        sqlx::query_as::<_, NodeTypeAttributeDefinition>(query)
+            .bind(graph_id)
+            .fetch_all(pool)
+            .await
+    }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, Display, EnumString, AsRefStr)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Display, EnumString, AsRefStr)]
 #[strum(serialize_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
 pub enum NodeTypeAttributeDataType {
@@ -239,6 +501,8 @@ pub enum NodeTypeAttributeDataType {
     Number,
     Boolean,
     Date,
+    // An https URL, e.g. of an image the UI can render
+    Url,
     // Add other types as needed
 }
 
@@ -258,6 +522,13 @@ impl<'r> FromRow<'r, PgRow> for NodeTypeAttributeDefinition {
             data_type,
             required: row.try_get("required")?,
             description: row.try_get("description")?,
+            example: row.try_get("example")?,
+            dictionary_id: row.try_get("dictionary_id")?,
+            position: row.try_get("position")?,
+            deprecated: row.try_get("deprecated")?,
+            deprecated_reason: row.try_get("deprecated_reason")?,
+            replaced_by: row.try_get("replaced_by")?,
+            max_length: row.try_get("max_length")?,
         })
     }
 }
@@ -0,0 +1,178 @@
+use super::{NodeType, PropertyFilter};
+use crate::graph::EffectiveRole;
+use crate::ids::{GraphId, NodeTypeId};
+
+// Nothing hidden, see NodeVisibility::everything
+static EVERYTHING: NodeVisibility = NodeVisibility {
+    hidden_types: Vec::new(),
+};
+
+// The node types whose nodes a caller can't see. Nodes of restricted types are only
+// visible to graph admins
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct NodeVisibility {
+    hidden_types: Vec<NodeTypeId>,
+}
+
+impl NodeVisibility {
+    // Every node, for graph admins and for work that covers the whole graph
+    pub fn everything() -> &'static Self {
+        &EVERYTHING
+    }
+
+    // What a caller with `role` sees of a graph with the given restricted types
+    pub fn new(role: EffectiveRole, restricted_types: Vec<NodeTypeId>) -> Self {
+        if role.can_admin() {
+            return Self::default();
+        }
+        Self {
+            hidden_types: restricted_types,
+        }
+    }
+
+    pub async fn load(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        role: EffectiveRole,
+    ) -> Result<Self, sqlx::Error> {
+        if role.can_admin() {
+            return Ok(Self::default());
+        }
+        let restricted_types = NodeType::restricted_ids(pool, graph_id).await?;
+        Ok(Self::new(role, restricted_types))
+    }
+}
+
+// The nodes a query covers. Listing, counting, stats and export all build their MATCH
+// pattern and WHERE clause from a scope, so a total always agrees with the list under it.
+// Conditions that hide nodes from everyone, or from the caller, belong here rather than in
+// individual queries
+#[derive(Debug, Clone, Copy)]
+pub struct NodeScope<'a> {
+    pub node_type: Option<&'a NodeType>,
+    pub filters: &'a [PropertyFilter],
+    pub visibility: &'a NodeVisibility,
+}
+
+impl<'a> NodeScope<'a> {
+    // Every node in the graph, regardless of who asks
+    pub fn all() -> Self {
+        Self::visible(NodeVisibility::everything())
+    }
+
+    // Every node the caller can see
+    pub fn visible(visibility: &'a NodeVisibility) -> Self {
+        Self::new(None, &[], visibility)
+    }
+
+    pub fn new(
+        node_type: Option<&'a NodeType>,
+        filters: &'a [PropertyFilter],
+        visibility: &'a NodeVisibility,
+    ) -> Self {
+        Self {
+            node_type,
+            filters,
+            visibility,
+        }
+    }
+
+    // Node pattern binding `variable`. Only the stored type id is interpolated as the
+    // label, never client input. Nodes whose type was deleted keep a label no type has,
+    // so they only ever match the untyped pattern
+    pub fn pattern(&self, variable: &str) -> String {
+        match self.node_type {
+            Some(node_type) => format!("({}:{})", variable, node_type.id),
+            None => format!("({})", variable),
+        }
+    }
+
+    // Conditions a node bound to `variable` must meet. Hidden types are stored type ids
+    pub fn conditions(&self, variable: &str) -> Vec<String> {
+        let mut conditions: Vec<String> =
+            self.filters.iter().map(|f| f.to_cypher(variable)).collect();
+        let hidden = &self.visibility.hidden_types;
+        if !hidden.is_empty() {
+            let labels: Vec<String> = hidden.iter().map(|id| format!("'{}'", id)).collect();
+            conditions.push(format!(
+                "NOT label({}) IN [{}]",
+                variable,
+                labels.join(", ")
+            ));
+        }
+        conditions
+    }
+
+    // WHERE clause for a node bound to `variable`, or nothing when there are no conditions
+    pub fn where_clause(&self, variable: &str) -> String {
+        where_all(&self.conditions(variable))
+    }
+}
+
+// Join conditions into a WHERE clause, or nothing when there are none
+pub fn where_all(conditions: &[String]) -> String {
+    if conditions.is_empty() {
+        return String::new();
+    }
+    format!("WHERE {}", conditions.join(" AND "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::FilterOp;
+
+    fn restricted() -> Vec<NodeTypeId> {
+        vec![
+            NodeTypeId::parse("vAAAAAAAA").unwrap(),
+            NodeTypeId::parse("vBBBBBBBB").unwrap(),
+        ]
+    }
+
+    // The MATCH and WHERE a node total is counted with, as in Node::orphans
+    fn count_query(scope: &NodeScope) -> String {
+        format!(
+            "MATCH {} {} RETURN count(n)",
+            scope.pattern("n"),
+            scope.where_clause("n")
+        )
+    }
+
+    #[test]
+    fn viewer_totals_exclude_restricted_types() {
+        for role in [EffectiveRole::Viewer, EffectiveRole::Editor] {
+            let visibility = NodeVisibility::new(role, restricted());
+            assert_eq!(
+                count_query(&NodeScope::visible(&visibility)),
+                "MATCH (n) WHERE NOT label(n) IN ['vAAAAAAAA', 'vBBBBBBBB'] RETURN count(n)",
+                "{}",
+                role
+            );
+        }
+    }
+
+    #[test]
+    fn admin_totals_include_restricted_types() {
+        let visibility = NodeVisibility::new(EffectiveRole::Admin, restricted());
+        assert_eq!(
+            count_query(&NodeScope::visible(&visibility)),
+            "MATCH (n)  RETURN count(n)"
+        );
+        assert_eq!(count_query(&NodeScope::all()), "MATCH (n)  RETURN count(n)");
+    }
+
+    #[test]
+    fn visibility_is_combined_with_filters() {
+        let filters = [PropertyFilter {
+            attribute: "age".into(),
+            op: FilterOp::Gt,
+            value: 36.into(),
+        }];
+        let visibility = NodeVisibility::new(EffectiveRole::Viewer, restricted());
+        let scope = NodeScope::new(None, &filters, &visibility);
+        assert_eq!(
+            scope.where_clause("v"),
+            "WHERE v.`age` > 36 AND NOT label(v) IN ['vAAAAAAAA', 'vBBBBBBBB']"
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use crate::utils::normalize;
+use sqlx::Row;
+use std::collections::{HashMap, HashSet};
+
+// Tables holding a normalized_name, with the column it has to be unique within
+const NORMALIZED_TABLES: [(&str, &str); 5] = [
+    ("node_types", "graph_id"),
+    ("edge_type", "graph_id"),
+    ("node_type_attributes", "type_id"),
+    ("edge_type_attribute", "type_id"),
+    ("org_attribute", "org_id"),
+];
+
+// A stored normalized name that differs from what `normalize` gives for its name
+#[derive(Debug)]
+pub struct NormalizationMismatch {
+    pub table: &'static str,
+    pub id: String,
+    pub name: String,
+    pub stored: String,
+    pub expected: String,
+    // Set when the expected value is taken by another row in the same scope, in which
+    // case the stored value is left as is
+    pub conflict: bool,
+}
+
+struct StoredName {
+    id: String,
+    scope: String,
+    name: String,
+    stored: String,
+    expected: String,
+}
+
+// Recompute every stored normalized name and update the ones that changed. Rows whose new
+// value would clash with another name in the same scope can't be updated without breaking
+// uniqueness, they are returned with `conflict` set for someone to rename
+pub async fn sync_normalized_names(
+    pool: &sqlx::PgPool,
+) -> Result<Vec<NormalizationMismatch>, sqlx::Error> {
+    let mut mismatches = Vec::new();
+    for (table, scope) in NORMALIZED_TABLES {
+        let query = format!(
+            "SELECT id::text AS id, {}::text AS scope, name, normalized_name FROM app_data.{}",
+            scope, table
+        );
+        let rows: Vec<StoredName> = sqlx::query(&query)
+            .fetch_all(pool)
+            .await?
+            .iter()
+            .map(|row| {
+                let name: String = row.try_get("name")?;
+                Ok(StoredName {
+                    id: row.try_get("id")?,
+                    scope: row.try_get("scope")?,
+                    expected: normalize(&name),
+                    name,
+                    stored: row.try_get("normalized_name")?,
+                })
+            })
+            .collect::<Result<_, sqlx::Error>>()?;
+
+        // A value is taken when another row of the scope would end up with it, or still
+        // stores it
+        let mut holders: HashMap<(&str, &str), HashSet<&str>> = HashMap::new();
+        for row in &rows {
+            for value in [&row.expected, &row.stored] {
+                holders
+                    .entry((row.scope.as_str(), value.as_str()))
+                    .or_default()
+                    .insert(row.id.as_str());
+            }
+        }
+
+        let mut tx = pool.begin().await?;
+        let update = format!(
+            "UPDATE app_data.{} SET normalized_name = $2 WHERE id::text = $1",
+            table
+        );
+        for row in rows.iter().filter(|row| row.stored != row.expected) {
+            let conflict = holders[&(row.scope.as_str(), row.expected.as_str())].len() > 1;
+            if !conflict {
+                sqlx::query(&update)
+                    .bind(&row.id)
+                    .bind(&row.expected)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            mismatches.push(NormalizationMismatch {
+                table,
+                id: row.id.clone(),
+                name: row.name.clone(),
+                stored: row.stored.clone(),
+                expected: row.expected.clone(),
+                conflict,
+            });
+        }
+        tx.commit().await?;
+    }
+    Ok(mismatches)
+}
@@ -0,0 +1,62 @@
+use super::Notification;
+use async_trait::async_trait;
+use std::sync::Arc;
+use tracing::{error, info};
+
+// An outbound channel (email, webhook...) a notification is pushed to once the change it
+// describes has been committed. The in-app row is always written; channels are extra
+#[async_trait]
+pub trait NotificationChannel: std::fmt::Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn deliver(&self, notification: &Notification) -> Result<(), String>;
+}
+
+// Writes notifications to the application log
+#[derive(Debug)]
+pub struct LogChannel;
+
+#[async_trait]
+impl NotificationChannel for LogChannel {
+    fn name(&self) -> &'static str {
+        "log"
+    }
+
+    async fn deliver(&self, notification: &Notification) -> Result<(), String> {
+        info!(
+            "Notification {} ({}) for user {}",
+            notification.id, notification.kind, notification.user_id
+        );
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Notifier {
+    channels: Vec<Arc<dyn NotificationChannel>>,
+}
+
+impl Notifier {
+    pub fn new(channels: Vec<Arc<dyn NotificationChannel>>) -> Self {
+        Self { channels }
+    }
+
+    // Hand the notification to every channel in the background. A failing channel is
+    // logged and never fails the request that triggered the notification
+    pub fn dispatch(&self, notification: Notification) {
+        for channel in &self.channels {
+            let channel = Arc::clone(channel);
+            let notification = notification.clone();
+            tokio::spawn(async move {
+                if let Err(e) = channel.deliver(&notification).await {
+                    error!(
+                        "Failed to deliver notification {} via {}: {}",
+                        notification.id,
+                        channel.name(),
+                        e
+                    );
+                }
+            });
+        }
+    }
+}
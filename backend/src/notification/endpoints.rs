@@ -0,0 +1,85 @@
+use super::Notification;
+use crate::auth::Auth;
+use crate::config::AppState;
+use crate::error::ApiError;
+use crate::utils::Page;
+use axum::extract::{Extension, Path, Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct GetNotificationsQueryParams {
+    page: Option<u32>,
+    page_size: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct NotificationsResponse {
+    #[serde(flatten)]
+    pub page: Page<Notification>,
+    pub unread_count: i64,
+}
+
+pub async fn get_notifications(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Query(params): Query<GetNotificationsQueryParams>,
+) -> Result<Json<NotificationsResponse>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let (page, page_size) = Page::<Notification>::bounds(params.page, params.page_size);
+    let notifications = Notification::list_for_user(&state.pool, user.id, page, page_size)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch notifications: {:?}", e);
+            ApiError::InternalServerError
+        })?;
+    let (total, unread_count) = Notification::counts_for_user(&state.pool, user.id)
+        .await
+        .map_err(|e| {
+            error!("Failed to count notifications: {:?}", e);
+            ApiError::InternalServerError
+        })?;
+
+    Ok(Json(NotificationsResponse {
+        page: Page {
+            items: notifications,
+            page,
+            page_size,
+            total,
+        },
+        unread_count,
+    }))
+}
+
+pub async fn mark_notification_read(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(notification_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let updated = Notification::mark_read(&state.pool, notification_id, user.id)
+        .await
+        .map_err(|e| {
+            error!("Failed to mark notification as read: {:?}", e);
+            ApiError::InternalServerError
+        })?;
+    if !updated {
+        return Err(ApiError::NotFound {
+            code: "NOTIFICATION_NOT_FOUND".into(),
+            message: "Notification not found".into(),
+        });
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
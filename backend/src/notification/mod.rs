@@ -0,0 +1,7 @@
+mod delivery;
+mod endpoints;
+mod notification;
+
+pub use delivery::*;
+pub use endpoints::*;
+pub use notification::*;
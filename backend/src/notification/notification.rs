@@ -0,0 +1,121 @@
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use sqlx::{postgres::PgRow, FromRow, PgConnection, Row};
+use strum_macros::{Display, EnumString};
+use uuid::Uuid;
+
+#[derive(PartialEq, Clone, Copy, Serialize, Debug, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    OrgMemberAdded,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub user_id: Uuid,
+    pub kind: NotificationKind,
+    pub payload: JsonValue,
+    #[serde(with = "crate::utils::rfc3339::option")]
+    pub read_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(with = "crate::utils::rfc3339")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl<'r> FromRow<'r, PgRow> for Notification {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        let kind: String = row.try_get("kind")?;
+
+        Ok(Self {
+            id: row.try_get("id")?,
+            user_id: row.try_get("user_id")?,
+            kind: kind.parse().map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+            payload: row.try_get("payload")?,
+            read_at: row.try_get("read_at")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+impl Notification {
+    pub fn new(user_id: Uuid, kind: NotificationKind, payload: JsonValue) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            user_id,
+            kind,
+            payload,
+            read_at: None,
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    // Takes a connection so the row is written in the caller's transaction, alongside
+    // the change it describes
+    pub async fn persist(&self, conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+        let query = "INSERT INTO app_data.notification (id, user_id, kind, payload, created_at) VALUES ($1, $2, $3, $4, $5)";
+        sqlx::query(query)
+            .bind(self.id)
+            .bind(self.user_id)
+            .bind(self.kind.to_string())
+            .bind(&self.payload)
+            .bind(self.created_at)
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+
+    // Newest first
+    pub async fn list_for_user(
+        pool: &sqlx::PgPool,
+        user_id: Uuid,
+        page: u32,
+        page_size: u32,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let query = "
+        SELECT * FROM app_data.notification
+        WHERE user_id = $1
+        ORDER BY created_at DESC, id
+        LIMIT $2 OFFSET $3
+        ";
+        sqlx::query_as::<_, Notification>(query)
+            .bind(user_id)
+            .bind(page_size as i64)
+            .bind(((page - 1) * page_size) as i64)
+            .fetch_all(pool)
+            .await
+    }
+
+    // Total and unread notification counts for a user
+    pub async fn counts_for_user(
+        pool: &sqlx::PgPool,
+        user_id: Uuid,
+    ) -> Result<(i64, i64), sqlx::Error> {
+        let query = "
+        SELECT COUNT(*), COUNT(*) FILTER (WHERE read_at IS NULL)
+        FROM app_data.notification
+        WHERE user_id = $1
+        ";
+        sqlx::query_as::<_, (i64, i64)>(query)
+            .bind(user_id)
+            .fetch_one(pool)
+            .await
+    }
+
+    // Mark one of the user's notifications as read. Marking it again keeps the original
+    // read_at. Returns false when the user has no such notification
+    pub async fn mark_read(
+        pool: &sqlx::PgPool,
+        id: Uuid,
+        user_id: Uuid,
+    ) -> Result<bool, sqlx::Error> {
+        let query = "UPDATE app_data.notification SET read_at = COALESCE(read_at, now()) WHERE id = $1 AND user_id = $2";
+        let result = sqlx::query(query)
+            .bind(id)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,122 @@
+use crate::config::AppState;
+use crate::error::ApiError;
+use crate::org::{Org, OrgMember, Role};
+use crate::user::User;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tracing::error;
+use uuid::Uuid;
+
+// Org wide actions the UI asks about before rendering controls for them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrgCapability {
+    // Reading every graph of the org
+    DataRead,
+    // Writing to every graph of the org
+    DataWrite,
+    // Changing the org attribute dictionary
+    SchemaWrite,
+    MemberManage,
+    GraphCreate,
+    OrgDelete,
+    Export,
+}
+
+impl OrgCapability {
+    pub const ALL: [OrgCapability; 7] = [
+        OrgCapability::DataRead,
+        OrgCapability::DataWrite,
+        OrgCapability::SchemaWrite,
+        OrgCapability::MemberManage,
+        OrgCapability::GraphCreate,
+        OrgCapability::OrgDelete,
+        OrgCapability::Export,
+    ];
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrgPermissions {
+    pub role: Role,
+    pub capabilities: BTreeMap<OrgCapability, bool>,
+}
+
+impl From<&OrgAccess> for OrgPermissions {
+    fn from(access: &OrgAccess) -> Self {
+        Self {
+            role: access.member.role.clone(),
+            capabilities: OrgCapability::ALL
+                .into_iter()
+                .map(|capability| (capability, access.can(capability)))
+                .collect(),
+        }
+    }
+}
+
+// An org together with the requesting user's membership of it. Follows the same policy as
+// GraphAccess: orgs the user isn't a member of are reported as not found
+pub struct OrgAccess {
+    pub org: Org,
+    pub member: OrgMember,
+}
+
+impl OrgAccess {
+    pub async fn resolve(state: &AppState, org_id: &Uuid, user: &User) -> Result<Self, ApiError> {
+        let pool = &state.pool;
+        let org = Org::from_id(pool, org_id).await.map_err(|e| match e {
+            sqlx::Error::RowNotFound => org_not_found(org_id),
+            e => {
+                error!("Failed to fetch org: {:?}", e);
+                ApiError::InternalServerError
+            }
+        })?;
+
+        // Memberships may be up to the cache TTL old, see MembershipCache
+        let member = state
+            .memberships
+            .get(pool, user)
+            .await
+            .map_err(|e| {
+                error!("Failed to fetch memberships: {:?}", e);
+                ApiError::InternalServerError
+            })?
+            .org_member(org_id)
+            .cloned()
+            .ok_or_else(|| {
+                error!("Requesting user is not a member of org {}", org_id);
+                org_not_found(org_id)
+            })?;
+
+        Ok(Self { org, member })
+    }
+
+    // Whether the user may perform the action. Everything but reading needs the admin
+    // role, the same check require_admin makes
+    pub fn can(&self, capability: OrgCapability) -> bool {
+        match capability {
+            OrgCapability::DataRead => true,
+            _ => self.is_admin(),
+        }
+    }
+
+    fn is_admin(&self) -> bool {
+        self.member.role == Role::Admin
+    }
+
+    pub fn require_admin(&self) -> Result<(), ApiError> {
+        if !self.is_admin() {
+            error!("Requesting user is not an admin of org {}", self.org.id);
+            return Err(ApiError::forbidden(
+                "This action requires the admin role on the org",
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn org_not_found(org_id: &Uuid) -> ApiError {
+    ApiError::NotFound {
+        code: "ORG_NOT_FOUND".into(),
+        message: format!("No organization with id '{}'", org_id),
+    }
+}
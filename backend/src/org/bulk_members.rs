@@ -0,0 +1,257 @@
+use super::{Org, OrgInvitation, Role};
+use crate::auth::{SecurityEvent, SecurityEventKind};
+use crate::csv;
+use crate::notification::Notification;
+use crate::user::User;
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+use std::collections::{HashMap, HashSet};
+use tracing::error;
+use uuid::Uuid;
+
+// Most rows accepted by one bulk member request
+pub const MAX_BULK_MEMBERS: usize = 500;
+// Rows written per transaction, so a large import doesn't hold the org's rows for long
+const BULK_MEMBER_CHUNK: usize = 50;
+
+// One requested membership. The role is checked per row, so one bad row doesn't reject
+// the whole batch
+#[derive(Debug, Deserialize)]
+pub struct BulkMemberRow {
+    pub email: String,
+    pub role: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkMemberStatus {
+    // An existing user was made a member
+    Added,
+    // No user has the email yet, they join when they sign up
+    Invited,
+    // The user is already a member, their role is left as is
+    AlreadyMember,
+    Invalid,
+    // The row's chunk could not be written
+    Failed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkMemberOutcome {
+    // 1-based position of the row in the request, a CSV header not counted
+    pub row: usize,
+    pub email: String,
+    pub status: BulkMemberStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct BulkMemberCounts {
+    pub added: usize,
+    pub invited: usize,
+    pub already_member: usize,
+    pub invalid: usize,
+    pub failed: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkMemberReport {
+    pub counts: BulkMemberCounts,
+    pub rows: Vec<BulkMemberOutcome>,
+}
+
+// Parse `email,role` records. A first record starting with an "email" column is taken as
+// a header
+pub fn parse_members_csv(body: &str) -> Result<Vec<BulkMemberRow>, String> {
+    let mut rows = Vec::new();
+    let records = csv::parse(body).map_err(|e| e.to_string())?;
+    for (index, record) in records.iter().enumerate() {
+        let fields: Vec<&str> = record.fields.iter().map(|field| field.trim()).collect();
+        if index == 0 && fields[0].eq_ignore_ascii_case("email") {
+            continue;
+        }
+        match fields.as_slice() {
+            [email, role] => rows.push(BulkMemberRow {
+                email: email.to_string(),
+                role: role.to_string(),
+            }),
+            _ => return Err(format!("Line {}: expected 'email,role'", record.line)),
+        }
+    }
+    Ok(rows)
+}
+
+// A row that passed validation
+struct ValidRow {
+    row: usize,
+    email: String,
+    role: Role,
+}
+
+impl Org {
+    // Add the listed people to the org. Existing users become members, unknown emails get an
+    // invitation and current members are skipped. Rows are written in chunks of transactions,
+    // a failed chunk marks its rows failed without undoing the others. Returns the report
+    // and the notifications of the users added, for delivery
+    pub async fn import_members(
+        &self,
+        pool: &sqlx::PgPool,
+        rows: Vec<BulkMemberRow>,
+        imported_by: &User,
+    ) -> Result<(BulkMemberReport, Vec<Notification>), sqlx::Error> {
+        let mut outcomes = Vec::with_capacity(rows.len());
+        let mut valid = Vec::new();
+        let mut seen = HashSet::new();
+        for (index, row) in rows.into_iter().enumerate() {
+            let email = row.email.trim().to_lowercase();
+            let invalid = if !validator::validate_email(email.as_str()) {
+                Some("Invalid email".to_string())
+            } else if !seen.insert(email.clone()) {
+                Some("Email is listed more than once".to_string())
+            } else {
+                match row.role.trim().to_lowercase().parse::<Role>() {
+                    Ok(role) => {
+                        valid.push(ValidRow {
+                            row: index + 1,
+                            email: email.clone(),
+                            role,
+                        });
+                        None
+                    }
+                    Err(_) => Some(format!("Unknown role '{}'", row.role)),
+                }
+            };
+            if let Some(message) = invalid {
+                outcomes.push(BulkMemberOutcome {
+                    row: index + 1,
+                    email,
+                    status: BulkMemberStatus::Invalid,
+                    message: Some(message),
+                });
+            }
+        }
+
+        let emails: Vec<&str> = valid.iter().map(|row| row.email.as_str()).collect();
+        let users: HashMap<String, Uuid> =
+            sqlx::query_as::<_, User>("SELECT * FROM app_data.user WHERE lower(email) = ANY($1)")
+                .bind(&emails)
+                .fetch_all(pool)
+                .await?
+                .into_iter()
+                .map(|user| (user.email.to_lowercase(), user.id))
+                .collect();
+
+        let mut notifications = Vec::new();
+        for chunk in valid.chunks(BULK_MEMBER_CHUNK) {
+            let mut tx = pool.begin().await?;
+            let written = match self
+                .import_chunk(&mut tx, chunk, &users, imported_by.id)
+                .await
+            {
+                Ok(written) => tx.commit().await.map(|_| written),
+                Err(e) => Err(e),
+            };
+            match written {
+                Ok(written) => {
+                    for (row, (status, notification)) in chunk.iter().zip(written) {
+                        outcomes.push(BulkMemberOutcome {
+                            row: row.row,
+                            email: row.email.clone(),
+                            status,
+                            message: None,
+                        });
+                        notifications.extend(notification);
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to import org members into {}: {:?}", self.id, e);
+                    outcomes.extend(chunk.iter().map(|row| BulkMemberOutcome {
+                        row: row.row,
+                        email: row.email.clone(),
+                        status: BulkMemberStatus::Failed,
+                        message: Some("Could not be saved".to_string()),
+                    }));
+                }
+            }
+        }
+        outcomes.sort_by_key(|outcome| outcome.row);
+
+        let mut counts = BulkMemberCounts::default();
+        for outcome in &outcomes {
+            match outcome.status {
+                BulkMemberStatus::Added => counts.added += 1,
+                BulkMemberStatus::Invited => counts.invited += 1,
+                BulkMemberStatus::AlreadyMember => counts.already_member += 1,
+                BulkMemberStatus::Invalid => counts.invalid += 1,
+                BulkMemberStatus::Failed => counts.failed += 1,
+            }
+        }
+
+        // One audit event for the whole import rather than one per row. The chunks are
+        // already committed, so a failure here is logged rather than returned
+        let mut conn = pool.acquire().await?;
+        if let Err(e) = SecurityEvent::record(
+            &mut conn,
+            imported_by.id,
+            SecurityEventKind::OrgMembersImported,
+            serde_json::json!({
+                "org_id": self.id,
+                "rows": outcomes.len(),
+                "counts": counts,
+            }),
+        )
+        .await
+        {
+            error!("Failed to record org member import of {}: {:?}", self.id, e);
+        }
+
+        Ok((
+            BulkMemberReport {
+                counts,
+                rows: outcomes,
+            },
+            notifications,
+        ))
+    }
+
+    async fn import_chunk(
+        &self,
+        conn: &mut PgConnection,
+        chunk: &[ValidRow],
+        users: &HashMap<String, Uuid>,
+        imported_by: Uuid,
+    ) -> Result<Vec<(BulkMemberStatus, Option<Notification>)>, sqlx::Error> {
+        let member_query = "
+        INSERT INTO app_data.org_member (org_id, user_id, role, created_at, updated_at)
+        VALUES ($1, $2, $3, now(), now())
+        ON CONFLICT (user_id, org_id) DO NOTHING
+        ";
+        let mut written = Vec::with_capacity(chunk.len());
+        for row in chunk {
+            let Some(user_id) = users.get(&row.email) else {
+                OrgInvitation::new(self.id, &row.email, row.role.clone(), imported_by)
+                    .persist(&mut *conn)
+                    .await?;
+                written.push((BulkMemberStatus::Invited, None));
+                continue;
+            };
+
+            let inserted = sqlx::query(member_query)
+                .bind(self.id)
+                .bind(user_id)
+                .bind(row.role.to_string())
+                .execute(&mut *conn)
+                .await?
+                .rows_affected();
+            if inserted == 0 {
+                written.push((BulkMemberStatus::AlreadyMember, None));
+                continue;
+            }
+            let notification = self.member_added_notification(*user_id, &row.role, imported_by);
+            notification.persist(&mut *conn).await?;
+            written.push((BulkMemberStatus::Added, Some(notification)));
+        }
+        Ok(written)
+    }
+}
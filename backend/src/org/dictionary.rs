@@ -0,0 +1,249 @@
+use crate::node::NodeTypeAttributeDataType;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sqlx::{postgres::PgRow, FromRow, Row};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+// A canonical attribute definition shared by the graphs of an org
+#[derive(Debug, Clone, Serialize)]
+pub struct OrgAttribute {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub name: String,
+    pub normalized_name: String,
+    pub data_type: NodeTypeAttributeDataType,
+    pub description: String,
+    // Free-form constraints, e.g. {"max_length": 100}
+    pub constraints: JsonValue,
+    pub created_by: Uuid,
+    #[serde(with = "crate::utils::rfc3339")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(with = "crate::utils::rfc3339")]
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl<'r> FromRow<'r, PgRow> for OrgAttribute {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        let data_type: String = row.try_get("data_type")?;
+        let data_type = data_type
+            .parse::<NodeTypeAttributeDataType>()
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+        Ok(Self {
+            id: row.try_get("id")?,
+            org_id: row.try_get("org_id")?,
+            name: row.try_get("name")?,
+            normalized_name: row.try_get("normalized_name")?,
+            data_type,
+            description: row.try_get("description")?,
+            constraints: row.try_get("constraints")?,
+            created_by: row.try_get("created_by")?,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+}
+
+// An attribute in a type creation request, either spelled out or copied from the
+// org dictionary with {"from_dictionary": "<id>"}
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum AttributeSpec<T> {
+    FromDictionary {
+        from_dictionary: Uuid,
+        #[serde(default)]
+        required: bool,
+    },
+    Inline(T),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DictionaryError {
+    #[error("Dictionary attribute '{0}' does not exist in this org")]
+    NotFound(Uuid),
+
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl<T> AttributeSpec<T> {
+    // Resolve the specs of a type creation request against the org dictionary. Dictionary
+    // entries are turned into definitions by `from_entry` and keep their id
+    pub async fn resolve_all(
+        pool: &sqlx::PgPool,
+        org_id: Uuid,
+        specs: Vec<Self>,
+        from_entry: impl Fn(&OrgAttribute, bool) -> T,
+    ) -> Result<Vec<(T, Option<Uuid>)>, DictionaryError> {
+        let ids: Vec<Uuid> = specs
+            .iter()
+            .filter_map(|spec| match spec {
+                AttributeSpec::FromDictionary {
+                    from_dictionary, ..
+                } => Some(*from_dictionary),
+                AttributeSpec::Inline(_) => None,
+            })
+            .collect();
+        let entries: HashMap<Uuid, OrgAttribute> = if ids.is_empty() {
+            HashMap::new()
+        } else {
+            OrgAttribute::from_ids(pool, org_id, &ids)
+                .await?
+                .into_iter()
+                .map(|entry| (entry.id, entry))
+                .collect()
+        };
+
+        specs
+            .into_iter()
+            .map(|spec| match spec {
+                AttributeSpec::Inline(definition) => Ok((definition, None)),
+                AttributeSpec::FromDictionary {
+                    from_dictionary,
+                    required,
+                } => {
+                    let entry = entries
+                        .get(&from_dictionary)
+                        .ok_or(DictionaryError::NotFound(from_dictionary))?;
+                    Ok((from_entry(entry, required), Some(entry.id)))
+                }
+            })
+            .collect()
+    }
+}
+
+impl OrgAttribute {
+    // The "max_length" constraint, copied onto the attributes created from the entry
+    pub fn max_length(&self) -> Option<i32> {
+        self.constraints
+            .get("max_length")
+            .and_then(JsonValue::as_i64)
+            .and_then(|n| i32::try_from(n).ok())
+            .filter(|n| *n > 0)
+    }
+
+    pub fn new(
+        org_id: Uuid,
+        name: &str,
+        data_type: NodeTypeAttributeDataType,
+        description: String,
+        constraints: JsonValue,
+        created_by: Uuid,
+    ) -> Self {
+        let now = chrono::Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            org_id,
+            name: name.to_string(),
+            normalized_name: crate::utils::normalize(name),
+            data_type,
+            description,
+            constraints,
+            created_by,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub async fn persist(&self, pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+        let query = r#"
+            INSERT INTO app_data.org_attribute (
+                id, org_id, name, normalized_name, data_type, description, constraints,
+                created_by, created_at, updated_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        "#;
+        sqlx::query(query)
+            .bind(self.id)
+            .bind(self.org_id)
+            .bind(&self.name)
+            .bind(&self.normalized_name)
+            .bind(self.data_type.to_string())
+            .bind(&self.description)
+            .bind(&self.constraints)
+            .bind(self.created_by)
+            .bind(self.created_at)
+            .bind(self.updated_at)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn list(pool: &sqlx::PgPool, org_id: Uuid) -> Result<Vec<Self>, sqlx::Error> {
+        let query = "SELECT * FROM app_data.org_attribute WHERE org_id = $1 ORDER BY name, id";
+        sqlx::query_as::<_, OrgAttribute>(query)
+            .bind(org_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn from_id(
+        pool: &sqlx::PgPool,
+        org_id: Uuid,
+        id: Uuid,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let query = "SELECT * FROM app_data.org_attribute WHERE org_id = $1 AND id = $2";
+        sqlx::query_as::<_, OrgAttribute>(query)
+            .bind(org_id)
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    async fn from_ids(
+        pool: &sqlx::PgPool,
+        org_id: Uuid,
+        ids: &[Uuid],
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let query = "SELECT * FROM app_data.org_attribute WHERE org_id = $1 AND id = ANY($2)";
+        sqlx::query_as::<_, OrgAttribute>(query)
+            .bind(org_id)
+            .bind(ids)
+            .fetch_all(pool)
+            .await
+    }
+
+    // Save the entry's data type, description and constraints. With `propagate`, node and
+    // edge type attributes created from the entry are updated in the same transaction.
+    // Returns the number of derived attributes updated
+    pub async fn update(&self, pool: &sqlx::PgPool, propagate: bool) -> Result<u64, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let query = r#"
+            UPDATE app_data.org_attribute
+            SET data_type = $2, description = $3, constraints = $4, updated_at = $5
+            WHERE id = $1
+        "#;
+        sqlx::query(query)
+            .bind(self.id)
+            .bind(self.data_type.to_string())
+            .bind(&self.description)
+            .bind(&self.constraints)
+            .bind(self.updated_at)
+            .execute(&mut *tx)
+            .await?;
+
+        let mut propagated = 0;
+        if propagate {
+            for table in [
+                "app_data.node_type_attributes",
+                "app_data.edge_type_attribute",
+            ] {
+                let query = format!(
+                    "UPDATE {} SET data_type = $2, description = $3, max_length = $4 WHERE dictionary_id = $1",
+                    table
+                );
+                propagated += sqlx::query(&query)
+                    .bind(self.id)
+                    .bind(self.data_type.to_string())
+                    .bind(&self.description)
+                    .bind(self.max_length())
+                    .execute(&mut *tx)
+                    .await?
+                    .rows_affected();
+            }
+        }
+
+        tx.commit().await?;
+        Ok(propagated)
+    }
+}
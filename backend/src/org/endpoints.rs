@@ -2,15 +2,26 @@ use super::Role;
 use crate::auth::Auth;
 use crate::config::AppState;
 use crate::error::ApiError;
-use crate::org::{Org, OrgMember};
+use crate::graph::GraphInfo;
+use crate::job::{Job, JobKind};
+use crate::node::NodeTypeAttributeDataType;
+use crate::org::{
+    parse_members_csv, BulkMemberRow, GraphOverview, Org, OrgAccess, OrgAttribute, OrgExport,
+    OrgPermissions, OrgSort, SchemaReport, UpdateRolesError, EXPORT_MAX_AGE_HOURS,
+    MAX_BULK_MEMBERS,
+};
 use crate::user::User;
+use crate::utils::Page;
 
-use axum::extract::{Extension, Path, State};
+use axum::body::Bytes;
+use axum::extract::{Extension, Path, Query, State};
+use axum::http::{header, HeaderMap};
 use axum::response::IntoResponse;
 use axum::Json;
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_json::{json, Value as JsonValue};
+use std::collections::HashSet;
 use tracing::{error, info};
 use uuid::Uuid;
 
@@ -34,10 +45,12 @@ pub async fn create_org(
 
     info!("Creating new organization: {}", body.name);
     let org = Org::new(&body.name, &body.description);
+    let user_id = user.id;
     org.persist(&state.pool, user).await.map_err(|e| {
         error!("Failed to create organization: {:?}", e);
         ApiError::InternalServerError
     })?;
+    state.memberships.invalidate([user_id]);
 
     Ok(StatusCode::CREATED)
 }
@@ -50,9 +63,19 @@ pub struct OrgMemberSummaryResponse {
     role: Role,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct GetOrgsQueryParams {
+    // Case-insensitive search on the org name
+    q: Option<String>,
+    page: Option<u32>,
+    page_size: Option<u32>,
+    sort: Option<OrgSort>,
+}
+
 pub async fn get_orgs(
     State(state): State<AppState>,
     Extension(auth): Extension<Auth>,
+    Query(params): Query<GetOrgsQueryParams>,
 ) -> Result<impl IntoResponse, ApiError> {
     // Anonymous users cannot be part of any organizations
     let user = auth.user.ok_or_else(|| {
@@ -60,39 +83,42 @@ pub async fn get_orgs(
         ApiError::Unauthorized
     })?;
 
-    let org_memberships = user.get_org_memberships(&state.pool).await.map_err(|e| {
-        error!("Failed to fetch org memberships: {:?}", e);
-        ApiError::InternalServerError
-    })?;
-    let org_ids: Vec<_> = org_memberships.iter().map(|m| m.org_id).collect();
+    let (page, page_size) = Page::<OrgMemberSummaryResponse>::bounds(params.page, params.page_size);
+    let q = params.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
 
-    let orgs = Org::get_many(&state.pool, org_ids).await.map_err(|e| {
+    let (orgs, total) = Org::search_for_user(
+        &state.pool,
+        user.id,
+        q,
+        params.sort.unwrap_or_default(),
+        page,
+        page_size,
+    )
+    .await
+    .map_err(|e| {
         error!("Failed to fetch orgs: {:?}", e);
         ApiError::InternalServerError
     })?;
 
-    let membership_map: HashMap<Uuid, &OrgMember> = org_memberships
-        .iter()
-        .map(|m| (m.org_id.clone(), m))
-        .collect();
-
     let org_summaries: Vec<OrgMemberSummaryResponse> = orgs
-        .clone()
         .into_iter()
-        .filter_map(|org| {
-            // Find the matching membership to get the role
-            membership_map
-                .get(&org.id)
-                .map(|membership| OrgMemberSummaryResponse {
-                    id: org.id.to_string(),
-                    name: org.name,
-                    description: org.description,
-                    role: membership.role.clone(),
-                })
+        .map(|o| OrgMemberSummaryResponse {
+            id: o.org.id.to_string(),
+            name: o.org.name,
+            description: o.org.description,
+            role: o.role,
         })
         .collect();
 
-    Ok((StatusCode::OK, Json(org_summaries)))
+    Ok((
+        StatusCode::OK,
+        Json(Page {
+            items: org_summaries,
+            page,
+            page_size,
+            total,
+        }),
+    ))
 }
 
 #[derive(Debug, Deserialize)]
@@ -112,28 +138,9 @@ pub async fn add_org_member(
         ApiError::Unauthorized
     })?;
 
-    let org = Org::from_id(&state.pool, &org_id).await.map_err(|e| {
-        error!("Failed to fetch org: {:?}", e);
-        ApiError::InternalServerError
-    })?;
-
-    // Check that the reqesting member is an admin
-    let requesting_member = org
-        .get_member(&state.pool, auth_user.id)
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch org member: {:?}", e);
-            ApiError::InternalServerError
-        })?
-        .ok_or_else(|| {
-            error!("Requesting user is not a member of the org");
-            ApiError::Unauthorized
-        })?;
-
-    if requesting_member.role != Role::Admin {
-        error!("Requesting user is not an admin of the org");
-        return Err(ApiError::Unauthorized);
-    }
+    let access = OrgAccess::resolve(&state, &org_id, &auth_user).await?;
+    access.require_admin()?;
+    let org = access.org;
 
     // Check that the user to be added exists
     let user = User::from_id(&state.pool, body.user_id)
@@ -154,12 +161,15 @@ pub async fn add_org_member(
     }
 
     // Add the user to the org
-    org.add_member(&state.pool, user, body.role)
+    let notification = org
+        .add_member(&state.pool, user, body.role, &auth_user)
         .await
         .map_err(|e| {
             error!("Failed to add user to org: {:?}", e);
             ApiError::InternalServerError
         })?;
+    state.memberships.invalidate([notification.user_id]);
+    state.notifier.dispatch(notification);
 
     Ok(StatusCode::CREATED)
 }
@@ -174,34 +184,541 @@ pub async fn get_org_members(
         ApiError::Unauthorized
     })?;
 
-    let org = Org::from_id(&state.pool, &org_id).await.map_err(|e| {
-        error!("Failed to fetch org: {:?}", e);
+    // Any member can list the members
+    let org = OrgAccess::resolve(&state, &org_id, &auth_user).await?.org;
+
+    let members = org.get_members_with_email(&state.pool).await.map_err(|e| {
+        error!("Failed to fetch org members: {:?}", e);
+        ApiError::InternalServerError
+    })?;
+
+    Ok((StatusCode::OK, Json(members)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MemberRoleChange {
+    user_id: Uuid,
+    role: Role,
+}
+
+pub async fn update_org_members(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(org_id): Path<Uuid>,
+    Json(body): Json<Vec<MemberRoleChange>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let auth_user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
         ApiError::Unauthorized
     })?;
 
-    // Check that the reqesting user is org member
-    let requesting_member = org
-        .get_member(&state.pool, auth_user.id)
+    let access = OrgAccess::resolve(&state, &org_id, &auth_user).await?;
+    access.require_admin()?;
+    let org = access.org;
+
+    if body.is_empty() {
+        return Err(ApiError::BadRequest("No role changes given".into()));
+    }
+    let mut seen = HashSet::new();
+    if let Some(change) = body.iter().find(|change| !seen.insert(change.user_id)) {
+        return Err(ApiError::BadRequest(format!(
+            "User '{}' is listed more than once",
+            change.user_id
+        )));
+    }
+
+    let changes: Vec<(Uuid, Role)> = body
+        .into_iter()
+        .map(|change| (change.user_id, change.role))
+        .collect();
+    org.update_member_roles(&state.pool, &changes)
+        .await
+        .map_err(|e| match e {
+            UpdateRolesError::NotMember(_) => ApiError::BadRequest(e.to_string()),
+            UpdateRolesError::NoAdminLeft => ApiError::Conflict {
+                code: "LAST_ADMIN".into(),
+                message: e.to_string(),
+                details: None,
+            },
+            UpdateRolesError::DatabaseError(e) => {
+                error!("Failed to update member roles: {:?}", e);
+                ApiError::InternalServerError
+            }
+        })?;
+    state
+        .memberships
+        .invalidate(changes.iter().map(|(user_id, _)| *user_id));
+    info!(
+        "Updated {} member role(s) in org {} by {}",
+        changes.len(),
+        org.id,
+        auth_user.id
+    );
+
+    let members = org.get_members_with_email(&state.pool).await.map_err(|e| {
+        error!("Failed to fetch org members: {:?}", e);
+        ApiError::InternalServerError
+    })?;
+
+    Ok((StatusCode::OK, Json(members)))
+}
+
+// Add many people at once, from a JSON list of {email, role} or a text/csv body of
+// `email,role` lines. Returns the outcome of every row
+pub async fn bulk_add_org_members(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(org_id): Path<Uuid>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, ApiError> {
+    let auth_user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = OrgAccess::resolve(&state, &org_id, &auth_user).await?;
+    access.require_admin()?;
+    let org = access.org;
+
+    let is_csv = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/csv"));
+    let rows = if is_csv {
+        let body = std::str::from_utf8(&body)
+            .map_err(|_| ApiError::BadRequest("CSV body must be UTF-8".into()))?;
+        parse_members_csv(body).map_err(ApiError::BadRequest)?
+    } else {
+        serde_json::from_slice::<Vec<BulkMemberRow>>(&body)
+            .map_err(|e| ApiError::BadRequest(format!("Invalid member list: {}", e)))?
+    };
+    if rows.is_empty() {
+        return Err(ApiError::BadRequest("No members given".into()));
+    }
+    if rows.len() > MAX_BULK_MEMBERS {
+        return Err(ApiError::BadRequest(format!(
+            "At most {} members can be added at once",
+            MAX_BULK_MEMBERS
+        )));
+    }
+
+    let (report, notifications) = org
+        .import_members(&state.pool, rows, &auth_user)
         .await
         .map_err(|e| {
-            error!("Failed to fetch org member: {:?}", e);
+            error!("Failed to import org members: {:?}", e);
             ApiError::InternalServerError
-        })?
-        .ok_or_else(|| {
-            error!("Requesting user is not a member of the org");
-            ApiError::Unauthorized
         })?;
+    // Every added member is notified, invited ones have no account yet
+    state.memberships.invalidate(
+        notifications
+            .iter()
+            .map(|notification| notification.user_id),
+    );
+    for notification in notifications {
+        state.notifier.dispatch(notification);
+    }
+    info!(
+        "Imported {} member row(s) into org {} by {}",
+        report.rows.len(),
+        org.id,
+        auth_user.id
+    );
+
+    Ok((StatusCode::OK, Json(report)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteOrgQueryParams {
+    // Required to delete an org that still has graphs. All of its graphs are dropped.
+    force: Option<bool>,
+    // Drop the graphs even without a recent org export
+    skip_export: Option<bool>,
+}
+
+pub async fn delete_org(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(org_id): Path<Uuid>,
+    Query(params): Query<DeleteOrgQueryParams>,
+) -> Result<StatusCode, ApiError> {
+    let auth_user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = OrgAccess::resolve(&state, &org_id, &auth_user).await?;
+    access.require_admin()?;
+    let org = access.org;
+
+    let graphs = GraphInfo::get_all(&state.pool, org.id).await.map_err(|e| {
+        error!("Failed to fetch graphs: {:?}", e);
+        ApiError::InternalServerError
+    })?;
 
-    // Check that the requesting member is an admin or member
-    if requesting_member.role != Role::Admin && requesting_member.role != Role::Viewer {
-        error!("Requesting user is not an admin of the org");
-        return Err(ApiError::Unauthorized);
+    // Protected graphs must be unprotected first, even with force
+    let protected: Vec<&GraphInfo> = graphs.iter().filter(|g| g.deletion_protected).collect();
+    if !protected.is_empty() {
+        return Err(ApiError::Conflict {
+            code: "PROTECTED_RESOURCE".into(),
+            message: format!(
+                "Organization has {} deletion protected graph(s). Remove the protection before deleting",
+                protected.len()
+            ),
+            details: Some(
+                protected
+                    .iter()
+                    .map(|g| format!("{} ({})", g.name, g.graph_id))
+                    .collect(),
+            ),
+        });
     }
 
-    let members = org.get_members_with_email(&state.pool).await.map_err(|e| {
-        error!("Failed to fetch org members: {:?}", e);
+    // Refuse to drop graphs unless the caller explicitly asked for it
+    if !graphs.is_empty() && !params.force.unwrap_or(false) {
+        return Err(ApiError::Conflict {
+            code: "ORG_HAS_GRAPHS".into(),
+            message: format!(
+                "Organization has {} graph(s) which would be deleted. Retry with force=true to delete them",
+                graphs.len()
+            ),
+            details: Some(
+                graphs
+                    .iter()
+                    .map(|g| format!("{} ({})", g.name, g.graph_id))
+                    .collect(),
+            ),
+        });
+    }
+
+    // Dropping graphs needs an export from the last EXPORT_MAX_AGE_HOURS to fall back on
+    if !graphs.is_empty() && !params.skip_export.unwrap_or(false) {
+        let since = chrono::Utc::now() - chrono::Duration::hours(EXPORT_MAX_AGE_HOURS);
+        let export = Job::latest_completed(&state.pool, org.id, JobKind::OrgExport, since)
+            .await
+            .map_err(|e| {
+                error!("Failed to look up org export: {:?}", e);
+                ApiError::InternalServerError
+            })?;
+        if export.is_none() {
+            return Err(ApiError::Conflict {
+                code: "EXPORT_REQUIRED".into(),
+                message: format!(
+                    "Organization graphs can only be deleted after an export from the last {} hours. Start one with POST /orgs/{}/export or retry with skip_export=true",
+                    EXPORT_MAX_AGE_HOURS, org.id
+                ),
+                details: None,
+            });
+        }
+    }
+
+    info!(
+        "Deleting organization {} with {} graph(s), by: {}",
+        org.id,
+        graphs.len(),
+        auth_user.id
+    );
+    org.delete(&state.pool, &graphs).await.map_err(|e| {
+        error!("Failed to delete organization: {:?}", e);
         ApiError::InternalServerError
     })?;
 
-    Ok((StatusCode::OK, Json(members)))
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Bundle the org's graphs, members and audit log into a tar.gz in the background. The
+// result is downloaded from /jobs/:id/result
+pub async fn start_org_export_job(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(org_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<JsonValue>), ApiError> {
+    let auth_user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = OrgAccess::resolve(&state, &org_id, &auth_user).await?;
+    access.require_admin()?;
+    let org = access.org;
+
+    let mut job = Job::new(org.id, None, JobKind::OrgExport, auth_user.id);
+    job.persist(&state.pool).await.map_err(|e| {
+        error!("Failed to create org export job: {:?}", e);
+        ApiError::InternalServerError
+    })?;
+    let job_id = job.id;
+
+    let pool = state.pool.clone();
+    tokio::spawn(async move {
+        if let Err(e) = job.mark_running(&pool).await {
+            error!("Failed to start org export job {}: {:?}", job.id, e);
+            return;
+        }
+
+        let outcome = match OrgExport::build(&pool, &org).await {
+            Ok(bytes) => {
+                let filename = format!("{}-export.tar.gz", org.id);
+                job.complete(&pool, &bytes, "application/gzip", &filename)
+                    .await
+            }
+            Err(e) => {
+                error!("Org export job {} failed: {}", job.id, e);
+                job.fail(&pool, &e).await
+            }
+        };
+        if let Err(e) = outcome {
+            error!("Failed to record org export job {} result: {:?}", job.id, e);
+        }
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(json!({ "id": job_id }))))
+}
+
+// What the calling user may do in the org, for deciding which controls to show
+pub async fn get_org_permissions(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(org_id): Path<Uuid>,
+) -> Result<Json<OrgPermissions>, ApiError> {
+    let auth_user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = OrgAccess::resolve(&state, &org_id, &auth_user).await?;
+
+    Ok(Json(OrgPermissions::from(&access)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetOrgOverviewQueryParams {
+    page: Option<u32>,
+    page_size: Option<u32>,
+}
+
+// Type and data counts of the org's graphs in one response, for the dashboard. Paginated
+// like the graph listing so large orgs stay cheap to load
+pub async fn get_org_overview(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(org_id): Path<Uuid>,
+    Query(params): Query<GetOrgOverviewQueryParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let auth_user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    // Any member can read every graph of the org
+    let org = OrgAccess::resolve(&state, &org_id, &auth_user).await?.org;
+
+    let (page, page_size) = Page::<GraphOverview>::bounds(params.page, params.page_size);
+    let (graphs, total) = GraphOverview::page_for_org(&state.pool, org.id, page, page_size)
+        .await
+        .map_err(|e| {
+            error!("Failed to build org overview: {:?}", e);
+            ApiError::InternalServerError
+        })?;
+
+    Ok((
+        StatusCode::OK,
+        Json(Page {
+            items: graphs,
+            page,
+            page_size,
+            total,
+        }),
+    ))
+}
+
+pub async fn get_schema_report(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(org_id): Path<Uuid>,
+) -> Result<Json<SchemaReport>, ApiError> {
+    let auth_user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let org = OrgAccess::resolve(&state, &org_id, &auth_user).await?.org;
+
+    let report = SchemaReport::for_org(&state.pool, org.id)
+        .await
+        .map_err(|e| {
+            error!("Failed to build schema report: {:?}", e);
+            ApiError::InternalServerError
+        })?;
+
+    Ok(Json(report))
+}
+
+// Constraints are free-form, except for the ones copied onto derived attributes
+fn check_constraints(constraints: &JsonValue) -> Result<(), ApiError> {
+    if !constraints.is_object() {
+        return Err(ApiError::BadRequest("constraints must be an object".into()));
+    }
+    if let Some(max_length) = constraints.get("max_length") {
+        let valid = max_length
+            .as_i64()
+            .is_some_and(|n| n > 0 && i32::try_from(n).is_ok());
+        if !valid {
+            return Err(ApiError::BadRequest(
+                "constraints.max_length must be a positive integer".into(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateOrgAttributeRequest {
+    name: String,
+    data_type: NodeTypeAttributeDataType,
+    description: String,
+    constraints: Option<JsonValue>,
+}
+
+pub async fn create_org_attribute(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(org_id): Path<Uuid>,
+    Json(body): Json<CreateOrgAttributeRequest>,
+) -> Result<(StatusCode, Json<OrgAttribute>), ApiError> {
+    let auth_user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = OrgAccess::resolve(&state, &org_id, &auth_user).await?;
+    access.require_admin()?;
+    let org = access.org;
+
+    if body.name.trim().is_empty() {
+        return Err(ApiError::BadRequest(
+            "Attribute name cannot be empty".into(),
+        ));
+    }
+    let constraints = body.constraints.unwrap_or_else(|| json!({}));
+    check_constraints(&constraints)?;
+
+    let attribute = OrgAttribute::new(
+        org.id,
+        body.name.trim(),
+        body.data_type,
+        body.description,
+        constraints,
+        auth_user.id,
+    );
+    attribute.persist(&state.pool).await.map_err(|e| match e {
+        sqlx::Error::Database(ref db_err) if db_err.is_unique_violation() => ApiError::Conflict {
+            code: "ORG_ATTRIBUTE_EXISTS".into(),
+            message: format!(
+                "An attribute named '{}' already exists in this org's dictionary",
+                attribute.normalized_name
+            ),
+            details: None,
+        },
+        e => {
+            error!("Failed to save org attribute: {:?}", e);
+            ApiError::InternalServerError
+        }
+    })?;
+
+    Ok((StatusCode::CREATED, Json(attribute)))
+}
+
+pub async fn get_org_attributes(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(org_id): Path<Uuid>,
+) -> Result<Json<Vec<OrgAttribute>>, ApiError> {
+    let auth_user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let org = OrgAccess::resolve(&state, &org_id, &auth_user).await?.org;
+
+    let attributes = OrgAttribute::list(&state.pool, org.id).await.map_err(|e| {
+        error!("Failed to fetch org attributes: {:?}", e);
+        ApiError::InternalServerError
+    })?;
+
+    Ok(Json(attributes))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateOrgAttributeRequest {
+    data_type: NodeTypeAttributeDataType,
+    description: String,
+    constraints: Option<JsonValue>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateOrgAttributeQueryParams {
+    // Also update the node and edge type attributes created from this entry
+    propagate: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub struct UpdateOrgAttributeResponse {
+    #[serde(flatten)]
+    attribute: OrgAttribute,
+    propagated: u64,
+}
+
+pub async fn update_org_attribute(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path((org_id, attribute_id)): Path<(Uuid, Uuid)>,
+    Query(params): Query<UpdateOrgAttributeQueryParams>,
+    Json(body): Json<UpdateOrgAttributeRequest>,
+) -> Result<Json<UpdateOrgAttributeResponse>, ApiError> {
+    let auth_user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = OrgAccess::resolve(&state, &org_id, &auth_user).await?;
+    access.require_admin()?;
+    let org = access.org;
+
+    let mut attribute = OrgAttribute::from_id(&state.pool, org.id, attribute_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch org attribute: {:?}", e);
+            ApiError::InternalServerError
+        })?
+        .ok_or_else(|| ApiError::NotFound {
+            code: "ORG_ATTRIBUTE_NOT_FOUND".into(),
+            message: format!("Dictionary attribute '{}' does not exist", attribute_id),
+        })?;
+
+    attribute.data_type = body.data_type;
+    attribute.description = body.description;
+    if let Some(constraints) = body.constraints {
+        check_constraints(&constraints)?;
+        attribute.constraints = constraints;
+    }
+    attribute.updated_at = chrono::Utc::now();
+
+    let propagated = attribute
+        .update(&state.pool, params.propagate.unwrap_or(false))
+        .await
+        .map_err(|e| {
+            error!("Failed to update org attribute: {:?}", e);
+            ApiError::InternalServerError
+        })?;
+    info!(
+        "Updated org attribute {} by {}, propagated to {} attribute(s)",
+        attribute.id, auth_user.id, propagated
+    );
+
+    Ok(Json(UpdateOrgAttributeResponse {
+        attribute,
+        propagated,
+    }))
 }
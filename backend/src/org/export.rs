@@ -0,0 +1,71 @@
+use crate::auth::SecurityEvent;
+use crate::graph::{GraphExport, GraphInfo};
+use crate::node::NodeVisibility;
+use crate::org::Org;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+use tracing::info;
+
+// How recent an org export must be for the org's graphs to be deleted without skip_export
+pub const EXPORT_MAX_AGE_HOURS: i64 = 24;
+
+// Everything an org holds, bundled as a tar.gz before the org is deleted:
+// - graphs/<graph_id>.json: a snapshot export of each graph
+// - members.json: the org members with their email
+// - audit_log.json: security events about the org's graphs
+pub struct OrgExport;
+
+impl OrgExport {
+    pub async fn build(pool: &sqlx::PgPool, org: &Org) -> Result<Vec<u8>, String> {
+        info!("Exporting organization: {}", org.id);
+
+        let graphs = GraphInfo::get_all(pool, org.id)
+            .await
+            .map_err(|e| e.to_string())?;
+        let members = org
+            .get_members_with_email(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        let graph_ids: Vec<String> = graphs.iter().map(|g| g.graph_id.to_string()).collect();
+        let audit_log = SecurityEvent::for_graphs(pool, &graph_ids)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let mut archive = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for graph in &graphs {
+            let export =
+                GraphExport::collect(pool, &graph.graph_id, true, NodeVisibility::everything())
+                    .await
+                    .map_err(|e| e.to_string())?;
+            append_json(
+                &mut archive,
+                &format!("graphs/{}.json", graph.graph_id),
+                &export,
+            )?;
+        }
+        append_json(&mut archive, "members.json", &members)?;
+        append_json(&mut archive, "audit_log.json", &audit_log)?;
+
+        archive
+            .into_inner()
+            .and_then(|encoder| encoder.finish())
+            .map_err(|e| e.to_string())
+    }
+}
+
+fn append_json<T: Serialize>(
+    archive: &mut tar::Builder<GzEncoder<Vec<u8>>>,
+    path: &str,
+    value: &T,
+) -> Result<(), String> {
+    let bytes = serde_json::to_vec(value).map_err(|e| e.to_string())?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(bytes.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(chrono::Utc::now().timestamp() as u64);
+    header.set_cksum();
+    archive
+        .append_data(&mut header, path, bytes.as_slice())
+        .map_err(|e| e.to_string())
+}
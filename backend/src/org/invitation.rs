@@ -0,0 +1,83 @@
+use super::Role;
+use crate::user::User;
+use serde::Serialize;
+use sqlx::{postgres::PgRow, FromRow, PgConnection, Row};
+use uuid::Uuid;
+
+// A pending membership for an email that has no account yet
+#[derive(Debug, Serialize)]
+pub struct OrgInvitation {
+    pub org_id: Uuid,
+    pub email: String,
+    pub role: Role,
+    pub invited_by: Uuid,
+    #[serde(with = "crate::utils::rfc3339")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl<'r> FromRow<'r, PgRow> for OrgInvitation {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        let role: String = row.try_get("role")?;
+        let role = role
+            .parse::<Role>()
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+        Ok(Self {
+            org_id: row.try_get("org_id")?,
+            email: row.try_get("email")?,
+            role,
+            invited_by: row.try_get("invited_by")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+impl OrgInvitation {
+    pub fn new(org_id: Uuid, email: &str, role: Role, invited_by: Uuid) -> Self {
+        Self {
+            org_id,
+            email: email.trim().to_lowercase(),
+            role,
+            invited_by,
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    // Inviting an email again replaces the role of its pending invitation
+    pub async fn persist(&self, conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+        let query = "
+        INSERT INTO app_data.org_invitation (org_id, email, role, invited_by, created_at)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (org_id, email)
+        DO UPDATE SET role = EXCLUDED.role, invited_by = EXCLUDED.invited_by, created_at = EXCLUDED.created_at
+        ";
+        sqlx::query(query)
+            .bind(self.org_id)
+            .bind(&self.email)
+            .bind(self.role.to_string())
+            .bind(self.invited_by)
+            .bind(self.created_at)
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+
+    // Turn the invitations for a new user's email into memberships. Runs in the transaction
+    // that creates the user
+    pub async fn accept_all(conn: &mut PgConnection, user: &User) -> Result<u64, sqlx::Error> {
+        let query = "
+        WITH accepted AS (
+            DELETE FROM app_data.org_invitation WHERE email = lower($2) RETURNING org_id, role
+        )
+        INSERT INTO app_data.org_member (org_id, user_id, role, created_at, updated_at)
+        SELECT org_id, $1, role, now(), now() FROM accepted
+        ON CONFLICT (user_id, org_id) DO NOTHING
+        ";
+        let result = sqlx::query(query)
+            .bind(user.id)
+            .bind(&user.email)
+            .execute(conn)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
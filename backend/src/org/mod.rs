@@ -1,5 +1,19 @@
+mod access;
+mod bulk_members;
+mod dictionary;
 mod endpoints;
+mod export;
+mod invitation;
 mod org;
+mod overview;
+mod schema_report;
 
+pub use access::*;
+pub use bulk_members::*;
+pub use dictionary::*;
 pub use endpoints::*;
+pub use export::*;
+pub use invitation::*;
 pub use org::*;
+pub use overview::*;
+pub use schema_report::*;
@@ -1,6 +1,9 @@
+use crate::graph::GraphInfo;
+use crate::notification::{Notification, NotificationKind};
 use crate::user::User;
 use serde::{Deserialize, Serialize};
 use sqlx::{postgres::PgRow, FromRow, Row};
+use std::collections::HashMap;
 use strum_macros::{Display, EnumString};
 use uuid::Uuid;
 
@@ -11,12 +14,14 @@ pub enum Role {
     Viewer,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct OrgMember {
     pub org_id: Uuid,
     pub user_id: Uuid,
     pub role: Role,
+    #[serde(with = "crate::utils::rfc3339")]
     pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(with = "crate::utils::rfc3339")]
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
@@ -42,7 +47,9 @@ pub struct OrgMemberWithEmail {
     pub user_id: Uuid,
     pub role: Role,
     pub email: String,
+    #[serde(with = "crate::utils::rfc3339")]
     pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(with = "crate::utils::rfc3339")]
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
@@ -68,7 +75,9 @@ pub struct Org {
     pub id: Uuid,
     pub name: String,
     pub description: String,
+    #[serde(with = "crate::utils::rfc3339")]
     pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(with = "crate::utils::rfc3339")]
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
@@ -84,6 +93,47 @@ impl<'r> FromRow<'r, PgRow> for Org {
     }
 }
 
+// Column to order a user's orgs by
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrgSort {
+    #[default]
+    Name,
+    CreatedAt,
+}
+
+// An org together with the role of the user it was fetched for
+pub struct OrgWithRole {
+    pub org: Org,
+    pub role: Role,
+}
+
+impl<'r> FromRow<'r, PgRow> for OrgWithRole {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        let role: String = row.try_get("role")?;
+        let role = role
+            .parse::<Role>()
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+        Ok(Self {
+            org: Org::from_row(row)?,
+            role,
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum UpdateRolesError {
+    #[error("User '{0}' is not a member of the org")]
+    NotMember(Uuid),
+
+    #[error("The org must keep at least one admin")]
+    NoAdminLeft,
+
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
 impl OrgMember {
     pub fn new(org_id: Uuid, user_id: Uuid, role: Role) -> Self {
         let now = chrono::Utc::now();
@@ -138,6 +188,27 @@ impl Org {
         Ok(())
     }
 
+    // Delete the org along with the given graphs, which must be all of the org's graphs
+    pub async fn delete(
+        &self,
+        pool: &sqlx::PgPool,
+        graphs: &[GraphInfo],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        for graph in graphs {
+            graph.delete(&mut tx).await?;
+        }
+
+        let org_query = "DELETE FROM app_data.org WHERE id = $1";
+        sqlx::query(org_query)
+            .bind(self.id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
     pub async fn from_id(pool: &sqlx::PgPool, org_id: &Uuid) -> Result<Self, sqlx::Error> {
         let org_query = "SELECT * FROM app_data.org WHERE id = $1";
         sqlx::query_as::<_, Org>(org_query)
@@ -146,16 +217,48 @@ impl Org {
             .await
     }
 
-    // Get multiple orgs given a list of org ids
-    pub async fn get_many(
+    pub async fn search_for_user(
         pool: &sqlx::PgPool,
-        org_ids: Vec<Uuid>,
-    ) -> Result<Vec<Self>, sqlx::Error> {
-        let org_query = "SELECT * FROM app_data.org WHERE id = ANY($1)";
-        sqlx::query_as::<_, Org>(org_query)
-            .bind(&org_ids)
+        user_id: Uuid,
+        q: Option<&str>,
+        sort: OrgSort,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<OrgWithRole>, i64), sqlx::Error> {
+        let order_by = match sort {
+            OrgSort::Name => "o.name, o.id",
+            OrgSort::CreatedAt => "o.created_at DESC, o.id",
+        };
+        let query = format!(
+            r#"
+            SELECT o.*, m.role, COUNT(*) OVER () AS total
+            FROM app_data.org o
+            JOIN app_data.org_member m ON m.org_id = o.id
+            WHERE m.user_id = $1
+              AND ($2::text IS NULL OR o.name ILIKE '%' || $2 || '%')
+            ORDER BY {}
+            LIMIT $3 OFFSET $4
+            "#,
+            order_by
+        );
+
+        let rows = sqlx::query(&query)
+            .bind(user_id)
+            .bind(q)
+            .bind(page_size as i64)
+            .bind(((page - 1) * page_size) as i64)
             .fetch_all(pool)
-            .await
+            .await?;
+
+        let total = match rows.first() {
+            Some(row) => row.try_get("total")?,
+            None => 0,
+        };
+        let orgs = rows
+            .iter()
+            .map(OrgWithRole::from_row)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((orgs, total))
     }
 
     pub async fn get_member(
@@ -181,6 +284,7 @@ impl Org {
         FROM app_data.org_member om
         JOIN app_data.user u ON om.user_id = u.id
         WHERE om.org_id = $1
+        ORDER BY om.created_at, om.user_id
         ";
         sqlx::query_as::<_, OrgMemberWithEmail>(query)
             .bind(&self.id)
@@ -188,12 +292,15 @@ impl Org {
             .await
     }
 
+    // Add the user to the org and notify them, returning the notification for delivery
     pub async fn add_member(
         &self,
         pool: &sqlx::PgPool,
         user: User,
         role: Role,
-    ) -> Result<(), sqlx::Error> {
+        added_by: &User,
+    ) -> Result<Notification, sqlx::Error> {
+        let mut tx = pool.begin().await?;
         let org_user = OrgMember::new(self.id, user.id, role);
         let org_user_query =
             "INSERT INTO app_data.org_member (org_id, user_id, role, created_at, updated_at) VALUES ($1, $2, $3, $4, $5)";
@@ -203,9 +310,79 @@ impl Org {
             .bind(&org_user.role.to_string())
             .bind(&org_user.created_at)
             .bind(&org_user.updated_at)
-            .execute(pool)
+            .execute(&mut *tx)
             .await?;
 
+        let notification = self.member_added_notification(user.id, &org_user.role, added_by.id);
+        notification.persist(&mut tx).await?;
+
+        tx.commit().await?;
+        Ok(notification)
+    }
+
+    // Notification telling a user they were added to the org
+    pub fn member_added_notification(
+        &self,
+        user_id: Uuid,
+        role: &Role,
+        added_by: Uuid,
+    ) -> Notification {
+        Notification::new(
+            user_id,
+            NotificationKind::OrgMemberAdded,
+            serde_json::json!({
+                "org_id": self.id,
+                "org_name": self.name,
+                "role": role,
+                "added_by": added_by,
+            }),
+        )
+    }
+
+    // Change the roles of several members at once. The org must still have an admin once
+    // every change is applied, otherwise nothing is changed
+    pub async fn update_member_roles(
+        &self,
+        pool: &sqlx::PgPool,
+        changes: &[(Uuid, Role)],
+    ) -> Result<(), UpdateRolesError> {
+        let mut tx = pool.begin().await?;
+
+        // Lock the org's memberships so concurrent role changes can't both remove the last admin
+        let members_query = "SELECT * FROM app_data.org_member WHERE org_id = $1 FOR UPDATE";
+        let mut members: HashMap<Uuid, OrgMember> = sqlx::query_as::<_, OrgMember>(members_query)
+            .bind(self.id)
+            .fetch_all(&mut *tx)
+            .await?
+            .into_iter()
+            .map(|member| (member.user_id, member))
+            .collect();
+
+        let now = chrono::Utc::now();
+        let update_query =
+            "UPDATE app_data.org_member SET role = $3, updated_at = $4 WHERE org_id = $1 AND user_id = $2";
+        for (user_id, role) in changes {
+            let member = members
+                .get_mut(user_id)
+                .ok_or(UpdateRolesError::NotMember(*user_id))?;
+            if member.role == *role {
+                continue;
+            }
+            member.role = role.clone();
+            sqlx::query(update_query)
+                .bind(self.id)
+                .bind(user_id)
+                .bind(role.to_string())
+                .bind(now)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        if !members.values().any(|member| member.role == Role::Admin) {
+            return Err(UpdateRolesError::NoAdminLeft);
+        }
+
+        tx.commit().await?;
         Ok(())
     }
 }
@@ -0,0 +1,105 @@
+use serde::Serialize;
+use sqlx::Row;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+// Schema and data counts of one graph, for the org dashboard
+#[derive(Debug, Serialize)]
+pub struct GraphOverview {
+    pub graph_id: String,
+    pub name: String,
+    pub node_type_count: i64,
+    pub edge_type_count: i64,
+    pub node_count: i64,
+    pub edge_count: i64,
+}
+
+impl GraphOverview {
+    // One page of the org's graphs, ordered like the graph listing, with the total number of
+    // graphs. Type counts come from one query and data counts from another, whatever the
+    // page size
+    pub async fn page_for_org(
+        pool: &sqlx::PgPool,
+        org_id: Uuid,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<Self>, i64), sqlx::Error> {
+        let query = "
+        SELECT g.graph_id, g.name,
+            (SELECT count(*) FROM app_data.node_types t WHERE t.graph_id = g.graph_id) AS node_type_count,
+            (SELECT count(*) FROM app_data.edge_type t WHERE t.graph_id = g.graph_id) AS edge_type_count,
+            COUNT(*) OVER () AS total
+        FROM app_data.graph_info g
+        WHERE g.org_id = $1
+        ORDER BY g.name, g.graph_id
+        LIMIT $2 OFFSET $3
+        ";
+        let rows = sqlx::query(query)
+            .bind(org_id)
+            .bind(page_size as i64)
+            .bind(((page - 1) * page_size) as i64)
+            .fetch_all(pool)
+            .await?;
+
+        let total = match rows.first() {
+            Some(row) => row.try_get("total")?,
+            None => 0,
+        };
+        let mut graphs = rows
+            .iter()
+            .map(|row| {
+                Ok(Self {
+                    graph_id: row.try_get("graph_id")?,
+                    name: row.try_get("name")?,
+                    node_type_count: row.try_get("node_type_count")?,
+                    edge_type_count: row.try_get("edge_type_count")?,
+                    node_count: 0,
+                    edge_count: 0,
+                })
+            })
+            .collect::<Result<Vec<_>, sqlx::Error>>()?;
+
+        let counts = Self::data_counts(pool, &graphs).await?;
+        for graph in &mut graphs {
+            if let Some((nodes, edges)) = counts.get(&graph.graph_id) {
+                graph.node_count = *nodes;
+                graph.edge_count = *edges;
+            }
+        }
+        Ok((graphs, total))
+    }
+
+    // Node and edge counts per graph, read from the parent tables AGE keeps for all vertex
+    // and edge labels of a graph. Graph ids are generated by the application and are also
+    // the graphs' schema names
+    async fn data_counts(
+        pool: &sqlx::PgPool,
+        graphs: &[Self],
+    ) -> Result<HashMap<String, (i64, i64)>, sqlx::Error> {
+        if graphs.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let query = graphs
+            .iter()
+            .map(|graph| {
+                format!(
+                    "SELECT '{0}' AS graph_id, (SELECT count(*) FROM \"{0}\"._ag_label_vertex) AS node_count, (SELECT count(*) FROM \"{0}\"._ag_label_edge) AS edge_count",
+                    graph.graph_id
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" UNION ALL ");
+
+        sqlx::query(&query)
+            .fetch_all(pool)
+            .await?
+            .iter()
+            .map(|row| {
+                Ok((
+                    row.try_get("graph_id")?,
+                    (row.try_get("node_count")?, row.try_get("edge_count")?),
+                ))
+            })
+            .collect()
+    }
+}
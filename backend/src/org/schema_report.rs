@@ -0,0 +1,284 @@
+use serde::Serialize;
+use sqlx::Row;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet};
+use uuid::Uuid;
+
+// A type that appears in a graph of the org
+#[derive(Debug, Serialize)]
+pub struct TypeOccurrence {
+    pub graph_id: String,
+    pub graph_name: String,
+    pub type_id: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttributeIssue {
+    Missing,
+    TypeMismatch,
+    RequiredDrift,
+    DescriptionDrift,
+}
+
+// How an attribute is defined in one graph. Both fields are None when the
+// graph's type doesn't define the attribute at all
+#[derive(Debug, Serialize)]
+pub struct AttributeVariant {
+    pub graph_id: String,
+    pub data_type: Option<String>,
+    pub required: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AttributeDifference {
+    pub attribute: String,
+    pub issues: Vec<AttributeIssue>,
+    pub variants: Vec<AttributeVariant>,
+}
+
+// Types sharing a normalized name across more than one graph
+#[derive(Debug, Serialize)]
+pub struct TypeGroup {
+    pub normalized_name: String,
+    pub consistent: bool,
+    pub graphs: Vec<TypeOccurrence>,
+    pub differences: Vec<AttributeDifference>,
+}
+
+// A type attribute created from the org dictionary that no longer matches its entry
+#[derive(Debug, Serialize)]
+pub struct DictionaryDrift {
+    // "node_type" or "edge_type"
+    pub kind: &'static str,
+    pub graph_id: String,
+    pub type_id: String,
+    pub attribute: String,
+    pub dictionary_id: Uuid,
+    pub issues: Vec<AttributeIssue>,
+    pub data_type: String,
+    pub dictionary_data_type: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SchemaReport {
+    pub node_types: Vec<TypeGroup>,
+    pub edge_types: Vec<TypeGroup>,
+    pub dictionary_drift: Vec<DictionaryDrift>,
+}
+
+struct TypeDefinition {
+    occurrence: TypeOccurrence,
+    // Attribute normalized name -> (data type, required)
+    attributes: BTreeMap<String, (String, bool)>,
+}
+
+impl SchemaReport {
+    // Compare the node and edge types of every graph in the org. Each kind is
+    // loaded with a single query, regardless of the number of graphs
+    pub async fn for_org(pool: &sqlx::PgPool, org_id: Uuid) -> Result<Self, sqlx::Error> {
+        let node_types = Self::load_groups(
+            pool,
+            org_id,
+            "app_data.node_types",
+            "app_data.node_type_attributes",
+        )
+        .await?;
+        let edge_types = Self::load_groups(
+            pool,
+            org_id,
+            "app_data.edge_type",
+            "app_data.edge_type_attribute",
+        )
+        .await?;
+
+        let mut dictionary_drift = Self::load_drift(
+            pool,
+            org_id,
+            "node_type",
+            "app_data.node_types",
+            "app_data.node_type_attributes",
+        )
+        .await?;
+        dictionary_drift.extend(
+            Self::load_drift(
+                pool,
+                org_id,
+                "edge_type",
+                "app_data.edge_type",
+                "app_data.edge_type_attribute",
+            )
+            .await?,
+        );
+
+        Ok(Self {
+            node_types,
+            edge_types,
+            dictionary_drift,
+        })
+    }
+
+    async fn load_drift(
+        pool: &sqlx::PgPool,
+        org_id: Uuid,
+        kind: &'static str,
+        type_table: &str,
+        attribute_table: &str,
+    ) -> Result<Vec<DictionaryDrift>, sqlx::Error> {
+        let query = format!(
+            r#"
+            SELECT t.graph_id, t.id AS type_id, a.normalized_name AS attribute_name,
+                   a.data_type, a.description, d.id AS dictionary_id,
+                   d.data_type AS dictionary_data_type, d.description AS dictionary_description
+            FROM {} a
+            JOIN {} t ON t.id = a.type_id
+            JOIN app_data.org_attribute d ON d.id = a.dictionary_id
+            WHERE d.org_id = $1
+              AND (a.data_type <> d.data_type OR a.description <> d.description)
+            ORDER BY d.name, t.graph_id, t.id
+            "#,
+            attribute_table, type_table
+        );
+        let rows = sqlx::query(&query).bind(org_id).fetch_all(pool).await?;
+
+        rows.iter()
+            .map(|row| {
+                let data_type: String = row.try_get("data_type")?;
+                let dictionary_data_type: String = row.try_get("dictionary_data_type")?;
+                let description: String = row.try_get("description")?;
+                let dictionary_description: String = row.try_get("dictionary_description")?;
+
+                let mut issues = Vec::new();
+                if data_type != dictionary_data_type {
+                    issues.push(AttributeIssue::TypeMismatch);
+                }
+                if description != dictionary_description {
+                    issues.push(AttributeIssue::DescriptionDrift);
+                }
+                Ok(DictionaryDrift {
+                    kind,
+                    graph_id: row.try_get("graph_id")?,
+                    type_id: row.try_get("type_id")?,
+                    attribute: row.try_get("attribute_name")?,
+                    dictionary_id: row.try_get("dictionary_id")?,
+                    issues,
+                    data_type,
+                    dictionary_data_type,
+                })
+            })
+            .collect()
+    }
+
+    async fn load_groups(
+        pool: &sqlx::PgPool,
+        org_id: Uuid,
+        type_table: &str,
+        attribute_table: &str,
+    ) -> Result<Vec<TypeGroup>, sqlx::Error> {
+        let query = format!(
+            r#"
+            SELECT t.id AS type_id, t.normalized_name AS type_name, t.graph_id, g.name AS graph_name,
+                   a.normalized_name AS attribute_name, a.data_type, a.required
+            FROM {} t
+            JOIN app_data.graph_info g ON g.graph_id = t.graph_id
+            LEFT JOIN {} a ON a.type_id = t.id
+            WHERE g.org_id = $1
+            ORDER BY t.normalized_name, g.name
+            "#,
+            type_table, attribute_table
+        );
+        let rows = sqlx::query(&query).bind(org_id).fetch_all(pool).await?;
+
+        // Type name -> type id -> definition
+        let mut by_name: BTreeMap<String, BTreeMap<String, TypeDefinition>> = BTreeMap::new();
+        for row in rows {
+            let type_name: String = row.try_get("type_name")?;
+            let type_id: String = row.try_get("type_id")?;
+            let definition = match by_name.entry(type_name).or_default().entry(type_id.clone()) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(TypeDefinition {
+                    occurrence: TypeOccurrence {
+                        graph_id: row.try_get("graph_id")?,
+                        graph_name: row.try_get("graph_name")?,
+                        type_id,
+                    },
+                    attributes: BTreeMap::new(),
+                }),
+            };
+
+            let attribute_name: Option<String> = row.try_get("attribute_name")?;
+            if let Some(attribute_name) = attribute_name {
+                definition.attributes.insert(
+                    attribute_name,
+                    (row.try_get("data_type")?, row.try_get("required")?),
+                );
+            }
+        }
+
+        let groups = by_name
+            .into_iter()
+            .filter(|(_, definitions)| definitions.len() > 1)
+            .map(|(name, definitions)| Self::compare(name, definitions.into_values().collect()))
+            .collect();
+        Ok(groups)
+    }
+
+    fn compare(normalized_name: String, definitions: Vec<TypeDefinition>) -> TypeGroup {
+        let attribute_names: BTreeSet<&String> = definitions
+            .iter()
+            .flat_map(|d| d.attributes.keys())
+            .collect();
+
+        let mut differences = Vec::new();
+        for attribute in attribute_names {
+            let defined: Vec<&(String, bool)> = definitions
+                .iter()
+                .filter_map(|d| d.attributes.get(attribute))
+                .collect();
+
+            let mut issues = Vec::new();
+            if defined.len() < definitions.len() {
+                issues.push(AttributeIssue::Missing);
+            }
+            if defined
+                .iter()
+                .any(|(data_type, _)| *data_type != defined[0].0)
+            {
+                issues.push(AttributeIssue::TypeMismatch);
+            }
+            if defined
+                .iter()
+                .any(|(_, required)| *required != defined[0].1)
+            {
+                issues.push(AttributeIssue::RequiredDrift);
+            }
+            if issues.is_empty() {
+                continue;
+            }
+
+            let variants = definitions
+                .iter()
+                .map(|d| {
+                    let definition = d.attributes.get(attribute);
+                    AttributeVariant {
+                        graph_id: d.occurrence.graph_id.clone(),
+                        data_type: definition.map(|(data_type, _)| data_type.clone()),
+                        required: definition.map(|(_, required)| *required),
+                    }
+                })
+                .collect();
+            differences.push(AttributeDifference {
+                attribute: attribute.clone(),
+                issues,
+                variants,
+            });
+        }
+
+        TypeGroup {
+            normalized_name,
+            consistent: differences.is_empty(),
+            graphs: definitions.into_iter().map(|d| d.occurrence).collect(),
+            differences,
+        }
+    }
+}
@@ -0,0 +1,334 @@
+use crate::auth::Auth;
+use crate::config::AppState;
+use crate::error::ApiError;
+use crate::user::User;
+use axum::{
+    body::Body,
+    extract::State,
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter};
+use tracing::warn;
+use uuid::Uuid;
+
+// Per-user budget for write endpoints, enforced over a sliding window.
+// Usage is kept in memory, so each instance of the service tracks its own budget.
+#[derive(Debug)]
+pub struct WriteBudget {
+    limit: u32,
+    window: Duration,
+    usage: Mutex<Usage>,
+}
+
+#[derive(Debug, Default)]
+struct Usage {
+    // Writes per user as (time, number of items), oldest first
+    by_user: HashMap<Uuid, VecDeque<(Instant, u32)>>,
+    last_pruned: Option<Instant>,
+}
+
+impl WriteBudget {
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            usage: Mutex::new(Usage::default()),
+        }
+    }
+
+    // Spend `items` from the user's budget. Bulk endpoints should pass the number of
+    // items in the request rather than 1. Users flagged with an elevated budget are
+    // not limited.
+    pub fn consume(&self, user: &User, items: u32) -> Result<(), ApiError> {
+        if user.elevated_write_budget {
+            return Ok(());
+        }
+        self.consume_at(user.id, items, Instant::now())
+            .map_err(|reset_in| {
+                let reset_at: DateTime<Utc> =
+                    Utc::now() + chrono::Duration::from_std(reset_in).unwrap_or_default();
+                ApiError::RateLimited { reset_at }
+            })
+    }
+
+    // Spend `items` at `now`, or return how long until they would fit
+    fn consume_at(&self, user_id: Uuid, items: u32, now: Instant) -> Result<(), Duration> {
+        let mut usage = self.usage.lock().unwrap();
+        // Forget users whose writes have all left the window, at most once per window, so
+        // users who stopped writing don't stay in memory
+        if usage
+            .last_pruned
+            .is_none_or(|at| now.duration_since(at) >= self.window)
+        {
+            let window = self.window;
+            usage.by_user.retain(|_, entries| {
+                entries
+                    .back()
+                    .is_some_and(|(at, _)| now.duration_since(*at) < window)
+            });
+            usage.last_pruned = Some(now);
+        }
+
+        let entries = usage.by_user.entry(user_id).or_default();
+        while entries
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) >= self.window)
+        {
+            entries.pop_front();
+        }
+
+        let used: u32 = entries.iter().map(|(_, count)| count).sum();
+        if used.saturating_add(items) <= self.limit {
+            entries.push_back((now, items));
+            return Ok(());
+        }
+
+        // Find when enough of the oldest writes will have left the window
+        let mut remaining = used.saturating_add(items);
+        let mut reset_in = self.window;
+        for (at, count) in entries.iter() {
+            remaining -= count;
+            if remaining <= self.limit {
+                reset_in = self.window.saturating_sub(now.duration_since(*at));
+                break;
+            }
+        }
+
+        warn!(
+            "Write budget exceeded for user {}: {} used, {} requested",
+            user_id, used, items
+        );
+        Err(reset_in)
+    }
+}
+
+// Classes of requests that share a process-wide rate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Display, EnumIter)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum WriteClass {
+    // Orgs, graphs, node and edge types, settings
+    Metadata,
+    // Nodes and edges
+    Data,
+    Exports,
+}
+
+// Token bucket refilled at `rate` tokens per second, holding at most one second's worth.
+// A request that finds the bucket empty reserves the next token and waits for it, as long
+// as the wait is within the throttle's limit. Queued requests are spaced out at the
+// bucket's rate, which smooths a burst instead of rejecting it outright
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    // Available tokens, negative when tokens have been reserved by waiting requests
+    state: Mutex<(f64, Instant)>,
+    consumed: AtomicU64,
+    queued: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl TokenBucket {
+    fn new(rate: u32) -> Self {
+        Self {
+            rate: rate as f64,
+            state: Mutex::new((rate as f64, Instant::now())),
+            consumed: AtomicU64::new(0),
+            queued: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    // Take a token, returning how long to wait for it. Err with the time until a token
+    // would be available when that is longer than `max_wait`
+    fn reserve(&self, max_wait: Duration) -> Result<Duration, Duration> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let (tokens, refilled_at) = *state;
+        let tokens =
+            (tokens + now.duration_since(refilled_at).as_secs_f64() * self.rate).min(self.rate);
+
+        let wait = if tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - tokens) / self.rate)
+        };
+        if wait > max_wait {
+            *state = (tokens, now);
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(wait);
+        }
+
+        *state = (tokens - 1.0, now);
+        self.consumed.fetch_add(1, Ordering::Relaxed);
+        if !wait.is_zero() {
+            self.queued.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(wait)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ThrottleStats {
+    pub class: WriteClass,
+    pub rate_per_sec: u32,
+    pub consumed: u64,
+    // Requests that waited for a token before proceeding, included in `consumed`
+    pub queued: u64,
+    pub rejected: u64,
+}
+
+// Global safety valve protecting the shared database, independent of per-user budgets.
+// Every instance of the service enforces its own rates
+#[derive(Debug)]
+pub struct WriteThrottle {
+    // Classes without a bucket are not limited
+    buckets: HashMap<WriteClass, TokenBucket>,
+    max_wait: Duration,
+}
+
+impl WriteThrottle {
+    // A rate of 0 leaves the class unlimited
+    pub fn new(rates: &HashMap<WriteClass, u32>, max_wait: Duration) -> Self {
+        let buckets = rates
+            .iter()
+            .filter(|(_, rate)| **rate > 0)
+            .map(|(class, rate)| (*class, TokenBucket::new(*rate)))
+            .collect();
+        Self { buckets, max_wait }
+    }
+
+    pub async fn acquire(&self, class: WriteClass) -> Result<(), ApiError> {
+        let Some(bucket) = self.buckets.get(&class) else {
+            return Ok(());
+        };
+        match bucket.reserve(self.max_wait) {
+            Ok(wait) => {
+                if !wait.is_zero() {
+                    tokio::time::sleep(wait).await;
+                }
+                Ok(())
+            }
+            Err(retry_after) => {
+                warn!("Global {} throttle exceeded, rejecting request", class);
+                Err(ApiError::Overloaded { retry_after })
+            }
+        }
+    }
+
+    pub fn stats(&self) -> Vec<ThrottleStats> {
+        WriteClass::iter()
+            .filter_map(|class| {
+                let bucket = self.buckets.get(&class)?;
+                Some(ThrottleStats {
+                    class,
+                    rate_per_sec: bucket.rate as u32,
+                    consumed: bucket.consumed.load(Ordering::Relaxed),
+                    queued: bucket.queued.load(Ordering::Relaxed),
+                    rejected: bucket.rejected.load(Ordering::Relaxed),
+                })
+            })
+            .collect()
+    }
+}
+
+// Throttle a route group's requests as `class`. Requests without a signed in user are
+// let through, they are rejected by the handler and must not use up everyone's rate
+pub async fn throttle_middleware(
+    State((state, class)): State<(AppState, WriteClass)>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let signed_in = request
+        .extensions()
+        .get::<Auth>()
+        .is_some_and(|auth| auth.user.is_some());
+    if signed_in {
+        if let Err(e) = state.write_throttle.acquire(class).await {
+            return e.into_response();
+        }
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn write_budget_refills_as_writes_leave_the_window() {
+        let budget = WriteBudget::new(10, WINDOW);
+        let (user_id, start) = (Uuid::new_v4(), Instant::now());
+
+        assert_eq!(budget.consume_at(user_id, 6, start), Ok(()));
+        let later = start + Duration::from_secs(20);
+        assert_eq!(budget.consume_at(user_id, 4, later), Ok(()));
+        // Full until the first write leaves the window, 40s on
+        assert_eq!(
+            budget.consume_at(user_id, 1, later),
+            Err(Duration::from_secs(40))
+        );
+        assert_eq!(budget.consume_at(user_id, 6, start + WINDOW), Ok(()));
+    }
+
+    #[test]
+    fn write_budget_forgets_idle_users() {
+        let budget = WriteBudget::new(10, WINDOW);
+        let (idle, active, start) = (Uuid::new_v4(), Uuid::new_v4(), Instant::now());
+        let users = |budget: &WriteBudget| budget.usage.lock().unwrap().by_user.len();
+
+        budget.consume_at(idle, 1, start).unwrap();
+        budget.consume_at(active, 1, start).unwrap();
+        assert_eq!(users(&budget), 2);
+
+        // Within a window of the last prune nothing is dropped
+        budget
+            .consume_at(active, 1, start + WINDOW - Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(users(&budget), 2);
+
+        budget.consume_at(active, 1, start + WINDOW).unwrap();
+        assert_eq!(users(&budget), 1);
+        assert!(!budget.usage.lock().unwrap().by_user.contains_key(&idle));
+    }
+
+    #[test]
+    fn token_bucket_smooths_a_burst() {
+        // 25 requests at once against 10/s: a second's worth goes through, the next ones
+        // are spaced out at the rate until the wait would pass the limit
+        let bucket = TokenBucket::new(10);
+        let max_wait = Duration::from_millis(1050);
+        let outcomes: Vec<_> = (0..25).map(|_| bucket.reserve(max_wait)).collect();
+
+        for outcome in &outcomes[..10] {
+            assert_eq!(*outcome, Ok(Duration::ZERO));
+        }
+        for (i, outcome) in outcomes[10..20].iter().enumerate() {
+            let wait = outcome.expect("queued request was rejected");
+            let expected = Duration::from_millis(100 * (i as u64 + 1));
+            assert!(
+                wait <= expected && expected - wait < Duration::from_millis(20),
+                "request {} waits {:?}, expected about {:?}",
+                i + 10,
+                wait,
+                expected
+            );
+        }
+        assert!(outcomes[20..].iter().all(Result::is_err));
+
+        assert_eq!(bucket.consumed.load(Ordering::Relaxed), 20);
+        assert_eq!(bucket.queued.load(Ordering::Relaxed), 10);
+        assert_eq!(bucket.rejected.load(Ordering::Relaxed), 5);
+    }
+}
@@ -0,0 +1,313 @@
+use super::{Selection, SelectionDetail, MAX_SELECTION_NAME_LENGTH, MAX_SELECTION_SIZE};
+use crate::auth::Auth;
+use crate::config::AppState;
+use crate::error::ApiError;
+use crate::graph::GraphAccess;
+use crate::ids::GraphId;
+use crate::node::Node;
+use axum::extract::{Extension, Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSelectionRequest {
+    pub name: String,
+    // Public ids of the nodes
+    #[serde(default)]
+    pub node_ids: Vec<String>,
+    #[serde(default)]
+    pub shared: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSelectionRequest {
+    pub name: Option<String>,
+    pub shared: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddSelectionNodesRequest {
+    // Public ids of the nodes
+    pub node_ids: Vec<String>,
+}
+
+fn validate_name(name: &str) -> Result<String, ApiError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ApiError::BadRequest(
+            "Selection name cannot be empty".into(),
+        ));
+    }
+    if name.chars().count() > MAX_SELECTION_NAME_LENGTH {
+        return Err(ApiError::BadRequest(format!(
+            "Selection name must be at most {} characters",
+            MAX_SELECTION_NAME_LENGTH
+        )));
+    }
+    Ok(name.to_string())
+}
+
+// Append `node_ids` to `members`, skipping ones already in it. Every new id must be the
+// public id of a node of the graph
+async fn add_members(
+    pool: &sqlx::PgPool,
+    graph_id: &GraphId,
+    members: &mut Vec<String>,
+    node_ids: &[String],
+) -> Result<(), ApiError> {
+    let mut new_ids: Vec<String> = Vec::new();
+    for id in node_ids {
+        if !members.contains(id) && !new_ids.contains(id) {
+            new_ids.push(id.clone());
+        }
+    }
+    if members.len() + new_ids.len() > MAX_SELECTION_SIZE {
+        return Err(ApiError::BadRequest(format!(
+            "A selection can hold at most {} nodes",
+            MAX_SELECTION_SIZE
+        )));
+    }
+    if new_ids.is_empty() {
+        return Ok(());
+    }
+
+    let found = Node::get_many_by_public_id(pool, graph_id, &new_ids)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch nodes: {}", e);
+            ApiError::InternalServerError
+        })?;
+    let missing: Vec<&str> = new_ids
+        .iter()
+        .map(String::as_str)
+        .filter(|id| !found.iter().any(|node| node.public_id() == Some(*id)))
+        .collect();
+    if !missing.is_empty() {
+        return Err(ApiError::BadRequest(format!(
+            "Nodes do not exist in this graph: {}",
+            missing.join(", ")
+        )));
+    }
+
+    members.extend(new_ids);
+    Ok(())
+}
+
+// A selection the user can see. Other users' private selections are reported as missing
+async fn visible_selection(
+    state: &AppState,
+    graph_id: &GraphId,
+    selection_id: Uuid,
+    user_id: Uuid,
+) -> Result<Selection, ApiError> {
+    let selection = Selection::from_id(&state.pool, graph_id, selection_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch selection: {}", e);
+            ApiError::InternalServerError
+        })?;
+    match selection {
+        Some(selection) if selection.is_visible_to(user_id) => Ok(selection),
+        _ => Err(ApiError::NotFound {
+            code: "SELECTION_NOT_FOUND".into(),
+            message: "Selection not found".into(),
+        }),
+    }
+}
+
+// Shared selections can be read by everyone but only changed by their creator
+fn require_owner(selection: &Selection, user_id: Uuid) -> Result<(), ApiError> {
+    if selection.created_by != user_id {
+        return Err(ApiError::forbidden(
+            "Only the creator of a selection can change it",
+        ));
+    }
+    Ok(())
+}
+
+pub async fn create_selection(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+    Json(request): Json<CreateSelectionRequest>,
+) -> Result<(StatusCode, Json<Selection>), ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    let graph_id = &access.graph.graph_id;
+
+    let name = validate_name(&request.name)?;
+    let mut node_ids = Vec::new();
+    add_members(&state.pool, graph_id, &mut node_ids, &request.node_ids).await?;
+
+    let selection = Selection::new(graph_id, &name, node_ids, request.shared, user.id);
+    selection.persist(&state.pool).await.map_err(|e| {
+        error!("Failed to save selection: {}", e);
+        ApiError::InternalServerError
+    })?;
+
+    Ok((StatusCode::CREATED, Json(selection)))
+}
+
+pub async fn get_selections(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+) -> Result<Json<Vec<Selection>>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+
+    let selections = Selection::list_visible(&state.pool, &access.graph.graph_id, user.id)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch selections: {}", e);
+            ApiError::InternalServerError
+        })?;
+
+    Ok(Json(selections))
+}
+
+pub async fn get_selection(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path((graph_id, selection_id)): Path<(GraphId, Uuid)>,
+) -> Result<Json<SelectionDetail>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+
+    let selection =
+        visible_selection(&state, &access.graph.graph_id, selection_id, user.id).await?;
+    let detail = selection.hydrate(&state.pool).await.map_err(|e| {
+        error!("Failed to load selection members: {}", e);
+        ApiError::InternalServerError
+    })?;
+
+    Ok(Json(detail))
+}
+
+pub async fn update_selection(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path((graph_id, selection_id)): Path<(GraphId, Uuid)>,
+    Json(request): Json<UpdateSelectionRequest>,
+) -> Result<Json<Selection>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+
+    let mut selection =
+        visible_selection(&state, &access.graph.graph_id, selection_id, user.id).await?;
+    require_owner(&selection, user.id)?;
+
+    if let Some(name) = &request.name {
+        selection.name = validate_name(name)?;
+    }
+    if let Some(shared) = request.shared {
+        selection.shared = shared;
+    }
+    selection.update(&state.pool).await.map_err(|e| {
+        error!("Failed to update selection: {}", e);
+        ApiError::InternalServerError
+    })?;
+
+    Ok(Json(selection))
+}
+
+pub async fn delete_selection(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path((graph_id, selection_id)): Path<(GraphId, Uuid)>,
+) -> Result<StatusCode, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+
+    let selection =
+        visible_selection(&state, &access.graph.graph_id, selection_id, user.id).await?;
+    require_owner(&selection, user.id)?;
+
+    selection.delete(&state.pool).await.map_err(|e| {
+        error!("Failed to delete selection: {}", e);
+        ApiError::InternalServerError
+    })?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn add_selection_nodes(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path((graph_id, selection_id)): Path<(GraphId, Uuid)>,
+    Json(request): Json<AddSelectionNodesRequest>,
+) -> Result<Json<Selection>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+
+    let mut selection =
+        visible_selection(&state, &access.graph.graph_id, selection_id, user.id).await?;
+    require_owner(&selection, user.id)?;
+
+    add_members(
+        &state.pool,
+        &access.graph.graph_id,
+        &mut selection.node_ids,
+        &request.node_ids,
+    )
+    .await?;
+    selection.update(&state.pool).await.map_err(|e| {
+        error!("Failed to update selection: {}", e);
+        ApiError::InternalServerError
+    })?;
+
+    Ok(Json(selection))
+}
+
+pub async fn remove_selection_node(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path((graph_id, selection_id, public_id)): Path<(GraphId, Uuid, String)>,
+) -> Result<Json<Selection>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+
+    let mut selection =
+        visible_selection(&state, &access.graph.graph_id, selection_id, user.id).await?;
+    require_owner(&selection, user.id)?;
+
+    // Removing a node that isn't a member, or doesn't exist, leaves the selection as it is
+    selection.node_ids.retain(|id| *id != public_id);
+    selection.update(&state.pool).await.map_err(|e| {
+        error!("Failed to update selection: {}", e);
+        ApiError::InternalServerError
+    })?;
+
+    Ok(Json(selection))
+}
@@ -0,0 +1,5 @@
+mod endpoints;
+mod selection;
+
+pub use endpoints::*;
+pub use selection::*;
@@ -0,0 +1,192 @@
+use crate::edge::Edge;
+use crate::ids::GraphId;
+use crate::node::Node;
+use serde::Serialize;
+use sqlx::{postgres::PgRow, FromRow, Row};
+use uuid::Uuid;
+
+pub const MAX_SELECTION_NAME_LENGTH: usize = 100;
+pub const MAX_SELECTION_SIZE: usize = 500;
+
+// A named set of vertices a user is investigating, in the order they were added
+#[derive(Debug, Serialize)]
+pub struct Selection {
+    pub id: Uuid,
+    pub graph_id: GraphId,
+    pub name: String,
+    // Public ids of the member nodes, which stay the same when a node is recreated
+    pub node_ids: Vec<String>,
+    // Visible to everyone who can read the graph rather than only the creator
+    pub shared: bool,
+    pub created_by: Uuid,
+    #[serde(with = "crate::utils::rfc3339")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(with = "crate::utils::rfc3339")]
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl<'r> FromRow<'r, PgRow> for Selection {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            graph_id: row.try_get("graph_id")?,
+            name: row.try_get("name")?,
+            node_ids: row.try_get("node_ids")?,
+            shared: row.try_get("shared")?,
+            created_by: row.try_get("created_by")?,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+}
+
+// A selection with its nodes and the edges between them
+#[derive(Debug, Serialize)]
+pub struct SelectionDetail {
+    #[serde(flatten)]
+    pub selection: Selection,
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+impl Selection {
+    pub fn new(
+        graph_id: &GraphId,
+        name: &str,
+        node_ids: Vec<String>,
+        shared: bool,
+        created_by: Uuid,
+    ) -> Self {
+        let now = chrono::Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            graph_id: graph_id.clone(),
+            name: name.to_string(),
+            node_ids,
+            shared,
+            created_by,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn is_visible_to(&self, user_id: Uuid) -> bool {
+        self.shared || self.created_by == user_id
+    }
+
+    pub async fn persist(&self, pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+        let query = r#"
+            INSERT INTO app_data.selection (id, graph_id, name, node_ids, shared, created_by, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#;
+        sqlx::query(query)
+            .bind(self.id)
+            .bind(&self.graph_id)
+            .bind(&self.name)
+            .bind(&self.node_ids)
+            .bind(self.shared)
+            .bind(self.created_by)
+            .bind(self.created_at)
+            .bind(self.updated_at)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    // Write back the name, members and shared flag
+    pub async fn update(&mut self, pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+        self.updated_at = chrono::Utc::now();
+        let query = r#"
+            UPDATE app_data.selection
+            SET name = $2, node_ids = $3, shared = $4, updated_at = $5
+            WHERE id = $1
+        "#;
+        sqlx::query(query)
+            .bind(self.id)
+            .bind(&self.name)
+            .bind(&self.node_ids)
+            .bind(self.shared)
+            .bind(self.updated_at)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    // Selections of the graph the user created or that are shared, newest first
+    pub async fn list_visible(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        user_id: Uuid,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let query = r#"
+            SELECT * FROM app_data.selection
+            WHERE graph_id = $1 AND (created_by = $2 OR shared)
+            ORDER BY updated_at DESC, id
+        "#;
+        sqlx::query_as::<_, Selection>(query)
+            .bind(graph_id)
+            .bind(user_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn from_id(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        id: Uuid,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let query = "SELECT * FROM app_data.selection WHERE graph_id = $1 AND id = $2";
+        sqlx::query_as::<_, Selection>(query)
+            .bind(graph_id)
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    pub async fn delete(&self, pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM app_data.selection WHERE id = $1")
+            .bind(self.id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    // Load the member nodes and the edges among them. Members whose node no longer exists
+    // are dropped from the stored selection on the way
+    pub async fn hydrate(mut self, pool: &sqlx::PgPool) -> Result<SelectionDetail, sqlx::Error> {
+        let nodes = Node::get_many_by_public_id(pool, &self.graph_id, &self.node_ids).await?;
+        if nodes.len() < self.node_ids.len() {
+            let dangling: Vec<String> = self
+                .node_ids
+                .iter()
+                .filter(|id| {
+                    !nodes
+                        .iter()
+                        .any(|node| node.public_id() == Some(id.as_str()))
+                })
+                .cloned()
+                .collect();
+            // Only remove the ids found missing, in case members were added meanwhile
+            let query = r#"
+                UPDATE app_data.selection
+                SET node_ids = ARRAY(SELECT id FROM unnest(node_ids) WITH ORDINALITY AS m(id, ord)
+                                     WHERE id <> ALL($2) ORDER BY ord)
+                WHERE id = $1
+            "#;
+            sqlx::query(query)
+                .bind(self.id)
+                .bind(&dangling)
+                .execute(pool)
+                .await?;
+            self.node_ids.retain(|id| !dangling.contains(id));
+        }
+
+        let vertex_ids: Vec<i64> = nodes.iter().map(Node::id).collect();
+        let edges = Edge::among(pool, &self.graph_id, &vertex_ids).await?;
+        Ok(SelectionDetail {
+            selection: self,
+            nodes,
+            edges,
+        })
+    }
+}
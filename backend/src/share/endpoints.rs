@@ -0,0 +1,392 @@
+use super::{
+    GraphShare, ShareDenial, ShareSnapshot, SharedSubgraph, MAX_SHARE_HOURS,
+    MAX_SHARE_LABEL_LENGTH, MAX_SHARE_NODES, MIN_PASSCODE_LENGTH,
+};
+use crate::auth::Auth;
+use crate::config::AppState;
+use crate::error::ApiError;
+use crate::graph::GraphAccess;
+use crate::ids::GraphId;
+use crate::node::{
+    resolve_node_type, Node, NodeScope, NodeSort, NodeTypeAttributeDefinition, NodeVisibility,
+    PropertyFilter,
+};
+use crate::selection::Selection;
+use axum::extract::{Extension, Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+// Header carrying the passcode of a protected share, kept out of the URL so it doesn't
+// end up in logs
+const PASSCODE_HEADER: &str = "x-share-passcode";
+
+// Nodes of one type matching property conditions, like the filters of the node listing
+#[derive(Debug, Deserialize)]
+pub struct ShareFilter {
+    // Id or name of the node type
+    pub node_type: String,
+    #[serde(default)]
+    pub conditions: Vec<ShareCondition>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShareCondition {
+    pub attribute: String,
+    // eq when left out
+    pub op: Option<String>,
+    pub value: String,
+}
+
+// The nodes are given as public node ids, a selection or a filter, exactly one of them
+#[derive(Debug, Deserialize)]
+pub struct CreateShareRequest {
+    pub label: Option<String>,
+    #[serde(default)]
+    pub node_ids: Vec<String>,
+    pub selection_id: Option<Uuid>,
+    pub filter: Option<ShareFilter>,
+    pub expires_in_hours: u32,
+    pub passcode: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreatedShare {
+    #[serde(flatten)]
+    pub share: GraphShare,
+    // Only returned here, the share is opened at /shares/<token>
+    pub token: String,
+}
+
+fn validate_label(label: Option<&str>) -> Result<Option<String>, ApiError> {
+    let Some(label) = label.map(str::trim).filter(|label| !label.is_empty()) else {
+        return Ok(None);
+    };
+    if label.chars().count() > MAX_SHARE_LABEL_LENGTH {
+        return Err(ApiError::BadRequest(format!(
+            "Share label must be at most {} characters",
+            MAX_SHARE_LABEL_LENGTH
+        )));
+    }
+    Ok(Some(label.to_string()))
+}
+
+fn too_many_nodes() -> ApiError {
+    ApiError::BadRequest(format!(
+        "A share can hold at most {} nodes",
+        MAX_SHARE_NODES
+    ))
+}
+
+// The nodes a share request asks for, checked to exist in the graph
+async fn requested_nodes(
+    state: &AppState,
+    graph_id: &GraphId,
+    request: &CreateShareRequest,
+    user_id: Uuid,
+) -> Result<Vec<Node>, ApiError> {
+    let sources = [
+        !request.node_ids.is_empty(),
+        request.selection_id.is_some(),
+        request.filter.is_some(),
+    ];
+    if sources.iter().filter(|given| **given).count() != 1 {
+        return Err(ApiError::BadRequest(
+            "Give exactly one of node_ids, selection_id or filter".into(),
+        ));
+    }
+
+    let node_ids = if let Some(selection_id) = request.selection_id {
+        let selection = Selection::from_id(&state.pool, graph_id, selection_id)
+            .await
+            .map_err(|e| {
+                error!("Failed to fetch selection: {}", e);
+                ApiError::InternalServerError
+            })?;
+        match selection {
+            Some(selection) if selection.is_visible_to(user_id) => selection.node_ids,
+            _ => {
+                return Err(ApiError::NotFound {
+                    code: "SELECTION_NOT_FOUND".into(),
+                    message: "Selection not found".into(),
+                })
+            }
+        }
+    } else if let Some(filter) = &request.filter {
+        return filtered_nodes(state, graph_id, filter).await;
+    } else {
+        let mut node_ids: Vec<String> = Vec::new();
+        for id in &request.node_ids {
+            if !node_ids.contains(id) {
+                node_ids.push(id.clone());
+            }
+        }
+        node_ids
+    };
+
+    if node_ids.len() > MAX_SHARE_NODES {
+        return Err(too_many_nodes());
+    }
+    let nodes = Node::get_many_by_public_id(&state.pool, graph_id, &node_ids)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch nodes: {}", e);
+            ApiError::InternalServerError
+        })?;
+    let missing: Vec<&str> = node_ids
+        .iter()
+        .map(String::as_str)
+        .filter(|id| !nodes.iter().any(|node| node.public_id() == Some(*id)))
+        .collect();
+    if !missing.is_empty() {
+        return Err(ApiError::BadRequest(format!(
+            "Nodes do not exist in this graph: {}",
+            missing.join(", ")
+        )));
+    }
+    if nodes.is_empty() {
+        return Err(ApiError::BadRequest("The selection has no nodes".into()));
+    }
+    Ok(nodes)
+}
+
+async fn filtered_nodes(
+    state: &AppState,
+    graph_id: &GraphId,
+    filter: &ShareFilter,
+) -> Result<Vec<Node>, ApiError> {
+    let node_type = resolve_node_type(&state.pool, graph_id, &filter.node_type).await?;
+    let mut lineage = node_type.lineage(&state.pool).await.map_err(|e| {
+        error!("Failed to resolve node type lineage: {}", e);
+        ApiError::InternalServerError
+    })?;
+    let attributes = NodeTypeAttributeDefinition::resolve(&state.pool, &lineage).await?;
+    // Same form as the query parameters of the node listing
+    let params: Vec<(String, String)> = filter
+        .conditions
+        .iter()
+        .map(|condition| {
+            let key = match &condition.op {
+                Some(op) => format!("filter[{}][{}]", condition.attribute, op),
+                None => format!("filter[{}]", condition.attribute),
+            };
+            (key, condition.value.clone())
+        })
+        .collect();
+    let filters = PropertyFilter::parse_all(&params, &attributes)?;
+    let sort = NodeSort::parse(None, None, None)?;
+    let node_type = lineage.swap_remove(0);
+
+    // Shares are created by graph admins, who see every node
+    let scope = NodeScope::new(Some(&node_type), &filters, NodeVisibility::everything());
+    let nodes = Node::list_window(
+        &state.pool,
+        graph_id,
+        &scope,
+        &sort,
+        0,
+        MAX_SHARE_NODES as u32 + 1,
+    )
+    .await
+    .map_err(|e| {
+        error!("Failed to fetch nodes: {}", e);
+        ApiError::InternalServerError
+    })?;
+    if nodes.len() > MAX_SHARE_NODES {
+        return Err(too_many_nodes());
+    }
+    if nodes.is_empty() {
+        return Err(ApiError::BadRequest("The filter matches no nodes".into()));
+    }
+    Ok(nodes)
+}
+
+pub async fn create_share(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+    Json(request): Json<CreateShareRequest>,
+) -> Result<(StatusCode, Json<CreatedShare>), ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_admin()?;
+    let graph_id = &access.graph.graph_id;
+
+    let label = validate_label(request.label.as_deref())?;
+    if request.expires_in_hours == 0 || request.expires_in_hours > MAX_SHARE_HOURS {
+        return Err(ApiError::BadRequest(format!(
+            "expires_in_hours must be between 1 and {}",
+            MAX_SHARE_HOURS
+        )));
+    }
+    if let Some(passcode) = &request.passcode {
+        if passcode.chars().count() < MIN_PASSCODE_LENGTH {
+            return Err(ApiError::BadRequest(format!(
+                "Passcode must be at least {} characters",
+                MIN_PASSCODE_LENGTH
+            )));
+        }
+    }
+
+    let nodes = requested_nodes(&state, graph_id, &request, user.id).await?;
+    let snapshot = ShareSnapshot::capture(&state.pool, graph_id, nodes)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch edges for share: {}", e);
+            ApiError::InternalServerError
+        })?;
+
+    let expires_at = chrono::Utc::now() + chrono::Duration::hours(request.expires_in_hours as i64);
+    let (share, token) = GraphShare::new(
+        graph_id,
+        label,
+        &snapshot,
+        request.passcode.as_deref(),
+        expires_at,
+        user.id,
+    )
+    .map_err(|e| {
+        error!("Failed to hash share passcode: {}", e);
+        ApiError::InternalServerError
+    })?;
+    share.persist(&state.pool, &snapshot).await.map_err(|e| {
+        error!("Failed to save share: {}", e);
+        ApiError::InternalServerError
+    })?;
+    info!(
+        "Share {} of {} node(s) created in graph {} by {}",
+        share.id, share.node_count, graph_id, user.id
+    );
+
+    Ok((StatusCode::CREATED, Json(CreatedShare { share, token })))
+}
+
+pub async fn get_shares(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+) -> Result<Json<Vec<GraphShare>>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_admin()?;
+
+    let shares = GraphShare::list_for_graph(&state.pool, &access.graph.graph_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch shares: {}", e);
+            ApiError::InternalServerError
+        })?;
+
+    Ok(Json(shares))
+}
+
+// Revoking stops the link from opening. The share stays listed, revoking it again does
+// nothing
+pub async fn revoke_share(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path((graph_id, share_id)): Path<(GraphId, Uuid)>,
+) -> Result<StatusCode, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_admin()?;
+
+    let share = GraphShare::from_id(&state.pool, &access.graph.graph_id, share_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch share: {}", e);
+            ApiError::InternalServerError
+        })?;
+    let Some(mut share) = share else {
+        return Err(share_not_found());
+    };
+    if share.revoked_at.is_none() {
+        share.revoke(&state.pool, user.id).await.map_err(|e| {
+            error!("Failed to revoke share: {}", e);
+            ApiError::InternalServerError
+        })?;
+        info!("Share {} revoked by {}", share.id, user.id);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn share_not_found() -> ApiError {
+    ApiError::NotFound {
+        code: "SHARE_NOT_FOUND".into(),
+        message: "Share not found".into(),
+    }
+}
+
+// Open a share link. Needs no account, only the token and the passcode if one was set
+pub async fn get_shared_subgraph(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<SharedSubgraph>, ApiError> {
+    let share = GraphShare::from_token(&state.pool, &token)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch share: {}", e);
+            ApiError::InternalServerError
+        })?;
+    let Some(mut share) = share else {
+        return Err(share_not_found());
+    };
+
+    let passcode = headers
+        .get(PASSCODE_HEADER)
+        .and_then(|value| value.to_str().ok());
+    if let Err(denial) = share.check_access(&state.pool, passcode).await {
+        return Err(match denial {
+            ShareDenial::Revoked => ApiError::Gone {
+                code: "SHARE_REVOKED".into(),
+                message: "This share has been revoked".into(),
+            },
+            ShareDenial::Expired => ApiError::Gone {
+                code: "SHARE_EXPIRED".into(),
+                message: "This share has expired".into(),
+            },
+            ShareDenial::Locked => ApiError::Locked {
+                code: "SHARE_LOCKED".into(),
+                message: "Too many wrong passcodes were tried for this share".into(),
+            },
+            ShareDenial::PasscodeRequired => ApiError::Forbidden {
+                code: "PASSCODE_REQUIRED".into(),
+                message: format!(
+                    "This share needs a passcode in the {} header",
+                    PASSCODE_HEADER
+                ),
+            },
+            ShareDenial::WrongPasscode => ApiError::Forbidden {
+                code: "INVALID_PASSCODE".into(),
+                message: "The passcode is not correct".into(),
+            },
+            ShareDenial::Database(e) => {
+                error!("Failed to count passcode attempt: {}", e);
+                ApiError::InternalServerError
+            }
+        });
+    }
+
+    let subgraph = share.open(&state.pool).await.map_err(|e| {
+        error!("Failed to open share: {}", e);
+        ApiError::InternalServerError
+    })?;
+
+    Ok(Json(subgraph))
+}
@@ -0,0 +1,5 @@
+mod endpoints;
+mod share;
+
+pub use endpoints::*;
+pub use share::*;
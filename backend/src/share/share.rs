@@ -0,0 +1,363 @@
+use crate::auth::{SecurityEvent, SecurityEventKind};
+use crate::edge::Edge;
+use crate::ids::GraphId;
+use crate::node::Node;
+use crate::utils::create_id;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::{DateTime, Utc};
+use rand::{rng, Rng};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+use sqlx::{postgres::PgRow, FromRow, Row};
+use uuid::Uuid;
+
+pub const MAX_SHARE_NODES: usize = 500;
+pub const MAX_SHARE_LABEL_LENGTH: usize = 100;
+// Longest a share can stay valid, 30 days
+pub const MAX_SHARE_HOURS: u32 = 24 * 30;
+pub const MIN_PASSCODE_LENGTH: usize = 6;
+// Wrong passcodes a share takes before it stops opening
+pub const MAX_PASSCODE_ATTEMPTS: i32 = 10;
+
+const SHARE_TOKEN_LENGTH: u64 = 40;
+// Everything but the snapshot, which is only read when a share is opened
+const SHARE_COLUMNS: &str = "id, graph_id, label, token_hash, passcode_hash, node_count, edge_count, expires_at, created_by, created_at, revoked_at, access_count, last_accessed_at, failed_passcode_attempts";
+
+// A read-only link to a frozen copy of some nodes of a graph and the edges among them.
+// The token and passcode are only kept as hashes
+#[derive(Debug, Serialize)]
+pub struct GraphShare {
+    pub id: Uuid,
+    pub graph_id: String,
+    pub label: Option<String>,
+    #[serde(skip)]
+    token_hash: String,
+    #[serde(skip)]
+    passcode_hash: Option<String>,
+    pub has_passcode: bool,
+    pub node_count: i32,
+    pub edge_count: i32,
+    #[serde(with = "crate::utils::rfc3339")]
+    pub expires_at: DateTime<Utc>,
+    pub created_by: Uuid,
+    #[serde(with = "crate::utils::rfc3339")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::rfc3339::option")]
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub access_count: i64,
+    #[serde(with = "crate::utils::rfc3339::option")]
+    pub last_accessed_at: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    failed_passcode_attempts: i32,
+}
+
+impl<'r> FromRow<'r, PgRow> for GraphShare {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        let passcode_hash: Option<String> = row.try_get("passcode_hash")?;
+        Ok(Self {
+            id: row.try_get("id")?,
+            graph_id: row.try_get("graph_id")?,
+            label: row.try_get("label")?,
+            token_hash: row.try_get("token_hash")?,
+            has_passcode: passcode_hash.is_some(),
+            passcode_hash,
+            node_count: row.try_get("node_count")?,
+            edge_count: row.try_get("edge_count")?,
+            expires_at: row.try_get("expires_at")?,
+            created_by: row.try_get("created_by")?,
+            created_at: row.try_get("created_at")?,
+            revoked_at: row.try_get("revoked_at")?,
+            access_count: row.try_get("access_count")?,
+            last_accessed_at: row.try_get("last_accessed_at")?,
+            failed_passcode_attempts: row.try_get("failed_passcode_attempts")?,
+        })
+    }
+}
+
+// Nodes and edges as they were when the share was created
+#[derive(Debug, Serialize)]
+pub struct ShareSnapshot {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+impl ShareSnapshot {
+    pub async fn capture(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        nodes: Vec<Node>,
+    ) -> Result<Self, sqlx::Error> {
+        let node_ids: Vec<i64> = nodes.iter().map(|node| node.id()).collect();
+        let edges = Edge::among(pool, graph_id, &node_ids).await?;
+        Ok(Self { nodes, edges })
+    }
+}
+
+// What someone opening a share link sees
+#[derive(Debug, Serialize)]
+pub struct SharedSubgraph {
+    pub label: Option<String>,
+    // When the snapshot was taken
+    #[serde(with = "crate::utils::rfc3339")]
+    pub captured_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::rfc3339")]
+    pub expires_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub snapshot: JsonValue,
+}
+
+// Why a share link can't be opened
+#[derive(Debug)]
+pub enum ShareDenial {
+    Revoked,
+    Expired,
+    // Too many wrong passcodes were tried
+    Locked,
+    PasscodeRequired,
+    WrongPasscode,
+    // The passcode attempt couldn't be counted
+    Database(sqlx::Error),
+}
+
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+fn hash_passcode(passcode: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::encode_b64(&rng().random::<[u8; 16]>())?;
+    Ok(Argon2::default()
+        .hash_password(passcode.as_bytes(), &salt)?
+        .to_string())
+}
+
+impl GraphShare {
+    // A new share and its token. The token is not stored, so it can only be handed out now
+    pub fn new(
+        graph_id: &GraphId,
+        label: Option<String>,
+        snapshot: &ShareSnapshot,
+        passcode: Option<&str>,
+        expires_at: DateTime<Utc>,
+        created_by: Uuid,
+    ) -> Result<(Self, String), argon2::password_hash::Error> {
+        let token = create_id(SHARE_TOKEN_LENGTH);
+        let passcode_hash = passcode.map(hash_passcode).transpose()?;
+        let share = Self {
+            id: Uuid::new_v4(),
+            graph_id: graph_id.to_string(),
+            label,
+            token_hash: hash_token(&token),
+            has_passcode: passcode_hash.is_some(),
+            passcode_hash,
+            node_count: snapshot.nodes.len() as i32,
+            edge_count: snapshot.edges.len() as i32,
+            expires_at,
+            created_by,
+            created_at: Utc::now(),
+            revoked_at: None,
+            access_count: 0,
+            last_accessed_at: None,
+            failed_passcode_attempts: 0,
+        };
+        Ok((share, token))
+    }
+
+    // Save the share with its snapshot, recording who created it
+    pub async fn persist(
+        &self,
+        pool: &sqlx::PgPool,
+        snapshot: &ShareSnapshot,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let query = r#"
+            INSERT INTO app_data.graph_share
+                (id, graph_id, label, token_hash, passcode_hash, snapshot, node_count, edge_count, expires_at, created_by, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        "#;
+        sqlx::query(query)
+            .bind(self.id)
+            .bind(&self.graph_id)
+            .bind(&self.label)
+            .bind(&self.token_hash)
+            .bind(&self.passcode_hash)
+            .bind(serde_json::to_value(snapshot).map_err(|e| sqlx::Error::Encode(Box::new(e)))?)
+            .bind(self.node_count)
+            .bind(self.edge_count)
+            .bind(self.expires_at)
+            .bind(self.created_by)
+            .bind(self.created_at)
+            .execute(&mut *tx)
+            .await?;
+        SecurityEvent::record(
+            &mut tx,
+            self.created_by,
+            SecurityEventKind::ShareLinkCreated,
+            self.event_detail(),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    fn event_detail(&self) -> JsonValue {
+        serde_json::json!({
+            "graph_id": self.graph_id,
+            "share_id": self.id,
+        })
+    }
+
+    // Shares of the graph, revoked and expired ones included, newest first
+    pub async fn list_for_graph(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let query = format!(
+            "SELECT {} FROM app_data.graph_share WHERE graph_id = $1 ORDER BY created_at DESC, id",
+            SHARE_COLUMNS
+        );
+        sqlx::query_as::<_, GraphShare>(&query)
+            .bind(graph_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn from_id(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        id: Uuid,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let query = format!(
+            "SELECT {} FROM app_data.graph_share WHERE graph_id = $1 AND id = $2",
+            SHARE_COLUMNS
+        );
+        sqlx::query_as::<_, GraphShare>(&query)
+            .bind(graph_id)
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    pub async fn from_token(pool: &sqlx::PgPool, token: &str) -> Result<Option<Self>, sqlx::Error> {
+        let query = format!(
+            "SELECT {} FROM app_data.graph_share WHERE token_hash = $1",
+            SHARE_COLUMNS
+        );
+        sqlx::query_as::<_, GraphShare>(&query)
+            .bind(hash_token(token))
+            .fetch_optional(pool)
+            .await
+    }
+
+    // Whether the share can be opened with the given passcode. Wrong passcodes are
+    // counted, enough of them lock the share
+    pub async fn check_access(
+        &self,
+        pool: &sqlx::PgPool,
+        passcode: Option<&str>,
+    ) -> Result<(), ShareDenial> {
+        if self.revoked_at.is_some() {
+            return Err(ShareDenial::Revoked);
+        }
+        if self.expires_at <= Utc::now() {
+            return Err(ShareDenial::Expired);
+        }
+        let Some(passcode_hash) = &self.passcode_hash else {
+            return Ok(());
+        };
+        if self.failed_passcode_attempts >= MAX_PASSCODE_ATTEMPTS {
+            return Err(ShareDenial::Locked);
+        }
+        let Some(passcode) = passcode else {
+            return Err(ShareDenial::PasscodeRequired);
+        };
+
+        // Count the attempt before checking it, in the same statement as the limit, so
+        // guesses sent in parallel can't get past MAX_PASSCODE_ATTEMPTS together. The right
+        // passcode gives its attempt back
+        let query = "UPDATE app_data.graph_share SET failed_passcode_attempts = failed_passcode_attempts + 1 WHERE id = $1 AND failed_passcode_attempts < $2 RETURNING failed_passcode_attempts";
+        let attempts: Option<i32> = sqlx::query_scalar(query)
+            .bind(self.id)
+            .bind(MAX_PASSCODE_ATTEMPTS)
+            .fetch_optional(pool)
+            .await
+            .map_err(ShareDenial::Database)?;
+        if attempts.is_none() {
+            return Err(ShareDenial::Locked);
+        }
+
+        let matches = PasswordHash::new(passcode_hash)
+            .map(|hash| {
+                Argon2::default()
+                    .verify_password(passcode.as_bytes(), &hash)
+                    .is_ok()
+            })
+            .unwrap_or(false);
+        if !matches {
+            return Err(ShareDenial::WrongPasscode);
+        }
+        let query = "UPDATE app_data.graph_share SET failed_passcode_attempts = failed_passcode_attempts - 1 WHERE id = $1 AND failed_passcode_attempts > 0";
+        if let Err(e) = sqlx::query(query).bind(self.id).execute(pool).await {
+            tracing::error!(
+                "Failed to give back passcode attempt on share {}: {}",
+                self.id,
+                e
+            );
+        }
+        Ok(())
+    }
+
+    // Count an opening of the share and return the snapshot to show. Whoever opens it has
+    // no account, so the event is recorded against the share's creator
+    pub async fn open(&mut self, pool: &sqlx::PgPool) -> Result<SharedSubgraph, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let query = r#"
+            UPDATE app_data.graph_share
+            SET access_count = access_count + 1, last_accessed_at = now()
+            WHERE id = $1
+            RETURNING snapshot, access_count, last_accessed_at
+        "#;
+        let row = sqlx::query(query).bind(self.id).fetch_one(&mut *tx).await?;
+        self.access_count = row.try_get("access_count")?;
+        self.last_accessed_at = row.try_get("last_accessed_at")?;
+        SecurityEvent::record(
+            &mut tx,
+            self.created_by,
+            SecurityEventKind::ShareLinkAccessed,
+            self.event_detail(),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(SharedSubgraph {
+            label: self.label.clone(),
+            captured_at: self.created_at,
+            expires_at: self.expires_at,
+            snapshot: row.try_get("snapshot")?,
+        })
+    }
+
+    pub async fn revoke(
+        &mut self,
+        pool: &sqlx::PgPool,
+        revoked_by: Uuid,
+    ) -> Result<(), sqlx::Error> {
+        let revoked_at = Utc::now();
+        let mut tx = pool.begin().await?;
+        sqlx::query("UPDATE app_data.graph_share SET revoked_at = $2 WHERE id = $1")
+            .bind(self.id)
+            .bind(revoked_at)
+            .execute(&mut *tx)
+            .await?;
+        SecurityEvent::record(
+            &mut tx,
+            revoked_by,
+            SecurityEventKind::ShareLinkRevoked,
+            self.event_detail(),
+        )
+        .await?;
+        tx.commit().await?;
+        self.revoked_at = Some(revoked_at);
+        Ok(())
+    }
+}
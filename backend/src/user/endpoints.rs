@@ -14,7 +14,9 @@ pub struct Profile {
     pub first_name: String,
     pub last_name: String,
     pub email: String,
+    #[serde(with = "crate::utils::rfc3339")]
     pub registered_at: DateTime<Utc>,
+    #[serde(with = "crate::utils::rfc3339")]
     pub last_update: DateTime<Utc>,
 }
 
@@ -24,6 +24,8 @@ pub struct User {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub is_active: bool,
+    // Service accounts that are exempt from the write budget
+    pub elevated_write_budget: bool,
 }
 
 impl<'r> sqlx::FromRow<'r, sqlx::postgres::PgRow> for User {
@@ -40,6 +42,7 @@ impl<'r> sqlx::FromRow<'r, sqlx::postgres::PgRow> for User {
             created_at: row.try_get("created_at")?,
             updated_at: row.try_get("updated_at")?,
             is_active: row.try_get("is_active")?,
+            elevated_write_budget: row.try_get("elevated_write_budget")?,
         })
     }
 }
@@ -57,9 +60,14 @@ impl User {
             created_at,
             updated_at,
             is_active: true,
+            elevated_write_budget: false,
         }
     }
 
+    pub fn is_superadmin(&self) -> bool {
+        matches!(self.global_role, Some(GlobalRole::SuperAdmin))
+    }
+
     pub async fn persist(
         &self,
         tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
@@ -175,6 +183,8 @@ impl FederatedUser {
         }
     }
 
+    // Subjects are only unique within a provider, and Microsoft subjects are pairwise per
+    // application, so a user is looked up by the pair. Rows store the provider's issuer URL
     pub async fn from_sub(
         pg_pool: &sqlx::PgPool,
         provider: AuthProvider,
@@ -193,6 +203,14 @@ impl FederatedUser {
         Ok(result)
     }
 
+    pub async fn from_id(pg_pool: &sqlx::PgPool, id: Uuid) -> Result<FederatedUser, sqlx::Error> {
+        let query = "SELECT * FROM app_data.federated_user WHERE id = $1";
+        sqlx::query_as::<_, FederatedUser>(query)
+            .bind(id)
+            .fetch_one(pg_pool)
+            .await
+    }
+
     pub async fn persist(
         &self,
         tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
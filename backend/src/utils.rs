@@ -1,8 +1,10 @@
+use crate::ids::NodeTypeId;
 use serde_json::Value as JsonValue;
 use serde_json::Value;
 use std::collections::HashMap;
 
 use rand::{distr::Alphanumeric, rng, Rng};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
 use validator::ValidationError;
 
 pub fn create_id(length: u64) -> String {
@@ -12,29 +14,200 @@ pub fn create_id(length: u64) -> String {
     code.to_uppercase()
 }
 
+// Length of the random part of node and edge type ids
+pub const TYPE_ID_LENGTH: u64 = 8;
+
+// Whether `id` looks like one `create_id` generated for a type, i.e. `prefix` followed by
+// TYPE_ID_LENGTH uppercase letters or digits. Lets obviously bad ids fail before a query
+pub fn is_type_id(prefix: char, id: &str) -> bool {
+    let Some(rest) = id.strip_prefix(prefix) else {
+        return false;
+    };
+    rest.len() == TYPE_ID_LENGTH as usize
+        && rest
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+}
+
+// Request field check for a node type id, with the error NodeTypeId::parse gives
+pub fn validate_node_type_id(id: &str) -> Result<(), ValidationError> {
+    NodeTypeId::parse(id).map(|_| ()).map_err(|e| {
+        let mut error = ValidationError::new("invalid_node_type_id");
+        error.message = Some(e.to_string().into());
+        error
+    })
+}
+
+// Serde helper to serialize timestamps as RFC3339 in UTC, always with a `Z` suffix and
+// millisecond precision, e.g. `2025-02-01T09:30:00.000Z`.
+// Use with `#[serde(with = "crate::utils::rfc3339")]`
+pub mod rfc3339 {
+    use chrono::{DateTime, SecondsFormat, Utc};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn format(datetime: &DateTime<Utc>) -> String {
+        datetime.to_rfc3339_opts(SecondsFormat::Millis, true)
+    }
+
+    pub fn serialize<S>(datetime: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&format(datetime))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        DateTime::parse_from_rfc3339(&value)
+            .map(|datetime| datetime.with_timezone(&Utc))
+            .map_err(serde::de::Error::custom)
+    }
+
+    // Same as the parent module, for optional timestamps. Serialization only.
+    // Use with `#[serde(with = "crate::utils::rfc3339::option")]`
+    pub mod option {
+        use chrono::{DateTime, Utc};
+        use serde::Serializer;
+
+        pub fn serialize<S>(
+            datetime: &Option<DateTime<Utc>>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match datetime {
+                Some(datetime) => serializer.serialize_str(&super::format(datetime)),
+                None => serializer.serialize_none(),
+            }
+        }
+    }
+}
+
+pub const DEFAULT_PAGE_SIZE: u32 = 20;
+pub const MAX_PAGE_SIZE: u32 = 100;
+
+// Standard envelope for paginated list responses. Pages start at 1
+#[derive(Debug, serde::Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub page: u32,
+    pub page_size: u32,
+    pub total: i64,
+}
+
+impl<T> Page<T> {
+    // Clamp the requested page and page size to valid values
+    pub fn bounds(page: Option<u32>, page_size: Option<u32>) -> (u32, u32) {
+        let page = page.unwrap_or(1).max(1);
+        let page_size = page_size
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        (page, page_size)
+    }
+}
+
+// Normalize a type or attribute name into the form used for lookups and uniqueness, in order:
+// - NFKD decomposition with combining marks dropped, so "Café" and "Cafe" collide, as do
+//   compatibility forms like "ﬁ" and "fi" or fullwidth and ASCII letters
+// - leading and trailing whitespace removed and each inner run of Unicode whitespace turned
+//   into a single underscore
+// - full Unicode uppercasing, e.g. "ß" becomes "SS"
+// Other characters, including punctuation and zero-width ones, are kept as they are.
+// Every normalized_name in the tree must come from here so lookups match what was stored,
+// names stored under an older rule are brought in line at startup
 pub fn normalize(text: &str) -> String {
-    text.to_uppercase().replace(" ", "_")
+    text.nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("_")
+        .to_uppercase()
 }
 
+// Render properties as a cypher map literal for CREATE/SET clauses.
+// Serialization rules per JSON type:
+// - null: the key is left out, AGE has no stored nulls and a missing key reads back as null
+// - string: single-quoted with backslashes and quotes escaped. `$` is written as `\u0024`
+//   so a value can never close the `$$` quoting around the cypher query
+// - number, bool: written as-is
+// - array, object: nested cypher list/map literals using the same rules, so they are
+//   stored as agtype lists/maps instead of strings
+// Dates are already RFC3339 strings by the time they get here and are written as strings.
 pub fn generate_props_clause(properties: &HashMap<String, Value>) -> String {
     let prop_strings: Vec<String> = properties
         .iter()
-        .map(|(key, value)| {
-            let value_str = match value {
-                Value::String(s) => format!("'{}'", s.replace("'", "\\'")),
-                Value::Number(n) => n.to_string(),
-                Value::Bool(b) => b.to_string(),
-                Value::Null => "null".to_string(),
-                _ => format!("'{}'", value.to_string().replace("'", "\\'")),
-            };
-            format!("{}: {}", key, value_str)
-        })
+        .filter(|(_, value)| !value.is_null())
+        .map(|(key, value)| format!("{}: {}", cypher_key(key), cypher_literal(value)))
         .collect();
 
     format!("{{{}}}", prop_strings.join(", "))
 }
 
+// Render a SET clause assigning each property of `variable`, e.g. "n.`age` = 42".
+// Unlike generate_props_clause, null values are kept: setting a property to null removes it
+pub fn generate_set_clause(variable: &str, properties: &HashMap<String, Value>) -> String {
+    let mut keys: Vec<&String> = properties.keys().collect();
+    keys.sort();
+    let assignments: Vec<String> = keys
+        .into_iter()
+        .map(|key| {
+            format!(
+                "{}.{} = {}",
+                variable,
+                cypher_key(key),
+                cypher_literal(&properties[key])
+            )
+        })
+        .collect();
+
+    format!("SET {}", assignments.join(", "))
+}
+
+pub fn cypher_key(key: &str) -> String {
+    format!("`{}`", key.replace('`', "``"))
+}
+
+pub(crate) fn cypher_string(s: &str) -> String {
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('\'', "\\'")
+        .replace('$', "\\u0024");
+    format!("'{}'", escaped)
+}
+
+pub fn cypher_literal(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => cypher_string(s),
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(cypher_literal).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Object(map) => {
+            let entries: Vec<String> = map
+                .iter()
+                .map(|(key, value)| format!("{}: {}", cypher_key(key), cypher_literal(value)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+    }
+}
+
+// AGE labels are stored as Postgres identifiers, which are limited to NAMEDATALEN - 1 bytes
+pub const MAX_LABEL_LENGTH: usize = 63;
+
+// Validate a label against the identifier rules enforced by AGE
 pub fn validate_label(label: &str) -> Result<(), ValidationError> {
+    if label.len() > MAX_LABEL_LENGTH {
+        return Err(ValidationError::new("label_too_long"));
+    }
     if !label
         .chars()
         .next()
@@ -48,71 +221,398 @@ pub fn validate_label(label: &str) -> Result<(), ValidationError> {
     Ok(())
 }
 
+pub const MAX_PROPERTIES: usize = 100;
+pub const MAX_PROPERTY_KEY_LENGTH: usize = 50;
+pub const MAX_STRING_VALUE_LENGTH: usize = 1000;
+pub const MAX_ARRAY_LENGTH: usize = 100;
+
+// Build a property validation error naming the offending key. ApiError reports it
+// against `properties.<key>` rather than the whole properties map
+fn property_error(code: &'static str, key: &str, message: String) -> ValidationError {
+    let mut error = ValidationError::new(code);
+    error.add_param("key".into(), &key);
+    error.message = Some(message.into());
+    error
+}
+
+// Length and charset rules for a property key. Attribute names are property keys too
+pub fn validate_property_key(key: &str) -> Result<(), ValidationError> {
+    if key.len() > MAX_PROPERTY_KEY_LENGTH {
+        return Err(property_error(
+            "property_key_too_long",
+            key,
+            format!(
+                "Property key must be at most {} characters",
+                MAX_PROPERTY_KEY_LENGTH
+            ),
+        ));
+    }
+    if !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(property_error(
+            "invalid_property_key_characters",
+            key,
+            "Property key may only contain letters, numbers and underscores".into(),
+        ));
+    }
+    Ok(())
+}
+
 pub fn validate_properties(props: &HashMap<String, JsonValue>) -> Result<(), ValidationError> {
     // Check for maximum number of properties
-    if props.len() > 100 {
-        return Err(ValidationError::new("too_many_properties"));
+    if props.len() > MAX_PROPERTIES {
+        let mut error = ValidationError::new("too_many_properties");
+        error.message = Some(
+            format!(
+                "At most {} properties are allowed, got {}",
+                MAX_PROPERTIES,
+                props.len()
+            )
+            .into(),
+        );
+        return Err(error);
     }
 
+    // Check keys in a stable order so the same payload always reports the same key
+    let mut keys: Vec<&String> = props.keys().collect();
+    keys.sort();
+
     // Validate property keys
-    for key in props.keys() {
-        if key.len() > 50 {
-            return Err(ValidationError::new("property_key_too_long"));
-        }
-        if !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
-            return Err(ValidationError::new("invalid_property_key_characters"));
-        }
+    for key in &keys {
+        validate_property_key(key)?;
     }
 
     // Validate property values
-    for value in props.values() {
-        match value {
-            JsonValue::String(s) if s.len() > 1000 => {
-                return Err(ValidationError::new("string_value_too_long"));
+    for key in keys {
+        match &props[key] {
+            JsonValue::String(s) if s.len() > MAX_STRING_VALUE_LENGTH => {
+                return Err(property_error(
+                    "string_value_too_long",
+                    key,
+                    format!(
+                        "String value must be at most {} characters",
+                        MAX_STRING_VALUE_LENGTH
+                    ),
+                ));
             }
             JsonValue::Array(arr) => {
-                if arr.len() > 100 {
-                    return Err(ValidationError::new("array_too_large"));
+                if arr.len() > MAX_ARRAY_LENGTH {
+                    return Err(property_error(
+                        "array_too_large",
+                        key,
+                        format!("Array must have at most {} elements", MAX_ARRAY_LENGTH),
+                    ));
                 }
                 // Validate array elements
                 for elem in arr {
                     match elem {
-                        JsonValue::String(s) if s.len() > 1000 => {
-                            return Err(ValidationError::new("array_string_too_long"));
+                        JsonValue::String(s) if s.len() > MAX_STRING_VALUE_LENGTH => {
+                            return Err(property_error(
+                                "array_string_too_long",
+                                key,
+                                format!(
+                                    "Array strings must be at most {} characters",
+                                    MAX_STRING_VALUE_LENGTH
+                                ),
+                            ));
                         }
                         JsonValue::Array(_) => {
-                            return Err(ValidationError::new("nested_arrays_not_allowed"));
+                            return Err(property_error(
+                                "nested_arrays_not_allowed",
+                                key,
+                                "Arrays may not contain arrays".into(),
+                            ));
                         }
                         JsonValue::Object(_) => {
-                            return Err(ValidationError::new("objects_in_arrays_not_allowed"));
+                            return Err(property_error(
+                                "objects_in_arrays_not_allowed",
+                                key,
+                                "Arrays may not contain objects".into(),
+                            ));
                         }
                         JsonValue::Null => {
-                            return Err(ValidationError::new("null_values_not_allowed"));
+                            return Err(property_error(
+                                "null_values_not_allowed",
+                                key,
+                                "Arrays may not contain null values".into(),
+                            ));
                         }
                         JsonValue::Number(_) | JsonValue::Bool(_) | JsonValue::String(_) => {}
                     }
                 }
             }
             JsonValue::Object(_) => {
-                return Err(ValidationError::new("nested_objects_not_allowed"));
+                return Err(property_error(
+                    "nested_objects_not_allowed",
+                    key,
+                    "Nested objects are not allowed".into(),
+                ));
             }
             JsonValue::Null => {
-                return Err(ValidationError::new("null_values_not_allowed"));
+                return Err(property_error(
+                    "null_values_not_allowed",
+                    key,
+                    "Null values are not allowed".into(),
+                ));
             }
-            JsonValue::Number(_) | JsonValue::Bool(_) | JsonValue::String(_) => {}
+            JsonValue::Number(n) => {
+                if n.as_f64()
+                    .is_some_and(|n| !n.is_finite() || n.abs() > 1e308)
+                {
+                    return Err(property_error(
+                        "numeric_value_out_of_bounds",
+                        key,
+                        "Numeric value is out of bounds".into(),
+                    ));
+                }
+            }
+            JsonValue::Bool(_) | JsonValue::String(_) => {}
         }
     }
 
-    // Add numeric validation
-    for value in props.values() {
-        if let JsonValue::Number(n) = value {
-            if let Some(n) = n.as_f64() {
-                if !n.is_finite() || n.abs() > 1e308 {
-                    return Err(ValidationError::new("numeric_value_out_of_bounds"));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const TRICKY_NAMES: [&str; 9] = [
+        "Café",
+        "Cafe\u{301}",
+        "\u{FB01}rst name",
+        "\u{FF21}\u{FF22}\u{FF23}",
+        "first\u{A0}name",
+        "Straße",
+        "  first \t\n name  ",
+        "zero\u{200B}width",
+        "",
+    ];
+
+    #[test]
+    fn node_type_id_field_check_agrees_with_parse() {
+        assert!(validate_node_type_id("vPERSON01").is_ok());
+        let error = validate_node_type_id("eKNOWS001").unwrap_err();
+        assert_eq!(error.code, "invalid_node_type_id");
+        assert_eq!(
+            error.message.as_deref(),
+            Some("'eKNOWS001' is not a valid node type id")
+        );
+    }
+
+    #[test]
+    fn normalize_folds_accents_compatibility_forms_and_case() {
+        assert_eq!(normalize("Café"), "CAFE");
+        assert_eq!(normalize("Cafe\u{301}"), "CAFE");
+        assert_eq!(normalize("\u{FB01}rst name"), "FIRST_NAME");
+        assert_eq!(normalize("\u{FF21}\u{FF22}\u{FF23}"), "ABC");
+        assert_eq!(normalize("Straße"), "STRASSE");
+    }
+
+    #[test]
+    fn normalize_joins_whitespace_runs_with_one_underscore() {
+        assert_eq!(normalize("first\u{A0}name"), "FIRST_NAME");
+        assert_eq!(normalize("  first \t\n name  "), "FIRST_NAME");
+        assert_eq!(normalize("   "), "");
+    }
+
+    #[test]
+    fn normalize_keeps_zero_width_characters() {
+        assert_eq!(normalize("zero\u{200B}width"), "ZERO\u{200B}WIDTH");
+    }
+
+    #[test]
+    fn normalize_is_idempotent() {
+        for name in TRICKY_NAMES {
+            let once = normalize(name);
+            assert_eq!(normalize(&once), once, "{:?}", name);
+        }
+    }
+
+    fn label_error(label: &str) -> Option<String> {
+        validate_label(label).err().map(|e| e.code.to_string())
+    }
+
+    #[test]
+    fn label_of_max_length_is_accepted() {
+        let label = format!("a{}", "b".repeat(MAX_LABEL_LENGTH - 1));
+        assert_eq!(label.len(), 63);
+        assert_eq!(label_error(&label), None);
+    }
+
+    #[test]
+    fn label_over_max_length_is_rejected() {
+        let label = format!("a{}", "b".repeat(MAX_LABEL_LENGTH));
+        assert_eq!(label.len(), 64);
+        assert_eq!(label_error(&label).as_deref(), Some("label_too_long"));
+    }
+
+    #[test]
+    fn label_characters() {
+        assert_eq!(label_error("Person_2"), None);
+        assert_eq!(
+            label_error("2Person").as_deref(),
+            Some("label_must_start_with_letter")
+        );
+        assert_eq!(
+            label_error("").as_deref(),
+            Some("label_must_start_with_letter")
+        );
+        assert_eq!(
+            label_error("first name").as_deref(),
+            Some("invalid_label_characters")
+        );
+        assert_eq!(
+            label_error("café").as_deref(),
+            Some("invalid_label_characters")
+        );
+    }
+
+    // Reads back what cypher_literal writes, the way AGE's parser would
+    struct LiteralReader<'a> {
+        chars: std::iter::Peekable<std::str::Chars<'a>>,
+    }
+
+    impl LiteralReader<'_> {
+        fn read(literal: &str) -> Value {
+            let mut reader = LiteralReader {
+                chars: literal.chars().peekable(),
+            };
+            let value = reader.value();
+            assert_eq!(reader.chars.next(), None, "trailing input in {}", literal);
+            value
+        }
+
+        fn skip_spaces(&mut self) {
+            while self.chars.next_if(|c| *c == ' ').is_some() {}
+        }
+
+        fn expect(&mut self, expected: char) {
+            self.skip_spaces();
+            assert_eq!(self.chars.next(), Some(expected));
+        }
+
+        fn value(&mut self) -> Value {
+            self.skip_spaces();
+            match self.chars.peek() {
+                Some('\'') => Value::String(self.string()),
+                Some('[') => {
+                    self.chars.next();
+                    let mut items = Vec::new();
+                    while !self.close(']') {
+                        items.push(self.value());
+                    }
+                    Value::Array(items)
+                }
+                Some('{') => Value::Object(self.map()),
+                _ => {
+                    let mut token = String::new();
+                    while let Some(c) = self.chars.next_if(|c| !", ]}".contains(*c)) {
+                        token.push(c);
+                    }
+                    serde_json::from_str(&token).unwrap()
+                }
+            }
+        }
+
+        fn map(&mut self) -> serde_json::Map<String, Value> {
+            self.expect('{');
+            let mut map = serde_json::Map::new();
+            while !self.close('}') {
+                let key = self.key();
+                self.expect(':');
+                map.insert(key, self.value());
+            }
+            map
+        }
+
+        // Consume a separator, and the closing bracket once the collection ends
+        fn close(&mut self, bracket: char) -> bool {
+            self.skip_spaces();
+            self.chars.next_if_eq(&',');
+            self.skip_spaces();
+            self.chars.next_if_eq(&bracket).is_some()
+        }
+
+        fn key(&mut self) -> String {
+            self.expect('`');
+            let mut key = String::new();
+            loop {
+                match self.chars.next().unwrap() {
+                    '`' if self.chars.next_if_eq(&'`').is_some() => key.push('`'),
+                    '`' => return key,
+                    c => key.push(c),
+                }
+            }
+        }
+
+        fn string(&mut self) -> String {
+            self.expect('\'');
+            let mut s = String::new();
+            loop {
+                match self.chars.next().unwrap() {
+                    '\\' => match self.chars.next().unwrap() {
+                        'u' => {
+                            let hex: String = self.chars.by_ref().take(4).collect();
+                            let code = u32::from_str_radix(&hex, 16).unwrap();
+                            s.push(char::from_u32(code).unwrap());
+                        }
+                        c => s.push(c),
+                    },
+                    '\'' => return s,
+                    c => s.push(c),
                 }
             }
         }
     }
 
-    Ok(())
+    fn read_props(properties: &Value) -> Value {
+        let properties: HashMap<String, Value> =
+            serde_json::from_value(properties.clone()).unwrap();
+        let clause = generate_props_clause(&properties);
+        assert!(!clause.contains('$'), "{}", clause);
+        LiteralReader::read(&clause)
+    }
+
+    #[test]
+    fn props_clause_round_trips_special_characters() {
+        let properties = json!({
+            "price": "$$ 5",
+            "path": "C:\\temp\\",
+            "quote": "it's 'quoted'",
+            "mixed": "\\'$\\$'",
+            "we`ird key": "ok",
+            "newline": "a\nb",
+        });
+        assert_eq!(read_props(&properties), properties);
+    }
+
+    #[test]
+    fn props_clause_round_trips_nested_values() {
+        let properties = json!({
+            "tags": ["a", "b's", "$"],
+            "matrix": [[1, 2], [3.5, -4]],
+            "meta": {"source": {"name": "import $1", "rows": 10}, "ok": true},
+            "empty_list": [],
+            "empty_map": {},
+            "nulls": [null, {"inner": null}],
+        });
+        assert_eq!(read_props(&properties), properties);
+    }
+
+    #[test]
+    fn props_clause_leaves_out_top_level_nulls() {
+        let properties = json!({"name": "Ada", "email": null});
+        assert_eq!(read_props(&properties), json!({"name": "Ada"}));
+        assert_eq!(read_props(&json!({"email": null})), json!({}));
+    }
+
+    #[test]
+    fn cypher_literal_of_scalars() {
+        assert_eq!(cypher_literal(&json!(null)), "null");
+        assert_eq!(cypher_literal(&json!(true)), "true");
+        assert_eq!(cypher_literal(&json!(-2.5)), "-2.5");
+        assert_eq!(cypher_literal(&json!("a$b")), r"'a\u0024b'");
+        assert_eq!(cypher_literal(&json!(r"\'")), r"'\\\''");
+    }
 }
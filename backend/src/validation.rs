@@ -0,0 +1,869 @@
+use crate::edge::{EdgeTypeAttributeDataType, EdgeTypeAttributeDefinition};
+use crate::node::{NodeTypeAttributeDataType, NodeTypeAttributeDefinition};
+use crate::utils::rfc3339;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::fmt;
+use url::Url;
+
+// Longest URL attribute value accepted, in bytes
+pub const MAX_URL_LENGTH: usize = 2048;
+
+// Data types an attribute value can be validated against. Node and edge
+// attribute definitions both map onto this.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeKind {
+    String,
+    Number,
+    Boolean,
+    Date,
+    Url,
+}
+
+impl AttributeKind {
+    fn expected(&self) -> &'static str {
+        match self {
+            AttributeKind::String => "string",
+            AttributeKind::Number => "number",
+            AttributeKind::Boolean => "boolean",
+            AttributeKind::Date => "RFC3339 date string",
+            AttributeKind::Url => "https URL of at most 2048 characters, without credentials",
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            AttributeKind::String => "string",
+            AttributeKind::Number => "number",
+            AttributeKind::Boolean => "boolean",
+            AttributeKind::Date => "date",
+            AttributeKind::Url => "url",
+        }
+    }
+}
+
+impl From<&NodeTypeAttributeDataType> for AttributeKind {
+    fn from(data_type: &NodeTypeAttributeDataType) -> Self {
+        match data_type {
+            NodeTypeAttributeDataType::String => AttributeKind::String,
+            NodeTypeAttributeDataType::Number => AttributeKind::Number,
+            NodeTypeAttributeDataType::Boolean => AttributeKind::Boolean,
+            NodeTypeAttributeDataType::Date => AttributeKind::Date,
+            NodeTypeAttributeDataType::Url => AttributeKind::Url,
+        }
+    }
+}
+
+impl From<&EdgeTypeAttributeDataType> for AttributeKind {
+    fn from(data_type: &EdgeTypeAttributeDataType) -> Self {
+        match data_type {
+            EdgeTypeAttributeDataType::String => AttributeKind::String,
+            EdgeTypeAttributeDataType::Number => AttributeKind::Number,
+            EdgeTypeAttributeDataType::Boolean => AttributeKind::Boolean,
+            EdgeTypeAttributeDataType::Date => AttributeKind::Date,
+            EdgeTypeAttributeDataType::Url => AttributeKind::Url,
+        }
+    }
+}
+
+// An attribute definition that properties can be validated against.
+pub trait AttributeRule {
+    fn name(&self) -> &str;
+    fn kind(&self) -> AttributeKind;
+    fn required(&self) -> bool;
+    fn description(&self) -> &str;
+
+    // Deprecated attributes are rejected on new writes but kept on existing data.
+    fn deprecated(&self) -> bool {
+        false
+    }
+
+    fn replaced_by(&self) -> Option<&str> {
+        None
+    }
+
+    // Longest string value accepted on new writes, in characters.
+    fn max_length(&self) -> Option<usize> {
+        None
+    }
+}
+
+impl AttributeRule for NodeTypeAttributeDefinition {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn kind(&self) -> AttributeKind {
+        AttributeKind::from(&self.data_type)
+    }
+
+    fn required(&self) -> bool {
+        self.required
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn deprecated(&self) -> bool {
+        self.deprecated
+    }
+
+    fn replaced_by(&self) -> Option<&str> {
+        self.replaced_by.as_deref()
+    }
+
+    fn max_length(&self) -> Option<usize> {
+        self.max_length.and_then(|n| usize::try_from(n).ok())
+    }
+}
+
+impl AttributeRule for EdgeTypeAttributeDefinition {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn kind(&self) -> AttributeKind {
+        AttributeKind::from(&self.data_type)
+    }
+
+    fn required(&self) -> bool {
+        self.required
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn max_length(&self) -> Option<usize> {
+        self.max_length.and_then(|n| usize::try_from(n).ok())
+    }
+}
+
+#[derive(Debug)]
+pub enum AttributeValidationError {
+    MissingAttribute {
+        name: String,
+    },
+    WrongType {
+        name: String,
+        expected: &'static str,
+    },
+    // A coercible value rejected because the request asked for strict validation
+    NotStrict {
+        name: String,
+        message: String,
+    },
+    // A value given for a deprecated attribute on a new write
+    Deprecated {
+        name: String,
+        replaced_by: Option<String>,
+    },
+    // A string value longer than the attribute or the server allows on new writes
+    TooLong {
+        name: String,
+        max_length: usize,
+    },
+}
+
+impl AttributeValidationError {
+    // Stable identifier of the kind of violation, e.g. for grouping in reports
+    pub fn code(&self) -> &'static str {
+        match self {
+            AttributeValidationError::MissingAttribute { .. } => "missing_attribute",
+            AttributeValidationError::WrongType { .. } => "wrong_type",
+            AttributeValidationError::NotStrict { .. } => "needs_coercion",
+            AttributeValidationError::Deprecated { .. } => "deprecated_attribute",
+            AttributeValidationError::TooLong { .. } => "value_too_long",
+        }
+    }
+
+    pub fn attribute(&self) -> &str {
+        match self {
+            AttributeValidationError::MissingAttribute { name }
+            | AttributeValidationError::WrongType { name, .. }
+            | AttributeValidationError::NotStrict { name, .. }
+            | AttributeValidationError::Deprecated { name, .. }
+            | AttributeValidationError::TooLong { name, .. } => name,
+        }
+    }
+}
+
+impl fmt::Display for AttributeValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttributeValidationError::MissingAttribute { name } => {
+                write!(f, "Missing attribute: {}", name)
+            }
+            AttributeValidationError::WrongType { name, expected } => {
+                write!(f, "Attribute '{}' must be of type {}", name, expected)
+            }
+            AttributeValidationError::NotStrict { name, message } => {
+                write!(f, "Attribute '{}' {}", name, message)
+            }
+            AttributeValidationError::Deprecated { name, replaced_by } => {
+                write!(
+                    f,
+                    "Attribute '{}' {}",
+                    name,
+                    deprecated_message(replaced_by)
+                )
+            }
+            AttributeValidationError::TooLong { name, max_length } => {
+                write!(
+                    f,
+                    "Attribute '{}' must be at most {} characters",
+                    name, max_length
+                )
+            }
+        }
+    }
+}
+
+// What to tell a client that wrote a deprecated attribute
+pub fn deprecated_message(replaced_by: &Option<String>) -> String {
+    match replaced_by {
+        Some(replacement) => format!("is deprecated, use '{}' instead", replacement),
+        None => "is deprecated".to_string(),
+    }
+}
+
+#[derive(Debug)]
+pub struct ValidationErrorList(pub Vec<AttributeValidationError>);
+
+impl fmt::Display for ValidationErrorList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let messages: Vec<String> = self.0.iter().map(|e| e.to_string()).collect();
+        write!(f, "{}", messages.join("; "))
+    }
+}
+
+impl IntoIterator for ValidationErrorList {
+    type Item = AttributeValidationError;
+    type IntoIter = std::vec::IntoIter<AttributeValidationError>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+// How a new write is validated
+#[derive(Debug, Clone, Copy)]
+pub struct WriteRules {
+    // Report anything that would have been coerced as an error instead
+    pub strict: bool,
+    // Longest string value, in characters, of any property. An attribute's own
+    // `max_length` can only lower it
+    pub max_value_length: usize,
+}
+
+// A required attribute of the type being validated against. Sent alongside
+// validation errors so clients can explain what is needed without refetching the type.
+#[derive(Debug, Clone, Serialize)]
+pub struct RequiredAttributeHint {
+    pub name: String,
+    pub data_type: &'static str,
+    pub description: String,
+}
+
+impl RequiredAttributeHint {
+    pub fn for_attributes<A: AttributeRule>(attributes: &[A]) -> Vec<Self> {
+        attributes
+            .iter()
+            .filter(|attr| attr.required())
+            .map(|attr| Self {
+                name: attr.name().to_string(),
+                data_type: attr.kind().as_str(),
+                description: attr.description().to_string(),
+            })
+            .collect()
+    }
+}
+
+// A problem with a property value that was fixed up rather than rejected
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationWarning {
+    pub field: String,
+    pub message: String,
+}
+
+// Result of validating a set of properties against attribute definitions.
+// `coerced_properties` holds the properties with every warning applied and
+// is what should be persisted when `errors` is empty.
+#[derive(Debug, Default)]
+pub struct ValidationOutcome {
+    pub errors: Vec<AttributeValidationError>,
+    pub warnings: Vec<ValidationWarning>,
+    pub coerced_properties: HashMap<String, JsonValue>,
+}
+
+impl ValidationOutcome {
+    // Validates `properties` against `attributes`. Properties without a
+    // definition are passed through untouched. With `strict` set, anything
+    // that would have been coerced is reported as an error instead.
+    pub fn validate<A: AttributeRule>(
+        attributes: &[A],
+        properties: HashMap<String, JsonValue>,
+        strict: bool,
+    ) -> Self {
+        let mut outcome = ValidationOutcome {
+            coerced_properties: properties,
+            ..Default::default()
+        };
+
+        for attr in attributes {
+            let value = match outcome.coerced_properties.get(attr.name()) {
+                None | Some(JsonValue::Null) => {
+                    if attr.required() {
+                        outcome
+                            .errors
+                            .push(AttributeValidationError::MissingAttribute {
+                                name: attr.name().to_string(),
+                            });
+                    }
+                    continue;
+                }
+                Some(value) => value,
+            };
+
+            match check_value(attr.kind(), value) {
+                Check::Valid => {}
+                Check::Canonical(canonical) => {
+                    outcome
+                        .coerced_properties
+                        .insert(attr.name().to_string(), canonical);
+                }
+                Check::Coerced(coerced, message) => {
+                    if strict {
+                        outcome.errors.push(AttributeValidationError::NotStrict {
+                            name: attr.name().to_string(),
+                            message: message.to_string(),
+                        });
+                    } else {
+                        outcome.warnings.push(ValidationWarning {
+                            field: attr.name().to_string(),
+                            message: message.to_string(),
+                        });
+                        outcome
+                            .coerced_properties
+                            .insert(attr.name().to_string(), coerced);
+                    }
+                }
+                Check::Invalid => {
+                    outcome.errors.push(AttributeValidationError::WrongType {
+                        name: attr.name().to_string(),
+                        expected: attr.kind().expected(),
+                    });
+                }
+            }
+        }
+
+        outcome
+    }
+
+    // Validates the properties of a new write. On top of `validate`, values for
+    // deprecated attributes are rejected and string values are held to their length
+    // limit. Reads, restores and reports use `validate` so data written before a
+    // deprecation or a lower limit keeps passing.
+    pub fn validate_write<A: AttributeRule>(
+        attributes: &[A],
+        properties: HashMap<String, JsonValue>,
+        rules: WriteRules,
+    ) -> Self {
+        let mut outcome = Self::validate(attributes, properties, rules.strict);
+        for attr in attributes.iter().filter(|attr| attr.deprecated()) {
+            if matches!(
+                outcome.coerced_properties.get(attr.name()),
+                None | Some(JsonValue::Null)
+            ) {
+                continue;
+            }
+            outcome.errors.retain(|e| e.attribute() != attr.name());
+            outcome.errors.push(AttributeValidationError::Deprecated {
+                name: attr.name().to_string(),
+                replaced_by: attr.replaced_by().map(str::to_string),
+            });
+        }
+
+        // Properties without a definition are capped too. Keys are checked in a stable
+        // order so the same payload always reports the same errors
+        let mut keys: Vec<&String> = outcome.coerced_properties.keys().collect();
+        keys.sort();
+        let mut too_long = Vec::new();
+        for key in keys {
+            if outcome.errors.iter().any(|e| e.attribute() == key) {
+                continue;
+            }
+            let max_length = attributes
+                .iter()
+                .find(|attr| attr.name() == key)
+                .and_then(|attr| attr.max_length())
+                .map_or(rules.max_value_length, |max| {
+                    max.min(rules.max_value_length)
+                });
+            if longest_string(&outcome.coerced_properties[key]) > max_length {
+                too_long.push(AttributeValidationError::TooLong {
+                    name: key.clone(),
+                    max_length,
+                });
+            }
+        }
+        outcome.errors.extend(too_long);
+        outcome
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+// Check an attribute's example value. Examples are shown as-is, so anything that would
+// need coercing is rejected rather than fixed up
+pub fn validate_example<A: AttributeRule>(
+    attr: &A,
+    example: &JsonValue,
+) -> Result<(), AttributeValidationError> {
+    match check_value(attr.kind(), example) {
+        Check::Valid | Check::Canonical(_) => {}
+        Check::Coerced(..) | Check::Invalid => {
+            return Err(AttributeValidationError::WrongType {
+                name: attr.name().to_string(),
+                expected: attr.kind().expected(),
+            })
+        }
+    }
+    match attr.max_length() {
+        Some(max_length) if longest_string(example) > max_length => {
+            Err(AttributeValidationError::TooLong {
+                name: attr.name().to_string(),
+                max_length,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+// Length in characters of the longest string in a value, looking into arrays
+fn longest_string(value: &JsonValue) -> usize {
+    match value {
+        JsonValue::String(s) => s.chars().count(),
+        JsonValue::Array(items) => items.iter().map(longest_string).max().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+enum Check {
+    Valid,
+    // Valid, but stored in its canonical form. Not a coercion, so strict mode accepts it
+    Canonical(JsonValue),
+    Coerced(JsonValue, &'static str),
+    Invalid,
+}
+
+fn check_value(kind: AttributeKind, value: &JsonValue) -> Check {
+    match (kind, value) {
+        (AttributeKind::String, JsonValue::String(s)) => {
+            let trimmed = s.trim();
+            if trimmed.len() == s.len() {
+                Check::Valid
+            } else {
+                Check::Coerced(
+                    JsonValue::String(trimmed.to_string()),
+                    "had surrounding whitespace removed",
+                )
+            }
+        }
+        (AttributeKind::Number, JsonValue::Number(_)) => Check::Valid,
+        (AttributeKind::Number, JsonValue::String(s)) => {
+            match s
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+            {
+                Some(n) => {
+                    // Keep integers as integers rather than turning 42 into 42.0
+                    let number = s
+                        .trim()
+                        .parse::<i64>()
+                        .map(serde_json::Number::from)
+                        .unwrap_or(n);
+                    Check::Coerced(
+                        JsonValue::Number(number),
+                        "was converted from a string to a number",
+                    )
+                }
+                None => Check::Invalid,
+            }
+        }
+        (AttributeKind::Boolean, JsonValue::Bool(_)) => Check::Valid,
+        (AttributeKind::Boolean, JsonValue::String(s)) => match s.trim().to_lowercase().as_str() {
+            "true" => Check::Coerced(
+                JsonValue::Bool(true),
+                "was converted from a string to a boolean",
+            ),
+            "false" => Check::Coerced(
+                JsonValue::Bool(false),
+                "was converted from a string to a boolean",
+            ),
+            _ => Check::Invalid,
+        },
+        // Dates are stored as UTC RFC3339 with millisecond precision, so they compare and
+        // sort correctly as strings
+        (AttributeKind::Date, JsonValue::String(s)) => {
+            if let Ok(date) = DateTime::parse_from_rfc3339(s) {
+                let canonical = rfc3339::format(&date.to_utc());
+                if canonical == *s {
+                    return Check::Valid;
+                }
+                return Check::Canonical(JsonValue::String(canonical));
+            }
+            match parse_lenient_date(s.trim()) {
+                Some(date) => Check::Coerced(
+                    JsonValue::String(rfc3339::format(&date)),
+                    "was converted to an RFC3339 date",
+                ),
+                None => Check::Invalid,
+            }
+        }
+        // URLs are stored as given, only trimmed. They are shown to other users, so only
+        // https links to a host are accepted, and credentials are kept out of the data
+        (AttributeKind::Url, JsonValue::String(s)) => {
+            let trimmed = s.trim();
+            if !is_safe_url(trimmed) {
+                Check::Invalid
+            } else if trimmed.len() == s.len() {
+                Check::Valid
+            } else {
+                Check::Coerced(
+                    JsonValue::String(trimmed.to_string()),
+                    "had surrounding whitespace removed",
+                )
+            }
+        }
+        _ => Check::Invalid,
+    }
+}
+
+fn is_safe_url(s: &str) -> bool {
+    if s.len() > MAX_URL_LENGTH {
+        return false;
+    }
+    Url::parse(s).is_ok_and(|url| {
+        url.scheme() == "https"
+            && url.has_host()
+            && url.username().is_empty()
+            && url.password().is_none()
+    })
+}
+
+// Canonical form of a stored date, or None when it can't be read as a date
+pub fn canonical_date(s: &str) -> Option<String> {
+    parse_lenient_date(s.trim()).map(|date| rfc3339::format(&date))
+}
+
+// Accepts RFC3339 with stray whitespace, naive date-times (assumed UTC)
+// and plain dates (midnight UTC).
+fn parse_lenient_date(s: &str) -> Option<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(s) {
+        return Some(date.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(s, format) {
+            return Some(date.and_utc());
+        }
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|date| date.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    struct Attr {
+        name: &'static str,
+        kind: AttributeKind,
+        required: bool,
+        deprecated: bool,
+        max_length: Option<usize>,
+    }
+
+    impl Attr {
+        fn new(name: &'static str, kind: AttributeKind) -> Self {
+            Self {
+                name,
+                kind,
+                required: false,
+                deprecated: false,
+                max_length: None,
+            }
+        }
+    }
+
+    impl AttributeRule for Attr {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn kind(&self) -> AttributeKind {
+            self.kind
+        }
+
+        fn required(&self) -> bool {
+            self.required
+        }
+
+        fn description(&self) -> &str {
+            ""
+        }
+
+        fn deprecated(&self) -> bool {
+            self.deprecated
+        }
+
+        fn replaced_by(&self) -> Option<&str> {
+            self.deprecated.then_some("replacement")
+        }
+
+        fn max_length(&self) -> Option<usize> {
+            self.max_length
+        }
+    }
+
+    fn properties(value: JsonValue) -> HashMap<String, JsonValue> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn codes(outcome: &ValidationOutcome) -> Vec<(&str, &'static str)> {
+        outcome
+            .errors
+            .iter()
+            .map(|e| (e.attribute(), e.code()))
+            .collect()
+    }
+
+    fn warned(outcome: &ValidationOutcome) -> Vec<&str> {
+        let mut fields: Vec<&str> = outcome.warnings.iter().map(|w| w.field.as_str()).collect();
+        fields.sort();
+        fields
+    }
+
+    fn coercible() -> (Vec<Attr>, HashMap<String, JsonValue>) {
+        let attributes = vec![
+            Attr::new("age", AttributeKind::Number),
+            Attr::new("active", AttributeKind::Boolean),
+            Attr::new("name", AttributeKind::String),
+            Attr::new("born", AttributeKind::Date),
+            Attr::new("site", AttributeKind::Url),
+        ];
+        let properties = properties(json!({
+            "age": "42",
+            "active": " TRUE ",
+            "name": "  Ada ",
+            "born": "1815-12-10",
+            "site": " https://example.com ",
+        }));
+        (attributes, properties)
+    }
+
+    #[test]
+    fn coercions_are_warnings_by_default() {
+        let (attributes, sent) = coercible();
+        let outcome = ValidationOutcome::validate(&attributes, sent, false);
+
+        assert!(outcome.is_valid(), "{:?}", outcome.errors);
+        assert_eq!(warned(&outcome), ["active", "age", "born", "name", "site"]);
+        assert_eq!(
+            outcome.coerced_properties,
+            properties(json!({
+                "age": 42,
+                "active": true,
+                "name": "Ada",
+                "born": "1815-12-10T00:00:00.000Z",
+                "site": "https://example.com",
+            }))
+        );
+    }
+
+    #[test]
+    fn coercions_are_errors_in_strict_mode() {
+        let (attributes, original) = coercible();
+        let outcome = ValidationOutcome::validate(&attributes, original.clone(), true);
+
+        assert!(outcome.warnings.is_empty());
+        let mut errors = codes(&outcome);
+        errors.sort();
+        assert_eq!(
+            errors,
+            [
+                ("active", "needs_coercion"),
+                ("age", "needs_coercion"),
+                ("born", "needs_coercion"),
+                ("name", "needs_coercion"),
+                ("site", "needs_coercion"),
+            ]
+        );
+        assert_eq!(outcome.coerced_properties, original);
+    }
+
+    #[test]
+    fn canonical_dates_are_neither_warnings_nor_errors() {
+        let attributes = [Attr::new("born", AttributeKind::Date)];
+        for strict in [false, true] {
+            let outcome = ValidationOutcome::validate(
+                &attributes,
+                properties(json!({ "born": "1815-12-10T01:00:00+01:00" })),
+                strict,
+            );
+            assert!(outcome.is_valid());
+            assert!(outcome.warnings.is_empty());
+            assert_eq!(
+                outcome.coerced_properties["born"],
+                json!("1815-12-10T00:00:00.000Z")
+            );
+        }
+    }
+
+    #[test]
+    fn wrong_types_are_errors_in_either_mode() {
+        let attributes = [
+            Attr::new("age", AttributeKind::Number),
+            Attr::new("active", AttributeKind::Boolean),
+            Attr::new("site", AttributeKind::Url),
+        ];
+        for strict in [false, true] {
+            let outcome = ValidationOutcome::validate(
+                &attributes,
+                properties(json!({
+                    "age": "forty",
+                    "active": "yes",
+                    "site": "http://example.com",
+                })),
+                strict,
+            );
+            assert!(outcome.warnings.is_empty());
+            assert_eq!(
+                codes(&outcome),
+                [
+                    ("age", "wrong_type"),
+                    ("active", "wrong_type"),
+                    ("site", "wrong_type")
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn required_attributes_and_undefined_properties() {
+        let mut name = Attr::new("name", AttributeKind::String);
+        name.required = true;
+        let mut email = Attr::new("email", AttributeKind::String);
+        email.required = true;
+        let outcome = ValidationOutcome::validate(
+            &[name, email],
+            properties(json!({ "email": null, "nickname": "  Ada " })),
+            false,
+        );
+
+        assert_eq!(
+            codes(&outcome),
+            [
+                ("name", "missing_attribute"),
+                ("email", "missing_attribute")
+            ]
+        );
+        // Without a definition the value is kept as sent
+        assert!(outcome.warnings.is_empty());
+        assert_eq!(outcome.coerced_properties["nickname"], json!("  Ada "));
+    }
+
+    #[test]
+    fn deprecated_attributes_are_rejected_on_writes_only() {
+        let mut legacy = Attr::new("legacy", AttributeKind::Number);
+        legacy.deprecated = true;
+        let attributes = [legacy];
+        let rules = WriteRules {
+            strict: false,
+            max_value_length: 100,
+        };
+
+        let write = ValidationOutcome::validate_write(
+            &attributes,
+            properties(json!({ "legacy": "not a number" })),
+            rules,
+        );
+        assert_eq!(codes(&write), [("legacy", "deprecated_attribute")]);
+        assert_eq!(
+            write.errors[0].to_string(),
+            "Attribute 'legacy' is deprecated, use 'replacement' instead"
+        );
+
+        let read =
+            ValidationOutcome::validate(&attributes, properties(json!({ "legacy": 1 })), false);
+        assert!(read.is_valid());
+
+        let unset = ValidationOutcome::validate_write(
+            &attributes,
+            properties(json!({ "legacy": null })),
+            rules,
+        );
+        assert!(unset.is_valid());
+    }
+
+    #[test]
+    fn long_values_are_rejected_on_writes() {
+        let mut code = Attr::new("code", AttributeKind::String);
+        code.max_length = Some(3);
+        let mut bio = Attr::new("bio", AttributeKind::String);
+        // Above the server-wide limit, which wins
+        bio.max_length = Some(50);
+        let rules = WriteRules {
+            strict: false,
+            max_value_length: 5,
+        };
+
+        let outcome = ValidationOutcome::validate_write(
+            &[code, bio],
+            properties(json!({
+                "code": "ABCD",
+                "bio": "Mathematician",
+                "tags": ["short", "too long"],
+                // Five characters, six bytes
+                "note": "héllo",
+            })),
+            rules,
+        );
+        assert_eq!(
+            codes(&outcome),
+            [
+                ("bio", "value_too_long"),
+                ("code", "value_too_long"),
+                ("tags", "value_too_long"),
+            ]
+        );
+        // Trimmed before it is measured
+        let outcome = ValidationOutcome::validate_write(
+            &[Attr::new("code", AttributeKind::String)],
+            properties(json!({ "code": "  ABCDE  " })),
+            rules,
+        );
+        assert!(outcome.is_valid());
+        assert_eq!(warned(&outcome), ["code"]);
+    }
+
+    #[test]
+    fn examples_must_not_need_coercion() {
+        let age = Attr::new("age", AttributeKind::Number);
+        assert!(validate_example(&age, &json!(42)).is_ok());
+        assert_eq!(
+            validate_example(&age, &json!("42")).unwrap_err().code(),
+            "wrong_type"
+        );
+    }
+}
@@ -0,0 +1,325 @@
+use super::{parse_webhook_url, PublicResolver, WebhookEvent, WebhookSubscription};
+use crate::ids::GraphId;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+use uuid::Uuid;
+
+// Bumped whenever the envelope or an event's `data` changes incompatibly
+pub const WEBHOOK_PAYLOAD_VERSION: u32 = 1;
+
+// Body POSTed to subscribers
+#[derive(Debug, Serialize)]
+pub struct WebhookPayload {
+    pub version: u32,
+    pub id: Uuid,
+    pub event: String,
+    pub graph_id: String,
+    #[serde(with = "crate::utils::rfc3339")]
+    pub occurred_at: chrono::DateTime<chrono::Utc>,
+    // The full definition or record the event is about
+    pub data: JsonValue,
+}
+
+impl WebhookPayload {
+    pub fn new(graph_id: &GraphId, event: WebhookEvent, data: JsonValue) -> Self {
+        Self {
+            version: WEBHOOK_PAYLOAD_VERSION,
+            id: Uuid::new_v4(),
+            event: event.name(),
+            graph_id: graph_id.to_string(),
+            occurred_at: chrono::Utc::now(),
+            data,
+        }
+    }
+}
+
+// Receivers get this long to accept a delivery
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub struct WebhookDispatcher {
+    client: reqwest::Client,
+    // Events are dropped when the webhooks feature is off
+    enabled: bool,
+}
+
+impl Default for WebhookDispatcher {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
+
+impl WebhookDispatcher {
+    pub fn new(enabled: bool) -> Self {
+        // Redirects are not followed, they could point to an internal address
+        let client = reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .dns_resolver(Arc::new(PublicResolver))
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("Failed to build webhook HTTP client");
+        Self { client, enabled }
+    }
+
+    // Send the event to every subscription of the graph in its category. Delivery happens
+    // in the background and failures are only logged, so a slow or broken receiver never
+    // fails the change that triggered it
+    pub async fn dispatch(
+        &self,
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        event: WebhookEvent,
+        data: JsonValue,
+    ) {
+        if !self.enabled {
+            return;
+        }
+        let subscriptions =
+            match WebhookSubscription::for_category(pool, graph_id, event.category()).await {
+                Ok(subscriptions) => subscriptions,
+                Err(e) => {
+                    error!("Failed to fetch webhook subscriptions: {}", e);
+                    return;
+                }
+            };
+        if subscriptions.is_empty() {
+            return;
+        }
+
+        let payload = WebhookPayload::new(graph_id, event, data);
+        for subscription in subscriptions {
+            // Subscriptions stored before addresses were checked may still point inside
+            if let Err(e) = parse_webhook_url(&subscription.url) {
+                error!("Not delivering to webhook {}: {}", subscription.id, e);
+                continue;
+            }
+            let request = self.client.post(&subscription.url).json(&payload);
+            let event = payload.event.clone();
+            tokio::spawn(async move {
+                match request.send().await.and_then(|r| r.error_for_status()) {
+                    Ok(_) => info!("Delivered {} to webhook {}", event, subscription.id),
+                    Err(e) => error!(
+                        "Failed to deliver {} to webhook {}: {}",
+                        event, subscription.id, e
+                    ),
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edge::{EdgeType, EdgeTypeAttributeDefinition, EdgeTypeResponse};
+    use crate::ids::{GraphId, NodeTypeId};
+    use crate::node::{NodeType, NodeTypeAttributeDefinition, NodeTypeResponse};
+    use serde_json::json;
+
+    fn graph_id() -> GraphId {
+        GraphId::parse("gABCDEFGH").unwrap()
+    }
+
+    fn payload(event: WebhookEvent, data: JsonValue) -> JsonValue {
+        serde_json::to_value(WebhookPayload::new(&graph_id(), event, data)).unwrap()
+    }
+
+    fn keys(value: &JsonValue) -> Vec<&str> {
+        let mut keys: Vec<&str> = value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    fn attribute() -> JsonValue {
+        json!({
+            "name": "since",
+            "data_type": "date",
+            "required": true,
+            "description": "When it started",
+            "example": "2024-01-01T00:00:00.000Z",
+        })
+    }
+
+    #[test]
+    fn envelope() {
+        let payload = payload(WebhookEvent::NodeCreated, json!({"id": 1}));
+        assert_eq!(
+            keys(&payload),
+            ["data", "event", "graph_id", "id", "occurred_at", "version"]
+        );
+        assert_eq!(payload["version"], json!(WEBHOOK_PAYLOAD_VERSION));
+        assert_eq!(payload["event"], json!("data.node_created"));
+        assert_eq!(payload["graph_id"], json!("gABCDEFGH"));
+        assert!(payload["id"].as_str().unwrap().parse::<Uuid>().is_ok());
+        assert!(payload["occurred_at"].as_str().unwrap().ends_with('Z'));
+        assert_eq!(payload["data"], json!({"id": 1}));
+    }
+
+    #[test]
+    fn event_names() {
+        for (event, name) in [
+            (WebhookEvent::NodeTypeCreated, "schema.node_type_created"),
+            (WebhookEvent::NodeTypeUpdated, "schema.node_type_updated"),
+            (WebhookEvent::NodeTypeDeleted, "schema.node_type_deleted"),
+            (WebhookEvent::EdgeTypeCreated, "schema.edge_type_created"),
+            (WebhookEvent::EdgeTypeDeleted, "schema.edge_type_deleted"),
+            (WebhookEvent::NodeCreated, "data.node_created"),
+            (WebhookEvent::EdgeCreated, "data.edge_created"),
+        ] {
+            assert_eq!(event.name(), name);
+        }
+    }
+
+    #[test]
+    fn node_type_created() {
+        let node_type = NodeType::new(&graph_id(), "Person", "People".into(), Uuid::nil()).unwrap();
+        let attributes = [NodeTypeAttributeDefinition::from_request(
+            &serde_json::from_value(attribute()).unwrap(),
+            &node_type.id,
+        )];
+        let data = json!(NodeTypeResponse::from(&node_type, &attributes));
+        let payload = payload(WebhookEvent::NodeTypeCreated, data);
+
+        let data = &payload["data"];
+        assert_eq!(
+            keys(data),
+            [
+                "attributes",
+                "created_at",
+                "created_by",
+                "description",
+                "extends",
+                "graph_id",
+                "id",
+                "name"
+            ]
+        );
+        assert_eq!(data["id"], json!(node_type.id.to_string()));
+        assert_eq!(data["name"], json!("Person"));
+        assert_eq!(data["extends"], JsonValue::Null);
+        let attribute = &data["attributes"][0];
+        assert_eq!(attribute["name"], json!("since"));
+        assert_eq!(attribute["data_type"], json!("date"));
+        assert_eq!(attribute["required"], json!(true));
+        assert_eq!(attribute["example"], json!("2024-01-01T00:00:00.000Z"));
+    }
+
+    #[test]
+    fn edge_type_created() {
+        let edge_type =
+            EdgeType::new(&graph_id(), "Knows", "Acquaintances".into(), Uuid::nil()).unwrap();
+        let attributes = [EdgeTypeAttributeDefinition::from_request(
+            &serde_json::from_value(attribute()).unwrap(),
+            &edge_type.id,
+        )];
+        let data = json!(EdgeTypeResponse::from(&edge_type, &attributes));
+        let payload = payload(WebhookEvent::EdgeTypeCreated, data);
+
+        assert_eq!(payload["event"], json!("schema.edge_type_created"));
+        let data = &payload["data"];
+        assert_eq!(
+            keys(data),
+            [
+                "attributes",
+                "created_at",
+                "created_by",
+                "description",
+                "graph_id",
+                "id",
+                "name"
+            ]
+        );
+        assert_eq!(data["name"], json!("Knows"));
+        assert_eq!(data["attributes"][0]["data_type"], json!("date"));
+    }
+
+    #[test]
+    fn node_type_deleted() {
+        let mut node_type =
+            NodeType::new(&graph_id(), "Employee", "Staff".into(), Uuid::nil()).unwrap();
+        node_type.parent_id = Some(NodeTypeId::parse("vPERSON01").unwrap());
+        let attributes = [NodeTypeAttributeDefinition::from_request(
+            &serde_json::from_value(attribute()).unwrap(),
+            &node_type.id,
+        )];
+        let data = json!(NodeTypeResponse::from(&node_type, &attributes));
+        let payload = payload(WebhookEvent::NodeTypeDeleted, data);
+
+        assert_eq!(
+            keys(&payload),
+            ["data", "event", "graph_id", "id", "occurred_at", "version"]
+        );
+        assert_eq!(payload["event"], json!("schema.node_type_deleted"));
+        // The definition as it was before the deletion, in the shape of a creation
+        let data = &payload["data"];
+        assert_eq!(
+            keys(data),
+            [
+                "attributes",
+                "created_at",
+                "created_by",
+                "description",
+                "extends",
+                "graph_id",
+                "id",
+                "name"
+            ]
+        );
+        assert_eq!(data["id"], json!(node_type.id.to_string()));
+        assert_eq!(data["graph_id"], json!("gABCDEFGH"));
+        assert_eq!(data["name"], json!("Employee"));
+        assert_eq!(data["extends"], json!("vPERSON01"));
+        let attribute = &data["attributes"][0];
+        assert_eq!(attribute["name"], json!("since"));
+        assert_eq!(attribute["required"], json!(true));
+    }
+
+    #[test]
+    fn edge_type_deleted() {
+        let edge_type = EdgeType::new(
+            &graph_id(),
+            "Reports To",
+            "Line management".into(),
+            Uuid::nil(),
+        )
+        .unwrap();
+        let attributes = [EdgeTypeAttributeDefinition::from_request(
+            &serde_json::from_value(attribute()).unwrap(),
+            &edge_type.id,
+        )];
+        let data = json!(EdgeTypeResponse::from(&edge_type, &attributes));
+        let payload = payload(WebhookEvent::EdgeTypeDeleted, data);
+
+        assert_eq!(
+            keys(&payload),
+            ["data", "event", "graph_id", "id", "occurred_at", "version"]
+        );
+        assert_eq!(payload["event"], json!("schema.edge_type_deleted"));
+        let data = &payload["data"];
+        assert_eq!(
+            keys(data),
+            [
+                "attributes",
+                "created_at",
+                "created_by",
+                "description",
+                "graph_id",
+                "id",
+                "name"
+            ]
+        );
+        assert_eq!(data["id"], json!(edge_type.id.to_string()));
+        assert_eq!(data["graph_id"], json!("gABCDEFGH"));
+        assert_eq!(data["name"], json!("Reports To"));
+        assert_eq!(data["attributes"][0]["name"], json!("since"));
+        assert_eq!(data["attributes"][0]["data_type"], json!("date"));
+    }
+}
@@ -0,0 +1,113 @@
+use super::{check_resolved, parse_webhook_url, EventCategory, WebhookSubscription};
+use crate::auth::Auth;
+use crate::config::AppState;
+use crate::error::ApiError;
+use crate::graph::GraphAccess;
+use crate::ids::GraphId;
+use axum::extract::{Extension, Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    // Defaults to every category
+    pub categories: Option<Vec<EventCategory>>,
+}
+
+pub async fn create_webhook(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+    Json(request): Json<CreateWebhookRequest>,
+) -> Result<(StatusCode, Json<WebhookSubscription>), ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_admin()?;
+
+    let url = parse_webhook_url(&request.url).map_err(ApiError::BadRequest)?;
+    check_resolved(&url).await.map_err(ApiError::BadRequest)?;
+
+    let mut categories: Vec<EventCategory> = Vec::new();
+    for category in request
+        .categories
+        .unwrap_or_else(|| vec![EventCategory::Schema, EventCategory::Data])
+    {
+        if !categories.contains(&category) {
+            categories.push(category);
+        }
+    }
+    if categories.is_empty() {
+        return Err(ApiError::BadRequest(
+            "At least one event category is required".into(),
+        ));
+    }
+
+    let subscription =
+        WebhookSubscription::new(&access.graph.graph_id, url.as_str(), categories, user.id);
+    subscription.persist(&state.pool).await.map_err(|e| {
+        error!("Failed to save webhook subscription: {}", e);
+        ApiError::InternalServerError
+    })?;
+
+    Ok((StatusCode::CREATED, Json(subscription)))
+}
+
+pub async fn get_webhooks(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path(graph_id): Path<GraphId>,
+) -> Result<Json<Vec<WebhookSubscription>>, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_admin()?;
+
+    let subscriptions = WebhookSubscription::list(&state.pool, &access.graph.graph_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch webhook subscriptions: {}", e);
+            ApiError::InternalServerError
+        })?;
+
+    Ok(Json(subscriptions))
+}
+
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    Extension(auth): Extension<Auth>,
+    Path((graph_id, webhook_id)): Path<(GraphId, Uuid)>,
+) -> Result<StatusCode, ApiError> {
+    let user = auth.user.ok_or_else(|| {
+        error!("Unauthorized access: no valid user found in middleware");
+        ApiError::Unauthorized
+    })?;
+
+    let access = GraphAccess::resolve(&state, &graph_id, &user).await?;
+    access.require_admin()?;
+
+    let deleted = WebhookSubscription::delete(&state.pool, &access.graph.graph_id, webhook_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to delete webhook subscription: {}", e);
+            ApiError::InternalServerError
+        })?;
+    if !deleted {
+        return Err(ApiError::NotFound {
+            code: "WEBHOOK_NOT_FOUND".into(),
+            message: "Webhook not found".into(),
+        });
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,9 @@
+mod delivery;
+mod endpoints;
+mod target;
+mod webhook;
+
+pub use delivery::*;
+pub use endpoints::*;
+pub use target::*;
+pub use webhook::*;
@@ -0,0 +1,147 @@
+use crate::ids::GraphId;
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, FromRow, Row};
+use strum_macros::{Display, EnumString};
+use uuid::Uuid;
+
+#[derive(PartialEq, Eq, Clone, Copy, Serialize, Deserialize, Debug, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum EventCategory {
+    // Node and edge type changes
+    Schema,
+    // Node and edge changes
+    Data,
+}
+
+// Events sent to webhook subscribers. The wire name is "<category>.<event>"
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum WebhookEvent {
+    NodeTypeCreated,
+    NodeTypeUpdated,
+    NodeTypeDeleted,
+    EdgeTypeCreated,
+    EdgeTypeDeleted,
+    NodeCreated,
+    EdgeCreated,
+}
+
+impl WebhookEvent {
+    pub fn category(&self) -> EventCategory {
+        match self {
+            WebhookEvent::NodeTypeCreated
+            | WebhookEvent::NodeTypeUpdated
+            | WebhookEvent::NodeTypeDeleted
+            | WebhookEvent::EdgeTypeCreated
+            | WebhookEvent::EdgeTypeDeleted => EventCategory::Schema,
+            WebhookEvent::NodeCreated | WebhookEvent::EdgeCreated => EventCategory::Data,
+        }
+    }
+
+    // e.g. "schema.node_type_created"
+    pub fn name(&self) -> String {
+        format!("{}.{}", self.category(), self)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookSubscription {
+    pub id: Uuid,
+    pub graph_id: String,
+    pub url: String,
+    pub categories: Vec<EventCategory>,
+    pub created_by: Uuid,
+    #[serde(with = "crate::utils::rfc3339")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl<'r> FromRow<'r, PgRow> for WebhookSubscription {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        let categories: Vec<String> = row.try_get("categories")?;
+        let categories = categories
+            .iter()
+            .map(|c| c.parse::<EventCategory>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+        Ok(Self {
+            id: row.try_get("id")?,
+            graph_id: row.try_get("graph_id")?,
+            url: row.try_get("url")?,
+            categories,
+            created_by: row.try_get("created_by")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+impl WebhookSubscription {
+    pub fn new(
+        graph_id: &GraphId,
+        url: &str,
+        categories: Vec<EventCategory>,
+        created_by: Uuid,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            graph_id: graph_id.to_string(),
+            url: url.to_string(),
+            categories,
+            created_by,
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    pub async fn persist(&self, pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+        let categories: Vec<String> = self.categories.iter().map(|c| c.to_string()).collect();
+        let query = "INSERT INTO app_data.webhook_subscription (id, graph_id, url, categories, created_by, created_at) VALUES ($1, $2, $3, $4, $5, $6)";
+        sqlx::query(query)
+            .bind(self.id)
+            .bind(&self.graph_id)
+            .bind(&self.url)
+            .bind(categories)
+            .bind(self.created_by)
+            .bind(self.created_at)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn list(pool: &sqlx::PgPool, graph_id: &GraphId) -> Result<Vec<Self>, sqlx::Error> {
+        let query = "SELECT * FROM app_data.webhook_subscription WHERE graph_id = $1 ORDER BY created_at, id";
+        sqlx::query_as::<_, WebhookSubscription>(query)
+            .bind(graph_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    // Subscriptions of the graph that receive events of the given category
+    pub async fn for_category(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        category: EventCategory,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let query = "SELECT * FROM app_data.webhook_subscription WHERE graph_id = $1 AND $2 = ANY(categories)";
+        sqlx::query_as::<_, WebhookSubscription>(query)
+            .bind(graph_id)
+            .bind(category.to_string())
+            .fetch_all(pool)
+            .await
+    }
+
+    // Returns false when the graph has no such subscription
+    pub async fn delete(
+        pool: &sqlx::PgPool,
+        graph_id: &GraphId,
+        id: Uuid,
+    ) -> Result<bool, sqlx::Error> {
+        let query = "DELETE FROM app_data.webhook_subscription WHERE graph_id = $1 AND id = $2";
+        let result = sqlx::query(query)
+            .bind(graph_id)
+            .bind(id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
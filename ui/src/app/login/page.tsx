@@ -13,6 +13,7 @@ export default function Login() {
         headers: {
           "Content-Type": "application/json",
         },
+        body: JSON.stringify({ provider: "google" }),
       });
 
       if (!response.ok) {